@@ -0,0 +1,24 @@
+extern crate cbindgen;
+
+use std::env;
+use std::path::PathBuf;
+
+/// Generates libmango.h from the sources.
+///
+/// The header ends up in OUT_DIR, tests/header.rs checks that the checked-in copy matches it.
+/// Setting MANGO_UPDATE_HEADER writes it to the crate root as well, `make header` does that.
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    let bindings = cbindgen::generate(&crate_dir).expect("could not generate libmango.h");
+    bindings.write_to_file(out_dir.join("libmango.h"));
+
+    if env::var_os("MANGO_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(crate_dir.join("libmango.h"));
+    }
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=MANGO_UPDATE_HEADER");
+}
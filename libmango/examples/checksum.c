@@ -2,15 +2,23 @@
 #include "../libmango.h"
 
 int main(int argc, char *argv[]) {
-    int error = -10;
-    MangoImage img = mangoimg_from_path(argv[1], &error);
+    MangoImage img;
+    MangoImageMeta meta;
+    char * checksum;
 
-    if (error == 0) {
-        MangoImageMeta meta = mangoimg_get_meta(img);
-
-        printf("%s\n", mangoimgmeta_checksum(meta));
+    mango_status status = mangoimg_from_path(argv[1], &img);
+    if (status != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return status;
     }
 
-    return error;
-}
+    mangoimg_get_meta(img, &meta);
+    mangoimgmeta_checksum(meta, &checksum);
+    printf("%s\n", checksum);
 
+    mango_string_free(checksum);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+
+    return 0;
+}
@@ -2,9 +2,15 @@
 #include "../libmango.h"
 
 int main(int argc, char *argv[]) {
-    MangoImage img  = mangoimg_from_path(argv[1], NULL);
+    MangoImage img;
+    ImageData data;
 
-    ImageData data = mangoimg_get_image_data(img);
+    if (mangoimg_from_path(argv[1], &img) != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return 1;
+    }
+
+    mangoimg_get_image_data(img, &data);
 
     uint8_t* it  = data.pointer;
     uint8_t* ite = it + data.length;
@@ -14,6 +20,8 @@ int main(int argc, char *argv[]) {
         printf("%i", (uint32_t)*it);
     }
 
+    mango_imagedata_free(data);
+    mangoimg_free(img);
+
     return 0;
 }
-
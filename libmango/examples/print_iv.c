@@ -1,24 +1,34 @@
 #include <stdio.h>
 #include "../libmango.h"
 
-void main(void) {
+int main(void) {
     MangoImage img;
+    MangoImageMeta meta;
+    ImageData iv;
 
-    img = mangoimg_from_path("test.jpg", NULL);
-    mangoimg_encrypt(img, "AES128", "1234567812345678");
-
-    int * iv = mangoimgmeta_iv(mangoimg_get_meta(img));
+    if (mangoimg_from_path("test.jpg", &img) != MANGO_STATUS_OK
+        || mangoimg_encrypt(img, "AES128", "1234567812345678") != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return 1;
+    }
 
-    int iv_size = mangoimgmeta_iv_size(mangoimg_get_meta(img));
+    mangoimg_get_meta(img, &meta);
+    mangoimgmeta_iv(meta, &iv);
 
     printf("[ ");
 
-    for(int i = 0; i < iv_size; i++){
-        printf( "%d", iv[i]);
-        if(i != iv_size - 1) {
+    for(size_t i = 0; i < iv.length; i++){
+        printf( "%d", iv.pointer[i]);
+        if(i != iv.length - 1) {
             printf( ", " );
         }
     }
 
     printf(" ]");
+
+    mango_imagedata_free(iv);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+
+    return 0;
 }
@@ -1,37 +1,51 @@
 #include <stdio.h>
 #include "../libmango.h"
 
-void main(void) {
-    MangoFile file;
+// prints the checksum of the first image of a file
+static void print_checksum(MangoFile file) {
     MangoImage img;
-    
+    MangoImageMeta meta;
+    char * checksum;
+
+    mangofile_get_image(file, 0, &img);
+    mangoimg_get_meta(img, &meta);
+    mangoimgmeta_checksum(meta, &checksum);
+
+    printf("%s\n", checksum);
+
+    mango_string_free(checksum);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+}
+
+int main(void) {
+    MangoFile file;
+
     // create file
     file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
+    if (mangofile_add_image_by_path(file, "test.jpg") != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return 1;
+    }
 
-    // get checksum
-    img = mangofile_get_image(file, 0);
-    char * checksum_before = mangoimgmeta_checksum(mangoimg_get_meta(img));
+    print_checksum(file);
 
     // save
-    mangofile_save(file, "testfile.mango");
-    
-    // print checksum
-    printf("%s\n", checksum_before);
-    
-    // reset variables
-    file = NULL;
-    img = NULL;
-    
+    if (mangofile_save(file, "testfile.mango") != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return 1;
+    }
+    mangofile_free(file);
+
     // open the created file
-    file = mangofile_open("testfile.mango", NULL);
-    
-    // get checksum
-    img = mangofile_get_image(file, 0);
-    char * checksum_after = mangoimgmeta_checksum(mangoimg_get_meta(img));
-    
-    // print checksum
-    printf("%s\n", checksum_after);
+    if (mangofile_open("testfile.mango", &file) != MANGO_STATUS_OK) {
+        fprintf(stderr, "%s\n", mango_last_error_message());
+        return 1;
+    }
+
+    print_checksum(file);
+    mangofile_free(file);
 
     remove("testfile.mango");
+    return 0;
 }
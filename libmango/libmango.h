@@ -1,173 +1,507 @@
+#ifndef LIBMANGO_H
+#define LIBMANGO_H
+
+/* This file is generated by cbindgen from the sources in src/, do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
 #include <stddef.h>
 #include <stdint.h>
+#include <stdlib.h>
 
-#ifdef __cplusplus
-extern "C" {
-#endif
+/**
+ * \mainpage
+ *
+ * All handles are opaque and own what they point to, a handle never becomes invalid
+ * because something happened to another one. They can be used from multiple threads,
+ * calls on the same handle get serialized, but a handle must not be freed while
+ * another thread still uses it.
+ *
+ * Every function that can fail returns a \link mango_status \endlink, results are written
+ * to the out parameters passed in, which are only written if the call succeeded.
+ * \link mango_last_error_message \endlink describes what went wrong.
+ *
+ * Strings returned by libmango must be freed with \link mango_string_free \endlink,
+ * ImageData with \link mango_imagedata_free \endlink. Getters of optional strings return
+ * NULL if the value isn't set, passing NULL to a setter unsets it.
+ */
+
+typedef struct mango_file *MangoFile;
+typedef struct mango_image *MangoImage;
+typedef struct mango_image_meta *MangoImageMeta;
+typedef struct mango_meta *MangoMeta;
+
+/**
+ * The version of the C API this header describes.
+ *
+ * It gets increased whenever a change breaks programs compiled against an older version,
+ * compare it with \link mango_abi_version \endlink to check if the loaded library matches.
+ */
+#define MANGO_ABI_VERSION 1
+
+/**
+ * How important a log message is, or which messages get passed to the callback.
+ */
+typedef enum mango_log_level {
+  /**
+   * no messages at all, only valid as the maximum level
+   */
+  MANGO_LOG_LEVEL_OFF = 0,
+  MANGO_LOG_LEVEL_ERROR = 1,
+  MANGO_LOG_LEVEL_WARN = 2,
+  MANGO_LOG_LEVEL_INFO = 3,
+  MANGO_LOG_LEVEL_DEBUG = 4,
+  MANGO_LOG_LEVEL_TRACE = 5,
+} mango_log_level;
+
+/**
+ * What a long running operation is busy with.
+ */
+typedef enum mango_progress_stage {
+  /**
+   * reading a .mango file
+   */
+  MANGO_PROGRESS_STAGE_READ = 0,
+  /**
+   * writing a .mango file
+   */
+  MANGO_PROGRESS_STAGE_WRITE = 1,
+  /**
+   * compressing the images of a file
+   */
+  MANGO_PROGRESS_STAGE_COMPRESS = 2,
+  /**
+   * encrypting the images of a file
+   */
+  MANGO_PROGRESS_STAGE_ENCRYPT = 3,
+  /**
+   * reading image files and adding them to a file
+   */
+  MANGO_PROGRESS_STAGE_IMPORT = 4,
+  /**
+   * decoding a .mango file which has been read
+   */
+  MANGO_PROGRESS_STAGE_DECODE = 5,
+  /**
+   * encoding a .mango file before it gets written
+   */
+  MANGO_PROGRESS_STAGE_ENCODE = 6,
+} mango_progress_stage;
+
+/**
+ * The result of every function that can fail.
+ *
+ * Use \link mango_last_error_message \endlink to get the details.
+ */
+typedef enum mango_status {
+  /**
+   * everything went ok
+   */
+  MANGO_STATUS_OK = 0,
+  /**
+   * a handle or another required pointer passed in is NULL
+   */
+  MANGO_STATUS_NULL_POINTER = 1,
+  /**
+   * an argument isn't valid, like a string that isn't utf-8 or an unknown type
+   */
+  MANGO_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * an image index is out of range
+   */
+  MANGO_STATUS_OUT_OF_RANGE = 3,
+  /**
+   * a file doesn't exist
+   */
+  MANGO_STATUS_NOT_FOUND = 4,
+  /**
+   * a file can't be read or written because of missing permissions
+   */
+  MANGO_STATUS_PERMISSION_DENIED = 5,
+  /**
+   * reading or writing failed for another reason
+   */
+  MANGO_STATUS_IO = 6,
+  /**
+   * the data couldn't be decoded, e.g. the file isn't a .mango file or is corrupted
+   */
+  MANGO_STATUS_DECODE = 7,
+  /**
+   * the data couldn't be encoded
+   */
+  MANGO_STATUS_ENCODE = 8,
+  /**
+   * encrypting or decrypting failed
+   */
+  MANGO_STATUS_CRYPTO = 9,
+  /**
+   * compressing or decompressing failed
+   */
+  MANGO_STATUS_COMPRESSION = 10,
+  /**
+   * the data doesn't match its checksum
+   */
+  MANGO_STATUS_INTEGRITY = 11,
+  /**
+   * the format or algorithm isn't supported, wasn't compiled in or can't be applied to the image
+   */
+  MANGO_STATUS_UNSUPPORTED = 12,
+  /**
+   * a bug in libmango, please report it
+   */
+  MANGO_STATUS_PANIC = 13,
+  /**
+   * the progress callback cancelled the operation
+   */
+  MANGO_STATUS_CANCELLED = 14,
+  /**
+   * writing a file failed for another reason, e.g. because the disk is full
+   */
+  MANGO_STATUS_WRITE = 15,
+} mango_status;
 
 /**
  * Repesents a MangoFile sturct from the mangofmt rust library.
  *
  * A MangoFile contains an instance of ManoMeta and can conatain multiple MangoImages.
  */
-typedef void * MangoFile;
+typedef struct mango_file mango_file;
 
 /**
  * Repesents a MangoImage sturct from the mangofmt rust library.
  *
  * A MangoImage conatains an instance of MangoImageMeta.
+ * It always owns its data, images taken out of a MangoFile are copies.
  */
-typedef void * MangoImage;
+typedef struct mango_image mango_image;
 
 /**
  * Repesents a MangoImageMeta sturct from the mangofmt rust library.
  *
- * A MangoImageMeta instance always belongs to a MangoImage.
+ * A MangoImageMeta instance always belongs to a MangoImage, it keeps the image alive.
  */
-typedef void * MangoImageMeta;
+typedef struct mango_image_meta mango_image_meta;
 
 /**
  * Repesents a MangoMeta sturct from the mangofmt rust library.
  *
- * A MangoMeta instance always belongs to a MangoFile.
+ * A MangoMeta instance either belongs to a MangoFile and keeps the file alive,
+ * or was created on its own with \link mangometa_new \endlink or \link mangometa_copy \endlink.
  */
-typedef void * MangoMeta;
+typedef struct mango_meta mango_meta;
 
+/**
+ * Bytes handed over to C, like the data of an image.
+ */
 typedef struct ImageData {
-    uint8_t* pointer;
-    size_t length;
+  uint8_t *pointer;
+  size_t length;
 } ImageData;
 
+/**
+ * Receives the log messages, see \link mango_set_log_callback \endlink.
+ */
+typedef void (*mango_log_callback)(enum mango_log_level, const char*, const char*, void*);
+
+/**
+ * How far an operation got, see \link mango_progress_callback \endlink.
+ */
+typedef struct mango_progress {
+  enum mango_progress_stage stage;
+  /**
+   * false while a file gets read, decoded, encoded or written
+   */
+  bool has_page;
+  /**
+   * index of the page which was just processed, only valid if *has_page* is true
+   */
+  size_t page;
+  /**
+   * number of pages the operation works on, 0 while they aren't known yet
+   */
+  size_t page_count;
+  /**
+   * bytes processed so far
+   */
+  uint64_t bytes;
+  /**
+   * bytes which get processed in total, 0 if that isn't known up front
+   */
+  uint64_t total_bytes;
+} mango_progress;
+
+/**
+ * Receives the progress of an operation and the user data passed along with it.
+ *
+ * Returning false cancels the operation, it fails with MANGO_STATUS_CANCELLED
+ * and leaves everything as it was before.
+ * The callback is called on the thread which started the operation,
+ * it must not use the handles the operation works on.
+ */
+typedef bool (*mango_progress_callback)(const struct mango_progress*, void*);
+
 /**
  * This struct maps to a rust Option containing an int.
  */
 typedef struct IntOption {
-    int value;
-    int present;
+  int value;
+  int present;
 } IntOption;
 
+/**
+ * This struct maps to a rust Option containing a double.
+ */
+typedef struct FloatOption {
+  double value;
+  int present;
+} FloatOption;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Gets the version of libmango, like "0.6.0".
+ *
+ * \returns the version, it belongs to libmango and must not be freed.
+ */
+const char *mango_version(void);
+
+/**
+ * Gets the version of the C API the loaded library implements.
+ *
+ * \returns the \link MANGO_ABI_VERSION \endlink libmango was built with.
+ */
+uint32_t mango_abi_version(void);
+
+/**
+ * Describes why the last call on this thread failed.
+ *
+ * \returns the message, NULL if nothing failed yet.
+ * It belongs to libmango and stays valid until the next call on this thread fails.
+ */
+const char *mango_last_error_message(void);
+
+/**
+ * Frees a string returned by libmango, NULL gets ignored.
+ */
+void mango_string_free(char *string);
+
+/**
+ * Frees data returned by libmango, an ImageData pointing to NULL gets ignored.
+ */
+void mango_imagedata_free(struct ImageData data);
+
+/**
+ * Passes the log messages of libmango to a callback instead of dropping them.
+ *
+ * libmango never writes to stdout, stderr or any file on its own,
+ * messages are only produced while a callback is set.
+ *
+ * \param callback gets called with the level, the module the message comes from, the message
+ * and *user_data*. It can be called from any thread using libmango,
+ * the strings are only valid during the call. NULL stops passing messages on.
+ * \param max_level the least important level which gets passed to *callback*
+ * \param user_data gets passed to *callback* as it is, can be NULL
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_UNSUPPORTED if the application
+ * installed its own rust logger.
+ */
+enum mango_status mango_set_log_callback(mango_log_callback callback,
+                                         enum mango_log_level max_level,
+                                         void *user_data);
+
 /**
  * Checks if support for encryption was compiled in.
  *
  * \param enc_type the encryption type you want to check.
  *
- * \returns 0 or 1 depending on if it is supported or not.
+ * \returns if it is supported, false if the type is unknown or NULL.
  */
-extern int mango_encryption_is_supported(char *enc_type);
+bool mango_encryption_is_supported(const char *enc_type);
 
 /**
  * Checks if support for compression was compiled in.
  *
  * \param comp_type the compression type you want to check.
  *
- * \returns 0 or 1 depending on if it is supported or not.
+ * \returns if it is supported, false if the type is unknown or NULL.
  */
-extern int mango_compression_is_supported(char *comp_type);
-
-
-//-------------------------------------------------------------------------------------------------
-// Mango File
-// ------------------------------------------------------------------------------------------------
+bool mango_compression_is_supported(const char *comp_type);
 
 /**
  * Creates a new MangoFile.
  *
  * \returns the newly created MangoFile.
  */
-extern MangoFile mangofile_new();
+struct mango_file *mangofile_new(void);
 
 /**
- * Frees the memory of a MangoFile.
+ * Frees a MangoFile, NULL gets ignored.
  *
- * this will also free all child elements, like MangoImage, and MangoMeta.
- * It will also free the MangoImageMeta children of the MangoImage instances.
+ * MangoMeta handles of the file stay valid, they have to be freed on their own.
  *
- * it is important that if you have an instance of the child element, 
- * that you must not free the child before the parent 
- * and when you free the parent all child references will be gone too. 
- *
- * so keep that in mind when writting your code.
- * 
  * \param file the file you want to free.
  */
-extern void mangofile_free(MangoFile file);
+void mangofile_free(struct mango_file *file);
 
 /**
- * Adds a MangoImage to a MangoFile.
+ * Adds an image file to a MangoFile.
  *
  * \param file
- * \param image
+ * \param path
  *
- * * \returns an error code:
- * - 0 ok
- * - -42 either of the pointers passed are null
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED,
+ * MANGO_STATUS_UNSUPPORTED if it isn't an image or another error.
  */
-extern int mangofile_add_image(MangoFile file, MangoImage image);
+enum mango_status mangofile_add_image_by_path(const struct mango_file *file, const char *path);
 
 /**
- * Adds a image to a MangoFile.
+ * Adds a copy of a MangoImage to a MangoFile.
  *
  * \param file
- * \param path
+ * \param image
  *
- * \returns an error code:
- * - 0 ok
- * - 1 permission denied
- * - -1 unknown error
- * - -42  MangoFile is null
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern int mangofile_add_image_by_path(MangoFile file , char *path);
+enum mango_status mangofile_add_image(const struct mango_file *file,
+                                      const struct mango_image *image);
 
 /**
- * Gets a MangoImage from the MangoFile by ID.
+ * Gets a copy of an image of a MangoFile.
+ *
+ * Changes to the copy don't affect the file,
+ * use \link mangofile_set_image \endlink to store them in the file.
  *
  * \param file
- * \param id
+ * \param index
+ * \param out receives the image, it has to be freed with \link mangoimg_free \endlink
  *
- * \returns the MangoFile requested, might be NULL
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
  */
-extern MangoImage mangofile_get_image(MangoFile file, int id);
+enum mango_status mangofile_get_image(const struct mango_file *file,
+                                      size_t index,
+                                      struct mango_image **out);
 
 /**
- * Sets a MangoImage in a MangoFile with a specific index.
- * 
+ * Replaces an image of a MangoFile with a copy of *image*.
+ *
  * \note you can't add a new image to a MangoFile with this function.
  *
  * \param file
  * \param image
- * \param id must be 0 or less that the image count of *file*
+ * \param index must be less than the image count of *file*
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_set_image(const struct mango_file *file,
+                                      const struct mango_image *image,
+                                      size_t index);
+
+/**
+ * Inserts a copy of a MangoImage at a specific index and shifts all images after it to the right.
+ *
+ * \param file
+ * \param image
+ * \param index must not be bigger than the image count of *file*,
+ *              the image count itself adds the image at the end
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_insert_image(const struct mango_file *file,
+                                         const struct mango_image *image,
+                                         size_t index);
+
+/**
+ * Removes an image of a MangoFile and shifts all images after it to the left.
+ *
+ * \param file
+ * \param index must be less than the image count of *file*
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_remove_image(const struct mango_file *file, size_t index);
+
+/**
+ * Moves an image to another index, the images in between get shifted.
+ *
+ * \param file
+ * \param from the index of the image
+ * \param to the index the image should have afterwards
  *
- * \returns 0 or 1 depending on if it worked.
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
  */
-extern int  mangofile_set_image(MangoFile file, MangoImage image, int index);
+enum mango_status mangofile_move_image(const struct mango_file *file, size_t from, size_t to);
 
 /**
- * Removes an image inside a MangoFile and shifts all images after it to the left.
+ * Swaps two images of a MangoFile.
  *
  * \param file
- * \param id must be an index that exists in the *file*
+ * \param a
+ * \param b
  *
- * \returns 0 or 1 depending on if it worked or not. (bool)
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
  */
-extern int  mangofile_remove_image(MangoFile file, int id);
+enum mango_status mangofile_swap_images(const struct mango_file *file, size_t a, size_t b);
+
+/**
+ * Reverses the order of all images of a MangoFile.
+ *
+ * \param file
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_reverse_images(const struct mango_file *file);
+
+/**
+ * Removes all images of a MangoFile for which *keep* returns false.
+ *
+ * *keep* gets called once for every image, in order.
+ * It gets a copy of the image, which is freed afterwards,
+ * the index it had before any image got removed and *user_data*.
+ *
+ * \param file
+ * \param keep decides if an image is kept
+ * \param user_data gets passed to *keep* as it is, can be NULL
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_NULL_POINTER or MANGO_STATUS_INVALID_ARGUMENT
+ * if *keep* added or removed images of the file.
+ */
+enum mango_status mangofile_retain_images(const struct mango_file *file,
+                                          bool (*keep)(struct mango_image*, size_t, void*),
+                                          void *user_data);
 
 /**
  * Gets the count of all Images of a MangoFile.
  *
  * \param file
+ * \param out receives the image count
  *
- * \returns image count
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern int mangofile_get_image_count(MangoFile file);
+enum mango_status mangofile_get_image_count(const struct mango_file *file, size_t *out);
 
 /**
  * Gets the metadata of a MangoFile.
  *
+ * Changes to the metadata are changes to the file.
+ *
+ * \param file
+ * \param out receives the metadata, it has to be freed with \link mangometa_free \endlink
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_get_meta(const struct mango_file *file, struct mango_meta **out);
+
+/**
+ * Replaces the metadata of a MangoFile with a copy of *meta*.
+ *
  * \param file
+ * \param meta can come from \link mangometa_new \endlink or another file
  *
- * \returns MangoMeta 
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern MangoMeta mangofile_get_meta(MangoFile file);
+enum mango_status mangofile_set_meta(const struct mango_file *file, const struct mango_meta *meta);
 
 /**
  * Saves a MangoFile to the file system.
@@ -180,24 +514,17 @@ extern MangoMeta mangofile_get_meta(MangoFile file);
  * - \link mangofile_save_cbor \endlink
  * - \link mangofile_save_json \endlink
  * - \link mangofile_save_bson \endlink
-
  *
  * All formats should use the file ending ".mango"
  * and can be opened with \link mangofile_open \endlink.
- * 
+ *
  * \param file the file to save
  * \param path the path where the file will get saved to
  *
- * \returns an error code containing if the file could have been saved.
- *
- * \return The error codes mean the following:
- * - 0 everything went ok
- * - 1 encode error
- * - 2 write error
- * - 3 permission error
- * - -1 input parameters weren't okay
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_ENCODE, MANGO_STATUS_NOT_FOUND if the directory doesn't
+ * exist, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_WRITE or MANGO_STATUS_NULL_POINTER
  */
-extern int mangofile_save(MangoFile file, char *path);
+enum mango_status mangofile_save(const struct mango_file *file, const char *path);
 
 /**
  * Saves a MangoFile in the cbor format to the file system.
@@ -214,16 +541,9 @@ extern int mangofile_save(MangoFile file, char *path);
  * \param file the file to save
  * \param path the path where the file will get saved to
  *
- * \returns an error code containing if the file could have been saved.
- *
- * \return The error codes mean the following:
- * - 0 everything went ok
- * - 1 encode error
- * - 2 write error
- * - 3 permission error
- * - -1 input parameters weren't okay
+ * \returns the same statuses as \link mangofile_save \endlink
  */
-extern int mangofile_save_cbor(MangoFile file, char *path);
+enum mango_status mangofile_save_cbor(const struct mango_file *file, const char *path);
 
 /**
  * Saves a MangoFile in the bson format to the file system.
@@ -233,20 +553,13 @@ extern int mangofile_save_cbor(MangoFile file, char *path);
  * - \link mangofile_save_cbor \endlink
  *
  * You should save your file with ending ".mango".
- * 
+ *
  * \param file the file to save
  * \param path the path where the file will get saved to
  *
- * \returns an error code containing if the file could have been saved.
- *
- * \return The error codes mean the following:
- * - 0 everything went ok
- * - 1 encode error
- * - 2 write error
- * - 3 permission error
- * - -1 input parameters weren't okay
+ * \returns the same statuses as \link mangofile_save \endlink
  */
-extern int mangofile_save_bson(MangoFile file, char *path);
+enum mango_status mangofile_save_bson(const struct mango_file *file, const char *path);
 
 /**
  * Saves a MangoFile in the json format to the file system.
@@ -266,16 +579,9 @@ extern int mangofile_save_bson(MangoFile file, char *path);
  * \param file the file to save
  * \param path the path where the file will get saved to
  *
- * \returns an error code containing if the file could have been saved.
- *
- * \return The error codes mean the following:
- * - 0 everything went ok
- * - 1 encode error
- * - 2 write error
- * - 3 permission error
- * - -1 input parameters weren't okay
+ * \returns the same statuses as \link mangofile_save \endlink
  */
-extern int mangofile_save_json(MangoFile file, char *path);
+enum mango_status mangofile_save_json(const struct mango_file *file, const char *path);
 
 /**
  * opens a MangoFile from the file system.
@@ -284,341 +590,621 @@ extern int mangofile_save_json(MangoFile file, char *path);
  * This function will detect the format and open it accordingly.
  *
  * \param path the path to the file
- * \param error 
- * \parablock
- * The Error code will be set in the variable passed in:
- * - 0 everything went ok
- * - 1 decode error
- * - 2 read error
- * - 3 permission error
- * - -1 input parameters weren't okay 
- * \endparablock
+ * \param out receives the opened file
  *
- * \returns the opened MangoFile
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_DECODE,
+ * MANGO_STATUS_IO or MANGO_STATUS_NULL_POINTER
  */
-extern MangoFile mangofile_open(char * path, int * error);
+enum mango_status mangofile_open(const char *path,
+                                 struct mango_file **out);
 
-// -------------------------------------------------------------------------------------------------
-// Mango Image
-// ------------------------------------------------------------------------------------------------
+/**
+ * Opens a MangoFile which is already in memory.
+ *
+ * The data is copied, the buffer can be freed afterwards.
+ *
+ * \param data pointer to the encoded file
+ * \param length the length of the data in bytes
+ * \param format "CBOR", "BSON" or "JSON", NULL detects the format
+ * like \link mangofile_open \endlink does
+ * \param out receives the opened file
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_DECODE, MANGO_STATUS_INVALID_ARGUMENT
+ * if the format is unknown or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangofile_open_buffer(const uint8_t *data,
+                                        size_t length,
+                                        const char *format,
+                                        struct mango_file **out);
 
 /**
- * Frees the memory of a MangoImage.
- * 
- * \param img the image you want to free.
+ * Encodes a MangoFile in memory instead of saving it to the file system.
+ *
+ * The data is the same \link mangofile_save \endlink and friends would write.
+ *
+ * \param file the file to encode
+ * \param format "CBOR", "BSON" or "JSON", NULL uses the default format "CBOR"
+ * \param out receives the encoded file, it has to be freed with \link mango_imagedata_free \endlink
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_ENCODE, MANGO_STATUS_INVALID_ARGUMENT
+ * if the format is unknown or MANGO_STATUS_NULL_POINTER
  */
-extern void mangoimg_free(MangoImage img);
+enum mango_status mangofile_save_to_buffer(const struct mango_file *file,
+                                           const char *format,
+                                           struct ImageData *out);
 
 /**
- * Creates a new MangoImage Struct out of a file from the harddrive.
- * 
- * \info currently only jpg & png are supported formats, more are planed in the future.
+ * Opens a MangoFile like \link mangofile_open \endlink and reports how many bytes were read.
  *
- * \param path the path to the image file, you should prefer full paths, tho relative paths should work too
- * \param error 
- * \parablock
- * The Error code, it will be set in the variable passed in:
- * - 0 everything went ok
- * - 1 file not found
- * - -1 input parameters (probably) weren't okay or anything that should not have wen't wrong, went wrong.
- * \endparablock
+ * \param path the path to the file
+ * \param callback gets called after every chunk which was read, can be NULL
+ * \param user_data gets passed to *callback* as it is, can be NULL
+ * \param out receives the opened file
+ *
+ * \returns the same statuses as \link mangofile_open \endlink or MANGO_STATUS_CANCELLED
+ */
+enum mango_status mangofile_open_with_progress(const char *path,
+                                               mango_progress_callback callback,
+                                               void *user_data,
+                                               struct mango_file **out);
 
- * \returns instance of MangoImage, containg the data of the image file.
+/**
+ * Saves a MangoFile like \link mangofile_save \endlink and reports how many bytes were written.
+ *
+ * A cancelled save leaves the previously saved file untouched.
+ *
+ * \param file the file to save
+ * \param path the path where the file will get saved to
+ * \param format "CBOR", "BSON" or "JSON", NULL uses the default format "CBOR"
+ * \param callback gets called after every chunk which was written, can be NULL
+ * \param user_data gets passed to *callback* as it is, can be NULL
+ *
+ * \returns the same statuses as \link mangofile_save \endlink,
+ * MANGO_STATUS_INVALID_ARGUMENT if the format is unknown or MANGO_STATUS_CANCELLED
  */
-extern MangoImage mangoimg_from_path(char * path, int * error);
+enum mango_status mangofile_save_with_progress(const struct mango_file *file,
+                                               const char *path,
+                                               const char *format,
+                                               mango_progress_callback callback,
+                                               void *user_data);
 
 /**
- * Compresses the data of an MangoImage
- * 
- * \info Currently only GZIP is supported.
- * \info You can use \link mango_compression_is_supported \endlink to check if support for a certain type was compiled in.
+ * Adds image files to a MangoFile and reports the progress after every image.
  *
- * \param image
- * \param type The type of compresseion you want to apply.
- * 
- * \todo the return type doesn't appear to be an actual boolean.
+ * Nothing gets added if one of the images can't be read or the import gets cancelled.
+ *
+ * \param file the file the images get added to
+ * \param paths the paths of the images, they are added in this order
+ * \param count the number of paths
+ * \param callback gets called after every image, can be NULL
+ * \param user_data gets passed to *callback* as it is, can be NULL
  *
- * \returns 1 if everything went okay and 2 if some error occurred  
+ * \returns the same statuses as \link mangofile_add_image_by_path \endlink
+ * or MANGO_STATUS_CANCELLED
  */
-extern int mangoimg_compress(MangoImage, char * type);
+enum mango_status mangofile_import_images(const struct mango_file *file,
+                                          const char *const *paths,
+                                          size_t count,
+                                          mango_progress_callback callback,
+                                          void *user_data);
 
 /**
- * Uncompresses the data of an compressed MangoImage.
+ * Compresses every image of a MangoFile which is neither compressed nor encrypted yet.
  *
- * You don't need to specify a compression type, it is stored in the metadata.
+ * The images stay as they were if one of them can't be compressed or the operation gets cancelled.
  *
- * \todo the return type doesn't appear to be an actual boolean
- * \todo maybe it should also return something if the image wasn't compressed at all
+ * \param file the file whose images get compressed
+ * \param type the type of compression, like \link mangoimg_compress \endlink takes it
+ * \param callback gets called after every image, can be NULL
+ * \param user_data gets passed to *callback* as it is, can be NULL
  *
- * \param image
- * 
- * \returns 1 if everything went okay and 2 if some error occurred  
+ * \returns the same statuses as \link mangoimg_compress \endlink or MANGO_STATUS_CANCELLED
  */
-extern int mangoimg_uncompress(MangoImage image);
+enum mango_status mangofile_compress_images(const struct mango_file *file,
+                                            const char *comp_type,
+                                            mango_progress_callback callback,
+                                            void *user_data);
 
 /**
- * Gets the metadata of a MangImage.
+ * Encrypts every image of a MangoFile which isn't encrypted yet with the same password.
  *
- * \param image
+ * The images stay as they were if one of them can't be encrypted or the operation gets cancelled.
+ *
+ * \param file the file whose images get encrypted
+ * \param type the type of encryption, like \link mangoimg_encrypt \endlink takes it
+ * \param password the password used for every image
+ * \param callback gets called after every image, can be NULL
+ * \param user_data gets passed to *callback* as it is, can be NULL
  *
- * \returns ManoImageMeta
+ * \returns the same statuses as \link mangoimg_encrypt \endlink or MANGO_STATUS_CANCELLED
  */
-extern MangoImageMeta mangoimg_get_meta(MangoImage image);
+enum mango_status mangofile_encrypt_images(const struct mango_file *file,
+                                           const char *enc_type,
+                                           const char *password,
+                                           mango_progress_callback callback,
+                                           void *user_data);
 
 /**
- * Returns the actual data of the imagefile stored inside of the MangoImage.
- * 
- * The ImageData struct has pointer to the data and the size of the data (in bytes), 
- * use those to read all of those bytes.
- * 
- * \param image
- * 
- * \returns ImageData struct
+ * Creates a new MangoMeta which doesn't belong to any file, all values are unset.
+ *
+ * Use \link mangofile_set_meta \endlink to store it in a file.
+ *
+ * \returns the newly created MangoMeta.
  */
-extern ImageData mangoimg_get_image_data(MangoImage image);
+struct mango_meta *mangometa_new(void);
 
 /**
- * Returns the actual data of the image file stored inside of the MangoImage, encoded as an base64 string.
- * 
- * You aren't supposed to call this, it creates an overhead, 
- * this mainly exists because I couldn't get python to do what I want.
+ * Copies a MangoMeta, the copy doesn't belong to any file.
  *
- * Use mangoimg_get_image_data() instead!
+ * \param meta
+ * \param out receives the copy, it has to be freed with \link mangometa_free \endlink
  *
- * \returns the image data encoded as base64
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern char * mangoimg_get_base64_image_data(MangoImage image);
+enum mango_status mangometa_copy(const struct mango_meta *meta, struct mango_meta **out);
 
 /**
- * Encrypt the data of an MangoImage
- * 
- * \info Currently only "AES128" & "AES256" are supported
- * \info You can use \link mango_encryption_is_supported \endlink to check if support for a certain type was compiled in.
+ * Frees a MangoMeta, NULL gets ignored.
+ */
+void mangometa_free(struct mango_meta *meta);
+
+/**
+ * Gets the title from a MangoMeta.
  *
- * \param image
- * \param type The type of encryption you want to apply.
- * \param password The password you want to use securiong the encryption.
- * 
- * \todo the return type doesn't appear to be an actual boolean.
+ * \param meta
+ * \param out receives the title of a MangoFile.
+ */
+enum mango_status mangometa_get_title(const struct mango_meta *meta, char **out);
+
+/**
+ * Sets the title of a MangoMeta.
+ */
+enum mango_status mangometa_set_title(const struct mango_meta *meta, const char *value);
+
+/**
+ * Gets the author from a MangoMeta.
  *
- * \returns 1 if everything went okay and 2 if some error occurred  
+ * \param meta
+ * \param out receives the author of a MangoFile.
  */
-extern int mangoimg_encrypt(MangoImage image, char * type, char * password);
+enum mango_status mangometa_get_author(const struct mango_meta *meta, char **out);
 
 /**
- * Decrypt the data of an compressed MangoImage.
+ * Sets the author of a MangoMeta.
+ */
+enum mango_status mangometa_set_author(const struct mango_meta *meta, const char *value);
+
+/**
+ * Gets the publisher from a MangoMeta.
  *
- * You don't need to specify a type, it's stored in the Metadata
+ * \param meta
+ * \param out receives the publisher of a MangoFile.
+ */
+enum mango_status mangometa_get_publisher(const struct mango_meta *meta, char **out);
+
+/**
+ * Sets the publisher of a MangoMeta.
+ */
+enum mango_status mangometa_set_publisher(const struct mango_meta *meta, const char *value);
+
+/**
+ * Gets the source from a MangoMeta.
  *
- * \todo the return type doesn't appear to be an actual boolean
+ * \param meta
+ * \param out receives the source of a MangoFile.
+ */
+enum mango_status mangometa_get_source(const struct mango_meta *meta, char **out);
+
+/**
+ * Sets the source of a MangoMeta.
+ */
+enum mango_status mangometa_set_source(const struct mango_meta *meta, const char *value);
+
+/**
+ * Gets the translation from a MangoMeta.
  *
- * \param image
- * \param password
- * 
- * \returns 1 if everything went okay and 2 if some error occurred  
+ * \param meta
+ * \param out receives the translation of a MangoFile.
  */
-extern int mangoimg_decrypt(MangoImage image, char * password);
+enum mango_status mangometa_get_translation(const struct mango_meta *meta, char **out);
 
 /**
- * Saves the MangoImage to a file.
+ * Sets the translation of a MangoMeta.
+ */
+enum mango_status mangometa_set_translation(const struct mango_meta *meta, const char *value);
+
+/**
+ * Gets the language from a MangoMeta.
  *
- * \param image the image to save
- * \param filename the path/filename of the file to be created
+ * \param meta
+ * \param out receives the language of a MangoFile as a BCP 47 language tag, like "en" or "pt-BR".
+ */
+enum mango_status mangometa_get_language(const struct mango_meta *meta, char **out);
+
+/**
+ * Sets the language of a MangoMeta.
  *
- * \returns The return codes mean:
- * - 0 everything OK!
- * - 1 permission denied!
- * - -1 we just don't know!     
+ * The tag gets normalized, "pt_br" will be stored as "pt-BR" for instance.
+ * The values of the old Language enum like "JP" are still accepted and get converted.
  *
+ * \param meta
+ * \param value must be a valid BCP 47 language tag,
+ * otherwise MANGO_STATUS_INVALID_ARGUMENT is returned and the language stays the same.
  */
-extern int mangoimg_save(MangoImage, char *);
+enum mango_status mangometa_set_language(const struct mango_meta *meta, const char *value);
 
-// ------------------------------------------------------------------------------------------------
-// Mango Image Meta
-// ------------------------------------------------------------------------------------------------
+/**
+ * Gets the volume from a MangoMeta.
+ *
+ * \param meta
+ * \param out receives what volume a MangoFile contains.
+ */
+enum mango_status mangometa_get_volume(const struct mango_meta *meta, struct IntOption *out);
 
 /**
- * Gets the compression type from a MangoImageMeta.
+ * Sets the volume of a MangoMeta.
  *
- * \returns the compression type of a MangoImage.
+ * \param meta
+ * \param value can be NULL
  */
-extern char * mangoimgmeta_compression(MangoImageMeta);
+enum mango_status mangometa_set_volume(const struct mango_meta *meta, const short *value);
 
 /**
- * Gets the encryption type from a MangoImageMeta.
+ * Gets the chapter from a MangoMeta.
  *
- * \returns the encryption type of a MangoImage.
+ * Fractional chapters like 10.5 get truncated,
+ * use \link mangometa_get_chapter_number \endlink to get the exact value.
+ *
+ * \param meta
+ * \param out receives what chapter a MangoFile contains.
  */
-extern char * mangoimgmeta_encryption(MangoImageMeta);
+enum mango_status mangometa_get_chapter(const struct mango_meta *meta, struct IntOption *out);
 
 /**
- * Gets the checksum from a MangoImageMeta.
+ * Sets the chapter of a MangoMeta.
  *
- * \returns the checksum of a MangoImage.
+ * \param meta
+ * \param value can be NULL
  */
-extern char * mangoimgmeta_checksum(MangoImageMeta);
+enum mango_status mangometa_set_chapter(const struct mango_meta *meta, const short *value);
 
 /**
- * Gets the mime type  from a MangoImageMeta.
+ * Gets the chapter from a MangoMeta, including fractional chapters like 10.5.
  *
- * \returns the mime type of a MangoImage.
+ * \param meta
+ * \param out receives what chapter a MangoFile contains.
  */
-extern char * mangoimgmeta_mime(MangoImageMeta);
+enum mango_status mangometa_get_chapter_number(const struct mango_meta *meta,
+                                               struct FloatOption *out);
 
 /**
- * Gets the filename from a MangoImageMeta.
+ * Sets the chapter of a MangoMeta, it can be fractional like 10.5.
  *
- * \returns the filename of a MangoImage.
+ * \param meta
+ * \param value can be NULL
  */
-extern char * mangoimgmeta_filename(MangoImageMeta);
+enum mango_status mangometa_set_chapter_number(const struct mango_meta *meta, const double *value);
 
 /**
- * Gets the iv from a MangoImageMeta.
+ * Gets the year from a MangoMeta.
  *
- * \returns the iv of a MangoImage.
+ * \param meta
+ * \param out receives what year a MangoFile is from.
  */
-extern int  * mangoimgmeta_iv(MangoImageMeta);
+enum mango_status mangometa_get_year(const struct mango_meta *meta, struct IntOption *out);
 
 /**
- * Gets the iv size from a MangoImageMeta.
+ * Sets the year of a MangoMeta.
  *
- * \returns the size of the  iv of a MangoImage.
+ * \param meta
+ * \param value can be NULL
  */
-extern int  * mangoimgmeta_iv_size(MangoImageMeta);
+enum mango_status mangometa_set_year(const struct mango_meta *meta, const short *value);
 
-// ------------------------------------------------------------------------------------------------
-// Mango Meta
-// ------------------------------------------------------------------------------------------------
+/**
+ * Frees a MangoImage, NULL gets ignored.
+ *
+ * \param img the image you want to free.
+ */
+void mangoimg_free(struct mango_image *img);
 
 /**
- * Gets the title from a MangoMeta.
+ * Creates a new MangoImage out of a file from the harddrive.
  *
- * \returns the title of a MangoFile.
+ * \param path the path to the image file, you should prefer full paths, tho relative paths should work too
+ * \param out receives the image
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED,
+ * MANGO_STATUS_UNSUPPORTED if it isn't an image or another error.
  */
-extern char * mangometa_get_title(MangoMeta meta);
+enum mango_status mangoimg_from_path(const char *path,
+                                     struct mango_image **out);
 
 /**
- * Sets the title of a MangoMeta.
+ * Creates a new MangoImage out of image data which is already in memory.
+ *
+ * The format of the image is detected from its content, the name is only used if that fails.
+ * The data is copied, the buffer can be freed afterwards.
+ *
+ * \param data pointer to the encoded image (png, jpg, webp, avif or jxl)
+ * \param length the length of the data in bytes
+ * \param name the filename which gets stored in the metadata of the image
+ * \param out receives the image
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the format isn't supported,
+ * MANGO_STATUS_INVALID_ARGUMENT if the name isn't utf-8 or MANGO_STATUS_NULL_POINTER
  */
-extern void mangometa_set_title(MangoMeta meta, char *value);
+enum mango_status mangoimg_from_buffer(const uint8_t *data,
+                                       size_t length,
+                                       const char *name,
+                                       struct mango_image **out);
 
 /**
- * Gets the author from a MangoMeta.
+ * Copies the actual data of the imagefile stored inside of the MangoImage.
+ *
+ * The ImageData struct has pointer to the data and the size of the data (in bytes),
+ * use those to read all of those bytes.
  *
- * \returns the author of a MangoFile.
+ * \param image
+ * \param out receives the data, it has to be freed with \link mango_imagedata_free \endlink
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern char * mangometa_get_author(MangoMeta meta);
+enum mango_status mangoimg_get_image_data(const struct mango_image *image, struct ImageData *out);
 
 /**
- * Sets the author of a MangoMeta.
+ * Returns the actual data of the image file stored inside of the MangoImage, encoded as an base64 string.
+ *
+ * You aren't supposed to call this, it creates an overhead,
+ * this mainly exists because I couldn't get python to do what I want.
+ *
+ * Use mangoimg_get_image_data() instead!
+ *
+ * \param image
+ * \param out receives the image data encoded as base64
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
  */
-extern void mangometa_set_author(MangoMeta, char *);
+enum mango_status mangoimg_get_base64_image_data(const struct mango_image *image,
+                                                 char **out);
 
+/**
+ * Gets the metadata of a MangImage.
+ *
+ * Changes to the metadata are changes to the image.
+ *
+ * \param image
+ * \param out receives the metadata, it has to be freed with \link mangoimgmeta_free \endlink
+ *
+ * \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangoimg_get_meta(const struct mango_image *image, struct mango_image_meta **out);
 
 /**
- * Gets the publisher from a MangoMeta.
+ * Compresses the data of an MangoImage
  *
- * \returns the publisher of a MangoFile.
+ * \info Currently only GZIP is supported.
+ * \info You can use \link mango_compression_is_supported \endlink to check if support for a certain type was compiled in.
+ *
+ * \param image
+ * \param type The type of compresseion you want to apply.
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the type is unknown,
+ * MANGO_STATUS_UNSUPPORTED if it isn't compiled in or the image is already compressed or encrypted
+ * or MANGO_STATUS_COMPRESSION
  */
-extern char * mangometa_get_publisher(MangoMeta meta);
+enum mango_status mangoimg_compress(const struct mango_image *image,
+                                    const char *comp_type);
 
 /**
- * Sets the publisher of a MangoMeta.
+ * Uncompresses the data of an compressed MangoImage.
+ *
+ * You don't need to specify a compression type, it is stored in the metadata.
+ *
+ * \param image
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the image isn't compressed or is encrypted,
+ * MANGO_STATUS_COMPRESSION or MANGO_STATUS_INTEGRITY if the result doesn't match its checksum,
+ * the image stays unchanged then
  */
-extern void mangometa_set_publisher(MangoMeta, char *);
+enum mango_status mangoimg_uncompress(const struct mango_image *image);
 
+/**
+ * Encrypt the data of an MangoImage
+ *
+ * \info Currently only "AES128" & "AES256" are supported
+ * \info You can use \link mango_encryption_is_supported \endlink to check if support for a certain type was compiled in.
+ *
+ * \param image
+ * \param type The type of encryption you want to apply.
+ * \param password The password you want to use securiong the encryption.
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the type is unknown,
+ * MANGO_STATUS_UNSUPPORTED if it isn't compiled in or the image is already encrypted
+ * or MANGO_STATUS_CRYPTO
+ */
+enum mango_status mangoimg_encrypt(const struct mango_image *image,
+                                   const char *enc_type,
+                                   const char *password);
 
 /**
- * Gets the source from a MangoMeta.
+ * Decrypt the data of an encrypted MangoImage.
+ *
+ * You don't need to specify a type, it's stored in the Metadata
+ *
+ * \param image
+ * \param password
  *
- * \returns the source of a MangoFile.
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the image isn't encrypted, MANGO_STATUS_CRYPTO
+ * or MANGO_STATUS_INTEGRITY if the result doesn't match its checksum, e.g. because the password
+ * is wrong, the image stays unchanged then
  */
-extern char * mangometa_get_source(MangoMeta meta);
+enum mango_status mangoimg_decrypt(const struct mango_image *image,
+                                   const char *password);
 
 /**
- * Sets the source of a MangoMeta.
+ * Saves the MangoImage to a file.
+ *
+ * \param image the image to save
+ * \param filename the path/filename of the file to be created
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_IO or MANGO_STATUS_NULL_POINTER
  */
-extern void mangometa_set_source(MangoMeta, char *);
+enum mango_status mangoimg_save(const struct mango_image *image,
+                                const char *filename);
 
+/**
+ * Frees a MangoImageMeta, NULL gets ignored.
+ */
+void mangoimgmeta_free(struct mango_image_meta *meta);
 
 /**
- * Gets the translation from a MangoMeta.
+ * Gets the compression type from a MangoImageMeta.
  *
- * \returns the translation of a MangoFile.
+ * \param meta
+ * \param out receives the compression type of a MangoImage, NULL if it isn't compressed.
  */
-extern char * mangometa_get_translation(MangoMeta meta);
+enum mango_status mangoimgmeta_compression(const struct mango_image_meta *meta, char **out);
 
 /**
- * Sets the translation of a MangoMeta.
+ * Gets the encryption type from a MangoImageMeta.
+ *
+ * \param meta
+ * \param out receives the encryption type of a MangoImage, NULL if it isn't encrypted.
  */
-extern void mangometa_set_translation(MangoMeta, char *);
+enum mango_status mangoimgmeta_encryption(const struct mango_image_meta *meta, char **out);
 
+/**
+ * Gets the checksum from a MangoImageMeta.
+ *
+ * \param meta
+ * \param out receives the checksum of a MangoImage.
+ */
+enum mango_status mangoimgmeta_checksum(const struct mango_image_meta *meta, char **out);
 
 /**
- * Gets the language from a MangoMeta.
+ * Gets the mime type from a MangoImageMeta.
  *
- * \returns the language of a MangoFile in a short form of 2-3 uppercase characters.
+ * \param meta
+ * \param out receives the mime type of a MangoImage, like "JPEG".
  */
-extern char * mangometa_get_language(MangoMeta meta);
+enum mango_status mangoimgmeta_mime(const struct mango_image_meta *meta, char **out);
 
 /**
- * Sets the language of a MangoMeta.
+ * Gets the filename from a MangoImageMeta.
  *
  * \param meta
- * \param value must be a valid Language, please check the mangofmt docs for the Language Enum.
+ * \param out receives the filename of a MangoImage.
  */
-extern void mangometa_set_language(MangoMeta meta, char *value);
+enum mango_status mangoimgmeta_filename(const struct mango_image_meta *meta, char **out);
 
+/**
+ * Copies the iv from a MangoImageMeta.
+ *
+ * \param meta
+ * \param out receives the iv of a MangoImage, pointing to NULL if it isn't encrypted.
+ * It has to be freed with \link mango_imagedata_free \endlink.
+ */
+enum mango_status mangoimgmeta_iv(const struct mango_image_meta *meta, struct ImageData *out);
 
 /**
- * Gets the volume from a MangoMeta.
+ * Sets the filename of a MangoImageMeta.
  *
- * \returns what volume a MangoFile contains.
+ * \param meta
+ * \param value the new filename, it can't be NULL
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if it isn't utf-8
+ * or MANGO_STATUS_NULL_POINTER
  */
-extern IntOption mangometa_get_volume(MangoMeta meta);
+enum mango_status mangoimgmeta_set_filename(const struct mango_image_meta *meta, const char *value);
 
 /**
- * Sets the volume of a MangoMeta.
+ * Gets what kind of page a MangoImage is.
  *
  * \param meta
- * \param value can be NULL
+ * \param out receives "FRONT_COVER", "STORY", "CREDITS", "ADVERTISEMENT", "BACK_COVER", "OTHER"
+ * or NULL if it is unknown.
  */
-extern void mangometa_set_volume(MangoMeta meta, short *value);
+enum mango_status mangoimgmeta_page_kind(const struct mango_image_meta *meta, char **out);
 
+/**
+ * Sets what kind of page a MangoImage is.
+ *
+ * \param meta
+ * \param value one of the kinds listed at \link mangoimgmeta_page_kind \endlink, NULL unsets it.
+ *
+ * \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the kind is unknown
+ * or MANGO_STATUS_NULL_POINTER
+ */
+enum mango_status mangoimgmeta_set_page_kind(const struct mango_image_meta *meta,
+                                             const char *value);
 
 /**
- * Gets the chapter from a MangoMeta.
+ * Gets if a MangoImage is a double-page spread.
+ *
+ * \param meta
+ * \param out receives true if the page should be shown on its own.
+ */
+enum mango_status mangoimgmeta_double_page(const struct mango_image_meta *meta, bool *out);
+
+/**
+ * Marks a MangoImage as a double-page spread or a normal page.
+ */
+enum mango_status mangoimgmeta_set_double_page(const struct mango_image_meta *meta, bool value);
+
+/**
+ * Gets the label from a MangoImageMeta.
  *
- * \returns what chapter a MangoFile contains.
+ * \param meta
+ * \param out receives the bookmark of a MangoImage, like the title of a chapter starting on it.
  */
-extern IntOption mangometa_get_chapter(MangoMeta meta);
+enum mango_status mangoimgmeta_label(const struct mango_image_meta *meta, char **out);
 
 /**
- * Sets the chapter of a MangoMeta.
+ * Sets the label of a MangoImageMeta.
+ */
+enum mango_status mangoimgmeta_set_label(const struct mango_image_meta *meta, const char *value);
+
+/**
+ * Gets the caption from a MangoImageMeta.
  *
  * \param meta
- * \param value can be NULL
+ * \param out receives the alternative text describing a MangoImage.
  */
-extern void mangometa_set_chapter(MangoMeta meta, short *value);
+enum mango_status mangoimgmeta_caption(const struct mango_image_meta *meta, char **out);
 
+/**
+ * Sets the caption of a MangoImageMeta.
+ */
+enum mango_status mangoimgmeta_set_caption(const struct mango_image_meta *meta, const char *value);
 
 /**
- * Gets the year from a MangoMeta.
+ * Gets the perceptual hash from a MangoImageMeta.
  *
- * \returns what year a MangoFile is from.
+ * \param meta
+ * \param out receives the hash as 16 hex digits,
+ * NULL if the image wasn't imported with support for perceptual hashes.
  */
-extern IntOption mangometa_get_year(MangoMeta meta);
+enum mango_status mangoimgmeta_perceptual_hash(const struct mango_image_meta *meta, char **out);
 
 /**
- * Sets the year of a MangoMeta.
+ * Gets the checksum of the file a MangoImage was transcoded from.
  *
  * \param meta
- * \param value can be NULL
+ * \param out receives the checksum of the source file, NULL if the image wasn't changed.
  */
-extern void mangometa_set_year(MangoMeta meta, short *value);
+enum mango_status mangoimgmeta_original_checksum(const struct mango_image_meta *meta, char **out);
 
+/**
+ * Gets the mime type of the file a MangoImage was transcoded from.
+ *
+ * \param meta
+ * \param out receives the mime type of the source file, NULL if the image wasn't changed.
+ */
+enum mango_status mangoimgmeta_original_mime(const struct mango_image_meta *meta, char **out);
 
 #ifdef __cplusplus
-}
-#endif
+} // extern "C"
+#endif // __cplusplus
 
+#endif /* LIBMANGO_H */
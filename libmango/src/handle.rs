@@ -0,0 +1,202 @@
+//! The opaque handles handed out to C and helpers to read the arguments of exported functions.
+//!
+//! Every handle owns a reference counted, locked value, so handles can be used from multiple
+//! threads and a metadata handle keeps its file or image alive. A handle never points into
+//! another value, it stays valid until it gets freed, no matter what happens to the others.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::slice;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use mangofmt::meta::{MangoImageMetadata, MangoMetadata};
+use mangofmt::{MangoFile, MangoImage};
+
+use status::{Failure, FfiResult};
+
+type Shared<T> = Arc<Mutex<T>>;
+
+/// Locks a value, a panic during an earlier call doesn't make it unusable.
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Repesents a MangoFile sturct from the mangofmt rust library.
+///
+/// A MangoFile contains an instance of ManoMeta and can conatain multiple MangoImages.
+pub struct FileHandle {
+    file: Shared<MangoFile>,
+}
+
+impl FileHandle {
+    pub fn into_raw(file: MangoFile) -> *mut FileHandle {
+        Box::into_raw(Box::new(FileHandle {
+            file: Arc::new(Mutex::new(file)),
+        }))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, MangoFile> {
+        lock(&self.file)
+    }
+
+    /// Creates a handle for the metadata of this file.
+    pub fn meta(&self) -> *mut MetaHandle {
+        Box::into_raw(Box::new(MetaHandle::File(self.file.clone())))
+    }
+}
+
+/// Repesents a MangoImage sturct from the mangofmt rust library.
+///
+/// A MangoImage conatains an instance of MangoImageMeta.
+/// It always owns its data, images taken out of a MangoFile are copies.
+pub struct ImageHandle {
+    image: Shared<MangoImage>,
+}
+
+impl ImageHandle {
+    pub fn into_raw(image: MangoImage) -> *mut ImageHandle {
+        Box::into_raw(Box::new(ImageHandle {
+            image: Arc::new(Mutex::new(image)),
+        }))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, MangoImage> {
+        lock(&self.image)
+    }
+
+    /// Creates a handle for the metadata of this image.
+    pub fn meta(&self) -> *mut ImageMetaHandle {
+        Box::into_raw(Box::new(ImageMetaHandle {
+            image: self.image.clone(),
+        }))
+    }
+}
+
+/// Repesents a MangoMeta sturct from the mangofmt rust library.
+///
+/// A MangoMeta instance either belongs to a MangoFile and keeps the file alive,
+/// or was created on its own with \link mangometa_new \endlink or \link mangometa_copy \endlink.
+pub enum MetaHandle {
+    /// The metadata of a file, changes are changes to the file.
+    File(Shared<MangoFile>),
+    Owned(Shared<MangoMetadata>),
+}
+
+impl MetaHandle {
+    pub fn into_raw(meta: MangoMetadata) -> *mut MetaHandle {
+        Box::into_raw(Box::new(MetaHandle::Owned(Arc::new(Mutex::new(meta)))))
+    }
+
+    pub fn with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut MangoMetadata) -> R,
+    {
+        match *self {
+            MetaHandle::File(ref file) => f(lock(file).get_meta_mut()),
+            MetaHandle::Owned(ref meta) => f(&mut lock(meta)),
+        }
+    }
+}
+
+/// Repesents a MangoImageMeta sturct from the mangofmt rust library.
+///
+/// A MangoImageMeta instance always belongs to a MangoImage, it keeps the image alive.
+pub struct ImageMetaHandle {
+    image: Shared<MangoImage>,
+}
+
+impl ImageMetaHandle {
+    pub fn with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut MangoImageMetadata) -> R,
+    {
+        f(lock(&self.image).get_meta_mut())
+    }
+}
+
+//----------------------------------------------------------------------------------------
+// Arguments
+//----------------------------------------------------------------------------------------
+
+/// Dereferences a handle passed in from C.
+pub fn get<'a, T>(pointer: *const T, name: &str) -> FfiResult<&'a T> {
+    unsafe { pointer.as_ref() }.ok_or_else(|| Failure::null_pointer(name))
+}
+
+/// Frees a handle, NULL gets ignored.
+pub fn free<T: ?Sized>(pointer: *mut T) {
+    if !pointer.is_null() {
+        drop(unsafe { Box::from_raw(pointer) });
+    }
+}
+
+/// Frees a string handed over to C, NULL gets ignored.
+pub fn free_string(pointer: *mut c_char) {
+    if !pointer.is_null() {
+        drop(unsafe { CString::from_raw(pointer) });
+    }
+}
+
+/// Reads an optional value passed in from C, NULL is None.
+pub fn optional<T: Copy>(pointer: *const T) -> Option<T> {
+    unsafe { pointer.as_ref() }.cloned()
+}
+
+/// Reads a buffer passed in from C.
+pub fn bytes<'a>(pointer: *const u8, length: usize, name: &str) -> FfiResult<&'a [u8]> {
+    if pointer.is_null() {
+        return Err(Failure::null_pointer(name));
+    }
+
+    Ok(unsafe { slice::from_raw_parts(pointer, length) })
+}
+
+/// Reads an array of strings passed in from C.
+pub fn strings<'a>(
+    pointer: *const *const c_char,
+    count: usize,
+    name: &str,
+) -> FfiResult<Vec<&'a str>> {
+    if pointer.is_null() {
+        return Err(Failure::null_pointer(name));
+    }
+
+    unsafe { slice::from_raw_parts(pointer, count) }
+        .iter()
+        .map(|&string| self::string(string, name))
+        .collect()
+}
+
+/// Reads a string passed in from C.
+pub fn string<'a>(pointer: *const c_char, name: &str) -> FfiResult<&'a str> {
+    optional_string(pointer, name)?.ok_or_else(|| Failure::null_pointer(name))
+}
+
+/// Reads a string passed in from C, NULL is None.
+pub fn optional_string<'a>(pointer: *const c_char, name: &str) -> FfiResult<Option<&'a str>> {
+    if pointer.is_null() {
+        return Ok(None);
+    }
+
+    unsafe { CStr::from_ptr(pointer) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| Failure::invalid_argument(format!("{} is not valid utf-8", name)))
+}
+
+/// The place a result gets written to, it is checked before the work is done.
+pub struct Out<T>(*mut T);
+
+impl<T> Out<T> {
+    pub fn new(pointer: *mut T) -> FfiResult<Self> {
+        if pointer.is_null() {
+            return Err(Failure::null_pointer("out"));
+        }
+
+        Ok(Out(pointer))
+    }
+
+    pub fn set(self, value: T) {
+        unsafe { self.0.write(value) }
+    }
+}
@@ -1,21 +1,29 @@
+extern crate log;
 extern crate mangofmt;
+mod handle;
+mod logging;
+mod progress;
+mod status;
 mod util;
 
-use std::ffi::{CStr, CString};
-use std::fs;
-use std::io;
-use std::os::raw::{c_char, c_int, c_short};
+use std::os::raw::{c_char, c_double, c_int, c_short, c_void};
 use std::path::Path;
-use std::slice;
+use std::ptr;
 
-use mangofmt::meta::MangoImageMetadata;
-use mangofmt::meta::MangoMetadata;
+use mangofmt::meta::{MangoImageMetadata, MangoMetadata};
 use mangofmt::MangoFile;
 use mangofmt::MangoImage;
+use mangofmt::Serialization;
+
+use handle::{FileHandle, ImageHandle, ImageMetaHandle, MetaHandle, Out};
+use logging::{MangoLogCallback, MangoLogLevel};
+use progress::{CallbackHandler, MangoProgressCallback};
+use status::{guard, guard_or, Failure, FfiResult, MangoStatus};
 
 //----------------------------------------------------------------------------------------
 // Helper Structs
 //----------------------------------------------------------------------------------------
+/// Bytes handed over to C, like the data of an image.
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct ImageData {
@@ -23,6 +31,26 @@ pub struct ImageData {
     pub length: usize,
 }
 
+impl ImageData {
+    fn new(data: Vec<u8>) -> ImageData {
+        // For simplicity, make the Vec into a boxed slice.
+        // This way we do not have to think of capacity and length separately.
+        // This is zero-cost if the global allocator is worth its salt.
+        let slice: Box<[u8]> = data.into_boxed_slice();
+
+        // Save the length now because otherwise we have to do it unsafely.
+        let length = slice.len();
+
+        // Retrieve the raw pointer from the boxed slice. The cast is because we cannot
+        // transmit a pointer to a slice (which has unstable ABI) to C, and we have the
+        // length anyway.
+        let pointer = Box::into_raw(slice) as *mut u8;
+
+        ImageData { pointer, length }
+    }
+}
+
+/// This struct maps to a rust Option containing an int.
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct IntOption {
@@ -30,796 +58,1753 @@ pub struct IntOption {
     pub present: c_int,
 }
 
-//----------------------------------------------------------------------------------------
-// Support Checks
-//----------------------------------------------------------------------------------------
-#[no_mangle]
-pub extern "C" fn mango_encryption_is_supported(enc_type: *const c_char) -> bool {
-    let enc_type_str = unsafe { CStr::from_ptr(enc_type).to_str() };
-
-    if enc_type_str.is_ok() {
-        let e_type = util::to_enc_type(enc_type_str.unwrap().to_string());
-        return match e_type {
-            Some(value) => value.is_supported(),
-            None => false,
-        };
+impl IntOption {
+    fn new(value: Option<c_int>) -> IntOption {
+        match value {
+            Some(value) => IntOption {
+                value,
+                present: 1, /* true */
+            },
+            None => IntOption {
+                value: 0,
+                present: 0, /* false */
+            },
+        }
     }
+}
 
-    false
+/// This struct maps to a rust Option containing a double.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct FloatOption {
+    pub value: c_double,
+    pub present: c_int,
 }
 
+//----------------------------------------------------------------------------------------
+// Version
+//----------------------------------------------------------------------------------------
+/// The version of the C API this header describes.
+///
+/// It gets increased whenever a change breaks programs compiled against an older version,
+/// compare it with \link mango_abi_version \endlink to check if the loaded library matches.
+pub const MANGO_ABI_VERSION: u32 = 1;
+
+/// Gets the version of libmango, like "0.6.0".
+///
+/// \returns the version, it belongs to libmango and must not be freed.
 #[no_mangle]
-pub extern "C" fn mango_compression_is_supported(comp_type: *const c_char) -> bool {
-    let comp_type_str = unsafe { CStr::from_ptr(comp_type).to_str() };
-
-    if comp_type_str.is_ok() {
-        let c_type = util::to_comp_type(comp_type_str.unwrap().to_string());
-        return match c_type {
-            Some(value) => value.is_supported(),
-            None => false,
-        };
-    }
+pub extern "C" fn mango_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
 
-    false
+/// Gets the version of the C API the loaded library implements.
+///
+/// \returns the \link MANGO_ABI_VERSION \endlink libmango was built with.
+#[no_mangle]
+pub extern "C" fn mango_abi_version() -> u32 {
+    MANGO_ABI_VERSION
 }
 
 //----------------------------------------------------------------------------------------
-// Mango File
+// Errors & Memory
 //----------------------------------------------------------------------------------------
+/// Describes why the last call on this thread failed.
+///
+/// \returns the message, NULL if nothing failed yet.
+/// It belongs to libmango and stays valid until the next call on this thread fails.
 #[no_mangle]
-pub extern "C" fn mangofile_new() -> *mut MangoFile {
-    Box::into_raw(Box::new(MangoFile::new()))
+pub extern "C" fn mango_last_error_message() -> *const c_char {
+    guard_or(ptr::null(), status::last_error_message)
 }
 
+/// Frees a string returned by libmango, NULL gets ignored.
 #[no_mangle]
-pub extern "C" fn mangofile_free(pointer: *mut MangoFile) {
-    if pointer.is_null() {
-        return;
-    }
-
-    unsafe {
-        Box::from_raw(pointer);
-    }
+pub extern "C" fn mango_string_free(string: *mut c_char) {
+    guard_or((), || {
+        handle::free_string(string);
+    })
 }
 
+/// Frees data returned by libmango, an ImageData pointing to NULL gets ignored.
 #[no_mangle]
-pub extern "C" fn mangofile_add_image_by_path(pointer: *mut MangoFile, path: *const c_char) -> i8 {
-    if pointer.is_null() {
-        return -42;
-    }
+pub extern "C" fn mango_imagedata_free(data: ImageData) {
+    guard_or((), || {
+        let ImageData { pointer, length } = data;
+        if pointer.is_null() {
+            return;
+        }
 
-    let file: &mut MangoFile = unsafe { &mut *pointer };
+        // Re-create the boxed slice from the pointer and length, and drop it.
+        // This will deallocate the memory.
+        handle::free(ptr::slice_from_raw_parts_mut(pointer, length));
+    })
+}
 
-    let c_str = unsafe { CStr::from_ptr(path) };
+/// Passes the log messages of libmango to a callback instead of dropping them.
+///
+/// libmango never writes to stdout, stderr or any file on its own,
+/// messages are only produced while a callback is set.
+///
+/// \param callback gets called with the level, the module the message comes from, the message
+/// and *user_data*. It can be called from any thread using libmango,
+/// the strings are only valid during the call. NULL stops passing messages on.
+/// \param max_level the least important level which gets passed to *callback*
+/// \param user_data gets passed to *callback* as it is, can be NULL
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_UNSUPPORTED if the application
+/// installed its own rust logger.
+#[no_mangle]
+pub extern "C" fn mango_set_log_callback(
+    callback: MangoLogCallback,
+    max_level: MangoLogLevel,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| logging::set_callback(callback, max_level, user_data))
+}
 
-    let path_str = c_str.to_str().unwrap();
-    let error = file.add_image_by_path(Path::new(&path_str.to_owned()));
+//----------------------------------------------------------------------------------------
+// Support Checks
+//----------------------------------------------------------------------------------------
+/// Checks if support for encryption was compiled in.
+///
+/// \param enc_type the encryption type you want to check.
+///
+/// \returns if it is supported, false if the type is unknown or NULL.
+#[no_mangle]
+pub extern "C" fn mango_encryption_is_supported(enc_type: *const c_char) -> bool {
+    guard_or(false, || match handle::string(enc_type, "type") {
+        Ok(value) => util::to_enc_type(value.to_string()).is_some_and(|e| e.is_supported()),
+        Err(_) => false,
+    })
+}
 
-    match error {
-        Ok(()) => 0,
-        Err(err) => match err.kind() {
-            io::ErrorKind::PermissionDenied => 1,
-            _ => -1,
-        },
-    }
+/// Checks if support for compression was compiled in.
+///
+/// \param comp_type the compression type you want to check.
+///
+/// \returns if it is supported, false if the type is unknown or NULL.
+#[no_mangle]
+pub extern "C" fn mango_compression_is_supported(comp_type: *const c_char) -> bool {
+    guard_or(false, || match handle::string(comp_type, "type") {
+        Ok(value) => util::to_comp_type(value.to_string()).is_some_and(|c| c.is_supported()),
+        Err(_) => false,
+    })
 }
 
+//----------------------------------------------------------------------------------------
+// Mango File
+//----------------------------------------------------------------------------------------
+/// Creates a new MangoFile.
+///
+/// \returns the newly created MangoFile.
 #[no_mangle]
-pub extern "C" fn mangofile_add_image(pointer: *mut MangoFile, img_pointer: *mut MangoImage) -> i8 {
-    if pointer.is_null() {
-        return -42;
-    }
+pub extern "C" fn mangofile_new() -> *mut FileHandle {
+    guard_or(ptr::null_mut(), || FileHandle::into_raw(MangoFile::new()))
+}
 
-    let file: &mut MangoFile = unsafe { &mut *pointer };
+/// Frees a MangoFile, NULL gets ignored.
+///
+/// MangoMeta handles of the file stay valid, they have to be freed on their own.
+///
+/// \param file the file you want to free.
+#[no_mangle]
+pub extern "C" fn mangofile_free(file: *mut FileHandle) {
+    guard_or((), || handle::free(file))
+}
 
-    if img_pointer.is_null() {
-        return -42;
-    }
+/// Adds an image file to a MangoFile.
+///
+/// \param file
+/// \param path
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED,
+/// MANGO_STATUS_UNSUPPORTED if it isn't an image or another error.
+#[no_mangle]
+pub extern "C" fn mangofile_add_image_by_path(
+    file: *const FileHandle,
+    path: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+
+        file.lock().add_image_by_path(Path::new(path))?;
+        Ok(())
+    })
+}
 
-    let img = unsafe { &mut *img_pointer };
+/// Adds a copy of a MangoImage to a MangoFile.
+///
+/// \param file
+/// \param image
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_add_image(
+    file: *const FileHandle,
+    image: *const ImageHandle,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let image = handle::get(image, "image")?.lock().clone();
+
+        file.lock().add_image(image);
+        Ok(())
+    })
+}
 
-    file.add_image(img.clone());
+/// Gets a copy of an image of a MangoFile.
+///
+/// Changes to the copy don't affect the file,
+/// use \link mangofile_set_image \endlink to store them in the file.
+///
+/// \param file
+/// \param index
+/// \param out receives the image, it has to be freed with \link mangoimg_free \endlink
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_get_image(
+    file: *const FileHandle,
+    index: usize,
+    out: *mut *mut ImageHandle,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let out = Out::new(out)?;
+
+        let file = file.lock();
+        let img = file
+            .get_image(index)
+            .ok_or_else(|| Failure::out_of_range(index, file.image_count()))?;
+
+        out.set(ImageHandle::into_raw(img.clone()));
+        Ok(())
+    })
+}
 
-    0
+/// Replaces an image of a MangoFile with a copy of *image*.
+///
+/// \note you can't add a new image to a MangoFile with this function.
+///
+/// \param file
+/// \param image
+/// \param index must be less than the image count of *file*
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_set_image(
+    file: *const FileHandle,
+    image: *const ImageHandle,
+    index: usize,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let image = handle::get(image, "image")?.lock().clone();
+
+        let mut file = file.lock();
+        let count = file.image_count();
+        match file.get_image_mut(index) {
+            Some(old) => {
+                *old = image;
+                Ok(())
+            }
+            None => Err(Failure::out_of_range(index, count)),
+        }
+    })
 }
 
+/// Inserts a copy of a MangoImage at a specific index and shifts all images after it to the right.
+///
+/// \param file
+/// \param image
+/// \param index must not be bigger than the image count of *file*,
+///              the image count itself adds the image at the end
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_get_image(pointer: *mut MangoFile, index: usize) -> *mut MangoImage {
-    let file: &mut MangoFile = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangofile_insert_image(
+    file: *const FileHandle,
+    image: *const ImageHandle,
+    index: usize,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let image = handle::get(image, "image")?.lock().clone();
+
+        file.lock().insert_image(index, image)?;
+        Ok(())
+    })
+}
 
-    let img = file.get_image_mut(index);
-    let p_mut: *mut MangoImage = img;
-    p_mut
+/// Removes an image of a MangoFile and shifts all images after it to the left.
+///
+/// \param file
+/// \param index must be less than the image count of *file*
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_remove_image(file: *const FileHandle, index: usize) -> MangoStatus {
+    guard(|| {
+        let mut file = handle::get(file, "file")?.lock();
+        let count = file.image_count();
+
+        match file.remove_image(index) {
+            Some(_) => Ok(()),
+            None => Err(Failure::out_of_range(index, count)),
+        }
+    })
 }
 
+/// Moves an image to another index, the images in between get shifted.
+///
+/// \param file
+/// \param from the index of the image
+/// \param to the index the image should have afterwards
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_set_image(
-    file_pointer: *mut MangoFile,
-    img_pointer: *mut MangoImage,
-    index: usize,
-) -> usize {
-    let file: &mut MangoFile = unsafe {
-        assert!(!file_pointer.is_null());
-        &mut *file_pointer
-    };
-
-    let img: &mut MangoImage = unsafe {
-        assert!(!img_pointer.is_null());
-        &mut *img_pointer
-    };
-
-    let mut imgs = file.get_images();
-    if index == 0 || index <= imgs.len() - 1 {
-        imgs[index] = img.clone();
-        file.set_images(imgs);
-        return 1;
-    }
+pub extern "C" fn mangofile_move_image(
+    file: *const FileHandle,
+    from: usize,
+    to: usize,
+) -> MangoStatus {
+    guard(|| {
+        handle::get(file, "file")?.lock().move_image(from, to)?;
+        Ok(())
+    })
+}
 
-    0
+/// Swaps two images of a MangoFile.
+///
+/// \param file
+/// \param a
+/// \param b
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_OUT_OF_RANGE or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_swap_images(
+    file: *const FileHandle,
+    a: usize,
+    b: usize,
+) -> MangoStatus {
+    guard(|| {
+        handle::get(file, "file")?.lock().swap_images(a, b)?;
+        Ok(())
+    })
 }
 
+/// Reverses the order of all images of a MangoFile.
+///
+/// \param file
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_remove_image(file_pointer: *mut MangoFile, index: usize) -> usize {
-    let file: &mut MangoFile = unsafe {
-        assert!(!file_pointer.is_null());
-        &mut *file_pointer
-    };
+pub extern "C" fn mangofile_reverse_images(file: *const FileHandle) -> MangoStatus {
+    guard(|| {
+        handle::get(file, "file")?.lock().reverse_images();
+        Ok(())
+    })
+}
 
-    let mut imgs = file.get_images();
-    if imgs.len() > 0 && (index == 0 || index <= imgs.len() - 1) {
-        imgs.remove(index);
-        file.set_images(imgs);
-        return 1;
-    }
+/// Removes all images of a MangoFile for which *keep* returns false.
+///
+/// *keep* gets called once for every image, in order.
+/// It gets a copy of the image, which is freed afterwards,
+/// the index it had before any image got removed and *user_data*.
+///
+/// \param file
+/// \param keep decides if an image is kept
+/// \param user_data gets passed to *keep* as it is, can be NULL
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_NULL_POINTER or MANGO_STATUS_INVALID_ARGUMENT
+/// if *keep* added or removed images of the file.
+#[no_mangle]
+pub extern "C" fn mangofile_retain_images(
+    file: *const FileHandle,
+    keep: Option<extern "C" fn(*mut ImageHandle, usize, *mut c_void) -> bool>,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let keep = keep.ok_or_else(|| Failure::null_pointer("keep"))?;
+
+        // the callback runs without holding the lock, so it can use the file too
+        let images: Vec<MangoImage> = file.lock().images().cloned().collect();
+        let decisions: Vec<bool> = images
+            .into_iter()
+            .enumerate()
+            .map(|(index, img)| {
+                let img = ImageHandle::into_raw(img);
+                let result = keep(img, index, user_data);
+                handle::free(img);
+                result
+            })
+            .collect();
+
+        let mut file = file.lock();
+        if file.image_count() != decisions.len() {
+            return Err(Failure::invalid_argument(
+                "the images of the file were changed while keep was running",
+            ));
+        }
 
-    0
+        // the callback got the index the image had before anything got removed
+        let mut decisions = decisions.into_iter();
+        file.retain(|_| decisions.next().unwrap_or(true));
+        Ok(())
+    })
 }
 
+/// Gets the count of all Images of a MangoFile.
+///
+/// \param file
+/// \param out receives the image count
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_get_image_count(pointer: *mut MangoFile) -> usize {
-    let file: &mut MangoFile = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangofile_get_image_count(
+    file: *const FileHandle,
+    out: *mut usize,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        Out::new(out)?.set(file.lock().image_count());
+        Ok(())
+    })
+}
 
-    let imgs = file.get_images();
-    imgs.len()
+/// Gets the metadata of a MangoFile.
+///
+/// Changes to the metadata are changes to the file.
+///
+/// \param file
+/// \param out receives the metadata, it has to be freed with \link mangometa_free \endlink
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_get_meta(
+    file: *const FileHandle,
+    out: *mut *mut MetaHandle,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        Out::new(out)?.set(file.meta());
+        Ok(())
+    })
 }
 
+/// Replaces the metadata of a MangoFile with a copy of *meta*.
+///
+/// \param file
+/// \param meta can come from \link mangometa_new \endlink or another file
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_get_meta(pointer: *mut MangoFile) -> *mut MangoMetadata {
-    let file: &mut MangoFile = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-    let meta = file.get_meta_mut();
-    let p_mut: *mut MangoMetadata = meta;
-    p_mut
+pub extern "C" fn mangofile_set_meta(
+    file: *const FileHandle,
+    meta: *const MetaHandle,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let meta = handle::get(meta, "meta")?.with(|meta| meta.clone());
+
+        file.lock().set_meta(meta);
+        Ok(())
+    })
 }
 
 // Save
+/// Saves a MangoFile to the file system.
+///
+/// This Function uses the default format "cbor".
+///
+/// If you want to use one of the other supported
+/// formats specifically  please use one of the following functions:
+///
+/// - \link mangofile_save_cbor \endlink
+/// - \link mangofile_save_json \endlink
+/// - \link mangofile_save_bson \endlink
+///
+/// All formats should use the file ending ".mango"
+/// and can be opened with \link mangofile_open \endlink.
+///
+/// \param file the file to save
+/// \param path the path where the file will get saved to
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_ENCODE, MANGO_STATUS_NOT_FOUND if the directory doesn't
+/// exist, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_WRITE or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_save(file: &mut MangoFile, path_ptr: *mut c_char) -> i16 {
-    if !path_ptr.is_null() {
-        let c_str = unsafe { CStr::from_ptr(path_ptr) };
+pub extern "C" fn mangofile_save(file: *const FileHandle, path: *const c_char) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+
+        file.lock().save(Path::new(path))?;
+        Ok(())
+    })
+}
 
-        if let Ok(value) = c_str.to_str() {
-            let result = file.save(std::path::Path::new(value));
-            if result.is_err() {
-                return util::handle_mangofile_error(result.err().unwrap());
-            }
-        } else {
-            return -1;
-        }
-    }
+/// Saves a MangoFile in the cbor format to the file system.
+///
+/// \note his is currently the default format, because it has the
+/// lowest overhead!
+///
+/// Following formats are also available:
+/// - \link mangofile_save_json \endlink
+/// - \link mangofile_save_bson \endlink
+///
+/// You should save your file with ending ".mango".
+///
+/// \param file the file to save
+/// \param path the path where the file will get saved to
+///
+/// \returns the same statuses as \link mangofile_save \endlink
+#[no_mangle]
+pub extern "C" fn mangofile_save_cbor(file: *const FileHandle, path: *const c_char) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+
+        file.lock().save_cbor(Path::new(path))?;
+        Ok(())
+    })
+}
 
-    0
+/// Saves a MangoFile in the bson format to the file system.
+///
+/// Following formats are also available:
+/// - \link mangofile_save_json \endlink
+/// - \link mangofile_save_cbor \endlink
+///
+/// You should save your file with ending ".mango".
+///
+/// \param file the file to save
+/// \param path the path where the file will get saved to
+///
+/// \returns the same statuses as \link mangofile_save \endlink
+#[no_mangle]
+pub extern "C" fn mangofile_save_bson(file: *const FileHandle, path: *const c_char) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+
+        file.lock().save_bson(Path::new(path))?;
+        Ok(())
+    })
 }
 
+/// Saves a MangoFile in the json format to the file system.
+///
+/// \warning You shouldn't really use this one,
+/// the overhead that gets generated by the base64 encoding is way too big.
+/// This is more meant for debuging purposes.
+///
+/// \note There are currently no plans to depreciate the json format.
+///
+/// Following formats are also available:
+/// - \link mangofile_save_bson \endlink
+/// - \link mangofile_save_cbor \endlink
+///
+/// You should save your file with ending ".mango".
+///
+/// \param file the file to save
+/// \param path the path where the file will get saved to
+///
+/// \returns the same statuses as \link mangofile_save \endlink
 #[no_mangle]
-pub extern "C" fn mangofile_save_cbor(file: &mut MangoFile, path_ptr: *mut c_char) -> i16 {
-    if !path_ptr.is_null() {
-        let c_str = unsafe { CStr::from_ptr(path_ptr) };
-        if let Ok(value) = c_str.to_str() {
-            let result = file.save_cbor(std::path::Path::new(value));
-            if result.is_err() {
-                return util::handle_mangofile_error(result.err().unwrap());
-            }
-        } else {
-            return -1;
-        }
+pub extern "C" fn mangofile_save_json(file: *const FileHandle, path: *const c_char) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+
+        file.lock().save_json(Path::new(path))?;
+        Ok(())
+    })
+}
+
+// Open
+/// opens a MangoFile from the file system.
+///
+/// \note The internal format of the MangoFile does not matter.
+/// This function will detect the format and open it accordingly.
+///
+/// \param path the path to the file
+/// \param out receives the opened file
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_DECODE,
+/// MANGO_STATUS_IO or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_open(path: *const c_char, out: *mut *mut FileHandle) -> MangoStatus {
+    guard(|| {
+        let path = handle::string(path, "path")?;
+        let out = Out::new(out)?;
+
+        let file = MangoFile::open(Path::new(path))?;
+        out.set(FileHandle::into_raw(file));
+        Ok(())
+    })
+}
+
+/// Reads the optional serialization format passed to the buffer functions.
+fn serialization(format: *const c_char) -> FfiResult<Option<Serialization>> {
+    match handle::optional_string(format, "format")? {
+        Some(value) => util::to_serialization(value).map(Some).ok_or_else(|| {
+            Failure::invalid_argument(format!("{} is not a serialization format", value))
+        }),
+        None => Ok(None),
     }
+}
 
-    0
+/// Opens a MangoFile which is already in memory.
+///
+/// The data is copied, the buffer can be freed afterwards.
+///
+/// \param data pointer to the encoded file
+/// \param length the length of the data in bytes
+/// \param format "CBOR", "BSON" or "JSON", NULL detects the format
+/// like \link mangofile_open \endlink does
+/// \param out receives the opened file
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_DECODE, MANGO_STATUS_INVALID_ARGUMENT
+/// if the format is unknown or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangofile_open_buffer(
+    data: *const u8,
+    length: usize,
+    format: *const c_char,
+    out: *mut *mut FileHandle,
+) -> MangoStatus {
+    guard(|| {
+        let bytes = handle::bytes(data, length, "data")?;
+        let format = serialization(format)?;
+        let out = Out::new(out)?;
+
+        let file = match format {
+            Some(format) => MangoFile::from_bytes_as(bytes, format)?,
+            None => MangoFile::from_bytes(bytes)?,
+        };
+        out.set(FileHandle::into_raw(file));
+        Ok(())
+    })
 }
 
+/// Encodes a MangoFile in memory instead of saving it to the file system.
+///
+/// The data is the same \link mangofile_save \endlink and friends would write.
+///
+/// \param file the file to encode
+/// \param format "CBOR", "BSON" or "JSON", NULL uses the default format "CBOR"
+/// \param out receives the encoded file, it has to be freed with \link mango_imagedata_free \endlink
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_ENCODE, MANGO_STATUS_INVALID_ARGUMENT
+/// if the format is unknown or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangofile_save_bson(file: &mut MangoFile, path_ptr: *mut c_char) -> i16 {
-    if !path_ptr.is_null() {
-        let c_str = unsafe { CStr::from_ptr(path_ptr) };
-        if let Ok(value) = c_str.to_str() {
-            let result = file.save_bson(std::path::Path::new(value));
-            if result.is_err() {
-                return util::handle_mangofile_error(result.err().unwrap());
-            }
-        }
-    } else {
-        return -1;
-    }
+pub extern "C" fn mangofile_save_to_buffer(
+    file: *const FileHandle,
+    format: *const c_char,
+    out: *mut ImageData,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let format = serialization(format)?.unwrap_or(Serialization::Cbor);
+        let out = Out::new(out)?;
+
+        let bytes = file.lock().to_bytes(format)?;
+        out.set(ImageData::new(bytes));
+        Ok(())
+    })
+}
 
-    0
+// Progress
+/// Opens a MangoFile like \link mangofile_open \endlink and reports how many bytes were read.
+///
+/// \param path the path to the file
+/// \param callback gets called after every chunk which was read, can be NULL
+/// \param user_data gets passed to *callback* as it is, can be NULL
+/// \param out receives the opened file
+///
+/// \returns the same statuses as \link mangofile_open \endlink or MANGO_STATUS_CANCELLED
+#[no_mangle]
+pub extern "C" fn mangofile_open_with_progress(
+    path: *const c_char,
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+    out: *mut *mut FileHandle,
+) -> MangoStatus {
+    guard(|| {
+        let path = handle::string(path, "path")?;
+        let out = Out::new(out)?;
+
+        let mut handler = CallbackHandler::new(callback, user_data);
+        let file = MangoFile::open_with_progress(Path::new(path), &mut handler)?;
+        out.set(FileHandle::into_raw(file));
+        Ok(())
+    })
 }
 
+/// Saves a MangoFile like \link mangofile_save \endlink and reports how many bytes were written.
+///
+/// A cancelled save leaves the previously saved file untouched.
+///
+/// \param file the file to save
+/// \param path the path where the file will get saved to
+/// \param format "CBOR", "BSON" or "JSON", NULL uses the default format "CBOR"
+/// \param callback gets called after every chunk which was written, can be NULL
+/// \param user_data gets passed to *callback* as it is, can be NULL
+///
+/// \returns the same statuses as \link mangofile_save \endlink,
+/// MANGO_STATUS_INVALID_ARGUMENT if the format is unknown or MANGO_STATUS_CANCELLED
 #[no_mangle]
-pub extern "C" fn mangofile_save_json(file: &mut MangoFile, path_ptr: *mut c_char) -> i16 {
-    if !path_ptr.is_null() {
-        let c_str = unsafe { CStr::from_ptr(path_ptr) };
-        if let Ok(value) = c_str.to_str() {
-            let result = file.save_json(std::path::Path::new(value));
-            if result.is_err() {
-                return util::handle_mangofile_error(result.err().unwrap());
-            }
-        }
-    } else {
-        return -1;
-    }
+pub extern "C" fn mangofile_save_with_progress(
+    file: *const FileHandle,
+    path: *const c_char,
+    format: *const c_char,
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let path = handle::string(path, "path")?;
+        let format = serialization(format)?.unwrap_or(Serialization::Cbor);
+
+        let mut handler = CallbackHandler::new(callback, user_data);
+        file.lock()
+            .save_with_progress(Path::new(path), format, &mut handler)?;
+        Ok(())
+    })
+}
 
-    0
+/// Adds image files to a MangoFile and reports the progress after every image.
+///
+/// Nothing gets added if one of the images can't be read or the import gets cancelled.
+///
+/// \param file the file the images get added to
+/// \param paths the paths of the images, they are added in this order
+/// \param count the number of paths
+/// \param callback gets called after every image, can be NULL
+/// \param user_data gets passed to *callback* as it is, can be NULL
+///
+/// \returns the same statuses as \link mangofile_add_image_by_path \endlink
+/// or MANGO_STATUS_CANCELLED
+#[no_mangle]
+pub extern "C" fn mangofile_import_images(
+    file: *const FileHandle,
+    paths: *const *const c_char,
+    count: usize,
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let paths = handle::strings(paths, count, "paths")?;
+
+        let mut handler = CallbackHandler::new(callback, user_data);
+        file.lock().import_images(&paths, &mut handler)?;
+        Ok(())
+    })
 }
 
-// Open
+/// Compresses every image of a MangoFile which is neither compressed nor encrypted yet.
+///
+/// The images stay as they were if one of them can't be compressed or the operation gets cancelled.
+///
+/// \param file the file whose images get compressed
+/// \param type the type of compression, like \link mangoimg_compress \endlink takes it
+/// \param callback gets called after every image, can be NULL
+/// \param user_data gets passed to *callback* as it is, can be NULL
+///
+/// \returns the same statuses as \link mangoimg_compress \endlink or MANGO_STATUS_CANCELLED
 #[no_mangle]
-#[allow(unused_variables, unused_assignments)]
-pub extern "C" fn mangofile_open(
-    path_pointer: *mut c_char,
-    error_code: *mut std::os::raw::c_int,
-) -> *mut MangoFile {
-    if !path_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(path_pointer) };
-        if let Ok(path) = c_str.to_str() {
-            let file = MangoFile::open(Path::new(path));
-            if file.is_ok() {
-                unsafe {
-                    *error_code = 0;
-                }
-                return Box::into_raw(Box::new(file.unwrap()));
-            } else {
-                unsafe {
-                    *error_code = util::handle_mangofile_error(file.err().unwrap()).into();
-                }
-            }
-        } else {
-            // set error code to -1 because something was wrong with the parameters given
-            unsafe {
-                *error_code = -1;
-            }
-        }
-    }
+pub extern "C" fn mangofile_compress_images(
+    file: *const FileHandle,
+    comp_type: *const c_char,
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let comp_type = handle::string(comp_type, "type")?;
+        let comp_type = util::to_comp_type(comp_type.to_string()).ok_or_else(|| {
+            Failure::invalid_argument(format!("{} is not a compression type", comp_type))
+        })?;
+
+        let mut handler = CallbackHandler::new(callback, user_data);
+        file.lock().compress_images(comp_type, &mut handler)?;
+        Ok(())
+    })
+}
 
-    std::ptr::null_mut()
+/// Encrypts every image of a MangoFile which isn't encrypted yet with the same password.
+///
+/// The images stay as they were if one of them can't be encrypted or the operation gets cancelled.
+///
+/// \param file the file whose images get encrypted
+/// \param type the type of encryption, like \link mangoimg_encrypt \endlink takes it
+/// \param password the password used for every image
+/// \param callback gets called after every image, can be NULL
+/// \param user_data gets passed to *callback* as it is, can be NULL
+///
+/// \returns the same statuses as \link mangoimg_encrypt \endlink or MANGO_STATUS_CANCELLED
+#[no_mangle]
+pub extern "C" fn mangofile_encrypt_images(
+    file: *const FileHandle,
+    enc_type: *const c_char,
+    password: *const c_char,
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+) -> MangoStatus {
+    guard(|| {
+        let file = handle::get(file, "file")?;
+        let password = handle::string(password, "password")?;
+        let enc_type = handle::string(enc_type, "type")?;
+        let enc_type = util::to_enc_type(enc_type.to_string()).ok_or_else(|| {
+            Failure::invalid_argument(format!("{} is not an encryption type", enc_type))
+        })?;
+
+        let mut handler = CallbackHandler::new(callback, user_data);
+        file.lock()
+            .encrypt_images(enc_type, password, &mut handler)?;
+        Ok(())
+    })
 }
 
 //----------------------------------------------------------------------------------------
 // Mango File Metadata
 //----------------------------------------------------------------------------------------
-#[no_mangle]
-pub extern "C" fn mangometa_get_title(pointer: *mut MangoMetadata) -> *mut c_char {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+fn get_meta_string<F>(meta: *const MetaHandle, out: *mut *mut c_char, field: F) -> MangoStatus
+where
+    F: FnOnce(&MangoMetadata) -> Option<String>,
+{
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(util::to_c_string(meta.with(|meta| field(meta))));
+        Ok(())
+    })
+}
 
-    match meta.clone().title {
-        Some(x) => CString::new(x).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+fn set_meta_string<F>(meta: *const MetaHandle, value: *const c_char, field: F) -> MangoStatus
+where
+    F: FnOnce(&mut MangoMetadata, Option<String>),
+{
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional_string(value, "value")?;
+
+        meta.with(|meta| field(meta, value.map(str::to_string)));
+        Ok(())
+    })
 }
 
+/// Creates a new MangoMeta which doesn't belong to any file, all values are unset.
+///
+/// Use \link mangofile_set_meta \endlink to store it in a file.
+///
+/// \returns the newly created MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_set_title(pointer: *mut MangoMetadata, value_pointer: *mut c_char) {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.title = Some(value.to_string());
-        }
-    } else {
-        meta.title = None;
-    }
+pub extern "C" fn mangometa_new() -> *mut MetaHandle {
+    guard_or(ptr::null_mut(), || {
+        MetaHandle::into_raw(MangoMetadata::new())
+    })
 }
 
+/// Copies a MangoMeta, the copy doesn't belong to any file.
+///
+/// \param meta
+/// \param out receives the copy, it has to be freed with \link mangometa_free \endlink
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangometa_get_author(pointer: *mut MangoMetadata) -> *mut c_char {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    match meta.clone().author {
-        Some(x) => CString::new(x).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+pub extern "C" fn mangometa_copy(
+    meta: *const MetaHandle,
+    out: *mut *mut MetaHandle,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(MetaHandle::into_raw(meta.with(|meta| meta.clone())));
+        Ok(())
+    })
 }
 
+/// Frees a MangoMeta, NULL gets ignored.
 #[no_mangle]
-pub extern "C" fn mangometa_set_author(pointer: *mut MangoMetadata, value_pointer: *mut c_char) {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_free(meta: *mut MetaHandle) {
+    guard_or((), || handle::free(meta))
+}
 
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.author = Some(value.to_string());
-        }
-    } else {
-        meta.author = None;
-    }
+/// Gets the title from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the title of a MangoFile.
+#[no_mangle]
+pub extern "C" fn mangometa_get_title(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.title.clone())
 }
 
+/// Sets the title of a MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_get_publisher(pointer: *mut MangoMetadata) -> *mut c_char {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_set_title(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_meta_string(meta, value, |meta, value| meta.title = value)
+}
 
-    match meta.clone().publisher {
-        Some(x) => CString::new(x).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+/// Gets the author from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the author of a MangoFile.
+#[no_mangle]
+pub extern "C" fn mangometa_get_author(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.author.clone())
 }
 
+/// Sets the author of a MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_set_publisher(pointer: *mut MangoMetadata, value_pointer: *mut c_char) {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_set_author(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_meta_string(meta, value, |meta, value| meta.author = value)
+}
 
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.publisher = Some(value.to_string());
-        }
-    } else {
-        meta.publisher = None;
-    }
+/// Gets the publisher from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the publisher of a MangoFile.
+#[no_mangle]
+pub extern "C" fn mangometa_get_publisher(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.publisher.clone())
 }
 
+/// Sets the publisher of a MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_get_source(pointer: *mut MangoMetadata) -> *mut c_char {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_set_publisher(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_meta_string(meta, value, |meta, value| meta.publisher = value)
+}
 
-    match meta.clone().source {
-        Some(x) => CString::new(x).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+/// Gets the source from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the source of a MangoFile.
+#[no_mangle]
+pub extern "C" fn mangometa_get_source(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.source.clone())
 }
 
+/// Sets the source of a MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_set_source(pointer: *mut MangoMetadata, value_pointer: *mut c_char) {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_set_source(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_meta_string(meta, value, |meta, value| meta.source = value)
+}
 
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.source = Some(value.to_string());
-        }
-    } else {
-        meta.source = None;
-    }
+/// Gets the translation from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the translation of a MangoFile.
+#[no_mangle]
+pub extern "C" fn mangometa_get_translation(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.translation.clone())
 }
 
+/// Sets the translation of a MangoMeta.
 #[no_mangle]
-pub extern "C" fn mangometa_get_language(meta: &mut MangoMetadata) -> *mut c_char {
-    match meta.language.clone() {
-        Some(lang) => CString::new(util::from_lang(lang)).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+pub extern "C" fn mangometa_set_translation(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_meta_string(meta, value, |meta, value| meta.translation = value)
 }
 
+/// Gets the language from a MangoMeta.
+///
+/// \param meta
+/// \param out receives the language of a MangoFile as a BCP 47 language tag, like "en" or "pt-BR".
 #[no_mangle]
-pub extern "C" fn mangometa_set_language(meta: &mut MangoMetadata, value_pointer: *mut c_char) {
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.language = util::to_lang(value);
-        }
-    } else {
-        meta.language = None;
-    }
+pub extern "C" fn mangometa_get_language(
+    meta: *const MetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_meta_string(meta, out, |meta| meta.language.clone().map(util::from_lang))
 }
 
+/// Sets the language of a MangoMeta.
+///
+/// The tag gets normalized, "pt_br" will be stored as "pt-BR" for instance.
+/// The values of the old Language enum like "JP" are still accepted and get converted.
+///
+/// \param meta
+/// \param value must be a valid BCP 47 language tag,
+/// otherwise MANGO_STATUS_INVALID_ARGUMENT is returned and the language stays the same.
 #[no_mangle]
-pub extern "C" fn mangometa_get_translation(pointer: *mut MangoMetadata) -> *mut c_char {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangometa_set_language(
+    meta: *const MetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let language = match handle::optional_string(value, "value")? {
+            Some(value) => Some(util::to_lang(value).ok_or_else(|| {
+                Failure::invalid_argument(format!("{} is not a valid language tag", value))
+            })?),
+            None => None,
+        };
 
-    match meta.clone().translation {
-        Some(x) => CString::new(x).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+        meta.with(|meta| meta.language = language);
+        Ok(())
+    })
 }
 
+/// Gets the volume from a MangoMeta.
+///
+/// \param meta
+/// \param out receives what volume a MangoFile contains.
 #[no_mangle]
-pub extern "C" fn mangometa_set_translation(
-    pointer: *mut MangoMetadata,
-    value_pointer: *mut c_char,
-) {
-    let meta: &mut MangoMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            meta.translation = Some(value.to_string());
-        }
-    } else {
-        meta.translation = None;
-    }
+pub extern "C" fn mangometa_get_volume(
+    meta: *const MetaHandle,
+    out: *mut IntOption,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(IntOption::new(
+            meta.with(|meta| meta.volume.map(c_int::from)),
+        ));
+        Ok(())
+    })
 }
 
+/// Sets the volume of a MangoMeta.
+///
+/// \param meta
+/// \param value can be NULL
 #[no_mangle]
-pub extern "C" fn mangometa_get_volume(meta: &mut MangoMetadata) -> IntOption {
-    match &meta.volume {
-        Some(value) => IntOption {
-            value: value.clone().into(),
-            present: 1, /* true */
-        },
-        None => IntOption {
-            value: 0,
-            present: 0, /* flase */
-        },
-    }
+pub extern "C" fn mangometa_set_volume(
+    meta: *const MetaHandle,
+    value: *const c_short,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional(value);
+
+        meta.with(|meta| meta.volume = value);
+        Ok(())
+    })
 }
 
+/// Gets the chapter from a MangoMeta.
+///
+/// Fractional chapters like 10.5 get truncated,
+/// use \link mangometa_get_chapter_number \endlink to get the exact value.
+///
+/// \param meta
+/// \param out receives what chapter a MangoFile contains.
 #[no_mangle]
-pub extern "C" fn mangometa_set_volume(meta: &mut MangoMetadata, value_pointer: *mut c_short) {
-    if !value_pointer.is_null() {
-        unsafe {
-            meta.volume = Some(*value_pointer);
-        }
-    } else {
-        meta.volume = None;
-    }
+pub extern "C" fn mangometa_get_chapter(
+    meta: *const MetaHandle,
+    out: *mut IntOption,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(IntOption::new(
+            meta.with(|meta| meta.chapter.map(|chapter| chapter as c_int)),
+        ));
+        Ok(())
+    })
 }
 
+/// Sets the chapter of a MangoMeta.
+///
+/// \param meta
+/// \param value can be NULL
 #[no_mangle]
-pub extern "C" fn mangometa_get_chapter(meta: &mut MangoMetadata) -> IntOption {
-    match &meta.chapter {
-        Some(value) => IntOption {
-            value: value.clone().into(),
-            present: 1, /* true */
-        },
-        None => IntOption {
-            value: 0,
-            present: 0, /* flase */
-        },
-    }
+pub extern "C" fn mangometa_set_chapter(
+    meta: *const MetaHandle,
+    value: *const c_short,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional(value).map(f64::from);
+
+        meta.with(|meta| meta.chapter = value);
+        Ok(())
+    })
 }
 
+/// Gets the chapter from a MangoMeta, including fractional chapters like 10.5.
+///
+/// \param meta
+/// \param out receives what chapter a MangoFile contains.
 #[no_mangle]
-pub extern "C" fn mangometa_set_chapter(meta: &mut MangoMetadata, value_pointer: *mut c_short) {
-    if !value_pointer.is_null() {
-        unsafe {
-            meta.chapter = Some(*value_pointer);
-        }
-    } else {
-        meta.chapter = None;
-    }
+pub extern "C" fn mangometa_get_chapter_number(
+    meta: *const MetaHandle,
+    out: *mut FloatOption,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(match meta.with(|meta| meta.chapter) {
+            Some(value) => FloatOption {
+                value,
+                present: 1, /* true */
+            },
+            None => FloatOption {
+                value: 0.0,
+                present: 0, /* false */
+            },
+        });
+        Ok(())
+    })
 }
 
+/// Sets the chapter of a MangoMeta, it can be fractional like 10.5.
+///
+/// \param meta
+/// \param value can be NULL
 #[no_mangle]
-pub extern "C" fn mangometa_get_year(meta: &mut MangoMetadata) -> IntOption {
-    match &meta.year {
-        Some(value) => IntOption {
-            value: value.clone().into(),
-            present: 1, /* true */
-        },
-        None => IntOption {
-            value: 0,
-            present: 0, /* flase */
-        },
-    }
+pub extern "C" fn mangometa_set_chapter_number(
+    meta: *const MetaHandle,
+    value: *const c_double,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional(value);
+
+        meta.with(|meta| meta.chapter = value);
+        Ok(())
+    })
 }
 
+/// Gets the year from a MangoMeta.
+///
+/// \param meta
+/// \param out receives what year a MangoFile is from.
 #[no_mangle]
-pub extern "C" fn mangometa_set_year(meta: &mut MangoMetadata, value_pointer: *mut c_short) {
-    if !value_pointer.is_null() {
-        unsafe {
-            meta.year = Some(*value_pointer);
-        }
-    } else {
-        meta.year = None;
-    }
+pub extern "C" fn mangometa_get_year(meta: *const MetaHandle, out: *mut IntOption) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(IntOption::new(meta.with(|meta| meta.year.map(c_int::from))));
+        Ok(())
+    })
+}
+
+/// Sets the year of a MangoMeta.
+///
+/// \param meta
+/// \param value can be NULL
+#[no_mangle]
+pub extern "C" fn mangometa_set_year(
+    meta: *const MetaHandle,
+    value: *const c_short,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional(value);
+
+        meta.with(|meta| meta.year = value);
+        Ok(())
+    })
 }
 
 //----------------------------------------------------------------------------------------
 // Mango Image
 //----------------------------------------------------------------------------------------
+/// Frees a MangoImage, NULL gets ignored.
+///
+/// \param img the image you want to free.
 #[no_mangle]
-pub extern "C" fn mangoimg_free(pointer: *mut MangoImage) {
-    // make sure memory will only be freed once
-    if pointer.is_null() {
-        return;
-    }
-
-    unsafe {
-        Box::from_raw(pointer);
-    }
+pub extern "C" fn mangoimg_free(img: *mut ImageHandle) {
+    guard_or((), || handle::free(img))
 }
 
+/// Creates a new MangoImage out of a file from the harddrive.
+///
+/// \param path the path to the image file, you should prefer full paths, tho relative paths should work too
+/// \param out receives the image
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_NOT_FOUND, MANGO_STATUS_PERMISSION_DENIED,
+/// MANGO_STATUS_UNSUPPORTED if it isn't an image or another error.
 #[no_mangle]
 pub extern "C" fn mangoimg_from_path(
-    value_pointer: *mut c_char,
-    error_code: *mut std::os::raw::c_int,
-) -> *mut MangoImage {
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            fs::write("/tmp/foo", value).expect("Unable to write file");
-
-            use mangofmt::ImageFile;
-
-            let file = ImageFile::open(std::path::Path::new(value));
-            if file.is_ok() {
-                let img = file.unwrap().to_mango_image();
-                //println!("{}", img.clone().get_meta().checksum);
-                unsafe {
-                    *error_code = 0;
-                }
-                return Box::into_raw(Box::new(img));
-            } else {
-                unsafe {
-                    *error_code = match file.err().unwrap().kind() {
-                        io::ErrorKind::NotFound => 1,
-                        _ => -1,
-                    };
-                }
-            }
-        }
-    }
-
-    std::ptr::null_mut()
+    path: *const c_char,
+    out: *mut *mut ImageHandle,
+) -> MangoStatus {
+    guard(|| {
+        let path = handle::string(path, "path")?;
+        let out = Out::new(out)?;
+
+        let file = mangofmt::ImageFile::open(Path::new(path))?;
+        out.set(ImageHandle::into_raw(file.to_mango_image()));
+        Ok(())
+    })
 }
 
+/// Creates a new MangoImage out of image data which is already in memory.
+///
+/// The format of the image is detected from its content, the name is only used if that fails.
+/// The data is copied, the buffer can be freed afterwards.
+///
+/// \param data pointer to the encoded image (png, jpg, webp, avif or jxl)
+/// \param length the length of the data in bytes
+/// \param name the filename which gets stored in the metadata of the image
+/// \param out receives the image
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the format isn't supported,
+/// MANGO_STATUS_INVALID_ARGUMENT if the name isn't utf-8 or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub unsafe extern "C" fn mango_imagedata_free(bytes: ImageData) {
-    let ImageData { pointer, length } = bytes;
-
-    // Re-create the slice from the pointer and length. The cast is because we
-    // are working in terms of a raw pointer, not a mutable reference with the
-    // arbitrary lifetime it would come up with.
-    let slice = slice::from_raw_parts_mut(pointer, length) as *mut _;
-
-    // Re-create the boxed slice, and drop it. This will deallocate t he memory.
-    drop(Box::from_raw(slice));
+pub extern "C" fn mangoimg_from_buffer(
+    data: *const u8,
+    length: usize,
+    name: *const c_char,
+    out: *mut *mut ImageHandle,
+) -> MangoStatus {
+    guard(|| {
+        let bytes = handle::bytes(data, length, "data")?.to_vec();
+        let name = handle::string(name, "name")?;
+        let out = Out::new(out)?;
+
+        out.set(ImageHandle::into_raw(MangoImage::from_bytes(bytes, name)?));
+        Ok(())
+    })
 }
 
+/// Copies the actual data of the imagefile stored inside of the MangoImage.
+///
+/// The ImageData struct has pointer to the data and the size of the data (in bytes),
+/// use those to read all of those bytes.
+///
+/// \param image
+/// \param out receives the data, it has to be freed with \link mango_imagedata_free \endlink
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangoimg_get_image_data(pointer: *mut MangoImage) -> ImageData {
-    let img: &mut MangoImage = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    // For simplicity, make the Vec into a boxed slice.
-    // This way we do not have to think of capacity and length separately.
-    // This is zero-cost if the global allocator is worth its salt.
-    let slice: Box<[u8]> = img.get_image_data().into_boxed_slice();
+pub extern "C" fn mangoimg_get_image_data(
+    image: *const ImageHandle,
+    out: *mut ImageData,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let out = Out::new(out)?;
+
+        out.set(ImageData::new(image.lock().get_image_data()));
+        Ok(())
+    })
+}
 
-    // Save the length now because otherwise we have to do it unsafely.
-    let length = slice.len();
+/// Returns the actual data of the image file stored inside of the MangoImage, encoded as an base64 string.
+///
+/// You aren't supposed to call this, it creates an overhead,
+/// this mainly exists because I couldn't get python to do what I want.
+///
+/// Use mangoimg_get_image_data() instead!
+///
+/// \param image
+/// \param out receives the image data encoded as base64
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangoimg_get_base64_image_data(
+    image: *const ImageHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let out = Out::new(out)?;
+
+        out.set(util::to_c_string(Some(
+            image.lock().get_base64_image_data(),
+        )));
+        Ok(())
+    })
+}
 
-    // Retrieve the raw pointer from the boxed slice. The cast is because we cannot
-    // transmit a pointer to a slice (which has unstable ABI) to C, and we have the
-    // length anyway.
-    let pointer = Box::into_raw(slice) as *mut u8;
+/// Gets the metadata of a MangImage.
+///
+/// Changes to the metadata are changes to the image.
+///
+/// \param image
+/// \param out receives the metadata, it has to be freed with \link mangoimgmeta_free \endlink
+///
+/// \returns MANGO_STATUS_OK or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangoimg_get_meta(
+    image: *const ImageHandle,
+    out: *mut *mut ImageMetaHandle,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        Out::new(out)?.set(image.meta());
+        Ok(())
+    })
+}
 
-    ImageData { pointer, length }
+/// Compresses the data of an MangoImage
+///
+/// \info Currently only GZIP is supported.
+/// \info You can use \link mango_compression_is_supported \endlink to check if support for a certain type was compiled in.
+///
+/// \param image
+/// \param type The type of compresseion you want to apply.
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the type is unknown,
+/// MANGO_STATUS_UNSUPPORTED if it isn't compiled in or the image is already compressed or encrypted
+/// or MANGO_STATUS_COMPRESSION
+#[no_mangle]
+pub extern "C" fn mangoimg_compress(
+    image: *const ImageHandle,
+    comp_type: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let comp_type = handle::string(comp_type, "type")?;
+        let comp_type = util::to_comp_type(comp_type.to_string()).ok_or_else(|| {
+            Failure::invalid_argument(format!("{} is not a compression type", comp_type))
+        })?;
+
+        let mut image = image.lock();
+        *image = image.compress(comp_type)?;
+        Ok(())
+    })
 }
 
+/// Uncompresses the data of an compressed MangoImage.
+///
+/// You don't need to specify a compression type, it is stored in the metadata.
+///
+/// \param image
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the image isn't compressed or is encrypted,
+/// MANGO_STATUS_COMPRESSION or MANGO_STATUS_INTEGRITY if the result doesn't match its checksum,
+/// the image stays unchanged then
 #[no_mangle]
-pub extern "C" fn mangoimg_get_base64_image_data(pointer: *mut MangoImage) -> *mut c_char {
-    let img: &mut MangoImage = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangoimg_uncompress(image: *const ImageHandle) -> MangoStatus {
+    guard(|| {
+        let mut image = handle::get(image, "image")?.lock();
+        let uncompressed = image.uncompress()?;
+        uncompressed.verify()?;
+        *image = uncompressed;
+        Ok(())
+    })
+}
 
-    CString::new(img.get_base64_image_data())
-        .unwrap()
-        .into_raw()
+/// Encrypt the data of an MangoImage
+///
+/// \info Currently only "AES128" & "AES256" are supported
+/// \info You can use \link mango_encryption_is_supported \endlink to check if support for a certain type was compiled in.
+///
+/// \param image
+/// \param type The type of encryption you want to apply.
+/// \param password The password you want to use securiong the encryption.
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the type is unknown,
+/// MANGO_STATUS_UNSUPPORTED if it isn't compiled in or the image is already encrypted
+/// or MANGO_STATUS_CRYPTO
+#[no_mangle]
+pub extern "C" fn mangoimg_encrypt(
+    image: *const ImageHandle,
+    enc_type: *const c_char,
+    password: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let password = handle::string(password, "password")?;
+        let enc_type = handle::string(enc_type, "type")?;
+        let enc_type = util::to_enc_type(enc_type.to_string()).ok_or_else(|| {
+            Failure::invalid_argument(format!("{} is not an encryption type", enc_type))
+        })?;
+
+        let mut image = image.lock();
+        *image = image.clone().encrypt(enc_type, password.to_string())?;
+        Ok(())
+    })
 }
 
+/// Decrypt the data of an encrypted MangoImage.
+///
+/// You don't need to specify a type, it's stored in the Metadata
+///
+/// \param image
+/// \param password
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_UNSUPPORTED if the image isn't encrypted, MANGO_STATUS_CRYPTO
+/// or MANGO_STATUS_INTEGRITY if the result doesn't match its checksum, e.g. because the password
+/// is wrong, the image stays unchanged then
 #[no_mangle]
-pub extern "C" fn mangoimg_get_meta(img: &mut MangoImage) -> *mut MangoImageMetadata {
-    let meta = img.get_meta_mut();
-    let p_mut: *mut MangoImageMetadata = meta;
-    p_mut
+pub extern "C" fn mangoimg_decrypt(
+    image: *const ImageHandle,
+    password: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let password = handle::string(password, "password")?;
+
+        let mut image = image.lock();
+        let decrypted = image.clone().decrypt(password.to_string())?;
+        // a compressed image can only be checked after uncompressing it
+        if decrypted.get_meta_ref().compression.is_none() {
+            decrypted.verify()?;
+        }
+        *image = decrypted;
+        Ok(())
+    })
 }
 
+/// Saves the MangoImage to a file.
+///
+/// \param image the image to save
+/// \param filename the path/filename of the file to be created
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_PERMISSION_DENIED, MANGO_STATUS_IO or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangoimg_compress(pointer: *mut MangoImage, value_pointer: *mut c_char) -> i8 {
-    let img: &mut MangoImage = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangoimg_save(image: *const ImageHandle, filename: *const c_char) -> MangoStatus {
+    guard(|| {
+        let image = handle::get(image, "image")?;
+        let filename = handle::string(filename, "filename")?;
+
+        image.lock().save(filename)?;
+        Ok(())
+    })
+}
 
-    if !value_pointer.is_null() {
-        let c_str = unsafe { CStr::from_ptr(value_pointer) };
-        if let Ok(value) = c_str.to_str() {
-            match util::to_comp_type(value.to_string()) {
-                Some(comptype) => {
-                    return match img.compress_mut(comptype) {
-                        true => 1,
-                        false => 2,
-                    }
-                }
-                None => {
-                    return 2;
-                }
-            }
-        }
-    }
+//----------------------------------------------------------------------------------------
+// Mango Imagemetadata
+//----------------------------------------------------------------------------------------
+/// Copies a string field of a MangoImageMeta into `out`.
+fn get_imgmeta_string<F>(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+    field: F,
+) -> MangoStatus
+where
+    F: FnOnce(&MangoImageMetadata) -> Option<String>,
+{
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(util::to_c_string(meta.with(|meta| field(meta))));
+        Ok(())
+    })
+}
 
-    2
+/// Sets a string field of a MangoImageMeta, NULL clears it.
+fn set_imgmeta_string<F>(
+    meta: *const ImageMetaHandle,
+    value: *const c_char,
+    field: F,
+) -> MangoStatus
+where
+    F: FnOnce(&mut MangoImageMetadata, Option<String>),
+{
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::optional_string(value, "value")?;
+
+        meta.with(|meta| field(meta, value.map(str::to_string)));
+        Ok(())
+    })
 }
 
+/// Frees a MangoImageMeta, NULL gets ignored.
 #[no_mangle]
-pub extern "C" fn mangoimg_uncompress(pointer: *mut MangoImage) -> i8 {
-    let img: &mut MangoImage = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    match img.uncompress_mut() {
-        true => 1,
-        false => 2,
-    }
+pub extern "C" fn mangoimgmeta_free(meta: *mut ImageMetaHandle) {
+    guard_or((), || handle::free(meta))
 }
 
+/// Gets the compression type from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the compression type of a MangoImage, NULL if it isn't compressed.
 #[no_mangle]
-pub extern "C" fn mangoimg_encrypt(
-    image: &mut MangoImage,
-    enc_type: *const c_char,
-    password: *const c_char,
-) -> i8 {
-    let pw = unsafe { CStr::from_ptr(password).to_str() };
-
-    if pw.is_ok() {
-        let enc_type_r = unsafe { CStr::from_ptr(enc_type).to_str() };
-
-        if enc_type_r.is_ok() {
-            let enc = util::to_enc_type(enc_type_r.unwrap().to_string());
-            if enc.is_some() {
-                return match image.encrypt_mut(enc.unwrap(), pw.unwrap().to_string()) {
-                    true => 1,
-                    false => 2,
-                };
-            }
-        }
-    }
+pub extern "C" fn mangoimgmeta_compression(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| {
+        meta.compression.clone().map(util::from_comp_type)
+    })
+}
 
-    2
+/// Gets the encryption type from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the encryption type of a MangoImage, NULL if it isn't encrypted.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_encryption(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| {
+        meta.encryption.clone().map(util::from_enc_type)
+    })
 }
 
+/// Gets the checksum from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the checksum of a MangoImage.
 #[no_mangle]
-pub extern "C" fn mangoimg_decrypt(image: &mut MangoImage, password: *const c_char) -> i8 {
-    let pw = unsafe { CStr::from_ptr(password).to_str() };
+pub extern "C" fn mangoimgmeta_checksum(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| Some(meta.checksum.clone()))
+}
 
-    if pw.is_ok() {
-        return match image.decrypt_mut(pw.unwrap().to_string()) {
-            true => 1,
-            false => 2,
-        };
-    }
+/// Gets the mime type from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the mime type of a MangoImage, like "JPEG".
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_mime(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| Some(util::from_mime(meta.mime)))
+}
 
-    2
+/// Gets the filename from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the filename of a MangoImage.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_filename(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| Some(meta.filename.clone()))
 }
 
+/// Copies the iv from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the iv of a MangoImage, pointing to NULL if it isn't encrypted.
+/// It has to be freed with \link mango_imagedata_free \endlink.
 #[no_mangle]
-pub extern "C" fn mangoimg_save(image: &MangoImage, filename: *const c_char) -> i8 {
-    let name = unsafe { CStr::from_ptr(filename).to_str() };
-    if name.is_ok() {
-        return match image.save(&name.unwrap().to_string()) {
-            Ok(()) => 0,
-            Err(err) => match err.kind() {
-                io::ErrorKind::PermissionDenied => 1,
-                _ => -1,
+pub extern "C" fn mangoimgmeta_iv(
+    meta: *const ImageMetaHandle,
+    out: *mut ImageData,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let out = Out::new(out)?;
+
+        out.set(match meta.with(|meta| meta.iv.clone()) {
+            Some(iv) => ImageData::new(iv),
+            None => ImageData {
+                pointer: ptr::null_mut(),
+                length: 0,
             },
-        };
-    }
+        });
+        Ok(())
+    })
+}
 
-    -1
+/// Sets the filename of a MangoImageMeta.
+///
+/// \param meta
+/// \param value the new filename, it can't be NULL
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if it isn't utf-8
+/// or MANGO_STATUS_NULL_POINTER
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_set_filename(
+    meta: *const ImageMetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let value = handle::string(value, "value")?;
+
+        meta.with(|meta| meta.filename = value.to_string());
+        Ok(())
+    })
 }
 
-//----------------------------------------------------------------------------------------
-// Mango Imagemetadata
-//----------------------------------------------------------------------------------------
+/// Gets what kind of page a MangoImage is.
+///
+/// \param meta
+/// \param out receives "FRONT_COVER", "STORY", "CREDITS", "ADVERTISEMENT", "BACK_COVER", "OTHER"
+/// or NULL if it is unknown.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_compression(pointer: *mut MangoImageMetadata) -> *mut c_char {
-    let meta: &mut MangoImageMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
-
-    match meta.compression.clone() {
-        Some(value) => CString::new(util::from_comp_type(value))
-            .unwrap()
-            .into_raw(),
-        None => std::ptr::null_mut(),
-    }
+pub extern "C" fn mangoimgmeta_page_kind(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| meta.page_kind.map(util::from_page_kind))
 }
 
+/// Sets what kind of page a MangoImage is.
+///
+/// \param meta
+/// \param value one of the kinds listed at \link mangoimgmeta_page_kind \endlink, NULL unsets it.
+///
+/// \returns MANGO_STATUS_OK, MANGO_STATUS_INVALID_ARGUMENT if the kind is unknown
+/// or MANGO_STATUS_NULL_POINTER
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_encryption(pointer: *mut MangoImageMetadata) -> *mut c_char {
-    let meta: &mut MangoImageMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangoimgmeta_set_page_kind(
+    meta: *const ImageMetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        let kind = match handle::optional_string(value, "value")? {
+            Some(value) => Some(util::to_page_kind(value).ok_or_else(|| {
+                Failure::invalid_argument(format!("{} is not a page kind", value))
+            })?),
+            None => None,
+        };
 
-    match meta.encryption.clone() {
-        Some(value) => CString::new(util::from_enc_type(value)).unwrap().into_raw(),
-        None => std::ptr::null_mut(),
-    }
+        meta.with(|meta| meta.page_kind = kind);
+        Ok(())
+    })
+}
+
+/// Gets if a MangoImage is a double-page spread.
+///
+/// \param meta
+/// \param out receives true if the page should be shown on its own.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_double_page(
+    meta: *const ImageMetaHandle,
+    out: *mut bool,
+) -> MangoStatus {
+    guard(|| {
+        let meta = handle::get(meta, "meta")?;
+        Out::new(out)?.set(meta.with(|meta| meta.double_page));
+        Ok(())
+    })
 }
 
+/// Marks a MangoImage as a double-page spread or a normal page.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_checksum(meta: &mut MangoImageMetadata) -> *mut c_char {
-    util::filter_nul_bytes(meta.checksum.clone()).into_raw()
+pub extern "C" fn mangoimgmeta_set_double_page(
+    meta: *const ImageMetaHandle,
+    value: bool,
+) -> MangoStatus {
+    guard(|| {
+        handle::get(meta, "meta")?.with(|meta| meta.double_page = value);
+        Ok(())
+    })
 }
 
+/// Gets the label from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the bookmark of a MangoImage, like the title of a chapter starting on it.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_mime(pointer: *mut MangoImageMetadata) -> *mut c_char {
-    let meta: &mut MangoImageMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangoimgmeta_label(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| meta.label.clone())
+}
 
-    let mime_str = util::from_mime(meta.mime);
+/// Sets the label of a MangoImageMeta.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_set_label(
+    meta: *const ImageMetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_imgmeta_string(meta, value, |meta, value| meta.label = value)
+}
 
-    CString::new(mime_str).unwrap().into_raw()
+/// Gets the caption from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the alternative text describing a MangoImage.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_caption(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| meta.caption.clone())
 }
 
+/// Sets the caption of a MangoImageMeta.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_filename(pointer: *mut MangoImageMetadata) -> *mut c_char {
-    let meta: &mut MangoImageMetadata = unsafe {
-        assert!(!pointer.is_null());
-        &mut *pointer
-    };
+pub extern "C" fn mangoimgmeta_set_caption(
+    meta: *const ImageMetaHandle,
+    value: *const c_char,
+) -> MangoStatus {
+    set_imgmeta_string(meta, value, |meta, value| meta.caption = value)
+}
 
-    CString::new(meta.filename.clone()).unwrap().into_raw()
+/// Gets the perceptual hash from a MangoImageMeta.
+///
+/// \param meta
+/// \param out receives the hash as 16 hex digits,
+/// NULL if the image wasn't imported with support for perceptual hashes.
+#[no_mangle]
+pub extern "C" fn mangoimgmeta_perceptual_hash(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| {
+        meta.perceptual_hash.map(|hash| hash.to_string())
+    })
 }
 
+/// Gets the checksum of the file a MangoImage was transcoded from.
+///
+/// \param meta
+/// \param out receives the checksum of the source file, NULL if the image wasn't changed.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_iv(meta: &MangoImageMetadata) -> *const u8 {
-    meta.iv
-        .as_ref()
-        .map_or(std::ptr::null_mut(), |iv| iv.as_ptr())
+pub extern "C" fn mangoimgmeta_original_checksum(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| {
+        meta.transform
+            .as_ref()
+            .map(|transform| transform.original_checksum.clone())
+    })
 }
 
+/// Gets the mime type of the file a MangoImage was transcoded from.
+///
+/// \param meta
+/// \param out receives the mime type of the source file, NULL if the image wasn't changed.
 #[no_mangle]
-pub extern "C" fn mangoimgmeta_iv_size(meta: &MangoImageMetadata) -> *const u16 {
-    meta.iv
-        .as_ref()
-        .map_or(0 as *const u16, |iv| iv.len() as *const u16)
+pub extern "C" fn mangoimgmeta_original_mime(
+    meta: *const ImageMetaHandle,
+    out: *mut *mut c_char,
+) -> MangoStatus {
+    get_imgmeta_string(meta, out, |meta| {
+        meta.transform
+            .as_ref()
+            .map(|transform| util::from_mime(transform.original_mime))
+    })
 }
@@ -0,0 +1,136 @@
+//! Forwards the log messages of mangofmt and libmango to a callback registered from C.
+
+use std::os::raw::{c_char, c_void};
+use std::sync::{Mutex, RwLock};
+
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+
+use status::{Failure, FfiResult, MangoStatus};
+use util;
+
+/// How important a log message is, or which messages get passed to the callback.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MangoLogLevel {
+    /// no messages at all, only valid as the maximum level
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl MangoLogLevel {
+    fn from_level(level: Level) -> Self {
+        match level {
+            Level::Error => MangoLogLevel::Error,
+            Level::Warn => MangoLogLevel::Warn,
+            Level::Info => MangoLogLevel::Info,
+            Level::Debug => MangoLogLevel::Debug,
+            Level::Trace => MangoLogLevel::Trace,
+        }
+    }
+
+    fn to_filter(self) -> LevelFilter {
+        match self {
+            MangoLogLevel::Off => LevelFilter::Off,
+            MangoLogLevel::Error => LevelFilter::Error,
+            MangoLogLevel::Warn => LevelFilter::Warn,
+            MangoLogLevel::Info => LevelFilter::Info,
+            MangoLogLevel::Debug => LevelFilter::Debug,
+            MangoLogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Receives the log messages, see \link mango_set_log_callback \endlink.
+pub type MangoLogCallback =
+    Option<extern "C" fn(MangoLogLevel, *const c_char, *const c_char, *mut c_void)>;
+
+struct Sink {
+    callback: extern "C" fn(MangoLogLevel, *const c_char, *const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// the user data belongs to whoever registered the callback, libmango only passes it along
+unsafe impl Send for Sink {}
+unsafe impl Sync for Sink {}
+
+struct CallbackLogger {
+    sink: RwLock<Option<Sink>>,
+}
+
+static LOGGER: CallbackLogger = CallbackLogger {
+    sink: RwLock::new(None),
+};
+
+impl Log for CallbackLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        self.sink.read().is_ok_and(|sink| sink.is_some())
+    }
+
+    fn log(&self, record: &Record) {
+        let sink = match self.sink.read() {
+            Ok(sink) => sink,
+            Err(_) => return,
+        };
+
+        if let Some(ref sink) = *sink {
+            let target = util::filter_nul_bytes(record.target().to_string());
+            let message = util::filter_nul_bytes(record.args().to_string());
+            (sink.callback)(
+                MangoLogLevel::from_level(record.level()),
+                target.as_ptr(),
+                message.as_ptr(),
+                sink.user_data,
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger the first time, it can only fail if the application installed its own.
+fn install() -> FfiResult<()> {
+    static INSTALLED: Mutex<bool> = Mutex::new(false);
+
+    let mut installed = INSTALLED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !*installed {
+        log::set_logger(&LOGGER).map_err(|_| {
+            Failure::new(
+                MangoStatus::Unsupported,
+                "the application installed another logger already",
+            )
+        })?;
+        *installed = true;
+    }
+
+    Ok(())
+}
+
+/// Replaces the callback, None stops forwarding messages.
+pub fn set_callback(
+    callback: MangoLogCallback,
+    max_level: MangoLogLevel,
+    user_data: *mut c_void,
+) -> FfiResult<()> {
+    install()?;
+
+    let mut sink = LOGGER
+        .sink
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    *sink = callback.map(|callback| Sink {
+        callback,
+        user_data,
+    });
+    log::set_max_level(match *sink {
+        Some(_) => max_level.to_filter(),
+        None => LevelFilter::Off,
+    });
+    Ok(())
+}
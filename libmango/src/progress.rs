@@ -0,0 +1,97 @@
+//! Passes the progress of long running operations to a callback registered from C.
+
+use std::os::raw::c_void;
+
+use mangofmt::progress::{Control, Progress, ProgressHandler, Stage};
+
+/// What a long running operation is busy with.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MangoProgressStage {
+    /// reading a .mango file
+    Read = 0,
+    /// writing a .mango file
+    Write = 1,
+    /// compressing the images of a file
+    Compress = 2,
+    /// encrypting the images of a file
+    Encrypt = 3,
+    /// reading image files and adding them to a file
+    Import = 4,
+    /// decoding a .mango file which has been read
+    Decode = 5,
+    /// encoding a .mango file before it gets written
+    Encode = 6,
+}
+
+/// How far an operation got, see \link mango_progress_callback \endlink.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct MangoProgress {
+    pub stage: MangoProgressStage,
+    /// false while a file gets read, decoded, encoded or written
+    pub has_page: bool,
+    /// index of the page which was just processed, only valid if *has_page* is true
+    pub page: usize,
+    /// number of pages the operation works on, 0 while they aren't known yet
+    pub page_count: usize,
+    /// bytes processed so far
+    pub bytes: u64,
+    /// bytes which get processed in total, 0 if that isn't known up front
+    pub total_bytes: u64,
+}
+
+/// Receives the progress of an operation and the user data passed along with it.
+///
+/// Returning false cancels the operation, it fails with MANGO_STATUS_CANCELLED
+/// and leaves everything as it was before.
+/// The callback is called on the thread which started the operation,
+/// it must not use the handles the operation works on.
+pub type MangoProgressCallback = Option<extern "C" fn(*const MangoProgress, *mut c_void) -> bool>;
+
+/// Calls the callback passed to an exported function, NULL only runs the operation.
+pub struct CallbackHandler {
+    callback: MangoProgressCallback,
+    user_data: *mut c_void,
+}
+
+impl CallbackHandler {
+    pub fn new(callback: MangoProgressCallback, user_data: *mut c_void) -> Self {
+        CallbackHandler {
+            callback,
+            user_data,
+        }
+    }
+}
+
+impl ProgressHandler for CallbackHandler {
+    fn report(&mut self, progress: &Progress) -> Control {
+        let callback = match self.callback {
+            Some(callback) => callback,
+            None => return Control::Continue,
+        };
+
+        let progress = MangoProgress {
+            stage: match progress.stage {
+                Stage::Read => MangoProgressStage::Read,
+                Stage::Write => MangoProgressStage::Write,
+                Stage::Compress => MangoProgressStage::Compress,
+                Stage::Encrypt => MangoProgressStage::Encrypt,
+                Stage::Import => MangoProgressStage::Import,
+                Stage::Decode => MangoProgressStage::Decode,
+                Stage::Encode => MangoProgressStage::Encode,
+            },
+            has_page: progress.page.is_some(),
+            page: progress.page.unwrap_or(0),
+            page_count: progress.page_count,
+            bytes: progress.bytes,
+            total_bytes: progress.total_bytes.unwrap_or(0),
+        };
+
+        if callback(&progress, self.user_data) {
+            Control::Continue
+        } else {
+            Control::Cancel
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Status codes, error messages and the panic guard every exported function runs in.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use mangofmt::{CompressionError, EncryptionError, Error, ErrorKind};
+
+use util;
+
+/// The result of every function that can fail.
+///
+/// Use \link mango_last_error_message \endlink to get the details.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MangoStatus {
+    /// everything went ok
+    Ok = 0,
+    /// a handle or another required pointer passed in is NULL
+    NullPointer = 1,
+    /// an argument isn't valid, like a string that isn't utf-8 or an unknown type
+    InvalidArgument = 2,
+    /// an image index is out of range
+    OutOfRange = 3,
+    /// a file doesn't exist
+    NotFound = 4,
+    /// a file can't be read or written because of missing permissions
+    PermissionDenied = 5,
+    /// reading or writing failed for another reason
+    Io = 6,
+    /// the data couldn't be decoded, e.g. the file isn't a .mango file or is corrupted
+    Decode = 7,
+    /// the data couldn't be encoded
+    Encode = 8,
+    /// encrypting or decrypting failed
+    Crypto = 9,
+    /// compressing or decompressing failed
+    Compression = 10,
+    /// the data doesn't match its checksum
+    Integrity = 11,
+    /// the format or algorithm isn't supported, wasn't compiled in or can't be applied to the image
+    Unsupported = 12,
+    /// a bug in libmango, please report it
+    Panic = 13,
+    /// the progress callback cancelled the operation
+    Cancelled = 14,
+    /// writing a file failed for another reason, e.g. because the disk is full
+    Write = 15,
+}
+
+/// Why a call failed, the message is kept for `mango_last_error_message`.
+#[derive(Debug)]
+pub struct Failure {
+    status: MangoStatus,
+    message: String,
+}
+
+pub type FfiResult<T> = Result<T, Failure>;
+
+impl Failure {
+    pub fn new<S: Into<String>>(status: MangoStatus, message: S) -> Self {
+        Failure {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn null_pointer(name: &str) -> Self {
+        Failure::new(
+            MangoStatus::NullPointer,
+            format!("{} is a null pointer", name),
+        )
+    }
+
+    pub fn invalid_argument<S: Into<String>>(message: S) -> Self {
+        Failure::new(MangoStatus::InvalidArgument, message)
+    }
+
+    pub fn out_of_range(index: usize, len: usize) -> Self {
+        Failure::new(
+            MangoStatus::OutOfRange,
+            format!("index {} is out of range, there are {} images", index, len),
+        )
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        let status = match error.io_kind() {
+            Some(io::ErrorKind::NotFound) => MangoStatus::NotFound,
+            Some(io::ErrorKind::PermissionDenied) => MangoStatus::PermissionDenied,
+            _ => match error.kind() {
+                ErrorKind::Io => MangoStatus::Io,
+                ErrorKind::Write => MangoStatus::Write,
+                ErrorKind::Decode => MangoStatus::Decode,
+                ErrorKind::Encode => MangoStatus::Encode,
+                ErrorKind::Crypto => MangoStatus::Crypto,
+                ErrorKind::Compression => MangoStatus::Compression,
+                ErrorKind::Integrity => MangoStatus::Integrity,
+                ErrorKind::UnsupportedFormat => MangoStatus::Unsupported,
+                ErrorKind::OutOfRange => MangoStatus::OutOfRange,
+                ErrorKind::Cancelled => MangoStatus::Cancelled,
+            },
+        };
+
+        Failure::new(status, error.to_string())
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Error::from(error).into()
+    }
+}
+
+impl From<CompressionError> for Failure {
+    fn from(error: CompressionError) -> Self {
+        Error::from(error).into()
+    }
+}
+
+impl From<EncryptionError> for Failure {
+    fn from(error: EncryptionError) -> Self {
+        Error::from(error).into()
+    }
+}
+
+//----------------------------------------------------------------------------------------
+// Last Error
+//----------------------------------------------------------------------------------------
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = util::filter_nul_bytes(message);
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// The message of the last failed call on this thread, NULL if nothing failed yet.
+///
+/// It stays valid until the next call on this thread fails.
+pub fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+//----------------------------------------------------------------------------------------
+// Guards
+//----------------------------------------------------------------------------------------
+
+/// Runs the body of an exported function.
+///
+/// Errors and panics are turned into a status, a panic must never unwind into C.
+pub fn guard<F>(body: F) -> MangoStatus
+where
+    F: FnOnce() -> FfiResult<()>,
+{
+    let failure = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return MangoStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(payload) => Failure::new(MangoStatus::Panic, panic_message(payload)),
+    };
+
+    set_last_error(failure.message);
+    failure.status
+}
+
+/// Like [guard], for the few functions that can't fail and don't return a status.
+///
+/// `fallback` gets returned if the body panics anyway.
+pub fn guard_or<T, F>(fallback: T, body: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        set_last_error(panic_message(payload));
+        fallback
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "unknown cause".to_string()),
+    };
+
+    format!("libmango panicked: {}", message)
+}
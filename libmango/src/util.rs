@@ -1,9 +1,11 @@
-use mangofmt::file::{ErrorKind, MangoFileError};
-use mangofmt::meta::Language;
+use mangofmt::meta::{Language, PageKind};
 use mangofmt::CompressionType;
 use mangofmt::EncryptionType;
 use mangofmt::Mime;
+use mangofmt::Serialization;
 use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
 
 pub fn to_comp_type(value: String) -> Option<CompressionType> {
     match value.as_ref() {
@@ -33,48 +35,54 @@ pub fn from_enc_type(value: EncryptionType) -> String {
     }
 }
 
+pub fn to_serialization(value: &str) -> Option<Serialization> {
+    match value {
+        "CBOR" => Some(Serialization::Cbor),
+        "BSON" => Some(Serialization::Bson),
+        "JSON" => Some(Serialization::Json),
+        _ => None,
+    }
+}
+
 pub fn from_mime(value: Mime) -> String {
     match value {
         Mime::JPEG => "JPEG".to_string(),
         Mime::PNG => "PNG".to_string(),
+        Mime::WEBP => "WEBP".to_string(),
+        Mime::AVIF => "AVIF".to_string(),
+        Mime::JXL => "JXL".to_string(),
     }
 }
 
-pub fn handle_mangofile_error(error: MangoFileError) -> i16 {
-    match error.get_kind() {
-        ErrorKind::DecodeError => 1,
-        ErrorKind::EncodeError => 1,
-        ErrorKind::ReadError => 2,
-        ErrorKind::WriteError => 2,
-        ErrorKind::PermissionError => 3,
+pub fn to_page_kind(value: &str) -> Option<PageKind> {
+    match value {
+        "FRONT_COVER" => Some(PageKind::FrontCover),
+        "STORY" => Some(PageKind::Story),
+        "CREDITS" => Some(PageKind::Credits),
+        "ADVERTISEMENT" => Some(PageKind::Advertisement),
+        "BACK_COVER" => Some(PageKind::BackCover),
+        "OTHER" => Some(PageKind::Other),
+        _ => None,
     }
 }
 
-pub fn to_lang(lang: &str) -> Option<Language> {
-    match lang {
-        "EN" => Some(Language::EN),
-        "JP" => Some(Language::JP),
-        "DE" => Some(Language::DE),
-        "FR" => Some(Language::FR),
-        "IT" => Some(Language::IT),
-        "CN" => Some(Language::CN),
-        "ES" => Some(Language::ES),
-        _ => None,
+pub fn from_page_kind(value: PageKind) -> String {
+    match value {
+        PageKind::FrontCover => "FRONT_COVER".to_string(),
+        PageKind::Story => "STORY".to_string(),
+        PageKind::Credits => "CREDITS".to_string(),
+        PageKind::Advertisement => "ADVERTISEMENT".to_string(),
+        PageKind::BackCover => "BACK_COVER".to_string(),
+        PageKind::Other => "OTHER".to_string(),
     }
 }
 
-pub fn from_lang(lang: Language) -> String {
-    let lang_str = match lang {
-        Language::EN => "EN",
-        Language::JP => "JP",
-        Language::DE => "DE",
-        Language::FR => "FR",
-        Language::IT => "IT",
-        Language::CN => "CN",
-        Language::ES => "ES",
-    };
+pub fn to_lang(lang: &str) -> Option<Language> {
+    Language::from_legacy(lang).or_else(|| Language::parse(lang).ok())
+}
 
-    String::from(lang_str)
+pub fn from_lang(lang: Language) -> String {
+    lang.to_string()
 }
 
 pub fn filter_nul_bytes(string: String) -> CString {
@@ -88,3 +96,10 @@ pub fn filter_nul_bytes(string: String) -> CString {
         }
     }
 }
+
+/// Hands a string over to C, it has to be freed with `mango_string_free`.
+pub fn to_c_string(string: Option<String>) -> *mut c_char {
+    string.map_or(ptr::null_mut(), |string| {
+        filter_nul_bytes(string).into_raw()
+    })
+}
@@ -1,6 +1,8 @@
 #ifndef TEST_H
 #define TEST_H
 
+#define ck_assert_ok(call) ck_assert_int_eq(MANGO_STATUS_OK, (call))
+
 Suite * make_file_suite(void);
 Suite * make_image_suite(void);
 
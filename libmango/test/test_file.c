@@ -4,291 +4,716 @@
 #include <stdbool.h>
 #include <check.h>
 #include <string.h>
+#include <pthread.h>
 #include "../libmango.h"
 #include "test.h"
 
+// returns the filename of the image at index, it has to be freed
+static char * filename_at(MangoFile file, size_t index) {
+    MangoImage img;
+    MangoImageMeta meta;
+    char * filename;
+
+    ck_assert_ok(mangofile_get_image(file, index, &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+    ck_assert_ok(mangoimgmeta_filename(meta, &filename));
+
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+    return filename;
+}
+
+static char * checksum_at(MangoFile file, size_t index) {
+    MangoImage img;
+    MangoImageMeta meta;
+    char * checksum;
+
+    ck_assert_ok(mangofile_get_image(file, index, &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+    ck_assert_ok(mangoimgmeta_checksum(meta, &checksum));
+
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+    return checksum;
+}
+
+static size_t image_count(MangoFile file) {
+    size_t count;
+    ck_assert_ok(mangofile_get_image_count(file, &count));
+    return count;
+}
+
 START_TEST(test_compress) {
-    void *file;
-    void *img;
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
+    MangoFile file = mangofile_new();
+    MangoImage img;
+    MangoImageMeta meta;
+    char * compression;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
 
-    img = mangofile_get_image(file, 0);
-    mangoimg_compress(img, "GZIP");
+    // images are copies, the compressed one has to be put back
+    ck_assert_ok(mangofile_get_image(file, 0, &img));
+    ck_assert_ok(mangoimg_compress(img, "GZIP"));
+    ck_assert_ok(mangofile_set_image(file, img, 0));
+    mangoimg_free(img);
 
-    img = mangofile_get_image(file, 0);
-    char * compression = mangoimgmeta_compression(mangoimg_get_meta(img));
+    ck_assert_ok(mangofile_get_image(file, 0, &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+    ck_assert_ok(mangoimgmeta_compression(meta, &compression));
     ck_assert(strcmp(compression, "GZIP") == 0);
+
+    mango_string_free(compression);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_null) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
     char * author = NULL;
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
 
-    mangometa_set_author(meta, "test");
-    mangometa_set_author(meta, author);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+
+    ck_assert_ok(mangometa_set_author(meta, "test"));
+    ck_assert_ok(mangometa_set_author(meta, NULL));
 
-    ck_assert(mangometa_get_author(meta) == NULL );
+    ck_assert_ok(mangometa_get_author(meta, &author));
+    ck_assert(author == NULL);
+
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_title) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * title;
 
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_title(meta, "test"));
 
-    mangometa_set_title(meta, "test");
+    ck_assert_ok(mangometa_get_title(meta, &title));
+    ck_assert(strcmp(title, "test") == 0);
 
-    ck_assert(strcmp(mangometa_get_title(meta), "test") == 0);
+    mango_string_free(title);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_author) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * author;
 
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_author(meta, "test"));
 
-    mangometa_set_author(meta, "test");
+    ck_assert_ok(mangometa_get_author(meta, &author));
+    ck_assert(strcmp(author, "test") == 0);
 
-    ck_assert(strcmp(mangometa_get_author(meta), "test") == 0);
+    mango_string_free(author);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_publisher) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * publisher;
 
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_publisher(meta, "test"));
 
-    mangometa_set_publisher(meta, "test");
+    ck_assert_ok(mangometa_get_publisher(meta, &publisher));
+    ck_assert(strcmp(publisher, "test") == 0);
 
-    ck_assert(strcmp(mangometa_get_publisher(meta), "test") == 0);
+    mango_string_free(publisher);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_translation) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * translation;
 
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_translation(meta, "test"));
 
-    mangometa_set_translation(meta, "test");
+    ck_assert_ok(mangometa_get_translation(meta, &translation));
+    ck_assert(strcmp(translation, "test") == 0);
 
-    ck_assert(strcmp(mangometa_get_translation(meta), "test") == 0);
+    mango_string_free(translation);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_set_source) {
-    void * file;
-    void * meta;
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * source;
 
-    file = mangofile_new();
-    meta = mangofile_get_meta(file);
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_source(meta, "test"));
 
-    mangometa_set_source(meta, "test");
+    ck_assert_ok(mangometa_get_source(meta, &source));
+    ck_assert(strcmp(source, "test") == 0);
 
-    ck_assert(strcmp(mangometa_get_source(meta), "test") == 0);
+    mango_string_free(source);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_volume) {
-    MangoFile file;
-    file = mangofile_new();
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    IntOption opt;
+
+    ck_assert_ok(mangofile_get_meta(file, &meta));
 
-    MangoMeta meta = mangofile_get_meta(file);
+    ck_assert_ok(mangometa_get_volume(meta, &opt));
+    ck_assert(!opt.present);
 
-    IntOption opt1 = mangometa_get_volume(meta);
-    ck_assert(!opt1.present);
-    
     short value = 3;
-    mangometa_set_volume(meta, &value);
+    ck_assert_ok(mangometa_set_volume(meta, &value));
 
-    IntOption opt2 = mangometa_get_volume(meta);
-    ck_assert(opt2.present);
-    ck_assert(opt2.value == 3);
+    ck_assert_ok(mangometa_get_volume(meta, &opt));
+    ck_assert(opt.present);
+    ck_assert(opt.value == 3);
+
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_chapter) {
-    MangoFile file;
-    file = mangofile_new();
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    IntOption opt;
+    FloatOption number;
+
+    ck_assert_ok(mangofile_get_meta(file, &meta));
 
-    MangoMeta meta = mangofile_get_meta(file);
+    ck_assert_ok(mangometa_get_chapter(meta, &opt));
+    ck_assert(!opt.present);
 
-    IntOption opt1 = mangometa_get_chapter(meta);
-    ck_assert(!opt1.present);
-    
     short value = 3;
-    mangometa_set_chapter(meta, &value);
+    ck_assert_ok(mangometa_set_chapter(meta, &value));
 
-    IntOption opt2 = mangometa_get_chapter(meta);
-    ck_assert(opt2.present);
-    ck_assert(opt2.value == 3);
+    ck_assert_ok(mangometa_get_chapter(meta, &opt));
+    ck_assert(opt.present);
+    ck_assert(opt.value == 3);
+
+    double fractional = 10.5;
+    ck_assert_ok(mangometa_set_chapter_number(meta, &fractional));
+
+    ck_assert_ok(mangometa_get_chapter_number(meta, &number));
+    ck_assert(number.present);
+    ck_assert(number.value == 10.5);
+
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_year) {
-    MangoFile file;
-    file = mangofile_new();
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    IntOption opt;
+
+    ck_assert_ok(mangofile_get_meta(file, &meta));
 
-    MangoMeta meta = mangofile_get_meta(file);
+    ck_assert_ok(mangometa_get_year(meta, &opt));
+    ck_assert(!opt.present);
 
-    IntOption opt1 = mangometa_get_year(meta);
-    ck_assert(!opt1.present);
-    
     short value = 2006;
-    mangometa_set_year(meta, &value);
+    ck_assert_ok(mangometa_set_year(meta, &value));
 
-    IntOption opt2 = mangometa_get_year(meta);
-    ck_assert(opt2.present);
-    ck_assert(opt2.value == 2006);
+    ck_assert_ok(mangometa_get_year(meta, &opt));
+    ck_assert(opt.present);
+    ck_assert(opt.value == 2006);
+
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_lang) {
-    MangoFile file;
-    file = mangofile_new();
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * lang;
+
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+
+    ck_assert_ok(mangometa_get_language(meta, &lang));
+    ck_assert(lang == NULL);
+
+    ck_assert_ok(mangometa_set_language(meta, "EN"));
+    ck_assert_ok(mangometa_get_language(meta, &lang));
+    ck_assert(strcmp(lang, "en") == 0);
+    mango_string_free(lang);
 
-    MangoMeta meta = mangofile_get_meta(file);
+    ck_assert_ok(mangometa_set_language(meta, "pt_br"));
+    ck_assert_ok(mangometa_get_language(meta, &lang));
+    ck_assert(strcmp(lang, "pt-BR") == 0);
+    mango_string_free(lang);
 
-    char * lang1 = mangometa_get_language(meta);
-    ck_assert(lang1 == NULL);
-    
-    char * value = "EN";
-    mangometa_set_language(meta, value);
+    // invalid tags are rejected and don't change anything
+    ck_assert_int_eq(MANGO_STATUS_INVALID_ARGUMENT, mangometa_set_language(meta, "not a language"));
+    ck_assert_ok(mangometa_get_language(meta, &lang));
+    ck_assert(strcmp(lang, "pt-BR") == 0);
+    mango_string_free(lang);
 
-    char * lang2 = mangometa_get_language(meta);
-    ck_assert(strcmp(lang2, "EN") == 0);
+    mangometa_free(meta);
+    mangofile_free(file);
 }
 END_TEST
 
-// Save
-START_TEST(test_save) {
-    MangoFile file;
+START_TEST(test_meta_outlives_file) {
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+    char * title;
+
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    mangofile_free(file);
+
+    ck_assert_ok(mangometa_set_title(meta, "still there"));
+    ck_assert_ok(mangometa_get_title(meta, &title));
+    ck_assert(strcmp(title, "still there") == 0);
+
+    mango_string_free(title);
+    mangometa_free(meta);
+}
+END_TEST
+
+START_TEST(test_meta_new) {
+    MangoFile file = mangofile_new();
+    MangoMeta meta = mangometa_new();
+    MangoMeta copy;
+    MangoMeta file_meta;
+    char * title;
+
+    ck_assert_ok(mangometa_set_title(meta, "standalone"));
+    ck_assert_ok(mangometa_copy(meta, &copy));
+    ck_assert_ok(mangometa_set_title(copy, "copy"));
+
+    ck_assert_ok(mangometa_get_title(meta, &title));
+    ck_assert(strcmp(title, "standalone") == 0);
+    mango_string_free(title);
+
+    ck_assert_ok(mangofile_set_meta(file, meta));
+    ck_assert_ok(mangometa_set_title(meta, "changed later"));
+
+    ck_assert_ok(mangofile_get_meta(file, &file_meta));
+    ck_assert_ok(mangometa_get_title(file_meta, &title));
+    ck_assert(strcmp(title, "standalone") == 0);
+    mango_string_free(title);
+
+    mangometa_free(file_meta);
+    mangometa_free(copy);
+    mangometa_free(meta);
+    mangofile_free(file);
+}
+END_TEST
+
+START_TEST(test_null_handles) {
+    size_t count;
     MangoImage img;
 
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_get_image_count(NULL, &count));
+    ck_assert(strcmp(mango_last_error_message(), "file is a null pointer") == 0);
+
+    MangoFile file = mangofile_new();
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_get_image_count(file, NULL));
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_add_image(file, NULL));
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_add_image_by_path(file, NULL));
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_retain_images(file, NULL, NULL));
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_get_image(file, 0, &img));
+
+    // freeing NULL is fine
+    mangofile_free(NULL);
+    mangoimg_free(NULL);
+    mangometa_free(NULL);
+    mangoimgmeta_free(NULL);
+    mango_string_free(NULL);
+    mangofile_free(file);
+}
+END_TEST
+
+// Save
+START_TEST(test_save) {
+    MangoFile file = mangofile_new();
 
-    mangofile_save(file, "testfile.mango");
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangofile_save(file, "testfile.mango"));
 
     // check if the file was created
     FILE * created_file;
     created_file = fopen("testfile.mango", "r");
 
     ck_assert(created_file != NULL);
+    fclose(created_file);
 
     // remove file
     remove("testfile.mango");
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_save_json) {
-    MangoFile file;
-    MangoImage img;
+    MangoFile file = mangofile_new();
 
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
-
-    mangofile_save_json(file, "testfile.json");
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangofile_save_json(file, "testfile.json"));
 
     // check if the file was created
     FILE * created_file;
     created_file = fopen("testfile.json", "r");
 
     ck_assert(created_file != NULL);
-    
+    fclose(created_file);
+
     // remove file
     remove("testfile.json");
+    mangofile_free(file);
 }
 END_TEST
 
 // Open
 START_TEST(test_open) {
-    MangoFile file;
-    MangoImage img;
+    MangoFile file = mangofile_new();
 
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
-    
-    img = mangofile_get_image(file, 0);
-    char * checksum_before = mangoimgmeta_checksum(mangoimg_get_meta(img));
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    char * checksum_before = checksum_at(file, 0);
 
-    mangofile_save(file, "testfile.mango");
-    
+    ck_assert_ok(mangofile_save(file, "testfile.mango"));
+    mangofile_free(file);
     file = NULL;
-    img = NULL;
-    
-    int open_error = -2;
-    file = mangofile_open("testfile.mango", &open_error);
-    ck_assert_int_eq(0, open_error);
-
-    img = mangofile_get_image(file, 0);
-    char * checksum_after = mangoimgmeta_checksum(mangoimg_get_meta(img));
-    
+
+    ck_assert_ok(mangofile_open("testfile.mango", &file));
+    char * checksum_after = checksum_at(file, 0);
+
     // strcmp returns 0 when the strings are equal!
     ck_assert(strcmp(checksum_before, checksum_after) == 0);
-    
-    remove("testfile.mango"); 
+
+    mango_string_free(checksum_before);
+    mango_string_free(checksum_after);
+    mangofile_free(file);
+    remove("testfile.mango");
+}
+END_TEST
+
+START_TEST(test_buffer) {
+    const char *formats[] = {NULL, "CBOR", "BSON", "JSON"};
+    MangoFile file = mangofile_new();
+    MangoMeta meta;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangofile_get_meta(file, &meta));
+    ck_assert_ok(mangometa_set_title(meta, "in memory"));
+    mangometa_free(meta);
+    char * checksum_before = checksum_at(file, 0);
+
+    for (int i = 0; i < 4; i++) {
+        ImageData data;
+        MangoFile opened = NULL;
+        char * title;
+
+        ck_assert_ok(mangofile_save_to_buffer(file, formats[i], &data));
+        ck_assert_ok(mangofile_open_buffer(data.pointer, data.length, formats[i], &opened));
+        mango_imagedata_free(data);
+
+        char * checksum_after = checksum_at(opened, 0);
+        ck_assert(strcmp(checksum_before, checksum_after) == 0);
+        mango_string_free(checksum_after);
+
+        ck_assert_ok(mangofile_get_meta(opened, &meta));
+        ck_assert_ok(mangometa_get_title(meta, &title));
+        ck_assert(strcmp(title, "in memory") == 0);
+        mango_string_free(title);
+        mangometa_free(meta);
+        mangofile_free(opened);
+    }
+
+    ImageData data;
+    MangoFile opened = NULL;
+    ck_assert_int_eq(MANGO_STATUS_INVALID_ARGUMENT, mangofile_save_to_buffer(file, "XML", &data));
+    ck_assert_int_eq(MANGO_STATUS_DECODE, mangofile_open_buffer((const uint8_t *) "text", 4, NULL, &opened));
+    ck_assert(opened == NULL);
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangofile_open_buffer(NULL, 4, NULL, &opened));
+
+    mango_string_free(checksum_before);
+    mangofile_free(file);
+}
+END_TEST
+
+static void count_messages(mango_log_level level, const char *target, const char *message, void *user_data) {
+    ck_assert(level >= MANGO_LOG_LEVEL_ERROR && level <= MANGO_LOG_LEVEL_DEBUG);
+    ck_assert(strncmp(target, "mangofmt", 8) == 0);
+    ck_assert(message != NULL);
+    (*(int *) user_data)++;
+}
+
+START_TEST(test_log_callback) {
+    int messages = 0;
+    MangoFile file = NULL;
+
+    ck_assert_ok(mango_set_log_callback(count_messages, MANGO_LOG_LEVEL_DEBUG, &messages));
+    ck_assert_int_eq(MANGO_STATUS_DECODE, mangofile_open_buffer((const uint8_t *) "text", 4, NULL, &file));
+    ck_assert(messages > 0);
+
+    int before = messages;
+    ck_assert_ok(mango_set_log_callback(NULL, MANGO_LOG_LEVEL_OFF, NULL));
+    ck_assert_int_eq(MANGO_STATUS_DECODE, mangofile_open_buffer((const uint8_t *) "text", 4, NULL, &file));
+    ck_assert_int_eq(before, messages);
+}
+END_TEST
+
+struct progress_counter {
+    int calls;
+    int cancel_after;
+    mango_progress last;
+};
+
+static bool count_progress(const mango_progress *progress, void *user_data) {
+    struct progress_counter *counter = user_data;
+    counter->calls++;
+    counter->last = *progress;
+    return counter->cancel_after == 0 || counter->calls < counter->cancel_after;
+}
+
+START_TEST(test_progress) {
+    MangoFile file = mangofile_new();
+    const char *paths[] = {"test.jpg", "test.jpg", "test.jpg"};
+    struct progress_counter counter = {0, 0};
+    size_t count;
+
+    ck_assert_ok(mangofile_import_images(file, paths, 3, count_progress, &counter));
+    ck_assert_int_eq(3, counter.calls);
+    ck_assert_int_eq(MANGO_PROGRESS_STAGE_IMPORT, counter.last.stage);
+    ck_assert(counter.last.has_page);
+    ck_assert_int_eq(2, counter.last.page);
+    ck_assert_int_eq(3, counter.last.page_count);
+
+    // a cancelled import doesn't add anything
+    counter = (struct progress_counter) {0, 2};
+    ck_assert_int_eq(MANGO_STATUS_CANCELLED, mangofile_import_images(file, paths, 3, count_progress, &counter));
+    ck_assert_int_eq(2, counter.calls);
+    ck_assert_ok(mangofile_get_image_count(file, &count));
+    ck_assert_int_eq(3, count);
+    ck_assert_ok(mangofile_import_images(file, paths, 1, NULL, NULL));
+
+    if (mango_compression_is_supported("GZIP")) {
+        counter = (struct progress_counter) {0, 0};
+        ck_assert_ok(mangofile_compress_images(file, "GZIP", count_progress, &counter));
+        ck_assert_int_eq(4, counter.calls);
+        ck_assert_int_eq(MANGO_PROGRESS_STAGE_COMPRESS, counter.last.stage);
+        ck_assert(counter.last.bytes == counter.last.total_bytes);
+    }
+
+    counter = (struct progress_counter) {0, 0};
+    ck_assert_ok(mangofile_save_with_progress(file, "progress.mango", NULL, count_progress, &counter));
+    ck_assert(counter.calls > 0);
+    ck_assert_int_eq(MANGO_PROGRESS_STAGE_WRITE, counter.last.stage);
+    ck_assert(!counter.last.has_page);
+    ck_assert(counter.last.bytes == counter.last.total_bytes);
+
+    counter = (struct progress_counter) {0, 1};
+    MangoFile opened = NULL;
+    ck_assert_int_eq(MANGO_STATUS_CANCELLED,
+                     mangofile_open_with_progress("progress.mango", count_progress, &counter, &opened));
+    ck_assert(opened == NULL);
+
+    counter = (struct progress_counter) {0, 0};
+    ck_assert_ok(mangofile_open_with_progress("progress.mango", count_progress, &counter, &opened));
+    ck_assert_int_eq(MANGO_PROGRESS_STAGE_DECODE, counter.last.stage);
+    ck_assert_ok(mangofile_get_image_count(opened, &count));
+    ck_assert_int_eq(4, count);
+
+    mangofile_free(opened);
+    mangofile_free(file);
+    remove("progress.mango");
+}
+END_TEST
+
+START_TEST(test_open_fail) {
+    MangoFile file = NULL;
+
+    ck_assert_int_eq(MANGO_STATUS_NOT_FOUND, mangofile_open("does_not_exist.mango", &file));
+    ck_assert(file == NULL);
+    ck_assert(mango_last_error_message() != NULL);
+
+    // a jpg isn't a mango file
+    ck_assert_int_eq(MANGO_STATUS_DECODE, mangofile_open("test.jpg", &file));
+    ck_assert(file == NULL);
 }
 END_TEST
 
 START_TEST(test_set_image) {
-    MangoFile file;
-
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
-    
-    MangoImage img1 = mangofile_get_image(file, 0);
-    char * checksum1 = mangoimgmeta_checksum(mangoimg_get_meta(img1));
-    
-    int err = -10;
-    MangoImage img_to_set = mangoimg_from_path("test2.jpg", &err);
-    mangofile_set_image(file, img_to_set, 0);
-    
-    MangoImage img2 = mangofile_get_image(file, 0);
-    char * checksum2 = mangoimgmeta_checksum(mangoimg_get_meta(img2));
+    MangoFile file = mangofile_new();
+    MangoImage img_to_set;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    char * checksum1 = checksum_at(file, 0);
 
+    ck_assert_ok(mangoimg_from_path("test2.jpg", &img_to_set));
+    ck_assert_ok(mangofile_set_image(file, img_to_set, 0));
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_set_image(file, img_to_set, 1));
+
+    char * checksum2 = checksum_at(file, 0);
     ck_assert(strcmp(checksum1, checksum2) != 0);
+
+    mango_string_free(checksum1);
+    mango_string_free(checksum2);
+    mangoimg_free(img_to_set);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_remove_image) {
-    MangoFile file;
+    MangoFile file = mangofile_new();
+    MangoImage img;
 
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
-    
-    int img_count1 = mangofile_get_image_count(file);
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert(image_count(file) == 1);
 
-    bool success = mangofile_remove_image(file, 0);
-    ck_assert(success == true);
+    // the copy isn't affected by removing the image from the file
+    ck_assert_ok(mangofile_get_image(file, 0, &img));
+    ck_assert_ok(mangofile_remove_image(file, 0));
+    ck_assert(image_count(file) == 0);
 
-    int img_count2 = mangofile_get_image_count(file);
+    ImageData data;
+    ck_assert_ok(mangoimg_get_image_data(img, &data));
+    ck_assert(data.length > 0);
 
-    ck_assert(img_count1 == 1);
-    ck_assert(img_count2 == 0);
+    mango_imagedata_free(data);
+    mangoimg_free(img);
+    mangofile_free(file);
 }
 END_TEST
 
 START_TEST(test_remove_image_fail) {
-    MangoFile file;
+    MangoFile file = mangofile_new();
 
-    file = mangofile_new();
-    mangofile_add_image_by_path(file, "test.jpg");
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_remove_image(file, 1));
+    ck_assert(image_count(file) == 1);
 
-    int success = mangofile_remove_image(file, 1);
-    ck_assert(success == false);
+    mangofile_free(file);
+}
+END_TEST
+
+START_TEST(test_insert_image) {
+    MangoFile file = mangofile_new();
+    MangoImage img;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangoimg_from_path("test2.jpg", &img));
+
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_insert_image(file, img, 2));
+    ck_assert_ok(mangofile_insert_image(file, img, 0));
+    ck_assert(image_count(file) == 2);
+
+    char * filename = filename_at(file, 0);
+    ck_assert(strcmp(filename, "test2.jpg") == 0);
+    mango_string_free(filename);
+
+    MangoImage missing = NULL;
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_get_image(file, 2, &missing));
+    ck_assert(missing == NULL);
+
+    mangoimg_free(img);
+    mangofile_free(file);
+}
+END_TEST
+
+START_TEST(test_move_image) {
+    MangoFile file = mangofile_new();
+    char * filename;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangofile_add_image_by_path(file, "test2.jpg"));
+
+    ck_assert_int_eq(MANGO_STATUS_OUT_OF_RANGE, mangofile_move_image(file, 0, 2));
+    ck_assert_ok(mangofile_move_image(file, 0, 1));
+    filename = filename_at(file, 0);
+    ck_assert(strcmp(filename, "test2.jpg") == 0);
+    mango_string_free(filename);
+
+    ck_assert_ok(mangofile_swap_images(file, 0, 1));
+    filename = filename_at(file, 0);
+    ck_assert(strcmp(filename, "test.jpg") == 0);
+    mango_string_free(filename);
+
+    ck_assert_ok(mangofile_reverse_images(file));
+    filename = filename_at(file, 0);
+    ck_assert(strcmp(filename, "test2.jpg") == 0);
+    mango_string_free(filename);
+
+    mangofile_free(file);
+}
+END_TEST
+
+static bool keep_odd(const MangoImage image, size_t index, void *user_data) {
+    (*(int *) user_data)++;
+    return index % 2 == 1;
+}
+
+START_TEST(test_retain_images) {
+    MangoFile file = mangofile_new();
+    int calls = 0;
+
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    ck_assert_ok(mangofile_add_image_by_path(file, "test2.jpg"));
+    ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+
+    ck_assert_ok(mangofile_retain_images(file, keep_odd, &calls));
+
+    ck_assert(calls == 3);
+    ck_assert(image_count(file) == 1);
+    char * filename = filename_at(file, 0);
+    ck_assert(strcmp(filename, "test2.jpg") == 0);
+
+    mango_string_free(filename);
+    mangofile_free(file);
+}
+END_TEST
+
+static void * add_images(void *file) {
+    for (int i = 0; i < 5; i++) {
+        ck_assert_ok(mangofile_add_image_by_path(file, "test.jpg"));
+    }
+    return NULL;
+}
+
+START_TEST(test_threads) {
+    MangoFile file = mangofile_new();
+    pthread_t threads[4];
+
+    for (int i = 0; i < 4; i++) {
+        pthread_create(&threads[i], NULL, add_images, file);
+    }
+    for (int i = 0; i < 4; i++) {
+        pthread_join(threads[i], NULL);
+    }
+
+    ck_assert(image_count(file) == 20);
+    mangofile_free(file);
+}
+END_TEST
+
+START_TEST(test_version) {
+    ck_assert_int_eq(MANGO_ABI_VERSION, mango_abi_version());
+    ck_assert(mango_version() != NULL);
+    ck_assert(strlen(mango_version()) > 0);
 }
 END_TEST
 
@@ -304,6 +729,7 @@ Suite * file_suite(void) {
     tc_io = tcase_create("IO");
 
     // Core
+    tcase_add_test(tc_core, test_version);
 
     if (mango_compression_is_supported("GZIP")) {
         tcase_add_test(tc_core, test_compress);
@@ -319,15 +745,26 @@ Suite * file_suite(void) {
     tcase_add_test(tc_core, test_chapter);
     tcase_add_test(tc_core, test_year);
     tcase_add_test(tc_core, test_lang);
-    
+    tcase_add_test(tc_core, test_meta_outlives_file);
+    tcase_add_test(tc_core, test_meta_new);
+    tcase_add_test(tc_core, test_null_handles);
+
     tcase_add_test(tc_core, test_set_image);
     tcase_add_test(tc_core, test_remove_image);
     tcase_add_test(tc_core, test_remove_image_fail);
+    tcase_add_test(tc_core, test_insert_image);
+    tcase_add_test(tc_core, test_move_image);
+    tcase_add_test(tc_core, test_retain_images);
+    tcase_add_test(tc_core, test_threads);
 
     // IO
     tcase_add_test(tc_io, test_save);
     tcase_add_test(tc_io, test_save_json);
     tcase_add_test(tc_io, test_open);
+    tcase_add_test(tc_io, test_open_fail);
+    tcase_add_test(tc_io, test_buffer);
+    tcase_add_test(tc_io, test_log_callback);
+    tcase_add_test(tc_io, test_progress);
 
     // add cases to suites
     suite_add_tcase(s, tc_core);
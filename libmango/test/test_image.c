@@ -6,46 +6,168 @@
 #include "../libmango.h"
 #include "test.h"
 
-// TODO: rewrite this
-// check if img is null
 START_TEST(test_create) {
     MangoImage img;
     MangoImageMeta meta;
-    
-    int error = -2;
+    char * filename;
 
-    img = mangoimg_from_path("test.jpg", &error);
-    ck_assert_int_eq(0, error);
+    ck_assert_ok(mangoimg_from_path("test.jpg", &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
 
-    meta = mangoimg_get_meta(img);
-    
     // strcmp returns 0 if the strings are equal
-    ck_assert(strcmp(mangoimgmeta_filename(meta), "test.jpg") == 0);
+    ck_assert_ok(mangoimgmeta_filename(meta, &filename));
+    ck_assert(strcmp(filename, "test.jpg") == 0);
+
+    mango_string_free(filename);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
 }
 END_TEST
 
+START_TEST(test_create_fail) {
+    MangoImage img = NULL;
+
+    ck_assert_int_eq(MANGO_STATUS_NOT_FOUND, mangoimg_from_path("does_not_exist.jpg", &img));
+    ck_assert(img == NULL);
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangoimg_from_path("test.jpg", NULL));
+}
+END_TEST
 
 START_TEST(test_decrypt) {
     MangoImage img;
-    
-    int error  = -2;
-    img = mangoimg_from_path("test.jpg", &error);
-    ck_assert_int_eq(0, error);
-    
+    MangoImageMeta meta;
+    char * enc_type;
+
+    ck_assert_ok(mangoimg_from_path("test.jpg", &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+
     // encrypt
-    mangoimg_encrypt(img, "AES128", "1234567812345678");
-    MangoFile f = mangofile_new();
-    mangofile_add_image(f, img);
-    mangofile_save(f, "lol.mango");
+    ck_assert_int_eq(MANGO_STATUS_INVALID_ARGUMENT, mangoimg_encrypt(img, "ROT13", "1234567812345678"));
+    ck_assert_ok(mangoimg_encrypt(img, "AES128", "1234567812345678"));
+
+    ck_assert_ok(mangoimgmeta_encryption(meta, &enc_type));
+    ck_assert(strcmp(enc_type, "AES128") == 0);
+    mango_string_free(enc_type);
+
+    ImageData iv;
+    ck_assert_ok(mangoimgmeta_iv(meta, &iv));
+    ck_assert(iv.pointer != NULL);
+    ck_assert(iv.length > 0);
+    mango_imagedata_free(iv);
 
-    char* enc_type_en = mangoimgmeta_encryption(mangoimg_get_meta(img));
-    ck_assert(strcmp(enc_type_en, "AES128") == 0);
+    // already encrypted
+    ck_assert_int_eq(MANGO_STATUS_UNSUPPORTED, mangoimg_encrypt(img, "AES128", "1234567812345678"));
 
-    
     // decrypt
-    mangoimg_decrypt(img, "1234567812345678");
-    char* enc_type_de = mangoimgmeta_encryption(mangoimg_get_meta(img));
-    ck_assert(enc_type_de == NULL);
+    ck_assert_ok(mangoimg_decrypt(img, "1234567812345678"));
+    ck_assert_ok(mangoimgmeta_encryption(meta, &enc_type));
+    ck_assert(enc_type == NULL);
+
+    ck_assert_ok(mangoimgmeta_iv(meta, &iv));
+    ck_assert(iv.pointer == NULL);
+
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+}
+END_TEST
+
+START_TEST(test_from_buffer) {
+    FILE *f = fopen("test.jpg", "rb");
+    ck_assert(f != NULL);
+    fseek(f, 0, SEEK_END);
+    long length = ftell(f);
+    fseek(f, 0, SEEK_SET);
+    uint8_t *buffer = malloc(length);
+    ck_assert_int_eq(length, fread(buffer, 1, length, f));
+    fclose(f);
+
+    MangoImage img;
+    ck_assert_ok(mangoimg_from_buffer(buffer, length, "page", &img));
+    free(buffer);
+
+    MangoImageMeta meta;
+    char * filename;
+    char * mime;
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+    ck_assert_ok(mangoimgmeta_filename(meta, &filename));
+    ck_assert_ok(mangoimgmeta_mime(meta, &mime));
+    ck_assert(strcmp(filename, "page") == 0);
+    ck_assert(strcmp(mime, "JPEG") == 0);
+
+    mango_string_free(filename);
+    mango_string_free(mime);
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
+
+    img = NULL;
+    ck_assert_int_eq(MANGO_STATUS_UNSUPPORTED, mangoimg_from_buffer((const uint8_t *) "text", 4, "notes.txt", &img));
+    ck_assert(img == NULL);
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangoimg_from_buffer(NULL, 4, "notes.txt", &img));
+}
+END_TEST
+
+START_TEST(test_image_data) {
+    MangoImage img;
+    ImageData data;
+    char * base64;
+
+    ck_assert_ok(mangoimg_from_path("test.jpg", &img));
+    ck_assert_ok(mangoimg_get_image_data(img, &data));
+    ck_assert_ok(mangoimg_get_base64_image_data(img, &base64));
+
+    // jpegs start with FF D8
+    ck_assert(data.length > 2);
+    ck_assert(data.pointer[0] == 0xFF && data.pointer[1] == 0xD8);
+    ck_assert(strncmp(base64, "/9j/", 4) == 0);
+
+    mango_imagedata_free(data);
+    mango_string_free(base64);
+    mangoimg_free(img);
+}
+END_TEST
+
+START_TEST(test_set_meta) {
+    MangoImage img;
+    MangoImageMeta meta;
+    char * value;
+    bool double_page;
+
+    ck_assert_ok(mangoimg_from_path("test.jpg", &img));
+    ck_assert_ok(mangoimg_get_meta(img, &meta));
+
+    ck_assert_ok(mangoimgmeta_set_filename(meta, "001.jpg"));
+    ck_assert_ok(mangoimgmeta_filename(meta, &value));
+    ck_assert(strcmp(value, "001.jpg") == 0);
+    mango_string_free(value);
+    ck_assert_int_eq(MANGO_STATUS_NULL_POINTER, mangoimgmeta_set_filename(meta, NULL));
+
+    ck_assert_ok(mangoimgmeta_page_kind(meta, &value));
+    ck_assert(value == NULL);
+    ck_assert_ok(mangoimgmeta_set_page_kind(meta, "FRONT_COVER"));
+    ck_assert_ok(mangoimgmeta_page_kind(meta, &value));
+    ck_assert(strcmp(value, "FRONT_COVER") == 0);
+    mango_string_free(value);
+    ck_assert_int_eq(MANGO_STATUS_INVALID_ARGUMENT, mangoimgmeta_set_page_kind(meta, "POSTER"));
+
+    ck_assert_ok(mangoimgmeta_set_double_page(meta, true));
+    ck_assert_ok(mangoimgmeta_double_page(meta, &double_page));
+    ck_assert(double_page);
+
+    ck_assert_ok(mangoimgmeta_set_label(meta, "Chapter 1"));
+    ck_assert_ok(mangoimgmeta_label(meta, &value));
+    ck_assert(strcmp(value, "Chapter 1") == 0);
+    mango_string_free(value);
+
+    ck_assert_ok(mangoimgmeta_set_caption(meta, "a cat"));
+    ck_assert_ok(mangoimgmeta_set_caption(meta, NULL));
+    ck_assert_ok(mangoimgmeta_caption(meta, &value));
+    ck_assert(value == NULL);
+
+    ck_assert_ok(mangoimgmeta_original_checksum(meta, &value));
+    ck_assert(value == NULL);
+
+    mangoimgmeta_free(meta);
+    mangoimg_free(img);
 }
 END_TEST
 
@@ -54,12 +176,16 @@ Suite * image_suite(void) {
     TCase *tc_core;
 
     s = suite_create("MangoImage");
-    
+
     tc_core = tcase_create("Core");
 
     // Core
     tcase_add_test(tc_core, test_create);
-    
+    tcase_add_test(tc_core, test_create_fail);
+    tcase_add_test(tc_core, test_from_buffer);
+    tcase_add_test(tc_core, test_image_data);
+    tcase_add_test(tc_core, test_set_meta);
+
     if (mango_encryption_is_supported("AES256")) {
         tcase_add_test(tc_core, test_decrypt);
     }
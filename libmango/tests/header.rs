@@ -0,0 +1,12 @@
+//! libmango.h gets generated by build.rs, the checked-in copy is what gets installed.
+
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/libmango.h"));
+const CHECKED_IN: &str = include_str!("../libmango.h");
+
+#[test]
+fn header_is_up_to_date() {
+    assert!(
+        GENERATED == CHECKED_IN,
+        "libmango.h is out of date, run `make header` and commit the result"
+    );
+}
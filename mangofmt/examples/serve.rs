@@ -0,0 +1,31 @@
+//! Serves the .mango files of a directory.
+//!
+//! `cargo run --example serve --features server -- <directory> [address]`
+
+extern crate mangofmt;
+
+use mangofmt::server::Server;
+use std::env;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <directory> [address]", args[0]);
+        process::exit(1);
+    }
+
+    let address = args.get(2).map_or("127.0.0.1:8080", String::as_str);
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("couldn't listen on {}: {}", address, e);
+            process::exit(1);
+        }
+    };
+
+    println!("serving {} on http://{}/volumes", args[1], address);
+    Server::new(Path::new(&args[1])).serve(listener);
+}
@@ -0,0 +1,629 @@
+//! Reading and writing MangoFiles with tokio, without blocking the runtime.
+//!
+//! **Feature:** async
+//!
+//! [open_async](../struct.MangoFile.html#method.open_async),
+//! [save_async](../struct.MangoFile.html#method.save_async) and
+//! [read_image_async](../struct.MangoFile.html#method.read_image_async) return the futures of
+//! this module. They have to run on a tokio runtime, decoding and encoding a whole file happens
+//! on its blocking threads, so `poll` only waits for the reader, the writer or that task.
+//!
+//! `read_image_async` only reads the parts of a CBOR or BSON file it needs to find the image,
+//! so single pages can be served from a big file without reading all of it.
+
+use error::{Error, ErrorKind};
+use file::{read_error, save_error, MangoFile, Serialization};
+use image::MangoImage;
+use layout::{MetaRegion, PageCursor, PageRegion};
+use std::cmp;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::panic;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use storage::StoredImage;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use tokio::task::{self, JoinError, JoinHandle};
+
+/// The most bytes which get read at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The size of the blocks `read_image_async` reads while it looks for an image.
+const BLOCK_SIZE: u64 = 4096;
+
+macro_rules! ready {
+    ($poll:expr) => {
+        match $poll {
+            Poll::Ready(value) => value,
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+impl MangoFile {
+    /// Reads a .mango file from an async reader, the serialization format gets detected
+    /// like [open](#method.open) does it.
+    ///
+    /// The reader is read to its end.
+    pub fn open_async<R>(reader: R) -> Open<R>
+    where
+        R: AsyncRead + Unpin,
+    {
+        Open {
+            reader,
+            bytes: Vec::new(),
+            decoding: None,
+        }
+    }
+
+    /// Writes the file to an async writer with the given serialization format.
+    ///
+    /// The future doesn't borrow the file, it works on a copy which shares the image data.
+    /// Unlike [save](#method.save) the data is written directly, a failed write can leave a
+    /// half written file behind.
+    pub fn save_async<W>(&self, writer: W, format: Serialization) -> Save<W>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        Save {
+            writer,
+            file: Some((self.clone(), format)),
+            encoding: None,
+            bytes: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Reads a single image of a .mango file from an async reader, like
+    /// [read_image](#method.read_image) does it.
+    pub fn read_image_async<R>(reader: R, index: usize) -> ReadImage<R>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        ReadImage {
+            reader,
+            index,
+            target: index,
+            cache: BlockCache {
+                len: 0,
+                blocks: HashMap::new(),
+            },
+            pending: None,
+            cursor: None,
+            state: State::Length(SeekTo::new(SeekFrom::End(0))),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+//  Open & Save
+//------------------------------------------------------------------------------
+
+/// Reads and decodes a whole .mango file, see
+/// [open_async](../struct.MangoFile.html#method.open_async).
+pub struct Open<R> {
+    reader: R,
+    bytes: Vec<u8>,
+    /// Decodes the bytes once all of them were read.
+    decoding: Option<JoinHandle<Result<MangoFile, Error>>>,
+}
+
+impl<R: AsyncRead + Unpin> Future for Open<R> {
+    type Output = Result<MangoFile, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(ref mut decoding) = this.decoding {
+                return Pin::new(decoding).poll(cx).map(joined);
+            }
+
+            let read = ready!(poll_append(
+                &mut this.reader,
+                cx,
+                &mut this.bytes,
+                CHUNK_SIZE
+            ))
+            .map_err(read_error)?;
+            if read == 0 {
+                let bytes = mem::take(&mut this.bytes);
+                this.decoding = Some(task::spawn_blocking(move || MangoFile::from_bytes(&bytes)));
+            }
+        }
+    }
+}
+
+/// Writes an encoded .mango file, see [save_async](../struct.MangoFile.html#method.save_async).
+pub struct Save<W> {
+    writer: W,
+    /// The file until its encoding starts with the first poll.
+    file: Option<(MangoFile, Serialization)>,
+    encoding: Option<JoinHandle<Result<Vec<u8>, Error>>>,
+    bytes: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> Future for Save<W> {
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        if let Some((file, format)) = this.file.take() {
+            this.encoding = Some(task::spawn_blocking(move || file.to_bytes(format)));
+        }
+        if let Some(ref mut encoding) = this.encoding {
+            let encoded = ready!(Pin::new(encoding).poll(cx));
+            this.encoding = None;
+            this.bytes = joined(encoded)?;
+        }
+
+        while this.written < this.bytes.len() {
+            let written =
+                ready!(Pin::new(&mut this.writer).poll_write(cx, &this.bytes[this.written..]))
+                    .map_err(save_error)?;
+            if written == 0 {
+                return Poll::Ready(Err(save_error(io::ErrorKind::WriteZero.into())));
+            }
+            this.written += written;
+        }
+
+        ready!(Pin::new(&mut this.writer).poll_flush(cx)).map_err(save_error)?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+//------------------------------------------------------------------------------
+//  Single Images
+//------------------------------------------------------------------------------
+
+/// Reads a single image, see [read_image_async](../struct.MangoFile.html#method.read_image_async).
+///
+/// The image gets searched by the same code [read_image](../struct.MangoFile.html#method.read_image)
+/// uses, it runs on the blocks of the file which were read so far.
+/// Whenever it needs a block which wasn't read yet, the block gets read and the search continues
+/// with the last image it got to. Only the bytes of the image itself are read in one go.
+pub struct ReadImage<R> {
+    reader: R,
+    index: usize,
+    /// The image which is searched right now, the one the requested image shares its data with
+    /// once that was found.
+    target: usize,
+    cache: BlockCache,
+    /// The requested image while the image with its data gets read.
+    pending: Option<StoredImage>,
+    /// Where the search continues once the block it was missing was read.
+    cursor: Option<PageCursor>,
+    state: State,
+}
+
+enum State {
+    /// Finding out how long the file is.
+    Length(SeekTo),
+    /// Reading a block the search needs.
+    Block(u64, ReadAt),
+    /// Reading the image which was found.
+    Image(PageRegion, ReadAt),
+    /// Reading a whole JSON file.
+    Json(ReadAt),
+    /// Decoding the JSON file.
+    JsonDecode(JoinHandle<Result<MangoImage, Error>>),
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> Future for ReadImage<R> {
+    type Output = Result<MangoImage, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        loop {
+            let next = match this.state {
+                State::Length(ref mut seek) => {
+                    this.cache.len = ready!(seek.poll(&mut this.reader, cx)).map_err(read_error)?;
+                    this.search()?
+                }
+                State::Block(block, ref mut read) => {
+                    ready!(read.poll(&mut this.reader, cx)).map_err(read_error)?;
+                    this.cache.blocks.insert(block, mem::take(&mut read.buf));
+                    this.search()?
+                }
+                State::Image(region, ref mut read) => {
+                    ready!(read.poll(&mut this.reader, cx)).map_err(read_error)?;
+                    let stored = region.decode(&read.buf)?;
+                    match this.found(stored)? {
+                        Some(image) => return Poll::Ready(Ok(image)),
+                        None => this.search()?,
+                    }
+                }
+                State::Json(ref mut read) => {
+                    ready!(read.poll(&mut this.reader, cx)).map_err(read_error)?;
+                    let bytes = mem::take(&mut read.buf);
+                    let index = this.index;
+                    State::JsonDecode(task::spawn_blocking(move || {
+                        let file = MangoFile::from_bytes_as(&bytes, Serialization::Json)?;
+                        file.get_image(index).cloned().ok_or_else(|| {
+                            Error::new(
+                                ErrorKind::OutOfRange,
+                                format!("page {} is out of range", index),
+                            )
+                        })
+                    }))
+                }
+                State::JsonDecode(ref mut decoding) => {
+                    return Pin::new(decoding).poll(cx).map(joined);
+                }
+            };
+            this.state = next;
+        }
+    }
+}
+
+impl<R> ReadImage<R> {
+    /// Searches the target with the cached blocks and decides what to read next.
+    ///
+    /// The search continues where it stopped when a block was missing, only the images after
+    /// the last one it got to are searched again.
+    fn search(&mut self) -> Result<State, Error> {
+        let mut reader = CacheReader {
+            cache: &self.cache,
+            position: 0,
+            missing: None,
+        };
+
+        let mut cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => {
+                let encoding = match MetaRegion::find(&mut reader) {
+                    Ok(Some(region)) => region.encoding,
+                    // neither CBOR nor BSON
+                    Ok(None) => return Ok(State::Json(ReadAt::new(0, self.cache.len))),
+                    Err(e) => return missing(&reader, e),
+                };
+
+                reader.position = 0;
+                reader.missing = None;
+                match PageCursor::find(&mut reader, encoding) {
+                    Ok(cursor) => cursor,
+                    Err(e) => return missing(&reader, e),
+                }
+            }
+        };
+
+        reader.missing = None;
+        let result = cursor.seek_page(&mut reader, self.target);
+        self.cursor = Some(cursor);
+        match result {
+            Ok(region) => Ok(State::Image(region, ReadAt::new(region.start, region.len))),
+            Err(e) => missing(&reader, e),
+        }
+    }
+
+    /// Handles the image which was read, returns None if the image with its data is needed.
+    fn found(&mut self, stored: StoredImage) -> Result<Option<MangoImage>, Error> {
+        if let Some(pending) = self.pending.take() {
+            let shared = stored.into_image(None)?;
+            return pending.into_image(Some(&shared)).map(Some);
+        }
+
+        match stored.data_ref() {
+            // the data always belongs to an image which comes before
+            Some(shared) if shared < self.index => {
+                self.pending = Some(stored);
+                self.target = shared;
+                Ok(None)
+            }
+            _ => stored.into_image(None).map(Some),
+        }
+    }
+}
+
+/// Reads the block the search is missing, or fails with the error of the search.
+fn missing(reader: &CacheReader, error: Error) -> Result<State, Error> {
+    match reader.missing {
+        Some(block) => Ok(State::Block(
+            block,
+            ReadAt::new(block * BLOCK_SIZE, BLOCK_SIZE),
+        )),
+        None => Err(error),
+    }
+}
+
+/// The blocks of a file which were read so far.
+struct BlockCache {
+    len: u64,
+    blocks: HashMap<u64, Vec<u8>>,
+}
+
+/// Reads from the cached blocks and remembers the first block which wasn't read yet.
+struct CacheReader<'a> {
+    cache: &'a BlockCache,
+    position: u64,
+    missing: Option<u64>,
+}
+
+impl<'a> Read for CacheReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.cache.len {
+            return Ok(0);
+        }
+
+        let block = self.position / BLOCK_SIZE;
+        let data = match self.cache.blocks.get(&block) {
+            Some(data) => data,
+            None => {
+                self.missing = self.missing.or(Some(block));
+                return Err(io::Error::other("the block wasn't read yet"));
+            }
+        };
+
+        let offset = (self.position - block * BLOCK_SIZE) as usize;
+        let len = cmp::min(buf.len(), data.len().saturating_sub(offset));
+        buf[..len].copy_from_slice(&data[offset..offset + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<'a> Seek for CacheReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.cache.len.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+//------------------------------------------------------------------------------
+//  Helpers
+//------------------------------------------------------------------------------
+
+/// A seek which is in progress.
+struct SeekTo {
+    pos: SeekFrom,
+    started: bool,
+}
+
+impl SeekTo {
+    fn new(pos: SeekFrom) -> Self {
+        SeekTo {
+            pos,
+            started: false,
+        }
+    }
+
+    fn poll<R: AsyncSeek + Unpin>(
+        &mut self,
+        reader: &mut R,
+        cx: &mut Context,
+    ) -> Poll<io::Result<u64>> {
+        if !self.started {
+            Pin::new(&mut *reader).start_seek(self.pos)?;
+            self.started = true;
+        }
+        Pin::new(reader).poll_complete(cx)
+    }
+}
+
+/// Reads `len` bytes at an offset, or less if the file ends before.
+struct ReadAt {
+    seek: SeekTo,
+    seeked: bool,
+    len: usize,
+    buf: Vec<u8>,
+}
+
+impl ReadAt {
+    fn new(offset: u64, len: u64) -> Self {
+        ReadAt {
+            seek: SeekTo::new(SeekFrom::Start(offset)),
+            seeked: false,
+            len: len as usize,
+            buf: Vec::new(),
+        }
+    }
+
+    fn poll<R: AsyncRead + AsyncSeek + Unpin>(
+        &mut self,
+        reader: &mut R,
+        cx: &mut Context,
+    ) -> Poll<io::Result<()>> {
+        if !self.seeked {
+            ready!(self.seek.poll(reader, cx))?;
+            self.seeked = true;
+        }
+
+        while self.buf.len() < self.len {
+            let max = cmp::min(self.len - self.buf.len(), CHUNK_SIZE);
+            if ready!(poll_append(reader, cx, &mut self.buf, max))? == 0 {
+                break;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Returns the result of a blocking task, a panic of the task continues in the caller.
+fn joined<T>(result: Result<Result<T, Error>, JoinError>) -> Result<T, Error> {
+    match result {
+        Ok(result) => result,
+        Err(e) => match e.try_into_panic() {
+            Ok(payload) => panic::resume_unwind(payload),
+            Err(e) => Err(Error::with_cause(
+                ErrorKind::Io,
+                "the runtime shut down before the task finished",
+                e,
+            )),
+        },
+    }
+}
+
+/// Appends up to `max` bytes to `buf`, 0 bytes mean the reader is at its end.
+fn poll_append<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> Poll<io::Result<usize>> {
+    let len = buf.len();
+    buf.resize(len + max, 0);
+
+    let mut read_buf = ReadBuf::new(&mut buf[len..]);
+    let result = Pin::new(reader).poll_read(cx, &mut read_buf);
+    let read = read_buf.filled().len();
+    buf.truncate(len + read);
+
+    result.map_ok(|()| read)
+}
+
+#[cfg(test)]
+mod tests {
+    use error::ErrorKind;
+    use file::{MangoFile, Serialization};
+    use std::future::Future;
+    use std::io::{Cursor, SeekFrom};
+    use std::path::Path;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+    use tokio::runtime::Builder;
+
+    fn run<F: Future>(future: F) -> F::Output {
+        Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn file() -> MangoFile {
+        let mut file = MangoFile::new();
+        file.get_meta_mut().title = Some("async".to_string());
+        file.add_image_by_path(Path::new("test.jpg")).unwrap();
+        file.add_image_by_path(Path::new("test2.jpg")).unwrap();
+        // shares the data of the first image
+        let copy = file.get_image(0).unwrap().clone();
+        file.add_image(copy);
+        file.set_deduplicate(true);
+        file
+    }
+
+    /// Counts the bytes which were read.
+    struct Counting {
+        inner: Cursor<Vec<u8>>,
+        read: usize,
+    }
+
+    impl AsyncRead for Counting {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &mut ReadBuf,
+        ) -> Poll<std::io::Result<()>> {
+            let before = buf.filled().len();
+            let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+            self.read += buf.filled().len() - before;
+            result
+        }
+    }
+
+    impl AsyncSeek for Counting {
+        fn start_seek(mut self: Pin<&mut Self>, pos: SeekFrom) -> std::io::Result<()> {
+            Pin::new(&mut self.inner).start_seek(pos)
+        }
+
+        fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<std::io::Result<u64>> {
+            Pin::new(&mut self.inner).poll_complete(cx)
+        }
+    }
+
+    #[test]
+    fn save_and_open() {
+        let file = file();
+
+        for format in &[
+            Serialization::Cbor,
+            Serialization::Bson,
+            Serialization::Json,
+        ] {
+            let mut bytes = Vec::new();
+            run(file.save_async(&mut bytes, *format)).unwrap();
+            assert_eq!(bytes, file.to_bytes(*format).unwrap());
+
+            let opened = run(MangoFile::open_async(&bytes[..])).unwrap();
+            assert_eq!(opened.get_meta().title, Some("async".to_string()));
+            assert_eq!(opened.image_count(), 3);
+        }
+
+        let error = run(MangoFile::open_async(&b"not a mango file"[..]))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+    }
+
+    #[test]
+    fn read_image() {
+        let file = file();
+
+        for format in &[
+            Serialization::Cbor,
+            Serialization::Bson,
+            Serialization::Json,
+        ] {
+            let bytes = file.to_bytes(*format).unwrap();
+
+            for (index, expected) in file.images().enumerate() {
+                let image = run(MangoFile::read_image_async(Cursor::new(&bytes), index)).unwrap();
+                assert_eq!(image.get_image_data_ref(), expected.get_image_data_ref());
+                assert_eq!(image.get_meta().filename, expected.get_meta().filename);
+            }
+
+            let error = run(MangoFile::read_image_async(Cursor::new(&bytes), 3))
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+    }
+
+    #[test]
+    fn read_image_reads_only_what_it_needs() {
+        let file = file();
+        let bytes = file.to_bytes(Serialization::Cbor).unwrap();
+        let len = bytes.len();
+        let second = file.get_image(1).unwrap().get_image_data_ref().len();
+
+        let mut reader = Counting {
+            inner: Cursor::new(bytes),
+            read: 0,
+        };
+        run(MangoFile::read_image_async(&mut reader, 1)).unwrap();
+        // the image and the few blocks with the headers before it
+        assert!(reader.read < len);
+        assert!(reader.read <= second + 4 * super::BLOCK_SIZE as usize);
+    }
+
+    #[test]
+    fn tokio_file() {
+        let path = Path::new("async.mango");
+        file().save(path).unwrap();
+
+        let image = run(async_file_image(path)).unwrap();
+        assert_eq!(image.get_meta().filename, "test2.jpg");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn async_file_image(
+        path: &Path,
+    ) -> impl Future<Output = Result<::image::MangoImage, ::error::Error>> {
+        let file = std::fs::File::open(path).unwrap();
+        MangoFile::read_image_async(tokio::fs::File::from_std(file), 1)
+    }
+}
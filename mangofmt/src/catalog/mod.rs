@@ -0,0 +1,643 @@
+//! A persistent index of the metadata of many .mango files.
+//!
+//! Opening thousands of files just to list or find them is slow, the catalog reads the
+//! metadata and page count of every file once and keeps them in a SQLite database.
+//! Rescanning a directory only reads files again whose modification time or size changed,
+//! and only if their checksum changed as well. Files which were moved keep their entry.
+//!
+//! The title, the authors, the genres and the tags are indexed for full text search.
+//!
+//! SQLite comes with the feature, it is built from source by rusqlite, so no system library is
+//! needed.
+//!
+//! ```no_run
+//! use mangofmt::catalog::Index;
+//! use std::path::Path;
+//!
+//! let mut index = Index::open(Path::new("library.db")).unwrap();
+//! let report = index.scan(Path::new("/srv/manga")).unwrap();
+//! println!("{} new volumes", report.added);
+//!
+//! for entry in index.search("one piece").unwrap() {
+//!     println!("{}: {} pages", entry.path.display(), entry.pages);
+//! }
+//! ```
+
+extern crate rusqlite;
+
+use self::rusqlite::types::ToSql;
+use self::rusqlite::Connection;
+use error::{Error, ErrorKind};
+use file::MangoFile;
+use library::find_volumes;
+use meta::{checksum, MangoMetadata};
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The version of the schema, stored as the user version of the database.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE volumes (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        size INTEGER NOT NULL,
+        modified INTEGER NOT NULL,
+        checksum TEXT NOT NULL,
+        pages INTEGER NOT NULL,
+        title TEXT,
+        author TEXT,
+        series TEXT,
+        language TEXT,
+        volume INTEGER,
+        chapter REAL,
+        authors TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        meta TEXT NOT NULL
+    );
+    CREATE INDEX volumes_checksum ON volumes (checksum);
+    CREATE INDEX volumes_series ON volumes (series, volume, chapter);
+
+    CREATE VIRTUAL TABLE volumes_search USING fts5(
+        title, authors, tags, content = 'volumes', content_rowid = 'id'
+    );
+    CREATE TRIGGER volumes_insert AFTER INSERT ON volumes BEGIN
+        INSERT INTO volumes_search (rowid, title, authors, tags)
+        VALUES (new.id, new.title, new.authors, new.tags);
+    END;
+    CREATE TRIGGER volumes_delete AFTER DELETE ON volumes BEGIN
+        INSERT INTO volumes_search (volumes_search, rowid, title, authors, tags)
+        VALUES ('delete', old.id, old.title, old.authors, old.tags);
+    END;
+    CREATE TRIGGER volumes_update AFTER UPDATE ON volumes BEGIN
+        INSERT INTO volumes_search (volumes_search, rowid, title, authors, tags)
+        VALUES ('delete', old.id, old.title, old.authors, old.tags);
+        INSERT INTO volumes_search (rowid, title, authors, tags)
+        VALUES (new.id, new.title, new.authors, new.tags);
+    END;
+";
+
+/// The columns an [Entry](struct.Entry.html) is read from, in this order.
+const ENTRY_COLUMNS: &str = "path, size, modified, checksum, pages, meta";
+
+/// A file in the index.
+#[derive(Clone)]
+pub struct Entry {
+    /// The absolute path of the file.
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+    /// The SHA-256 checksum of the whole file as hex string.
+    pub checksum: String,
+    pub pages: usize,
+    pub meta: MangoMetadata,
+}
+
+/// What a [scan](struct.Index.html#method.scan) changed in the index.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// Files which weren't in the index before.
+    pub added: usize,
+    /// Files whose content changed, their metadata was read again.
+    pub updated: usize,
+    /// Files which were renamed or moved, found by their checksum.
+    pub moved: usize,
+    /// Files which didn't change, including ones which only got a new modification time.
+    pub unchanged: usize,
+    /// Entries whose file doesn't exist anymore or couldn't be read.
+    pub removed: usize,
+    /// The files which couldn't be read, they are not in the index.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// The state of an indexed file, as far as a rescan needs to know it.
+struct Known {
+    id: i64,
+    size: u64,
+    modified: i64,
+    checksum: String,
+}
+
+/// The catalog database.
+pub struct Index {
+    connection: Connection,
+}
+
+impl Index {
+    /// Opens the index at `path`, it is created if it doesn't exist yet.
+    pub fn open(path: &Path) -> Result<Index, Error> {
+        Index::with_connection(Connection::open(path)?)
+    }
+
+    /// Creates an index which only lives in memory.
+    pub fn open_in_memory() -> Result<Index, Error> {
+        Index::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Index, Error> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version == 0 {
+            connection.execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                SCHEMA, SCHEMA_VERSION
+            ))?;
+        } else if version > SCHEMA_VERSION {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "the index was created by a newer version of mangofmt",
+            ));
+        }
+
+        Ok(Index { connection })
+    }
+
+    /// Indexes all .mango files below `root` and removes the entries of files which are gone.
+    ///
+    /// Entries of files outside of `root` are kept, so one index can hold several
+    /// directories. The scan runs in a single transaction, if it fails nothing changes.
+    pub fn scan(&mut self, root: &Path) -> Result<ScanReport, Error> {
+        let root = root.canonicalize()?;
+        let ids = find_volumes(&root)?;
+
+        self.connection.execute_batch("BEGIN")?;
+        match self.scan_files(&root, &ids) {
+            Ok(report) => {
+                self.connection.execute_batch("COMMIT")?;
+                Ok(report)
+            }
+            Err(e) => {
+                self.connection.execute_batch("ROLLBACK")?;
+                Err(e)
+            }
+        }
+    }
+
+    fn scan_files(&self, root: &Path, ids: &[String]) -> Result<ScanReport, Error> {
+        let mut report = ScanReport::default();
+
+        let mut known = HashMap::new();
+        let mut select = self
+            .connection
+            .prepare("SELECT id, path, size, modified, checksum FROM volumes")?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let path = PathBuf::from(row.get::<_, String>(1)?);
+            if path.starts_with(root) {
+                let entry = Known {
+                    id: row.get(0)?,
+                    size: row.get::<_, i64>(2)? as u64,
+                    modified: row.get(3)?,
+                    checksum: row.get(4)?,
+                };
+                known.insert(path, entry);
+            }
+        }
+        drop(rows);
+        drop(select);
+
+        // the ids of all entries which still belong to a file
+        let mut kept = HashSet::new();
+        for id in ids {
+            let path = root.join(id);
+            match self.scan_file(&path, known.get(&path), &mut report) {
+                Ok(id) => {
+                    kept.insert(id);
+                }
+                Err(e) => report.failed.push((path, e)),
+            }
+        }
+
+        let mut delete = self
+            .connection
+            .prepare("DELETE FROM volumes WHERE id = ?1")?;
+        for entry in known.values().filter(|entry| !kept.contains(&entry.id)) {
+            delete.execute([entry.id])?;
+            report.removed += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Brings the entry of a single file up to date and returns its id.
+    fn scan_file(
+        &self,
+        path: &Path,
+        known: Option<&Known>,
+        report: &mut ScanReport,
+    ) -> Result<i64, Error> {
+        let path_text = path
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::Io, "the path is not UTF-8"))?;
+        let stat = fs::metadata(path)?;
+        let size = stat.len();
+        let modified = timestamp(stat.modified()?);
+
+        if let Some(known) = known {
+            if known.size == size && known.modified == modified {
+                report.unchanged += 1;
+                return Ok(known.id);
+            }
+        }
+
+        let checksum = checksum(&fs::read(path)?);
+        let size = size as i64;
+        let stat_values: [&dyn ToSql; 3] = [&size, &modified, &path_text];
+        let mut update_stat = self
+            .connection
+            .prepare("UPDATE volumes SET size = ?1, modified = ?2, path = ?3 WHERE id = ?4")?;
+
+        if let Some(known) = known {
+            if known.checksum == checksum {
+                update_stat.execute(&*with_id(&stat_values, &known.id))?;
+                report.unchanged += 1;
+                return Ok(known.id);
+            }
+        } else if let Some(id) = self.find_moved(&checksum)? {
+            update_stat.execute(&*with_id(&stat_values, &id))?;
+            report.moved += 1;
+            return Ok(id);
+        }
+
+        let meta = MangoFile::read_metadata(path)?;
+        let pages = MangoFile::read_page_count(path)?;
+        let pages = pages as i64;
+        let row = Row::new(&meta)?;
+        let values: [&dyn ToSql; 14] = [
+            &path_text,
+            &size,
+            &modified,
+            &checksum,
+            &pages,
+            &meta.title,
+            &meta.author,
+            &meta.series,
+            &row.language,
+            &meta.volume,
+            &meta.chapter,
+            &row.authors,
+            &row.tags,
+            &row.meta,
+        ];
+
+        match known {
+            Some(known) => {
+                let mut update = self.connection.prepare(
+                    "UPDATE volumes SET path = ?1, size = ?2, modified = ?3, checksum = ?4,
+                        pages = ?5, title = ?6, author = ?7, series = ?8, language = ?9,
+                        volume = ?10, chapter = ?11, authors = ?12, tags = ?13, meta = ?14
+                    WHERE id = ?15",
+                )?;
+                update.execute(&*with_id(&values, &known.id))?;
+                report.updated += 1;
+                Ok(known.id)
+            }
+            None => {
+                let mut insert = self.connection.prepare(
+                    "INSERT INTO volumes (path, size, modified, checksum, pages, title, author,
+                        series, language, volume, chapter, authors, tags, meta)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                )?;
+                insert.execute(&values[..])?;
+                report.added += 1;
+                Ok(self.connection.last_insert_rowid())
+            }
+        }
+    }
+
+    /// Returns an entry with the checksum whose file doesn't exist anymore.
+    fn find_moved(&self, checksum: &str) -> Result<Option<i64>, Error> {
+        let mut select = self
+            .connection
+            .prepare("SELECT id, path FROM volumes WHERE checksum = ?1")?;
+        let mut rows = select.query([checksum])?;
+        while let Some(row) = rows.next()? {
+            if !Path::new(&row.get::<_, String>(1)?).exists() {
+                return Ok(Some(row.get(0)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the entry of a file, `path` has to be absolute like the paths in the index.
+    pub fn get(&self, path: &Path) -> Result<Option<Entry>, Error> {
+        let path = match path.to_str() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let sql = format!("SELECT {} FROM volumes WHERE path = ?1", ENTRY_COLUMNS);
+        Ok(self.query(&sql, &[&path])?.pop())
+    }
+
+    /// Returns all entries, sorted by series, volume, chapter and title.
+    pub fn entries(&self) -> Result<Vec<Entry>, Error> {
+        let sql = format!(
+            "SELECT {} FROM volumes ORDER BY series, volume, chapter, title, path",
+            ENTRY_COLUMNS
+        );
+        self.query(&sql, &[])
+    }
+
+    /// Searches the titles, authors, genres and tags, the best matches come first.
+    ///
+    /// Every word of the query has to appear in one of them, words also match as prefix,
+    /// so `one pie` finds "One Piece". The query is not interpreted as FTS5 syntax.
+    pub fn search(&self, query: &str) -> Result<Vec<Entry>, Error> {
+        let terms: Vec<_> = query
+            .split_whitespace()
+            .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let columns: Vec<_> = ENTRY_COLUMNS
+            .split(", ")
+            .map(|column| format!("volumes.{}", column))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM volumes_search JOIN volumes ON volumes.id = volumes_search.rowid
+            WHERE volumes_search MATCH ?1 ORDER BY rank",
+            columns.join(", ")
+        );
+        self.query(&sql, &[&terms.join(" ")])
+    }
+
+    fn query(&self, sql: &str, values: &[&dyn ToSql]) -> Result<Vec<Entry>, Error> {
+        let mut statement = self.connection.prepare(sql)?;
+        let mut rows = statement.query(values)?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next()? {
+            entries.push(read_entry(row)?);
+        }
+        Ok(entries)
+    }
+}
+
+/// The derived columns of a row, which need owned strings.
+struct Row {
+    language: Option<String>,
+    authors: String,
+    tags: String,
+    meta: String,
+}
+
+impl Row {
+    fn new(meta: &MangoMetadata) -> Result<Row, Error> {
+        let authors: Vec<&str> = meta
+            .author
+            .iter()
+            .map(String::as_str)
+            .chain(meta.creators.iter().map(|c| c.name.as_str()))
+            .collect();
+        let tags: Vec<&str> = meta
+            .genres
+            .iter()
+            .chain(&meta.tags)
+            .map(String::as_str)
+            .collect();
+        let json = serde_json::to_string(meta)
+            .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode metadata", e))?;
+
+        Ok(Row {
+            language: meta.language.as_ref().map(|l| l.as_str().to_string()),
+            authors: authors.join("\n"),
+            tags: tags.join("\n"),
+            meta: json,
+        })
+    }
+}
+
+fn read_entry(row: &rusqlite::Row) -> Result<Entry, Error> {
+    let meta = serde_json::from_str(&row.get::<_, String>(5)?)
+        .map_err(|e| Error::with_cause(ErrorKind::Decode, "corrupted metadata in index", e))?;
+
+    Ok(Entry {
+        path: PathBuf::from(row.get::<_, String>(0)?),
+        size: row.get::<_, i64>(1)? as u64,
+        modified: system_time(row.get(2)?),
+        checksum: row.get(3)?,
+        pages: row.get::<_, i64>(4)? as usize,
+        meta,
+    })
+}
+
+fn with_id<'a>(values: &[&'a dyn ToSql], id: &'a i64) -> Vec<&'a dyn ToSql> {
+    let mut values = values.to_vec();
+    values.push(id);
+    values
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Error::from_cause(ErrorKind::Io, error)
+    }
+}
+
+/// Nanoseconds since the unix epoch, negative before it.
+fn timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as i64,
+        Err(e) => -(e.duration().as_nanos() as i64),
+    }
+}
+
+fn system_time(nanos: i64) -> SystemTime {
+    let duration = Duration::from_nanos(nanos.unsigned_abs());
+    if nanos < 0 {
+        UNIX_EPOCH - duration
+    } else {
+        UNIX_EPOCH + duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Index;
+    use file::MangoFile;
+    use language::Language;
+    use meta::{Creator, CreatorRole, MangoMetadata};
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+
+    fn meta(title: &str, series: Option<&str>, volume: i16) -> MangoMetadata {
+        let mut meta = MangoMetadata::new();
+        meta.title = Some(title.to_string());
+        meta.series = series.map(str::to_string);
+        meta.volume = Some(volume);
+        meta.language = Some(Language::parse("ja").unwrap());
+        meta
+    }
+
+    /// Creates a directory with `shelf/one.mango` (CBOR) and `two.mango` (BSON).
+    fn library(name: &str) -> PathBuf {
+        let root = PathBuf::from(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("shelf")).unwrap();
+
+        let mut file = MangoFile::new();
+        file.add_image_by_path(Path::new("test.jpg")).unwrap();
+        file.add_image_by_path(Path::new("test2.jpg")).unwrap();
+        *file.get_meta_mut() = meta("Romance Dawn", Some("One Piece"), 1);
+        file.get_meta_mut().author = Some("Eiichiro Oda".to_string());
+        file.get_meta_mut().genres.push("Shōnen".to_string());
+        file.get_meta_mut().tags.push("pirates".to_string());
+        file.save(&root.join("shelf/one.mango")).unwrap();
+
+        *file.get_meta_mut() = meta("Uzumaki Naruto", Some("Naruto"), 1);
+        file.get_meta_mut()
+            .creators
+            .push(Creator::new("Masashi Kishimoto", CreatorRole::Writer));
+        file.save_bson(&root.join("two.mango")).unwrap();
+
+        root.canonicalize().unwrap()
+    }
+
+    /// Gives a file a distinct modification time, so coarse file system clocks can't hide it.
+    fn touch(path: &Path, seconds: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + seconds);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    fn titles(entries: &[super::Entry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.meta.title.clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn scan() {
+        let root = library("catalog_scan");
+        let mut index = Index::open_in_memory().unwrap();
+
+        let report = index.scan(&root).unwrap();
+        assert_eq!(report.added, 2);
+        assert!(report.failed.is_empty());
+
+        let entries = index.entries().unwrap();
+        assert_eq!(titles(&entries), vec!["Uzumaki Naruto", "Romance Dawn"]);
+        let one = index.get(&root.join("shelf/one.mango")).unwrap().unwrap();
+        assert_eq!(one.pages, 2);
+        assert_eq!(one.meta.series.as_deref(), Some("One Piece"));
+        assert_eq!(one.meta.language.as_ref().unwrap().as_str(), "ja");
+        assert_eq!(one.size, fs::metadata(&one.path).unwrap().len());
+        assert_eq!(one.checksum.len(), 64);
+
+        // nothing changed
+        let report = index.scan(&root).unwrap();
+        assert_eq!(report.unchanged, 2);
+        assert_eq!(report.added + report.updated + report.removed, 0);
+
+        // a new modification time, but the same content
+        touch(&root.join("two.mango"), 1);
+        let report = index.scan(&root).unwrap();
+        assert_eq!(report.unchanged, 2);
+        let two = index.get(&root.join("two.mango")).unwrap().unwrap();
+        assert_eq!(
+            two.modified,
+            fs::metadata(&two.path).unwrap().modified().unwrap()
+        );
+
+        // new metadata
+        let path = root.join("shelf/one.mango");
+        let mut changed = one.meta.clone();
+        changed.title = Some("Romance Dawn!".to_string());
+        MangoFile::update_metadata(&path, &changed).unwrap();
+        touch(&path, 2);
+        let report = index.scan(&root).unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.unchanged, 1);
+        let one = index.get(&path).unwrap().unwrap();
+        assert_eq!(one.meta.title.as_deref(), Some("Romance Dawn!"));
+
+        // moved files keep their entry, deleted ones lose it
+        let moved = root.join("shelf/moved.mango");
+        fs::rename(&path, &moved).unwrap();
+        fs::remove_file(root.join("two.mango")).unwrap();
+        fs::write(root.join("broken.mango"), b"not a mango file").unwrap();
+        let report = index.scan(&root).unwrap();
+        assert_eq!(report.moved, 1);
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, root.join("broken.mango"));
+        assert!(index.get(&path).unwrap().is_none());
+        let entries = index.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, moved);
+        assert_eq!(entries[0].checksum, one.checksum);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn search() {
+        let root = library("catalog_search");
+        let mut index = Index::open_in_memory().unwrap();
+        index.scan(&root).unwrap();
+
+        assert_eq!(titles(&index.search("dawn").unwrap()), vec!["Romance Dawn"]);
+        assert_eq!(
+            titles(&index.search("roman").unwrap()),
+            vec!["Romance Dawn"]
+        );
+        assert_eq!(titles(&index.search("oda").unwrap()), vec!["Romance Dawn"]);
+        assert_eq!(
+            titles(&index.search("Kishimoto").unwrap()),
+            vec!["Uzumaki Naruto"]
+        );
+        assert_eq!(
+            titles(&index.search("PIRATES").unwrap()),
+            vec!["Romance Dawn"]
+        );
+        assert_eq!(
+            titles(&index.search("shonen").unwrap()),
+            vec!["Romance Dawn"]
+        );
+        assert!(index.search("dawn naruto").unwrap().is_empty());
+        assert!(index.search("   ").unwrap().is_empty());
+
+        // FTS5 syntax is searched for literally
+        assert!(index.search("\"dawn OR title:*").unwrap().is_empty());
+        assert!(index.search("NOT (").unwrap().is_empty());
+
+        // the search index follows updates and removals
+        fs::remove_file(root.join("shelf/one.mango")).unwrap();
+        index.scan(&root).unwrap();
+        assert!(index.search("dawn").unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn persistent() {
+        let root = library("catalog_persistent");
+        let db = Path::new("catalog_persistent.db");
+        let _ = fs::remove_file(db);
+
+        Index::open(db).unwrap().scan(&root).unwrap();
+        let mut index = Index::open(db).unwrap();
+        assert_eq!(index.entries().unwrap().len(), 2);
+        assert_eq!(index.scan(&root).unwrap().unchanged, 2);
+
+        // other directories are kept
+        let other = library("catalog_persistent_other");
+        assert_eq!(index.scan(&other).unwrap().added, 2);
+        assert_eq!(index.entries().unwrap().len(), 4);
+        assert_eq!(index.scan(&root).unwrap().removed, 0);
+
+        drop(index);
+        fs::remove_file(db).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&other).unwrap();
+    }
+}
@@ -16,22 +16,24 @@ pub enum CompressionError {
     ExecutionError,
 }
 
+impl CompressionError {
+    fn message(&self) -> &'static str {
+        match self {
+            CompressionError::UnsupportedType => "The Compression Type is not supported",
+            CompressionError::ExecutionError => "while (de)compressing a error occurred",
+        }
+    }
+}
+
 impl fmt::Display for CompressionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "oh no something went wrong with the encryption")
+        write!(f, "compression failed: {}", self.message())
     }
 }
 
 impl error::Error for CompressionError {
     fn description(&self) -> &str {
-        match self {
-            CompressionError::UnsupportedType => "The Compression Type is not supported",
-            CompressionError::ExecutionError => "while (de)compressing a error occurred",
-        }
-    }
-
-    fn cause(&self) -> Option<&dyn error::Error> {
-        None
+        self.message()
     }
 }
 
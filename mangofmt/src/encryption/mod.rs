@@ -17,22 +17,24 @@ pub enum EncryptionError {
     ExecutionError,
 }
 
+impl EncryptionError {
+    fn message(&self) -> &'static str {
+        match self {
+            EncryptionError::UnsupportedType => "The Encryption Type is not supported",
+            EncryptionError::ExecutionError => "while en/decrypting a error occurred",
+        }
+    }
+}
+
 impl fmt::Display for EncryptionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "oh no something went wrong with the encryption")
+        write!(f, "encryption failed: {}", self.message())
     }
 }
 
 impl error::Error for EncryptionError {
     fn description(&self) -> &str {
-        match self {
-            EncryptionError::UnsupportedType => "The Encryption Type is not supported",
-            EncryptionError::ExecutionError => "while en/decrypting a error occurred",
-        }
-    }
-
-    fn cause(&self) -> Option<&dyn error::Error> {
-        None
+        self.message()
     }
 }
 
@@ -128,9 +130,9 @@ pub fn decrypt(
 ) -> Result<MangoImage, EncryptionError> {
     match etype {
         #[cfg(feature = "aes")]
-        EncryptionType::AES128 => Ok(openssl_mods::aes::decrypt_aes128(img, key, iv)),
+        EncryptionType::AES128 => openssl_mods::aes::decrypt_aes128(img, key, iv),
         #[cfg(feature = "aes")]
-        EncryptionType::AES256 => Ok(openssl_mods::aes::decrypt_aes256(img, key, iv)),
+        EncryptionType::AES256 => openssl_mods::aes::decrypt_aes256(img, key, iv),
         _ => Err(EncryptionError::UnsupportedType),
     }
 }
@@ -3,7 +3,7 @@ extern crate openssl;
 use self::openssl::rand::rand_bytes;
 use self::openssl::symm::Cipher;
 use super::tiger::tiger_128;
-use encryption::EncryptionType;
+use encryption::{EncryptionError, EncryptionType};
 use image::MangoImage;
 use sha2::{Digest, Sha256};
 
@@ -32,7 +32,12 @@ fn openssl_encrypt(
     key: String,
     cipher: Cipher,
 ) -> MangoImage {
-    println!("{}", cipher.key_len());
+    debug!(
+        "encrypting {} with {} and a {} byte key",
+        img.get_meta().filename,
+        etype,
+        cipher.key_len()
+    );
     let image_data: Vec<u8> = img.get_image_data();
     let iv = gen_iv(cipher);
     let encrypted_bytes = openssl::symm::encrypt(
@@ -49,26 +54,32 @@ fn openssl_encrypt(
 }
 
 #[cfg(feature = "aes")]
-fn openssl_decrypt(img: MangoImage, key: String, iv: &[u8], cipher: Cipher) -> MangoImage {
+fn openssl_decrypt(
+    img: MangoImage,
+    key: String,
+    iv: &[u8],
+    cipher: Cipher,
+) -> Result<MangoImage, EncryptionError> {
     let image_data: Vec<u8> = img.get_image_data();
-    let decrypted_bytes = openssl::symm::decrypt(
+    // a wrong key usually shows up as a padding error
+    let decrypted_data = openssl::symm::decrypt(
         cipher,
         openssl_hash(key, cipher).as_ref(),
         Some(iv),
         &image_data,
-    );
-    let decrypted_data = decrypted_bytes.unwrap();
+    )
+    .map_err(|_| EncryptionError::ExecutionError)?;
     let mut meta = img.get_meta().clone();
     meta.encryption = None;
     meta.iv = None;
-    MangoImage::new(decrypted_data, meta)
+    Ok(MangoImage::new(decrypted_data, meta))
 }
 
 #[cfg(feature = "aes")]
 pub mod aes {
     use super::Cipher;
     use super::{openssl_decrypt, openssl_encrypt};
-    use super::{EncryptionType, MangoImage};
+    use super::{EncryptionError, EncryptionType, MangoImage};
 
     pub fn encrypt_aes128(img: MangoImage, key: String) -> MangoImage {
         openssl_encrypt(EncryptionType::AES128, img, key, Cipher::aes_128_cbc())
@@ -78,11 +89,19 @@ pub mod aes {
         openssl_encrypt(EncryptionType::AES256, img, key, Cipher::aes_256_cbc())
     }
 
-    pub fn decrypt_aes128(img: MangoImage, key: String, iv: &[u8]) -> MangoImage {
+    pub fn decrypt_aes128(
+        img: MangoImage,
+        key: String,
+        iv: &[u8],
+    ) -> Result<MangoImage, EncryptionError> {
         openssl_decrypt(img, key, iv, Cipher::aes_128_cbc())
     }
 
-    pub fn decrypt_aes256(img: MangoImage, key: String, iv: &[u8]) -> MangoImage {
+    pub fn decrypt_aes256(
+        img: MangoImage,
+        key: String,
+        iv: &[u8],
+    ) -> Result<MangoImage, EncryptionError> {
         openssl_decrypt(img, key, iv, Cipher::aes_128_cbc())
     }
 }
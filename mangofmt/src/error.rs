@@ -0,0 +1,257 @@
+//! The error type shared by the whole crate.
+
+use compression::CompressionError;
+use encryption::EncryptionError;
+#[allow(deprecated)]
+use file::{self, MangoFileError};
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Holds all possible kinds of [Error](struct.Error.html).
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum ErrorKind {
+    /// Reading from the file system failed.
+    Io,
+    /// Writing a file failed, e.g. because the disk is full.
+    Write,
+    /// The data couldn't be decoded, e.g. the file is not a .mango file or is corrupted.
+    Decode,
+    /// The data couldn't be encoded.
+    Encode,
+    /// Encrypting or decrypting an image failed.
+    Crypto,
+    /// Compressing or decompressing an image failed.
+    Compression,
+    /// The data doesn't match its checksum.
+    Integrity,
+    /// The image format or algorithm is not supported or wasn't compiled in.
+    UnsupportedFormat,
+    /// A page index is out of range.
+    OutOfRange,
+    /// The progress handler of the operation asked to stop it.
+    Cancelled,
+}
+
+impl ErrorKind {
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io error",
+            ErrorKind::Write => "write error",
+            ErrorKind::Decode => "decode error",
+            ErrorKind::Encode => "encode error",
+            ErrorKind::Crypto => "encryption error",
+            ErrorKind::Compression => "compression error",
+            ErrorKind::Integrity => "integrity error",
+            ErrorKind::UnsupportedFormat => "unsupported format",
+            ErrorKind::OutOfRange => "index out of range",
+            ErrorKind::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// The Error used throughout mangofmt.
+///
+/// The older error types ([CompressionError](enum.CompressionError.html),
+/// [EncryptionError](enum.EncryptionError.html) and the deprecated
+/// [MangoFileError](file/struct.MangoFileError.html)) as well as `std::io::Error`
+/// can be converted into it, so `?` works across all of them.
+///
+/// A converted error keeps only its kind, the original error is its cause
+/// and can be reached with `source()`.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    msg: String,
+    cause: Option<Box<dyn error::Error + Send + Sync>>,
+}
+
+impl Error {
+    pub fn new<S>(kind: ErrorKind, msg: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            kind,
+            msg: msg.into(),
+            cause: None,
+        }
+    }
+
+    pub fn with_cause<S, E>(kind: ErrorKind, msg: S, cause: E) -> Self
+    where
+        S: Into<String>,
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Self {
+            kind,
+            msg: msg.into(),
+            cause: Some(cause.into()),
+        }
+    }
+
+    /// An error which is described by its cause alone, used for conversions.
+    pub(crate) fn from_cause<E>(kind: ErrorKind, cause: E) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Self::with_cause(kind, String::new(), cause)
+    }
+
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the kind of the first `std::io::Error` in the chain of causes, if there is one.
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        let mut current: Option<&(dyn error::Error + 'static)> = Some(self);
+
+        while let Some(err) = current {
+            if let Some(io_err) = err.downcast_ref::<io::Error>() {
+                return Some(io_err.kind());
+            }
+            current = err.source();
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.msg.is_empty() {
+            if let Some(ref cause) = self.cause {
+                return write!(f, "{}: {}", self.kind.description(), cause);
+            }
+        }
+
+        if let Some(ref cause) = self.cause {
+            return write!(
+                f,
+                "{} ({}); cause: {}",
+                self.msg,
+                self.kind.description(),
+                cause
+            );
+        }
+
+        write!(f, "{} ({})", self.msg, self.kind.description())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.cause
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn error::Error + 'static))
+    }
+}
+
+//------------------------------------------------------------------------------
+//  Conversions
+//------------------------------------------------------------------------------
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::from_cause(ErrorKind::Io, error)
+    }
+}
+
+#[allow(deprecated)]
+impl From<MangoFileError> for Error {
+    fn from(error: MangoFileError) -> Self {
+        let kind = match error.kind() {
+            file::ErrorKind::EncodeError => ErrorKind::Encode,
+            file::ErrorKind::DecodeError => ErrorKind::Decode,
+            file::ErrorKind::WriteError => ErrorKind::Write,
+            file::ErrorKind::ReadError | file::ErrorKind::PermissionError => ErrorKind::Io,
+        };
+
+        Error::from_cause(kind, error)
+    }
+}
+
+impl From<CompressionError> for Error {
+    fn from(error: CompressionError) -> Self {
+        let kind = match error {
+            CompressionError::UnsupportedType => ErrorKind::UnsupportedFormat,
+            CompressionError::ExecutionError => ErrorKind::Compression,
+        };
+
+        Error::from_cause(kind, error)
+    }
+}
+
+impl From<EncryptionError> for Error {
+    fn from(error: EncryptionError) -> Self {
+        let kind = match error {
+            EncryptionError::UnsupportedType => ErrorKind::UnsupportedFormat,
+            EncryptionError::ExecutionError => ErrorKind::Crypto,
+        };
+
+        Error::from_cause(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, ErrorKind};
+    use compression::CompressionError;
+    #[allow(deprecated)]
+    use file::{self, MangoFileError};
+    use std::error::Error as StdError;
+    use std::io;
+
+    #[test]
+    fn from_io() {
+        let error = Error::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(error.kind(), ErrorKind::Io);
+        assert_eq!(error.io_kind(), Some(io::ErrorKind::NotFound));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn from_mangofile_error_keeps_chain() {
+        let cause = io::Error::from(io::ErrorKind::PermissionDenied);
+        let mango_error = MangoFileError::with_cause(
+            file::ErrorKind::PermissionError,
+            "no access to the volume",
+            cause,
+        );
+        let error = Error::from(mango_error);
+
+        assert_eq!(error.kind(), ErrorKind::Io);
+        assert!(error.source().is_some());
+        assert_eq!(error.io_kind(), Some(io::ErrorKind::PermissionDenied));
+        assert_eq!(
+            error.to_string().matches("no access to the volume").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn conversions_print_the_message_once() {
+        let error = Error::from(io::Error::other("disk on fire"));
+        assert_eq!(error.to_string(), "io error: disk on fire");
+
+        let error = Error::from(CompressionError::ExecutionError);
+        assert_eq!(
+            error
+                .to_string()
+                .matches(&*CompressionError::ExecutionError.to_string())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn from_compression_error() {
+        let error = Error::from(CompressionError::UnsupportedType);
+        assert_eq!(error.kind(), ErrorKind::UnsupportedFormat);
+
+        let error = Error::from(CompressionError::ExecutionError);
+        assert_eq!(error.kind(), ErrorKind::Compression);
+        assert!(!error.to_string().contains("encryption"));
+    }
+}
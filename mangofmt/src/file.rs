@@ -1,23 +1,42 @@
 //! Contains the MangoFile struct and some related stuff.
 
 use bson;
-use image::{ImageFile, MangoImage};
+use compression::CompressionType;
+use encryption::EncryptionType;
+use error::Error as MangoError;
+use error::ErrorKind as MangoErrorKind;
+#[cfg(feature = "transcode")]
+use image::ImportOptions;
+use image::{ImageFile, MangoImage, MetadataKind};
 use json::JsonMangoFile;
-use meta::MangoMetadata;
+use layout;
+use layout::MetaRegion;
+use meta::{MangoMetadata, PerceptualHash};
+use progress::{self, Progress, ProgressHandler, Stage};
+use serde::{Serialize, Serializer};
 use serde_cbor;
-use std;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
+use std::fs;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::prelude::*;
-use std::path::Path;
+use std::io::SeekFrom;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use storage::{self, StoredFile, StoredImages};
+use toc::{TableOfContents, TocEntry};
 
 //------------------------------------------------------------------------------
 //  Custom Error
 //------------------------------------------------------------------------------
 
 /// Holds all possible Errors for MangoFileErrors
+#[deprecated(note = "MangoFile returns mangofmt::Error, use mangofmt::ErrorKind")]
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum ErrorKind {
     EncodeError,
@@ -27,6 +46,7 @@ pub enum ErrorKind {
     PermissionError,
 }
 
+#[allow(deprecated)]
 impl ErrorKind {
     pub fn description(self) -> &'static str {
         match self {
@@ -40,6 +60,11 @@ impl ErrorKind {
 }
 
 /// Custom Error for everything concerning MangoFiles
+///
+/// Nothing in mangofmt returns it anymore, it can still be converted into a
+/// [mangofmt::Error](../error/struct.Error.html).
+#[deprecated(note = "MangoFile returns mangofmt::Error")]
+#[allow(deprecated)]
 #[derive(Debug)]
 pub struct MangoFileError {
     kind: ErrorKind,
@@ -47,6 +72,7 @@ pub struct MangoFileError {
     cause: Option<Box<dyn Error + Send + Sync>>,
 }
 
+#[allow(deprecated)]
 impl MangoFileError {
     pub fn new(kind: ErrorKind, msg: &'static str) -> Self {
         Self {
@@ -71,6 +97,16 @@ impl MangoFileError {
         self.kind
     }
 
+    /// Returns the kind of the error without consuming it.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the message describing the error.
+    pub fn message(&self) -> &'static str {
+        self.msg
+    }
+
     pub fn convert_io_open(error: io::Error) -> Self {
         match error.kind() {
             io::ErrorKind::NotFound => {
@@ -90,13 +126,15 @@ impl MangoFileError {
             io::ErrorKind::PermissionDenied => {
                 MangoFileError::with_cause(ErrorKind::PermissionError, "permission denied", error)
             }
-            _ => {
-                MangoFileError::with_cause(ErrorKind::PermissionError, "unexpected io error", error)
+            io::ErrorKind::StorageFull => {
+                MangoFileError::with_cause(ErrorKind::WriteError, "no space left on device", error)
             }
+            _ => MangoFileError::with_cause(ErrorKind::WriteError, "could not write file", error),
         }
     }
 }
 
+#[allow(deprecated)]
 impl fmt::Display for MangoFileError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(ref cause) = self.cause {
@@ -113,13 +151,16 @@ impl fmt::Display for MangoFileError {
     }
 }
 
+#[allow(deprecated)]
 impl Error for MangoFileError {
     fn description(&self) -> &str {
         self.msg
     }
 
-    fn cause(&self) -> Option<&dyn Error> {
-        self.cause.as_ref().map(|e| e.as_ref() as &dyn Error)
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.cause
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn Error + 'static))
     }
 }
 
@@ -127,13 +168,61 @@ impl Error for MangoFileError {
 // MangoFile Struct
 //------------------------------------------------------------------------------
 
+/// The serialization formats a .mango file can be stored in.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Serialization {
+    /// The default format, it has the lowest overhead.
+    Cbor,
+    Bson,
+    /// Only meant for debugging, see [save_json](struct.MangoFile.html#method.save_json).
+    Json,
+}
+
 /// Rrepresents a mango file.
 ///
 /// It can be used to create, save and modify a file of the format.
-#[derive(Serialize, Deserialize)]
+///
+/// Saving never writes to the target directly, the data is written to a temporary file in the
+/// same directory, which then replaces the target.
+/// So a crash or a full disk doesn't destroy the previously saved file.
+///
+/// Images with the same data can be stored only once, see
+/// [set_deduplicate](#method.set_deduplicate).
+/// They also share their data after opening the file again.
+#[derive(Clone, Deserialize)]
 pub struct MangoFile {
     meta: MangoMetadata,
+    #[serde(default)]
+    toc: TableOfContents,
+    #[serde(deserialize_with = "storage::deserialize")]
     images: Vec<MangoImage>,
+    #[serde(skip)]
+    deduplicate: bool,
+}
+
+/// Two pages which look alike, see [similar_pages](struct.MangoFile.html#method.similar_pages).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SimilarPages {
+    pub first: usize,
+    pub second: usize,
+    /// The number of bits their perceptual hashes differ in.
+    pub distance: u32,
+}
+
+/// What [sanitize](struct.MangoFile.html#method.sanitize) removed from the images of a file.
+#[derive(Debug, Default)]
+pub struct SanitizeReport {
+    /// The pages which contained metadata and what got removed from them.
+    pub removed: Vec<(usize, Vec<MetadataKind>)>,
+    /// The pages which couldn't be sanitized, e.g. because they are encrypted.
+    pub failed: Vec<(usize, MangoError)>,
+}
+
+impl SanitizeReport {
+    /// Returns true if every page is free of metadata now.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
 }
 
 impl MangoFile {
@@ -141,76 +230,50 @@ impl MangoFile {
     pub fn new() -> MangoFile {
         MangoFile {
             meta: MangoMetadata::new(),
+            toc: TableOfContents::new(),
             images: Vec::new(),
+            deduplicate: false,
         }
     }
 
-    // TODO fix error (also handle read errors)
     /// Opens a existing .mango file
-    pub fn open(p: &Path) -> Result<MangoFile, MangoFileError> {
-        // try to open the default format cbor
-        let cbor_file = Self::open_cbor(&p);
-        if cbor_file.is_ok() {
-            return cbor_file;
-        }
-
-        // try bson as a fallback
-        let bson_file = Self::open_bson(&p);
-        if bson_file.is_ok() {
-            return bson_file;
-        }
-
-        // open json, should the support for this dropped?
-        let json_file = Self::open_json(&p);
-        if json_file.is_ok() {
-            return json_file;
-        }
+    pub fn open(p: &Path) -> Result<MangoFile, MangoError> {
+        Self::from_bytes(&read_file(p)?)
+    }
 
-        Err(MangoFileError::new(
-            ErrorKind::DecodeError,
-            "file is not a  MangoFile",
-        ))
+    /// Opens a existing .mango file like [open](#method.open) and reports how many bytes were
+    /// read so far and when the decoding starts, see the [progress module](progress/index.html).
+    pub fn open_with_progress(
+        p: &Path,
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<MangoFile, MangoError> {
+        let bytes = read_file_with_progress(p, progress)?;
+        progress::report(
+            progress,
+            Progress {
+                stage: Stage::Decode,
+                page: None,
+                page_count: 0,
+                bytes: 0,
+                total_bytes: Some(bytes.len() as u64),
+            },
+        )?;
+        Self::from_bytes(&bytes)
     }
 
     /// Opens a MangoFile which uses bson as for serialization.
     ///
     /// You probably don't want to use this function, if you don't know the serialization format,
     /// use just [open](#method.open) instead.
-    pub fn open_bson(p: &Path) -> Result<MangoFile, MangoFileError> {
-        let file = File::open(p);
-
-        if file.is_err() {
-            return Err(MangoFileError::convert_io_open(file.err().unwrap()));
-        }
-
-        let document = bson::decode_document(&mut file.unwrap());
-
-        if document.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::DecodeError,
-                "couldn't decode BSON Document",
-                document.err().unwrap(),
-            ));
-        }
-
-        let mangofile = bson::from_bson(bson::Bson::Document(document.unwrap()));
-
-        if mangofile.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::DecodeError,
-                "couldn't convert BSON Document to MangoFile",
-                mangofile.err().unwrap(),
-            ));
-        }
-
-        Ok(mangofile.unwrap())
+    pub fn open_bson(p: &Path) -> Result<MangoFile, MangoError> {
+        Self::from_bytes_as(&read_file(p)?, Serialization::Bson)
     }
 
     /// Opens a MangoFile which uses json as for serialization.
     ///
     /// You probably don't want to use this function, if you don't know the serialization format,
     /// use just [open](#method.open) instead.
-    pub fn open_json(p: &Path) -> Result<MangoFile, MangoFileError> {
+    pub fn open_json(p: &Path) -> Result<MangoFile, MangoError> {
         JsonMangoFile::open(&p)
     }
 
@@ -218,79 +281,170 @@ impl MangoFile {
     ///
     /// You probably don't want to use this function, if you don't know the serialization format,
     /// use just [open](#method.open) instead.
-    pub fn open_cbor(p: &Path) -> Result<MangoFile, MangoFileError> {
-        let file = File::open(p);
+    pub fn open_cbor(p: &Path) -> Result<MangoFile, MangoError> {
+        Self::from_bytes_as(&read_file(p)?, Serialization::Cbor)
+    }
 
-        if file.is_err() {
-            return Err(MangoFileError::convert_io_open(file.err().unwrap()));
+    /// Decodes a .mango file which is already in memory.
+    ///
+    /// Like [open](#method.open), the serialization format gets detected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<MangoFile, MangoError> {
+        // try the default format cbor first, json last
+        for format in &[
+            Serialization::Cbor,
+            Serialization::Bson,
+            Serialization::Json,
+        ] {
+            match Self::from_bytes_as(bytes, *format) {
+                Ok(file) => return Ok(file),
+                Err(e) => debug!("the data is not a {:?} MangoFile: {}", format, e),
+            }
         }
 
-        let mut bytes = Vec::new();
-        if file.unwrap().read_to_end(&mut bytes).is_err() {
-            return Err(MangoFileError::new(
-                ErrorKind::ReadError,
-                "could not read file",
-            ));
-        };
-
-        let mangofile = serde_cbor::from_slice(&bytes);
+        Err(MangoError::new(
+            MangoErrorKind::Decode,
+            "data is not a MangoFile",
+        ))
+    }
 
-        if mangofile.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::DecodeError,
-                "couldn't decode CBOR",
-                mangofile.err().unwrap(),
-            ));
+    /// Decodes a .mango file which is already in memory and uses the given serialization format.
+    pub fn from_bytes_as(bytes: &[u8], format: Serialization) -> Result<MangoFile, MangoError> {
+        match format {
+            Serialization::Cbor => serde_cbor::from_slice(bytes).map_err(|e| {
+                MangoError::with_cause(MangoErrorKind::Decode, "couldn't decode CBOR", e)
+            }),
+            Serialization::Bson => {
+                let document = bson::decode_document(&mut &bytes[..]).map_err(|e| {
+                    MangoError::with_cause(
+                        MangoErrorKind::Decode,
+                        "couldn't decode BSON Document",
+                        e,
+                    )
+                })?;
+
+                bson::from_bson(bson::Bson::Document(document)).map_err(|e| {
+                    MangoError::with_cause(
+                        MangoErrorKind::Decode,
+                        "couldn't convert BSON Document to MangoFile",
+                        e,
+                    )
+                })
+            }
+            Serialization::Json => JsonMangoFile::decode(bytes),
         }
+    }
 
-        Ok(mangofile.unwrap())
+    /// Reads only the metadata of a .mango file, without decoding its images.
+    ///
+    /// The images of CBOR and BSON files get skipped without reading them.
+    /// JSON files have to be parsed completely, but their images don't get decoded either.
+    pub fn read_metadata(p: &Path) -> Result<MangoMetadata, MangoError> {
+        let mut file = File::open(p).map_err(open_error)?;
+
+        match MetaRegion::find(&mut file)? {
+            Some(region) => region.read(&mut file),
+            None => JsonMangoFile::read_metadata(p),
+        }
     }
 
-    /// Saves a .mango file with the default serialization format. (currently cbor)
-    pub fn save(&self, p: &Path) -> Result<(), MangoFileError> {
-        // use cbor as the default format
-        // (lowest overhead)
-        self.save_cbor(p)?;
-        Ok(())
+    /// Reads a single image of a saved .mango file, without decoding the other ones.
+    ///
+    /// Only the image and, if it shares its data, the image it shares them with get read
+    /// from CBOR and BSON files. JSON files have to be decoded completely.
+    pub fn read_image(p: &Path, index: usize) -> Result<MangoImage, MangoError> {
+        let mut file = File::open(p).map_err(open_error)?;
+
+        match MetaRegion::find(&mut file)? {
+            Some(region) => layout::read_image(&mut file, region.encoding, index),
+            None => {
+                let file = Self::open_json(p)?;
+                file.images
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| out_of_range(index, file.images.len()))
+            }
+        }
     }
 
-    /// Saves a .mango file with the bson serialization format.    
-    pub fn save_bson(&self, p: &Path) -> Result<(), MangoFileError> {
-        let bson_data = bson::to_bson(&self);
+    /// Counts the images of a saved .mango file, without reading them.
+    ///
+    /// JSON files have to be decoded completely.
+    pub fn read_page_count(p: &Path) -> Result<usize, MangoError> {
+        let mut file = File::open(p).map_err(open_error)?;
 
-        if bson_data.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::EncodeError,
-                "couldn't encode to BSON",
-                bson_data.err().unwrap(),
-            ));
+        match MetaRegion::find(&mut file)? {
+            Some(region) => layout::page_count(&mut file, region.encoding),
+            None => Ok(Self::open_json(p)?.images.len()),
         }
+    }
 
-        if let bson::Bson::Document(document) = bson_data.unwrap() {
-            let mut buf = Vec::new();
-
-            let encode = bson::encode_document(&mut buf, &document);
-            if encode.is_err() {
-                return Err(MangoFileError::with_cause(
-                    ErrorKind::EncodeError,
-                    "couldn't encode to BSON",
-                    encode.err().unwrap(),
-                ));
+    /// Replaces the metadata of a saved .mango file, without decoding its images.
+    ///
+    /// The file gets rewritten, the images are copied over as they are. The rewritten file
+    /// replaces the old one in a single step, so a crash never leaves a half written file behind.
+    ///
+    /// Files of at least 16 MiB are updated in place instead if the new metadata fits into the
+    /// space of the old one, only the metadata gets overwritten then.
+    /// **This isn't crash safe**, a crash while the metadata gets written can leave the file
+    /// with metadata that is neither the old nor the new one and can't be decoded.
+    ///
+    /// JSON files are always saved again completely.
+    pub fn update_metadata(p: &Path, meta: &MangoMetadata) -> Result<(), MangoError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(p)
+            .map_err(open_error)?;
+
+        let region = match MetaRegion::find(&mut file)? {
+            Some(region) => region,
+            None => {
+                let mut json_file = Self::open_json(p)?;
+                json_file.set_meta(meta.clone());
+                return json_file.save_json(p);
             }
+        };
 
-            let file = File::create(p);
-            if file.is_err() {
-                return Err(MangoFileError::convert_io_save(file.err().unwrap()));
+        let bytes = match region.encode_fitting(meta)? {
+            Some(bytes) => {
+                if file.metadata().map_err(read_error)?.len() >= IN_PLACE_MIN_SIZE {
+                    return file
+                        .seek(SeekFrom::Start(region.start))
+                        .and_then(|_| file.write_all(&bytes))
+                        .and_then(|_| file.sync_all())
+                        .map_err(save_error);
+                }
+                bytes
             }
-            let write = file.unwrap().write_all(&buf);
-            if write.is_err() {
-                return Err(MangoFileError::convert_io_save(write.err().unwrap()));
-            }
-        }
+            None => layout::encode(region.encoding, meta)?,
+        };
+        let header = region.header(&mut file, bytes.len() as u64)?;
+        file.seek(SeekFrom::Start(region.start + region.len))?;
 
+        write_atomically(p, |out| {
+            out.write_all(&header)?;
+            out.write_all(&bytes)?;
+            io::copy(&mut file, out)?;
+            Ok(())
+        })
+        .map_err(save_error)?;
+
+        Ok(())
+    }
+
+    /// Saves a .mango file with the default serialization format. (currently cbor)
+    pub fn save(&self, p: &Path) -> Result<(), MangoError> {
+        // use cbor as the default format
+        // (lowest overhead)
+        self.save_cbor(p)?;
         Ok(())
     }
 
+    /// Saves a .mango file with the bson serialization format.
+    pub fn save_bson(&self, p: &Path) -> Result<(), MangoError> {
+        self.save_as(p, Serialization::Bson)
+    }
+
     /// Saves a .mango file with the json serialization format.
     ///
     /// **Important:** you should not use this for anything but debugging,
@@ -301,33 +455,109 @@ impl MangoFile {
     /// at json files, and see if everything works how it should.
     ///
     /// There are currently no plans to deprecate this serialization format.
-    pub fn save_json(&self, p: &Path) -> Result<(), MangoFileError> {
-        JsonMangoFile::save(p, self)?;
-        Ok(())
+    pub fn save_json(&self, p: &Path) -> Result<(), MangoError> {
+        self.save_as(p, Serialization::Json)
     }
 
     /// Saves a .mango file with the cbor serialization format. (default format)
-    pub fn save_cbor(&self, p: &Path) -> Result<(), MangoFileError> {
-        let bytes = serde_cbor::to_vec(&self);
-        if bytes.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::EncodeError,
-                "couldn't encode to CBOR",
-                bytes.err().unwrap(),
-            ));
-        }
+    pub fn save_cbor(&self, p: &Path) -> Result<(), MangoError> {
+        self.save_as(p, Serialization::Cbor)
+    }
 
-        let file = File::create(p);
-        if file.is_err() {
-            return Err(MangoFileError::convert_io_save(file.err().unwrap()));
-        }
+    fn save_as(&self, p: &Path, format: Serialization) -> Result<(), MangoError> {
+        let bytes = self.to_bytes(format)?;
+        write_atomically(p, |file| file.write_all(&bytes)).map_err(save_error)
+    }
+
+    /// Saves a .mango file with the given serialization format and reports when the encoding
+    /// starts and how many bytes were written so far, see the
+    /// [progress module](progress/index.html).
+    ///
+    /// A cancelled save leaves the previously saved file untouched.
+    pub fn save_with_progress(
+        &self,
+        p: &Path,
+        format: Serialization,
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<(), MangoError> {
+        let page_count = self.images.len();
+        progress::report(
+            progress,
+            Progress {
+                stage: Stage::Encode,
+                page: None,
+                page_count,
+                bytes: 0,
+                total_bytes: None,
+            },
+        )?;
+        let bytes = self.to_bytes(format)?;
+        let total = bytes.len() as u64;
+        let mut cancelled = None;
+
+        let result = write_atomically(p, |file| {
+            let mut written = 0;
+            for chunk in bytes.chunks(CHUNK_SIZE) {
+                file.write_all(chunk)?;
+                written += chunk.len() as u64;
+
+                let report = Progress {
+                    stage: Stage::Write,
+                    page: None,
+                    page_count,
+                    bytes: written,
+                    total_bytes: Some(total),
+                };
+                if let Err(e) = progress::report(progress, report) {
+                    cancelled = Some(e);
+                    return Err(io::Error::new(
+                        io::ErrorKind::Interrupted,
+                        "save was cancelled",
+                    ));
+                }
+            }
+            Ok(())
+        });
 
-        let write = file.unwrap().write_all(&bytes.unwrap());
-        if write.is_err() {
-            return Err(MangoFileError::convert_io_save(write.err().unwrap()));
+        match (result, cancelled) {
+            (_, Some(e)) => Err(e),
+            (Ok(()), None) => Ok(()),
+            (Err(e), None) => Err(save_error(e)),
         }
+    }
 
-        Ok(())
+    /// Encodes the file in memory, the bytes are the same the save functions would write.
+    pub fn to_bytes(&self, format: Serialization) -> Result<Vec<u8>, MangoError> {
+        match format {
+            Serialization::Cbor => serde_cbor::to_vec(&self).map_err(|e| {
+                MangoError::with_cause(MangoErrorKind::Encode, "couldn't encode to CBOR", e)
+            }),
+            Serialization::Bson => {
+                let document = match bson::to_bson(&self) {
+                    Ok(bson::Bson::Document(document)) => document,
+                    Ok(_) => {
+                        return Err(MangoError::new(
+                            MangoErrorKind::Encode,
+                            "MangoFile is not a BSON Document",
+                        ))
+                    }
+                    Err(e) => {
+                        return Err(MangoError::with_cause(
+                            MangoErrorKind::Encode,
+                            "couldn't encode to BSON",
+                            e,
+                        ))
+                    }
+                };
+
+                let mut buf = Vec::new();
+                bson::encode_document(&mut buf, &document).map_err(|e| {
+                    MangoError::with_cause(MangoErrorKind::Encode, "couldn't encode to BSON", e)
+                })?;
+                Ok(buf)
+            }
+            Serialization::Json => JsonMangoFile::encode(self),
+        }
     }
 
     /// Adds a MangoImage to the file
@@ -338,29 +568,449 @@ impl MangoFile {
     }
 
     /// Adds a MangoImage to the file by Path
-    pub fn add_image_by_path(&mut self, p: &Path) -> Result<(), std::io::Error> {
+    pub fn add_image_by_path(&mut self, p: &Path) -> Result<(), MangoError> {
         let image_file = ImageFile::open(p)?;
         self.images.push(image_file.to_mango_image());
         Ok(())
     }
 
+    /// Adds an image to the file by Path, changing it as the options say
+    #[cfg(feature = "transcode")]
+    pub fn add_image_by_path_with(
+        &mut self,
+        p: &Path,
+        options: &ImportOptions,
+    ) -> Result<(), MangoError> {
+        let image_file = ImageFile::open(p)?;
+        self.images.push(image_file.to_mango_image_with(options)?);
+        Ok(())
+    }
+
+    /// Adds the image files at the given paths to the file, in that order.
+    ///
+    /// The progress gets reported after every image, see the [progress module](progress/index.html).
+    /// Nothing gets added if one of the images can't be read or the import gets cancelled.
+    pub fn import_images<P>(
+        &mut self,
+        paths: &[P],
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<(), MangoError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut images = Vec::with_capacity(paths.len());
+        let mut bytes = 0;
+
+        for (index, path) in paths.iter().enumerate() {
+            let image = ImageFile::open(path.as_ref())?.to_mango_image();
+            bytes += image.get_image_data_ref().len() as u64;
+            images.push(image);
+
+            progress::report(
+                progress,
+                Progress {
+                    stage: Stage::Import,
+                    page: Some(index),
+                    page_count: paths.len(),
+                    bytes,
+                    total_bytes: None,
+                },
+            )?;
+        }
+
+        self.images.extend(images);
+        Ok(())
+    }
+
+    /// Compresses every image which is neither compressed nor encrypted yet.
+    ///
+    /// The progress gets reported after every image, see the [progress module](progress/index.html).
+    /// The images stay as they were if one of them can't be compressed
+    /// or the operation gets cancelled.
+    pub fn compress_images(
+        &mut self,
+        comp: CompressionType,
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<(), MangoError> {
+        self.transform_images(Stage::Compress, progress, |image| {
+            let meta = image.get_meta_ref();
+            if meta.compression.is_some() || meta.encryption.is_some() {
+                return Ok(None);
+            }
+            Ok(Some(image.compress(comp.clone())?))
+        })
+    }
+
+    /// Encrypts every image which isn't encrypted yet with the same key.
+    ///
+    /// The progress gets reported after every image, see the [progress module](progress/index.html).
+    /// The images stay as they were if one of them can't be encrypted
+    /// or the operation gets cancelled.
+    pub fn encrypt_images(
+        &mut self,
+        etype: EncryptionType,
+        key: &str,
+        progress: &mut dyn ProgressHandler,
+    ) -> Result<(), MangoError> {
+        self.transform_images(Stage::Encrypt, progress, |image| {
+            if image.get_meta_ref().encryption.is_some() {
+                return Ok(None);
+            }
+            Ok(Some(image.clone().encrypt(etype.clone(), key.to_string())?))
+        })
+    }
+
+    /// Runs `transform` on every image and only replaces them once all of them are done.
+    fn transform_images<F>(
+        &mut self,
+        stage: Stage,
+        progress: &mut dyn ProgressHandler,
+        mut transform: F,
+    ) -> Result<(), MangoError>
+    where
+        F: FnMut(&MangoImage) -> Result<Option<MangoImage>, MangoError>,
+    {
+        let total = self
+            .images
+            .iter()
+            .map(|image| image.get_image_data_ref().len() as u64)
+            .sum();
+        let mut changed = Vec::new();
+        let mut bytes = 0;
+
+        for (index, image) in self.images.iter().enumerate() {
+            if let Some(transformed) = transform(image)? {
+                changed.push((index, transformed));
+            }
+            bytes += image.get_image_data_ref().len() as u64;
+
+            progress::report(
+                progress,
+                Progress {
+                    stage,
+                    page: Some(index),
+                    page_count: self.images.len(),
+                    bytes,
+                    total_bytes: Some(total),
+                },
+            )?;
+        }
+
+        for (index, image) in changed {
+            self.images[index] = image;
+        }
+        Ok(())
+    }
+
     /// Gets all images of the file
+    ///
+    /// This copies every image, use [images](#method.images) to iterate over them instead.
     pub fn get_images(&self) -> Vec<MangoImage> {
         self.images.clone()
     }
 
+    /// Gets an iterator over all images of the file
+    pub fn images(&self) -> slice::Iter<'_, MangoImage> {
+        self.images.iter()
+    }
+
+    /// Gets an iterator over all images of the file, which allows modifying them
+    pub fn images_mut(&mut self) -> slice::IterMut<'_, MangoImage> {
+        self.images.iter_mut()
+    }
+
+    /// Gets the count of all images of the file
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
     /// Gets an image of the file
     pub fn get_image(&self, index: usize) -> Option<&MangoImage> {
-        if self.images.len() > index {
-            return Some(&self.images[index]);
+        self.images.get(index)
+    }
+
+    /// Gets a mutable image from the file
+    pub fn get_image_mut(&mut self, index: usize) -> Option<&mut MangoImage> {
+        self.images.get_mut(index)
+    }
+
+    /// Inserts an image at `index` and shifts all images after it to the right.
+    ///
+    /// An index equal to the image count adds the image at the end.
+    /// The image becomes part of the chapter which contains the page it got inserted in front of.
+    pub fn insert_image(&mut self, index: usize, image: MangoImage) -> Result<(), MangoError> {
+        if index > self.images.len() {
+            return Err(out_of_range(index, self.images.len()));
         }
 
-        None
+        self.images.insert(index, image);
+        self.toc.page_inserted(index);
+        Ok(())
     }
 
-    /// Gets a mutable image from the file
-    pub fn get_image_mut(&mut self, index: usize) -> &mut MangoImage {
-        &mut self.images[index]
+    /// Removes the image at `index` and shifts all images after it to the left.
+    ///
+    /// Returns None if there is no image at the index.
+    /// Entries of the table of contents which don't contain any image anymore get removed.
+    pub fn remove_image(&mut self, index: usize) -> Option<MangoImage> {
+        if index >= self.images.len() {
+            return None;
+        }
+
+        let image = self.images.remove(index);
+        self.toc.page_removed(index, self.images.len());
+        Some(image)
+    }
+
+    /// Moves the image at `from` to `to`, the images in between get shifted.
+    pub fn move_image(&mut self, from: usize, to: usize) -> Result<(), MangoError> {
+        let len = self.images.len();
+        if from >= len || to >= len {
+            return Err(out_of_range(from.max(to), len));
+        }
+
+        let image = self.images.remove(from);
+        self.images.insert(to, image);
+        self.toc.page_moved(from, to, len);
+        Ok(())
+    }
+
+    /// Swaps two images, the table of contents stays as it is.
+    pub fn swap_images(&mut self, a: usize, b: usize) -> Result<(), MangoError> {
+        let len = self.images.len();
+        if a >= len || b >= len {
+            return Err(out_of_range(a.max(b), len));
+        }
+
+        self.images.swap(a, b);
+        Ok(())
+    }
+
+    /// Reverses the order of all images, the chapters keep their images.
+    pub fn reverse_images(&mut self) {
+        self.images.reverse();
+        self.toc.pages_reversed(self.images.len());
+    }
+
+    /// Keeps only the images for which `keep` returns true.
+    ///
+    /// Entries of the table of contents which don't contain any image anymore get removed.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&MangoImage) -> bool,
+    {
+        let mut removed = Vec::with_capacity(self.images.len());
+        self.images.retain(|image| {
+            let kept = keep(image);
+            removed.push(!kept);
+            kept
+        });
+
+        if removed.contains(&true) {
+            self.toc.pages_removed(&removed, self.images.len());
+        }
+    }
+
+    /// Removes metadata like EXIF data, ICC profiles and comments from all images,
+    /// see [MangoImage::sanitize](../image/struct.MangoImage.html#method.sanitize).
+    ///
+    /// Pages which can't be sanitized are kept as they are and listed in the report.
+    pub fn sanitize(&mut self) -> SanitizeReport {
+        let mut report = SanitizeReport::default();
+
+        for (index, image) in self.images.iter_mut().enumerate() {
+            match image.sanitize() {
+                Ok(ref removed) if removed.is_empty() => (),
+                Ok(removed) => report.removed.push((index, removed)),
+                Err(error) => report.failed.push((index, error)),
+            }
+        }
+
+        report
+    }
+
+    /// Stores images with the same data only once when the file gets saved as CBOR or BSON,
+    /// every further copy only refers to the first one. It is off by default.
+    ///
+    /// This breaks compatibility: older readers expect the data in every image and can't open
+    /// files which contain such references. Only turn it on if every reader is new enough.
+    /// JSON files always contain every image.
+    pub fn set_deduplicate(&mut self, deduplicate: bool) {
+        self.deduplicate = deduplicate;
+    }
+
+    /// Returns true if images with the same data are only stored once,
+    /// see [set_deduplicate](#method.set_deduplicate).
+    pub fn deduplicate(&self) -> bool {
+        self.deduplicate
+    }
+
+    /// Finds the pages which contain the same image, like a credit page at the end of every
+    /// chapter.
+    ///
+    /// Returns the indices of every group of pages with the same checksum, in the order of the
+    /// first page of each group. Pages without a duplicate aren't part of the result.
+    /// The checksum belongs to the original image, so compressed or encrypted pages are
+    /// found as well.
+    pub fn find_duplicates(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_checksum: HashMap<&str, usize> = HashMap::new();
+
+        for (index, image) in self.images.iter().enumerate() {
+            let checksum = image.get_meta_ref().checksum.as_str();
+            match by_checksum.get(checksum) {
+                Some(&group) => groups[group].push(index),
+                None => {
+                    by_checksum.insert(checksum, groups.len());
+                    groups.push(vec![index]);
+                }
+            }
+        }
+
+        groups.retain(|pages| pages.len() > 1);
+        groups
+    }
+
+    /// Finds pages which look alike by comparing their perceptual hashes, e.g. pages which got
+    /// re-encoded or scanned twice.
+    ///
+    /// Returns every pair of pages whose hashes differ in at most `threshold` of their 64 bits,
+    /// ordered by their first page. Pages without a perceptual hash are ignored,
+    /// the hashes get computed on import with the `phash` feature.
+    pub fn similar_pages(&self, threshold: u32) -> Vec<SimilarPages> {
+        let hashes: Vec<(usize, PerceptualHash)> = self
+            .images
+            .iter()
+            .enumerate()
+            .filter_map(|(i, image)| image.get_meta_ref().perceptual_hash.map(|h| (i, h)))
+            .collect();
+
+        let mut similar = Vec::new();
+        for (n, &(first, hash)) in hashes.iter().enumerate() {
+            for &(second, other) in &hashes[n + 1..] {
+                let distance = hash.distance(other);
+                if distance <= threshold {
+                    similar.push(SimilarPages {
+                        first,
+                        second,
+                        distance,
+                    });
+                }
+            }
+        }
+
+        similar
+    }
+
+    /// Finds pages which are probably out of order.
+    ///
+    /// Returns the pages whose original filename comes before the filename of the page in front
+    /// of them. Numbers inside of the filenames are compared by their value, so "10.jpg" comes
+    /// after "9.jpg".
+    pub fn misordered_pages(&self) -> Vec<usize> {
+        let keys: Vec<_> = self
+            .images
+            .iter()
+            .map(|image| natural_key(&image.get_meta_ref().filename))
+            .collect();
+
+        (1..keys.len()).filter(|&i| keys[i] < keys[i - 1]).collect()
+    }
+
+    /// Binds multiple files into one, e.g. chapters into a volume.
+    ///
+    /// The images are taken over as they are, compressed and encrypted images stay so.
+    /// Every file with a title or chapter number becomes an entry of the table of contents,
+    /// which contains the entries of the file. The entries of other files are added as they are.
+    pub fn concat<I>(files: I, meta: MangoMetadata) -> MangoFile
+    where
+        I: IntoIterator<Item = MangoFile>,
+    {
+        let mut result = MangoFile::new();
+        result.meta = meta;
+
+        for file in files {
+            if file.images.is_empty() {
+                continue;
+            }
+
+            let mut toc = file.toc;
+            if file.meta.title.is_some() || file.meta.chapter.is_some() {
+                let mut entry = TocEntry::new(file.meta.title, file.meta.chapter, 0);
+                entry.children = mem::take(toc.entries_mut());
+                toc.add(entry);
+            }
+
+            result.toc.append(toc, result.images.len());
+            result.images.extend(file.images);
+        }
+
+        result
+    }
+
+    /// Splits the file into the images before `index` and the images starting at `index`.
+    ///
+    /// Both files get the metadata of this file and their part of the table of contents,
+    /// a chapter which gets split is part of both. The images are taken over as they are.
+    pub fn split_at(mut self, index: usize) -> Result<(MangoFile, MangoFile), MangoError> {
+        if index > self.images.len() {
+            return Err(out_of_range(index, self.images.len()));
+        }
+
+        let mut tail = MangoFile::new();
+        tail.meta = self.meta.clone();
+        tail.images = self.images.split_off(index);
+        tail.toc = self.toc.split_off(index);
+        tail.toc.truncate(tail.images.len());
+
+        Ok((self, tail))
+    }
+
+    /// Splits the file into one file per top level entry of the table of contents,
+    /// e.g. an omnibus into its chapters.
+    ///
+    /// Every file gets the metadata of this file, with the title and chapter number of its entry,
+    /// and the nested entries as table of contents.
+    /// Images in front of the first entry become a file of their own.
+    /// The images are taken over as they are.
+    pub fn split_by_toc(self) -> Vec<MangoFile> {
+        let mut starts: Vec<usize> = self
+            .toc
+            .entries()
+            .iter()
+            .map(|e| e.start)
+            .filter(|&start| start > 0 && start < self.images.len())
+            .collect();
+        starts.dedup();
+
+        let mut files = Vec::with_capacity(starts.len() + 1);
+        let mut rest = self;
+        for &start in starts.iter().rev() {
+            // the start is in range, so this can't fail
+            let (head, part) = rest.split_at(start).unwrap();
+            files.push(part);
+            rest = head;
+        }
+        files.push(rest);
+        files.reverse();
+
+        for file in files.iter_mut() {
+            // every part starts with its entry, except the pages in front of the first one
+            if file.toc.entries().first().is_none_or(|e| e.start != 0) {
+                continue;
+            }
+
+            let entry = file.toc.entries_mut().remove(0);
+            if entry.title.is_some() {
+                file.meta.title = entry.title;
+            }
+            if entry.chapter.is_some() {
+                file.meta.chapter = entry.chapter;
+            }
+            *file.toc.entries_mut() = entry.children;
+        }
+
+        files
     }
 
     /// Gets a copy of the metadata of the file
@@ -385,8 +1035,51 @@ impl MangoFile {
     }
 
     /// Sets the images of the file
+    ///
+    /// Entries of the table of contents which start after the last new image get removed.
     pub fn set_images(&mut self, imgs: Vec<MangoImage>) {
         self.images = imgs;
+        self.toc.truncate(self.images.len());
+    }
+
+    /// Gets a reference of the table of contents of the file
+    pub fn get_toc(&self) -> &TableOfContents {
+        &self.toc
+    }
+
+    /// Gets a mutable reference of the table of contents of the file
+    pub fn get_toc_mut(&mut self) -> &mut TableOfContents {
+        &mut self.toc
+    }
+
+    /// Sets a new table of contents
+    pub fn set_toc(&mut self, toc: TableOfContents) {
+        self.toc = toc;
+    }
+
+    /// Gets the innermost entry of the table of contents the page belongs to.
+    ///
+    /// Returns None if the page doesn't exist or isn't part of any entry.
+    pub fn chapter_of(&self, page: usize) -> Option<&TocEntry> {
+        if page >= self.images.len() {
+            return None;
+        }
+
+        self.toc.find(page)
+    }
+}
+
+impl Serialize for MangoFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredFile {
+            meta: &self.meta,
+            toc: &self.toc,
+            images: StoredImages {
+                images: &self.images,
+                deduplicate: self.deduplicate,
+            },
+        }
+        .serialize(serializer)
     }
 }
 
@@ -396,10 +1089,189 @@ impl Default for MangoFile {
     }
 }
 
+/// Splits a filename into text and the number following it, for comparing numbers by their value.
+fn natural_key(filename: &str) -> Vec<(String, Option<u64>)> {
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut digits = String::new();
+
+    for c in filename.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        if !digits.is_empty() {
+            key.push((mem::take(&mut text), digits.parse().ok()));
+            digits.clear();
+        }
+        text.extend(c.to_lowercase());
+    }
+
+    if !text.is_empty() || !digits.is_empty() {
+        key.push((text, digits.parse().ok()));
+    }
+
+    key
+}
+
+fn out_of_range(index: usize, len: usize) -> MangoError {
+    MangoError::new(
+        MangoErrorKind::OutOfRange,
+        format!("page {} is out of range, the file has {} pages", index, len),
+    )
+}
+
+/// Describes an error while opening a file for reading.
+pub(crate) fn open_error(error: io::Error) -> MangoError {
+    let msg = match error.kind() {
+        io::ErrorKind::NotFound => "not found",
+        io::ErrorKind::PermissionDenied => "permission denied",
+        _ => "could not open file",
+    };
+    MangoError::with_cause(MangoErrorKind::Io, msg, error)
+}
+
+pub(crate) fn read_error(error: io::Error) -> MangoError {
+    MangoError::with_cause(MangoErrorKind::Io, "could not read file", error)
+}
+
+/// Describes an error while writing a file.
+pub(crate) fn save_error(error: io::Error) -> MangoError {
+    let msg = match error.kind() {
+        io::ErrorKind::PermissionDenied => "permission denied",
+        io::ErrorKind::StorageFull => "no space left on device",
+        _ => "could not write file",
+    };
+    MangoError::with_cause(MangoErrorKind::Write, msg, error)
+}
+
+/// Files of at least this size get their metadata updated in place, smaller ones get rewritten.
+const IN_PLACE_MIN_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The size of the chunks in which files get read and written when the progress is reported.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reads a whole file, for decoding it afterwards.
+fn read_file(p: &Path) -> Result<Vec<u8>, MangoError> {
+    let mut bytes = Vec::new();
+    File::open(p)
+        .map_err(open_error)?
+        .read_to_end(&mut bytes)
+        .map_err(read_error)?;
+    Ok(bytes)
+}
+
+/// Reads a whole file in chunks and reports the progress after each of them.
+fn read_file_with_progress(
+    p: &Path,
+    progress: &mut dyn ProgressHandler,
+) -> Result<Vec<u8>, MangoError> {
+    let mut file = File::open(p).map_err(open_error)?;
+    let total = file.metadata().ok().map(|metadata| metadata.len());
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(read_error(e)),
+        };
+        bytes.extend_from_slice(&chunk[..read]);
+
+        progress::report(
+            progress,
+            Progress {
+                stage: Stage::Read,
+                page: None,
+                page_count: 0,
+                bytes: bytes.len() as u64,
+                total_bytes: total,
+            },
+        )?;
+    }
+
+    Ok(bytes)
+}
+
+/// Numbers the temporary files of this process, so every save gets a file of its own.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new temporary file next to the file `name` in `dir`.
+///
+/// The file is created exclusively, saves running at the same time in other threads or
+/// processes never write into the same temporary file.
+fn create_temp(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let mut attempts = 0;
+    loop {
+        let number = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp = dir.join(format!(".{}.{}.{}.tmp", name, process::id(), number));
+
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            // left behind by a crashed process which had the same id
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 100 => {
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes the temporary file which is going to replace `p`.
+fn fill_temp<F>(p: &Path, file: &mut File, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    // keep the permissions of the file which gets replaced
+    if let Ok(metadata) = fs::metadata(p) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    write(file)?;
+    file.sync_all()
+}
+
+/// Writes a file by writing a temporary file in the same directory first,
+/// which then replaces the file at `p`.
+pub(crate) fn write_atomically<F>(p: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let name = p.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the path doesn't point to a file",
+        )
+    })?;
+    let dir = match p.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let (temp, mut file) = create_temp(dir, &name.to_string_lossy())?;
+
+    let result = fill_temp(p, &mut file, write).and_then(|_| fs::rename(&temp, p));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    // make sure the rename itself is on the disk, this isn't possible on every platform
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::MangoFile;
+    use super::{MangoErrorKind as ErrorKind, MangoFile, Serialization};
     use encryption;
+    use std::fs;
+    use std::io;
     use std::path::Path;
 
     fn create() {
@@ -418,13 +1290,47 @@ mod tests {
         assert_eq!(file.unwrap().get_meta().title, Some("test".to_string()));
     }
 
+    #[test]
+    fn open_missing() {
+        let error = MangoFile::open(Path::new("does_not_exist.mango"))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Io);
+        assert_eq!(error.io_kind(), Some(io::ErrorKind::NotFound));
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let mut file = MangoFile::new();
+        file.get_meta_mut().title = Some("in memory".to_string());
+        file.add_image_by_path(Path::new("test.jpg")).unwrap();
+
+        for format in &[
+            Serialization::Cbor,
+            Serialization::Bson,
+            Serialization::Json,
+        ] {
+            let bytes = file.to_bytes(*format).unwrap();
+            let opened = MangoFile::from_bytes(&bytes).unwrap();
+            assert_eq!(opened.get_meta().title, Some("in memory".to_string()));
+            assert_eq!(
+                opened.get_image(0).unwrap().get_image_data(),
+                file.get_image(0).unwrap().get_image_data()
+            );
+            assert!(MangoFile::from_bytes_as(&bytes, *format).is_ok());
+        }
+
+        let error = MangoFile::from_bytes(b"not a mango file").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+    }
+
     // TODO move tests below to base64_image.rs
     #[test]
     #[cfg(feature = "aes")]
     fn encrypt() {
         let mut file = MangoFile::new();
-        file.add_image_by_path(Path::new("test.jpg"));
-        let image = file.get_image_mut(0);
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+        let image = file.get_image_mut(0).unwrap();
         let key = String::from("1234567812345678");
         let encrypted_image = image
             .clone()
@@ -440,7 +1346,7 @@ mod tests {
         let added = file.add_image_by_path(Path::new("test.jpg"));
         assert!(added.is_ok());
 
-        let image = file.get_image_mut(0);
+        let image = file.get_image_mut(0).unwrap();
         let save = image.save("test_unencrypted.jpg");
         assert!(save.is_ok());
     }
@@ -478,4 +1384,568 @@ mod tests {
         let save = file.save_bson(Path::new("save.bson"));
         assert!(save.is_ok());
     }
+
+    #[test]
+    fn toc_in_all_formats() {
+        use toc::TocEntry;
+
+        let mut file = MangoFile::new();
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+
+        let mut chapter = TocEntry::new(Some(String::from("first")), Some(1.0), 0);
+        chapter.add_child(TocEntry::new(Some(String::from("part 2")), None, 1));
+        file.get_toc_mut().add(chapter);
+        file.get_toc_mut()
+            .add(TocEntry::new(Some(String::from("second")), Some(1.5), 2));
+
+        assert!(file.save_cbor(Path::new("toc.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("toc.bson")).is_ok());
+        assert!(file.save_json(Path::new("toc.json")).is_ok());
+
+        for name in &["toc.cbor", "toc.bson", "toc.json"] {
+            let opened = MangoFile::open(Path::new(name)).unwrap();
+            assert_eq!(opened.get_toc(), file.get_toc());
+            assert_eq!(
+                opened.chapter_of(1).unwrap().title,
+                Some(String::from("part 2"))
+            );
+            assert_eq!(opened.chapter_of(2).unwrap().chapter, Some(1.5));
+            assert!(opened.chapter_of(3).is_none());
+        }
+    }
+
+    fn filenames(file: &MangoFile) -> Vec<String> {
+        file.images()
+            .map(|i| i.get_meta().filename.clone())
+            .collect()
+    }
+
+    fn file_with_pages(count: usize) -> MangoFile {
+        let mut file = MangoFile::new();
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+        for i in 0..count {
+            let mut image = file.get_image(0).unwrap().clone();
+            image.get_meta_mut().filename = i.to_string();
+            file.add_image(image);
+        }
+        file.remove_image(0);
+        file
+    }
+
+    #[test]
+    fn page_manipulation() {
+        use error::ErrorKind;
+        use toc::TocEntry;
+
+        let mut file = file_with_pages(4);
+        file.get_toc_mut().add(TocEntry::new(None, Some(1.0), 0));
+        file.get_toc_mut().add(TocEntry::new(None, Some(2.0), 2));
+
+        let image = file.remove_image(3).unwrap();
+        assert!(file.remove_image(3).is_none());
+        assert_eq!(filenames(&file), vec!["0", "1", "2"]);
+
+        assert!(file.insert_image(1, image.clone()).is_ok());
+        assert_eq!(filenames(&file), vec!["0", "3", "1", "2"]);
+        let error = file.insert_image(5, image).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::OutOfRange);
+
+        assert!(file.move_image(0, 3).is_ok());
+        assert_eq!(filenames(&file), vec!["3", "1", "2", "0"]);
+        assert!(file.move_image(0, 4).is_err());
+        assert_eq!(file.chapter_of(3).unwrap().chapter, Some(2.0));
+
+        assert!(file.swap_images(0, 3).is_ok());
+        assert_eq!(filenames(&file), vec!["0", "1", "2", "3"]);
+        assert!(file.swap_images(4, 0).is_err());
+
+        file.reverse_images();
+        assert_eq!(filenames(&file), vec!["3", "2", "1", "0"]);
+        assert_eq!(file.chapter_of(0).unwrap().chapter, Some(2.0));
+
+        file.retain(|i| i.get_meta().filename != "1");
+        assert_eq!(filenames(&file), vec!["3", "2", "0"]);
+
+        for image in file.images_mut() {
+            image.get_meta_mut().label = Some(String::from("page"));
+        }
+        assert!(file.images().all(|i| i.get_meta().label.is_some()));
+        assert!(file.get_image_mut(3).is_none());
+    }
+
+    #[test]
+    fn duplicates_are_stored_once() {
+        let mut file = file_with_pages(3);
+        let credits = file.get_image(0).unwrap().clone();
+        file.add_image(credits.clone());
+        file.add_image(credits);
+        let data_len = file.get_image(0).unwrap().get_image_data_ref().len();
+
+        assert_eq!(file.find_duplicates(), vec![vec![0, 1, 2, 3, 4]]);
+
+        // every copy is written unless it is turned on
+        assert!(!file.deduplicate());
+        assert!(file.save_cbor(Path::new("no_dedup.cbor")).is_ok());
+        let size = fs::metadata("no_dedup.cbor").unwrap().len() as usize;
+        assert!(size > 5 * data_len);
+
+        file.set_deduplicate(true);
+        assert!(file.save_cbor(Path::new("dedup.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("dedup.bson")).is_ok());
+
+        for name in &["dedup.cbor", "dedup.bson"] {
+            let size = fs::metadata(name).unwrap().len() as usize;
+            assert!(size < 2 * data_len);
+
+            let opened = MangoFile::open(Path::new(name)).unwrap();
+            assert_eq!(filenames(&opened), filenames(&file));
+            let image = opened.get_image(4).unwrap();
+            assert!(image.same_data(file.get_image(0).unwrap()));
+        }
+    }
+
+    #[test]
+    fn read_single_image() {
+        let mut file = file_with_pages(2);
+        file.add_image_by_path(Path::new("test2.jpg")).unwrap();
+        file.get_meta_mut().title = Some("pages".to_string());
+
+        assert!(file.save_cbor(Path::new("single.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("single.bson")).is_ok());
+        assert!(file.save_json(Path::new("single.json")).is_ok());
+
+        for name in &["single.cbor", "single.bson", "single.json"] {
+            for (index, expected) in file.images().enumerate() {
+                let image = MangoFile::read_image(Path::new(name), index).unwrap();
+                assert_eq!(image.get_meta().filename, expected.get_meta().filename);
+                assert_eq!(image.get_image_data_ref(), expected.get_image_data_ref());
+            }
+
+            let error = MangoFile::read_image(Path::new(name), 3).err().unwrap();
+            assert_eq!(error.kind(), super::MangoErrorKind::OutOfRange);
+            assert_eq!(MangoFile::read_page_count(Path::new(name)).unwrap(), 3);
+            fs::remove_file(name).unwrap();
+        }
+    }
+
+    #[test]
+    fn similar_and_misordered_pages() {
+        use meta::PerceptualHash;
+
+        let mut file = file_with_pages(4);
+        let hashes = [0xff00, 0xff01, 0x00ff, 0xff03];
+        for (image, &hash) in file.images_mut().zip(hashes.iter()) {
+            image.get_meta_mut().perceptual_hash = Some(PerceptualHash(hash));
+        }
+        file.get_image_mut(2)
+            .unwrap()
+            .get_meta_mut()
+            .perceptual_hash = None;
+
+        let similar: Vec<_> = file
+            .similar_pages(1)
+            .into_iter()
+            .map(|s| (s.first, s.second, s.distance))
+            .collect();
+        assert_eq!(similar, vec![(0, 1, 1), (1, 3, 1)]);
+        assert_eq!(file.similar_pages(2).len(), 3);
+
+        let names = ["page_9.jpg", "Page_10.jpg", "page_2.jpg", "page_11.jpg"];
+        for (image, name) in file.images_mut().zip(names.iter()) {
+            image.get_meta_mut().filename = name.to_string();
+        }
+        assert_eq!(file.misordered_pages(), vec![2]);
+    }
+
+    #[test]
+    fn sanitize() {
+        use image::{MangoImage, MetadataKind};
+        use meta::checksum;
+
+        let mut file = file_with_pages(2);
+        let clean = file.get_image(1).unwrap().get_image_data();
+        let mut meta = file.get_image(0).unwrap().get_meta();
+
+        // a JPEG with EXIF data right after the start of image
+        let mut data = clean[..2].to_vec();
+        data.extend_from_slice(&[0xff, 0xe1, 0x00, 0x0a]);
+        data.extend_from_slice(b"Exif\0\0GP");
+        data.extend_from_slice(&clean[2..]);
+        meta.checksum = checksum(&data);
+        file.insert_image(0, MangoImage::new(data, meta)).unwrap();
+
+        let report = file.sanitize();
+        assert_eq!(report.removed, vec![(0, vec![MetadataKind::Exif])]);
+        assert!(report.is_complete());
+
+        let image = file.get_image(0).unwrap();
+        assert_eq!(image.get_image_data(), clean);
+        let meta = image.get_meta();
+        assert_eq!(
+            meta.checksum,
+            file.get_image(1).unwrap().get_meta().checksum
+        );
+        let transform = meta.transform.unwrap();
+        assert!(transform.metadata_stripped);
+        assert_ne!(transform.original_checksum, meta.checksum);
+
+        // nothing left to remove
+        assert!(file.sanitize().removed.is_empty());
+    }
+
+    #[test]
+    fn concat_and_split() {
+        use meta::MangoMetadata;
+
+        let mut first = file_with_pages(2);
+        first.get_meta_mut().chapter = Some(1.0);
+        let mut second = file_with_pages(3);
+        second.get_meta_mut().title = Some(String::from("extra"));
+        second.get_meta_mut().chapter = Some(1.5);
+
+        let mut meta = MangoMetadata::new();
+        meta.volume = Some(1);
+        let volume = MangoFile::concat(vec![first, MangoFile::new(), second], meta);
+        assert_eq!(filenames(&volume), vec!["0", "1", "0", "1", "2"]);
+        assert_eq!(volume.get_toc().entries().len(), 2);
+        assert_eq!(volume.chapter_of(3).unwrap().chapter, Some(1.5));
+        let data = volume.get_image(2).unwrap().get_image_data();
+
+        let chapters = volume.split_by_toc();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(filenames(&chapters[1]), vec!["0", "1", "2"]);
+        assert_eq!(
+            chapters[1].get_meta_ref().title,
+            Some(String::from("extra"))
+        );
+        assert_eq!(chapters[1].get_meta_ref().chapter, Some(1.5));
+        assert_eq!(chapters[1].get_meta_ref().volume, Some(1));
+        assert!(chapters[1].get_toc().is_empty());
+        assert_eq!(chapters[1].get_image(0).unwrap().get_image_data(), data);
+
+        let (head, tail) = file_with_pages(3).split_at(1).unwrap();
+        assert_eq!(filenames(&head), vec!["0"]);
+        assert_eq!(filenames(&tail), vec!["1", "2"]);
+        assert!(file_with_pages(3).split_at(4).is_err());
+    }
+
+    #[test]
+    fn failed_save_keeps_old_file() {
+        use super::write_atomically;
+        use std::io;
+
+        let path = Path::new("atomic.cbor");
+        fs::write(path, b"old").unwrap();
+
+        let result = write_atomically(path, |_| Err(io::Error::other("crash")));
+        assert!(result.is_err());
+        assert_eq!(fs::read(path).unwrap(), b"old");
+
+        // the temporary file got removed
+        let leftovers = fs::read_dir(".")
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(".atomic.cbor.")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn progress_and_cancel() {
+        use error::ErrorKind;
+        use progress::{Control, Progress, Stage};
+
+        let path = Path::new("progress.cbor");
+        let file = get_full_file();
+
+        let mut reports: Vec<Progress> = Vec::new();
+        file.save_with_progress(path, Serialization::Cbor, &mut |p: &Progress| {
+            reports.push(*p);
+            Control::Continue
+        })
+        .unwrap();
+        assert_eq!(reports[0].stage, Stage::Encode);
+        assert_eq!(reports[0].page_count, file.image_count());
+        let last = reports.last().unwrap();
+        assert_eq!(last.stage, Stage::Write);
+        assert_eq!(Some(last.bytes), last.total_bytes);
+
+        // a cancelled save keeps the saved file
+        let saved = fs::read(path).unwrap();
+        let error = MangoFile::new()
+            .save_with_progress(path, Serialization::Cbor, &mut |_: &Progress| {
+                Control::Cancel
+            })
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Cancelled);
+        assert_eq!(fs::read(path).unwrap(), saved);
+
+        let mut reports: Vec<Progress> = Vec::new();
+        let opened = MangoFile::open_with_progress(path, &mut |p: &Progress| {
+            reports.push(*p);
+            Control::Continue
+        })
+        .unwrap();
+        let read = reports[reports.len() - 2];
+        assert_eq!((read.stage, read.bytes), (Stage::Read, saved.len() as u64));
+        assert_eq!(reports.last().unwrap().stage, Stage::Decode);
+        assert_eq!(opened.image_count(), file.image_count());
+
+        let error = MangoFile::open_with_progress(path, &mut |_: &Progress| Control::Cancel)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Cancelled);
+
+        // the decoding can be cancelled after the file has been read
+        let error = MangoFile::open_with_progress(path, &mut |p: &Progress| match p.stage {
+            Stage::Decode => Control::Cancel,
+            _ => Control::Continue,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Cancelled);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn import_images() {
+        use progress::{Control, Progress};
+
+        let mut file = MangoFile::new();
+        let mut pages = Vec::new();
+        file.import_images(&["test.jpg", "test2.jpg"], &mut |p: &Progress| {
+            pages.push((p.page, p.page_count));
+            Control::Continue
+        })
+        .unwrap();
+        assert_eq!(pages, vec![(Some(0), 2), (Some(1), 2)]);
+        assert_eq!(file.image_count(), 2);
+
+        // nothing gets added after a cancellation or a missing file
+        assert!(file
+            .import_images(&["test.jpg"], &mut |_: &Progress| Control::Cancel)
+            .is_err());
+        assert!(file
+            .import_images(&["test.jpg", "missing.jpg"], &mut |_: &Progress| {
+                Control::Continue
+            })
+            .is_err());
+        assert_eq!(file.image_count(), 2);
+    }
+
+    #[test]
+    #[cfg(all(feature = "gzip", feature = "aes"))]
+    fn bulk_compress_and_encrypt() {
+        use compression::CompressionType;
+        use encryption::EncryptionType;
+        use error::ErrorKind;
+        use progress::{Control, Progress, Stage};
+
+        let mut file = file_with_pages(3);
+        let original = file.get_images();
+
+        let mut seen = 0;
+        let error = file
+            .compress_images(CompressionType::GZIP, &mut |p: &Progress| {
+                seen += 1;
+                if p.page == Some(1) {
+                    Control::Cancel
+                } else {
+                    Control::Continue
+                }
+            })
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::Cancelled);
+        assert_eq!(seen, 2);
+        for (image, before) in file.images().zip(&original) {
+            assert!(image.get_meta_ref().compression.is_none());
+            assert_eq!(image.get_image_data_ref(), before.get_image_data_ref());
+        }
+
+        let mut stages = Vec::new();
+        file.compress_images(CompressionType::GZIP, &mut |p: &Progress| {
+            stages.push(p.stage);
+            Control::Continue
+        })
+        .unwrap();
+        file.encrypt_images(
+            EncryptionType::AES128,
+            "1234567812345678",
+            &mut |p: &Progress| {
+                stages.push(p.stage);
+                Control::Continue
+            },
+        )
+        .unwrap();
+        assert_eq!(stages.len(), 6);
+        assert_eq!(stages[0], Stage::Compress);
+        assert_eq!(stages[5], Stage::Encrypt);
+        assert!(file
+            .images()
+            .all(|image| image.get_meta_ref().encryption.is_some()));
+    }
+
+    #[test]
+    fn write_errors() {
+        let file = MangoFile::new();
+        let save = file.save(Path::new("does/not/exist.mango"));
+        let error = save.err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Write);
+        assert_eq!(error.io_kind(), Some(io::ErrorKind::NotFound));
+
+        let error = super::save_error(io::Error::from(io::ErrorKind::StorageFull));
+        assert_eq!(error.kind(), ErrorKind::Write);
+        assert!(error.to_string().starts_with("no space left on device"));
+    }
+
+    #[test]
+    fn read_metadata() {
+        let mut file = get_full_file();
+        file.get_meta_mut().title = Some(String::from("only the metadata"));
+
+        assert!(file.save_cbor(Path::new("read_meta.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("read_meta.bson")).is_ok());
+        assert!(file.save_json(Path::new("read_meta.json")).is_ok());
+
+        for name in &["read_meta.cbor", "read_meta.bson", "read_meta.json"] {
+            let meta = MangoFile::read_metadata(Path::new(name)).unwrap();
+            assert_eq!(meta.title, Some(String::from("only the metadata")));
+        }
+    }
+
+    #[test]
+    fn save_from_threads() {
+        use std::thread;
+
+        let dir = Path::new("save_threads");
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir(dir).unwrap();
+
+        let titles: Vec<String> = (0..8).map(|i| format!("saved by thread {}", i)).collect();
+        let threads: Vec<_> = titles
+            .iter()
+            .cloned()
+            .map(|title| {
+                thread::spawn(move || {
+                    let mut file = MangoFile::new();
+                    file.add_image_by_path(Path::new("test.jpg")).unwrap();
+                    file.get_meta_mut().title = Some(title);
+                    for _ in 0..5 {
+                        file.save(&Path::new("save_threads").join("shared.mango"))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // one of the saves won and no temporary file was left behind
+        let file = MangoFile::open(&dir.join("shared.mango")).unwrap();
+        assert!(titles.contains(file.get_meta().title.as_ref().unwrap()));
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn read_truncated() {
+        let mut file = MangoFile::new();
+        file.add_image_by_path(Path::new("test.jpg")).unwrap();
+        file.add_image_by_path(Path::new("test2.jpg")).unwrap();
+        file.save_cbor(Path::new("truncated.cbor")).unwrap();
+        file.save_bson(Path::new("truncated.bson")).unwrap();
+
+        for name in &["truncated.cbor", "truncated.bson"] {
+            let bytes = fs::read(name).unwrap();
+            fs::write(name, &bytes[..bytes.len() / 2]).unwrap();
+
+            // the error is about the file itself, it isn't mistaken for JSON
+            let error = MangoFile::read_image(Path::new(name), 1).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::Decode);
+            assert!(!error.to_string().contains("JSON"), "{}", error);
+
+            fs::write(name, &bytes[..20]).unwrap();
+            let error = MangoFile::read_metadata(Path::new(name)).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::Decode);
+            assert!(!error.to_string().contains("JSON"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn update_metadata() {
+        use meta::MangoMetadata;
+
+        let mut file = get_full_file();
+        file.get_meta_mut().title = Some("a rather long title ".repeat(3));
+
+        assert!(file.save_cbor(Path::new("update_meta.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("update_meta.bson")).is_ok());
+        assert!(file.save_json(Path::new("update_meta.json")).is_ok());
+
+        for name in &["update_meta.cbor", "update_meta.bson", "update_meta.json"] {
+            let path = Path::new(name);
+            let size = fs::metadata(path).unwrap().len();
+
+            // a shorter title fits into the old space
+            let mut meta = MangoMetadata::new();
+            meta.title = Some(String::from("short"));
+            MangoFile::update_metadata(path, &meta).unwrap();
+            if !name.ends_with(".json") {
+                assert_eq!(fs::metadata(path).unwrap().len(), size);
+            }
+
+            let opened = MangoFile::open(path).unwrap();
+            assert_eq!(opened.get_meta().title, Some(String::from("short")));
+
+            // a longer one needs a rewrite
+            meta.title = Some("a much longer title ".repeat(5));
+            MangoFile::update_metadata(path, &meta).unwrap();
+            assert!(fs::metadata(path).unwrap().len() > size);
+
+            let opened = MangoFile::open(path).unwrap();
+            assert_eq!(opened.get_meta().title, meta.title);
+            assert_eq!(
+                opened.get_image(0).unwrap().get_meta().checksum,
+                file.get_image(0).unwrap().get_meta().checksum
+            );
+        }
+    }
+
+    #[test]
+    fn page_metadata_in_all_formats() {
+        use meta::PageKind;
+
+        let mut file = MangoFile::new();
+        assert!(file.add_image_by_path(Path::new("test.jpg")).is_ok());
+        {
+            let meta = file.get_image_mut(0).unwrap().get_meta_mut();
+            meta.page_kind = Some(PageKind::Credits);
+            meta.double_page = true;
+            meta.caption = Some(String::from("the credits"));
+        }
+
+        assert!(file.save_cbor(Path::new("page_meta.cbor")).is_ok());
+        assert!(file.save_bson(Path::new("page_meta.bson")).is_ok());
+        assert!(file.save_json(Path::new("page_meta.json")).is_ok());
+
+        for name in &["page_meta.cbor", "page_meta.bson", "page_meta.json"] {
+            let opened = MangoFile::open(Path::new(name)).unwrap();
+            let meta = opened.get_image(0).unwrap().get_meta();
+            assert_eq!(meta.page_kind, Some(PageKind::Credits));
+            assert!(meta.double_page);
+            assert_eq!(meta.caption, Some(String::from("the credits")));
+            assert!(meta.label.is_none());
+        }
+    }
 }
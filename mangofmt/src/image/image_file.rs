@@ -1,6 +1,6 @@
+use error::Error;
 use image::MangoImage;
 use meta::ImageFileMetadata;
-use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 
 /// Represents an image file.
@@ -19,17 +19,12 @@ impl ImageFile {
     ///
     /// The new instance is based on a file from the file system.
     pub fn open(path: &Path) -> Result<ImageFile, Error> {
-        if path.is_file() {
-            match ImageFileMetadata::new(&path) {
-                Some(meta) => Ok(ImageFile {
-                    path: path.to_path_buf(),
-                    meta,
-                }),
-                None => Err(Error::new(ErrorKind::Other, "couldn't read metadata")),
-            }
-        } else {
-            Err(Error::new(ErrorKind::InvalidInput, "path is not a file"))
-        }
+        let meta = ImageFileMetadata::new(path)?;
+
+        Ok(ImageFile {
+            path: path.to_path_buf(),
+            meta,
+        })
     }
 
     /// Returns an instance of the internal Path to the File.
@@ -1,14 +1,18 @@
-use super::ImageFile;
+use super::sanitize::{strip_metadata, MetadataKind};
+use super::{ImageFile, Mime};
 use base64;
 use compression;
 use compression::{CompressionError, CompressionType};
 use encryption;
 use encryption::{EncryptionError, EncryptionType};
-use meta::MangoImageMetadata;
+use error::{Error, ErrorKind};
+use meta::{checksum, ImageFileMetadata, MangoImageMetadata, PerceptualHash, Transform};
+use serde::{Deserializer, Serializer};
 use serde_bytes;
 use std;
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::Arc;
 
 /// Represents an image inside of a MangoFile.
 ///
@@ -16,10 +20,15 @@ use std::io::prelude::*;
 /// an instance of MangoImageMetaData.
 ///
 /// The image data can be compressed and encrypted, see the coresponding methods for more info.
+///
+/// Copies of an image share their data until one of them gets changed.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MangoImage {
-    #[serde(with = "serde_bytes")]
-    data: Vec<u8>,
+    #[serde(
+        serialize_with = "serialize_data",
+        deserialize_with = "deserialize_data"
+    )]
+    data: Arc<Vec<u8>>,
     meta: MangoImageMetadata,
 }
 
@@ -28,7 +37,49 @@ impl MangoImage {
     ///
     /// but you have to provide all values.
     pub fn new(data: Vec<u8>, meta: MangoImageMetadata) -> MangoImage {
-        MangoImage { data, meta }
+        MangoImage {
+            data: Arc::new(data),
+            meta,
+        }
+    }
+
+    /// Creates a MangoImage which uses the same data as `other`, without copying it.
+    pub(crate) fn sharing_data(other: &MangoImage, meta: MangoImageMetadata) -> MangoImage {
+        MangoImage {
+            data: Arc::clone(&other.data),
+            meta,
+        }
+    }
+
+    /// Creates a new MangoImage from the bytes of an image file.
+    ///
+    /// The checksum gets computed and the format is determined by the bytes,
+    /// the extension of the filename is only used if the format can't be recognized.
+    pub fn from_bytes(data: Vec<u8>, filename: &str) -> Result<MangoImage, Error> {
+        let mime = match Mime::sniff(&data) {
+            Some(mime) => mime,
+            None => Mime::path_string_to_mime(filename).ok_or_else(|| {
+                Error::new(ErrorKind::UnsupportedFormat, "file format is not supported")
+            })?,
+        };
+
+        let mut meta = ImageFileMetadata {
+            path: filename.to_string(),
+            checksum: checksum(&data),
+            mime,
+        }
+        .to_base64_metadata();
+        meta.perceptual_hash = perceptual_hash(&data);
+
+        Ok(MangoImage::new(data, meta))
+    }
+
+    /// Creates a new MangoImage by reading an image file until its end,
+    /// see [from_bytes](#method.from_bytes).
+    pub fn from_reader<R: Read>(mut reader: R, filename: &str) -> Result<MangoImage, Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        MangoImage::from_bytes(data, filename)
     }
 
     /// Creates a new MangoImage based on an ImageFile.
@@ -42,7 +93,10 @@ impl MangoImage {
         let _ = file.read_to_end(&mut vec).is_err();
 
         let new_meta = file_image.get_meta();
-        MangoImage::new(vec, new_meta.to_base64_metadata())
+        let mut meta = new_meta.to_base64_metadata();
+        meta.perceptual_hash = perceptual_hash(&vec);
+
+        MangoImage::new(vec, meta)
     }
 
     /// Returns the meta data.
@@ -50,6 +104,11 @@ impl MangoImage {
         self.meta.clone()
     }
 
+    /// Returns a reference of the meta data.
+    pub fn get_meta_ref(&self) -> &MangoImageMetadata {
+        &self.meta
+    }
+
     /// Returns the meta data in a mutable form.
     pub fn get_meta_mut(&mut self) -> &mut MangoImageMetadata {
         &mut self.meta
@@ -57,12 +116,22 @@ impl MangoImage {
 
     /// Returns the raw image data.
     pub fn get_image_data(&self) -> Vec<u8> {
-        self.data.clone()
+        self.data.to_vec()
+    }
+
+    /// Returns a reference of the raw image data.
+    pub fn get_image_data_ref(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns true if both images contain the same bytes.
+    pub fn same_data(&self, other: &MangoImage) -> bool {
+        Arc::ptr_eq(&self.data, &other.data) || self.data == other.data
     }
 
     /// Returns the raw image data in a Base64 encoding.
     pub fn get_base64_image_data(&self) -> String {
-        base64::encode(&self.data.clone())
+        base64::encode(self.data.as_slice())
     }
 
     /// Compresses the MangoImage and returns a copy of it.
@@ -79,8 +148,8 @@ impl MangoImage {
         let compressed_opt = self.clone().compress(comp);
         if compressed_opt.is_ok() {
             let compressed_img = compressed_opt.unwrap();
-            self.data = compressed_img.get_image_data();
-            self.meta = compressed_img.get_meta();
+            self.data = compressed_img.data;
+            self.meta = compressed_img.meta;
             true
         } else {
             false
@@ -104,8 +173,8 @@ impl MangoImage {
         let uncompressed_opt = self.clone().uncompress();
         if uncompressed_opt.is_ok() {
             let uncompressed_img = uncompressed_opt.unwrap();
-            self.data = uncompressed_img.get_image_data();
-            self.meta = uncompressed_img.get_meta();
+            self.data = uncompressed_img.data;
+            self.meta = uncompressed_img.meta;
             true
         } else {
             false
@@ -130,8 +199,8 @@ impl MangoImage {
         let encrypted_opt = self.clone().encrypt(etype, key);
         if encrypted_opt.is_ok() {
             let encrypted_img = encrypted_opt.unwrap();
-            self.data = encrypted_img.get_image_data();
-            self.meta = encrypted_img.get_meta();
+            self.data = encrypted_img.data;
+            self.meta = encrypted_img.meta;
             true
         } else {
             false
@@ -154,14 +223,104 @@ impl MangoImage {
         let decrypted_opt = self.clone().decrypt(key);
         if decrypted_opt.is_ok() {
             let decrypted_img = decrypted_opt.unwrap();
-            self.data = decrypted_img.get_image_data();
-            self.meta = decrypted_img.get_meta();
+            self.data = decrypted_img.data;
+            self.meta = decrypted_img.meta;
             true
         } else {
             false
         }
     }
 
+    /// Checks that the data matches the checksum in the metadata.
+    ///
+    /// Only plain images can be checked, the checksum belongs to the data before it got
+    /// compressed or encrypted. Decrypting with a wrong key doesn't always fail, the data is
+    /// garbage then and the check fails with `ErrorKind::Integrity`.
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.meta.encryption.is_some() || self.meta.compression.is_some() {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "compressed or encrypted images can't be verified",
+            ));
+        }
+
+        if checksum(&self.data) != self.meta.checksum {
+            return Err(Error::new(
+                ErrorKind::Integrity,
+                "the image doesn't match its checksum",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Computes the perceptual hash of the image and stores it in the metadata.
+    ///
+    /// Compressed images get decompressed for it, encrypted images can't be hashed.
+    #[cfg(feature = "phash")]
+    pub fn compute_perceptual_hash(&mut self) -> Result<PerceptualHash, Error> {
+        if self.meta.encryption.is_some() {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "encrypted images can't be hashed",
+            ));
+        }
+
+        let hash = match self.meta.compression {
+            Some(_) => super::phash::compute(&self.uncompress()?.data)?,
+            None => super::phash::compute(&self.data)?,
+        };
+        self.meta.perceptual_hash = Some(hash);
+        Ok(hash)
+    }
+
+    /// Removes metadata like EXIF data, ICC profiles and comments from a JPEG, PNG or WebP image,
+    /// without re-encoding it.
+    ///
+    /// Returns what got removed. The checksum gets updated, the transform in the metadata keeps
+    /// the checksum of the original image.
+    /// Compressed images get decompressed and compressed again, encrypted images can't be
+    /// sanitized.
+    pub fn sanitize(&mut self) -> Result<Vec<MetadataKind>, Error> {
+        if self.meta.encryption.is_some() {
+            return Err(Error::new(
+                ErrorKind::Crypto,
+                "encrypted images can't be sanitized",
+            ));
+        }
+
+        let compression = self.meta.compression.clone();
+        let mut image = match compression {
+            Some(_) => self.uncompress()?,
+            None => self.clone(),
+        };
+
+        let (data, removed) = strip_metadata(image.meta.mime, &image.data)?;
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        let original = Transform {
+            original_mime: image.meta.mime,
+            original_checksum: image.meta.checksum.clone(),
+            metadata_stripped: false,
+            original_size: None,
+            grayscale: false,
+        };
+        image
+            .meta
+            .transform
+            .get_or_insert(original)
+            .metadata_stripped = true;
+        image.meta.checksum = checksum(&data);
+        image.data = Arc::new(data);
+
+        if let Some(compression) = compression {
+            image = image.compress(compression)?;
+        }
+        *self = image;
+        Ok(removed)
+    }
+
     /// saves the raw image data to a file.
     pub fn save(&self, file_name: &str) -> std::io::Result<()> {
         let mut file = File::create(file_name)?;
@@ -170,11 +329,31 @@ impl MangoImage {
     }
 }
 
+// images which can't be decoded just don't get a hash
+#[cfg(feature = "phash")]
+pub(super) fn perceptual_hash(data: &[u8]) -> Option<PerceptualHash> {
+    super::phash::compute(data).ok()
+}
+
+#[cfg(not(feature = "phash"))]
+pub(super) fn perceptual_hash(_data: &[u8]) -> Option<PerceptualHash> {
+    None
+}
+
+fn serialize_data<S: Serializer>(data: &Arc<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+    serde_bytes::serialize(data.as_slice(), serializer)
+}
+
+fn deserialize_data<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<Vec<u8>>, D::Error> {
+    serde_bytes::deserialize(deserializer).map(Arc::new)
+}
+
 #[cfg(test)]
 mod test {
-    use super::{ImageFile, MangoImage};
+    use super::{ImageFile, MangoImage, Mime};
     use compression::CompressionType;
     use encryption::EncryptionType;
+    use error::ErrorKind;
     use std;
 
     #[test]
@@ -212,6 +391,31 @@ mod test {
         assert_eq!(img.get_image_data(), clean_data);
     }
 
+    #[test]
+    fn verify() {
+        let p = std::path::Path::new("test.jpg");
+        let file = ImageFile::open(p).unwrap();
+        let mut img = MangoImage::from_file(&file);
+        assert!(img.verify().is_ok());
+
+        img.get_meta_mut().checksum = String::from("0");
+        let error = img.verify().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Integrity);
+    }
+
+    #[test]
+    #[cfg(all(feature = "phash", feature = "aes"))]
+    fn perceptual_hash_of_encrypted_image() {
+        let p = std::path::Path::new("test.jpg");
+        let img = MangoImage::from_file(&ImageFile::open(p).unwrap());
+        let mut encrypted = img
+            .encrypt(EncryptionType::AES128, String::from("key"))
+            .unwrap();
+
+        let error = encrypted.compute_perceptual_hash().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnsupportedFormat);
+    }
+
     #[test]
     fn check_sum() {
         let p = std::path::Path::new("test.jpg");
@@ -221,4 +425,22 @@ mod test {
         //img.meta.checksum
         assert_eq!(img.meta.checksum.len() > 0, true)
     }
+
+    #[test]
+    fn from_bytes() {
+        let p = std::path::Path::new("test.jpg");
+        let from_file = MangoImage::from_file(&ImageFile::open(p).unwrap());
+        let data = std::fs::read(p).unwrap();
+
+        // the wrong extension doesn't matter
+        let img = MangoImage::from_bytes(data.clone(), "page.png").unwrap();
+        assert_eq!(img.meta.mime, Mime::JPEG);
+        assert_eq!(img.meta.checksum, from_file.meta.checksum);
+        assert_eq!(img.meta.filename, "page.png");
+
+        let img = MangoImage::from_reader(std::io::Cursor::new(data), "page.jpg").unwrap();
+        assert_eq!(img.get_image_data(), from_file.get_image_data());
+
+        assert!(MangoImage::from_bytes(b"text".to_vec(), "notes.txt").is_err());
+    }
 }
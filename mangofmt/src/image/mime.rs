@@ -1,49 +1,130 @@
+use error::{Error, ErrorKind};
 use std::clone::Clone;
-use std::io::{Error, ErrorKind};
+use std::io;
 use std::path::Path;
 
 /// Contains all supported image file formats.
-#[derive(Copy, Serialize, Deserialize)]
+#[derive(Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub enum Mime {
     PNG,
     JPEG,
+    WEBP,
+    AVIF,
+    /// JPEG XL
+    JXL,
 }
 
 impl Mime {
-    fn path_string_to_mime(path: &str) -> Option<Mime> {
+    pub(crate) fn path_string_to_mime(path: &str) -> Option<Mime> {
         if path.ends_with("png") {
             Some(Mime::PNG)
         } else if path.ends_with("jpg") || path.ends_with("jpeg") {
             Some(Mime::JPEG)
+        } else if path.ends_with("webp") {
+            Some(Mime::WEBP)
+        } else if path.ends_with("avif") {
+            Some(Mime::AVIF)
+        } else if path.ends_with("jxl") {
+            Some(Mime::JXL)
         } else {
             None
         }
     }
 
+    /// Determines the mimetype from the first bytes of an image.
+    pub fn sniff(data: &[u8]) -> Option<Mime> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Mime::PNG)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Mime::JPEG)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Mime::WEBP)
+        } else if data.len() >= 12 && (&data[4..12] == b"ftypavif" || &data[4..12] == b"ftypavis") {
+            Some(Mime::AVIF)
+        } else if data.starts_with(&[0xff, 0x0a]) || data.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n") {
+            // a bare codestream or the container
+            Some(Mime::JXL)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the usual file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Mime::PNG => "png",
+            Mime::JPEG => "jpg",
+            Mime::WEBP => "webp",
+            Mime::AVIF => "avif",
+            Mime::JXL => "jxl",
+        }
+    }
+
+    /// Returns the media type of the format, e.g. for a `Content-Type` header.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Mime::PNG => "image/png",
+            Mime::JPEG => "image/jpeg",
+            Mime::WEBP => "image/webp",
+            Mime::AVIF => "image/avif",
+            Mime::JXL => "image/jxl",
+        }
+    }
+
     /// Determines the mimetype from the file extension of a path
     pub fn get_from_path(p: &Path) -> Result<Mime, Error> {
-        if p.is_file() {
-            match p.to_str() {
-                Some(path_str) => match Mime::path_string_to_mime(path_str) {
-                    Some(mime) => Ok(mime),
-                    None => Err(Error::new(
-                        ErrorKind::InvalidInput,
-                        "file format is not supported",
-                    )),
-                },
+        if !p.is_file() {
+            return Err(not_a_file(p));
+        }
+
+        match p.to_str() {
+            Some(path_str) => match Mime::path_string_to_mime(path_str) {
+                Some(mime) => Ok(mime),
                 None => Err(Error::new(
-                    ErrorKind::Other,
-                    "can't convert your path to string",
+                    ErrorKind::UnsupportedFormat,
+                    "file format is not supported",
                 )),
-            }
-        } else {
-            Err(Error::new(ErrorKind::InvalidInput, "path is not a file"))
+            },
+            None => Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "can't convert your path to string",
+            )),
         }
     }
 }
 
+/// Builds the error for a path which doesn't point to a regular file.
+fn not_a_file(p: &Path) -> Error {
+    let io_kind = if p.exists() {
+        io::ErrorKind::InvalidInput
+    } else {
+        io::ErrorKind::NotFound
+    };
+
+    Error::with_cause(
+        ErrorKind::Io,
+        "path is not a file",
+        io::Error::from(io_kind),
+    )
+}
+
 impl Clone for Mime {
     fn clone(&self) -> Mime {
         *self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Mime;
+
+    #[test]
+    fn sniff() {
+        assert_eq!(Mime::sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some(Mime::PNG));
+        assert_eq!(Mime::sniff(&[0xff, 0xd8, 0xff, 0xe0]), Some(Mime::JPEG));
+        assert_eq!(Mime::sniff(b"RIFF\0\0\0\0WEBPVP8L"), Some(Mime::WEBP));
+        assert_eq!(Mime::sniff(b"\0\0\0\x1cftypavif"), Some(Mime::AVIF));
+        assert_eq!(Mime::sniff(&[0xff, 0x0a, 0x00]), Some(Mime::JXL));
+        assert_eq!(Mime::sniff(b"GIF89a"), None);
+    }
+}
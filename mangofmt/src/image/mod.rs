@@ -3,7 +3,15 @@
 mod image_file;
 mod mango_image;
 mod mime;
+#[cfg(feature = "phash")]
+mod phash;
+mod sanitize;
+#[cfg(feature = "transcode")]
+mod transcode;
 
 pub use self::image_file::ImageFile;
 pub use self::mango_image::MangoImage;
 pub use self::mime::Mime;
+pub use self::sanitize::MetadataKind;
+#[cfg(feature = "transcode")]
+pub use self::transcode::ImportOptions;
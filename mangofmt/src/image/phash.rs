@@ -0,0 +1,56 @@
+//! Computes the perceptual hashes of images.
+
+extern crate image as codec;
+
+use self::codec::imageops::{self, FilterType};
+use error::{Error, ErrorKind};
+use meta::PerceptualHash;
+
+/// Computes the dHash of an encoded image.
+///
+/// The image gets scaled down to 9x8 gray pixels, every bit tells if a pixel is brighter than
+/// its right neighbour.
+pub fn compute(data: &[u8]) -> Result<PerceptualHash, Error> {
+    let image = codec::load_from_memory(data).map_err(|e| {
+        Error::with_cause(ErrorKind::UnsupportedFormat, "couldn't decode the image", e)
+    })?;
+
+    let gray = image.to_luma8();
+    let small = imageops::resize(&gray, 9, 8, FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(PerceptualHash(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::codec;
+    use super::compute;
+    use std::fs;
+    use std::io::Cursor;
+
+    #[test]
+    fn survives_reencoding() {
+        let jpeg = fs::read("test.jpg").unwrap();
+        let hash = compute(&jpeg).unwrap();
+
+        let mut png = Vec::new();
+        let image = codec::load_from_memory(&jpeg).unwrap();
+        let smaller = image.thumbnail(image.width() / 2, image.height() / 2);
+        smaller
+            .write_to(&mut Cursor::new(&mut png), codec::ImageFormat::Png)
+            .unwrap();
+
+        assert!(hash.distance(compute(&png).unwrap()) <= 4);
+        assert!(compute(b"not an image").is_err());
+    }
+}
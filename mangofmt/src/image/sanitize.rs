@@ -0,0 +1,301 @@
+//! Removes metadata like EXIF data or ICC profiles from encoded images, without re-encoding them.
+//!
+//! Photos and scans often contain GPS coordinates, serial numbers of devices or timestamps.
+//! Only the metadata gets removed, the pixels stay exactly the same.
+
+use super::Mime;
+use error::{Error, ErrorKind};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The flags of the VP8X chunk of a WebP image which tell if there is an ICC profile,
+/// EXIF data or XMP data.
+const WEBP_METADATA_FLAGS: u8 = 0x20 | 0x08 | 0x04;
+
+/// A kind of metadata which got removed from an image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MetadataKind {
+    /// EXIF data, which can contain GPS coordinates, the camera and timestamps.
+    Exif,
+    /// XMP data, which can contain the author or the software used.
+    Xmp,
+    /// A color profile, which can contain the name of the device.
+    IccProfile,
+    /// IPTC data written by Photoshop, which can contain the author and copyright.
+    Iptc,
+    /// A comment.
+    Comment,
+    /// Text like the author or a description.
+    Text,
+    /// The time the image was last changed.
+    Timestamp,
+    /// Any other data specific to an application.
+    Other,
+}
+
+/// Removes the metadata of a JPEG, PNG or WebP image.
+///
+/// Returns the image without metadata and what got removed.
+pub fn strip_metadata(mime: Mime, data: &[u8]) -> Result<(Vec<u8>, Vec<MetadataKind>), Error> {
+    match mime {
+        Mime::JPEG => strip_jpeg(data),
+        Mime::PNG => strip_png(data),
+        Mime::WEBP => strip_webp(data),
+        _ => Err(Error::new(
+            ErrorKind::UnsupportedFormat,
+            format!("can't remove the metadata of {:?} images", mime),
+        )),
+    }
+}
+
+fn strip_jpeg(data: &[u8]) -> Result<(Vec<u8>, Vec<MetadataKind>), Error> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Err(invalid("JPEG"));
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut removed = Vec::new();
+    result.extend_from_slice(&data[..2]);
+    let mut pos = 2;
+
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return Err(invalid("JPEG"));
+        }
+
+        let marker = data[pos + 1];
+        // the compressed image data follows the start of scan, it gets copied as it is
+        if marker == 0xda {
+            result.extend_from_slice(&data[pos..]);
+            return Ok((result, removed));
+        }
+
+        let len = usize::from(data[pos + 2]) << 8 | usize::from(data[pos + 3]);
+        let end = pos + 2 + len;
+        if len < 2 || end > data.len() {
+            return Err(invalid("JPEG"));
+        }
+
+        match jpeg_metadata(marker, &data[pos + 4..end]) {
+            Some(kind) => removed.push(kind),
+            None => result.extend_from_slice(&data[pos..end]),
+        }
+        pos = end;
+    }
+
+    Err(invalid("JPEG"))
+}
+
+/// Tells what kind of metadata a JPEG segment contains, None if it isn't metadata.
+fn jpeg_metadata(marker: u8, segment: &[u8]) -> Option<MetadataKind> {
+    match marker {
+        // APP0 (JFIF) and APP14 (Adobe) are needed to get the colors right
+        0xe0 | 0xee => None,
+        0xe1 if segment.starts_with(b"Exif\0") => Some(MetadataKind::Exif),
+        0xe1 if segment.starts_with(b"http://ns.adobe.com/xap/1.0/") => Some(MetadataKind::Xmp),
+        0xe2 if segment.starts_with(b"ICC_PROFILE\0") => Some(MetadataKind::IccProfile),
+        0xed if segment.starts_with(b"Photoshop 3.0\0") => Some(MetadataKind::Iptc),
+        0xe1..=0xef => Some(MetadataKind::Other),
+        0xfe => Some(MetadataKind::Comment),
+        _ => None,
+    }
+}
+
+fn strip_png(data: &[u8]) -> Result<(Vec<u8>, Vec<MetadataKind>), Error> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(invalid("PNG"));
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut removed = Vec::new();
+    result.extend_from_slice(PNG_SIGNATURE);
+    let mut pos = PNG_SIGNATURE.len();
+
+    while pos + 12 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        let chunk_type = &data[pos + 4..pos + 8];
+        // length, type, data and crc
+        let end = pos + 12 + len as usize;
+        if end > data.len() {
+            return Err(invalid("PNG"));
+        }
+
+        match png_metadata(chunk_type, &data[pos + 8..end - 4]) {
+            Some(kind) => removed.push(kind),
+            None => result.extend_from_slice(&data[pos..end]),
+        }
+        if chunk_type == b"IEND" {
+            return Ok((result, removed));
+        }
+        pos = end;
+    }
+
+    Err(invalid("PNG"))
+}
+
+fn png_metadata(chunk_type: &[u8], chunk: &[u8]) -> Option<MetadataKind> {
+    match chunk_type {
+        b"eXIf" => Some(MetadataKind::Exif),
+        b"iCCP" => Some(MetadataKind::IccProfile),
+        b"iTXt" if chunk.starts_with(b"XML:com.adobe.xmp\0") => Some(MetadataKind::Xmp),
+        b"tEXt" | b"zTXt" | b"iTXt" => Some(MetadataKind::Text),
+        b"tIME" => Some(MetadataKind::Timestamp),
+        _ => None,
+    }
+}
+
+fn strip_webp(data: &[u8]) -> Result<(Vec<u8>, Vec<MetadataKind>), Error> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(invalid("WebP"));
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut removed = Vec::new();
+    result.extend_from_slice(&data[..12]);
+    let mut pos = 12;
+
+    while pos + 8 <= data.len() {
+        let fourcc = &data[pos..pos + 4];
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]);
+        // chunks are padded to an even size
+        let end = pos + 8 + len as usize + (len as usize & 1);
+        if end > data.len() {
+            return Err(invalid("WebP"));
+        }
+
+        let kind = match fourcc {
+            b"EXIF" => Some(MetadataKind::Exif),
+            b"XMP " => Some(MetadataKind::Xmp),
+            b"ICCP" => Some(MetadataKind::IccProfile),
+            _ => None,
+        };
+        match kind {
+            Some(kind) => removed.push(kind),
+            None => {
+                let start = result.len();
+                result.extend_from_slice(&data[pos..end]);
+                if fourcc == b"VP8X" && len > 0 {
+                    result[start + 8] &= !WEBP_METADATA_FLAGS;
+                }
+            }
+        }
+        pos = end;
+    }
+
+    if pos != data.len() {
+        return Err(invalid("WebP"));
+    }
+
+    // the size of the RIFF container doesn't include its header
+    let riff_size = (result.len() - 8) as u32;
+    result[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok((result, removed))
+}
+
+fn invalid(format: &str) -> Error {
+    Error::new(
+        ErrorKind::Decode,
+        format!("the {} image is invalid", format),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_metadata, MetadataKind};
+    use image::Mime;
+
+    fn png_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        // the crc isn't checked
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    fn webp_chunk(fourcc: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn webp(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        webp.extend_from_slice(b"WEBP");
+        webp.extend(body);
+        webp
+    }
+
+    #[test]
+    fn strip_jpeg() {
+        let mut jpeg = vec![0xff, 0xd8];
+        jpeg.extend_from_slice(&[0xff, 0xe0, 0x00, 0x04, 0x4a, 0x46]);
+        jpeg.extend_from_slice(&[0xff, 0xe1, 0x00, 0x08]);
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&[0xff, 0xe2, 0x00, 0x03, 0x49]);
+        jpeg.extend_from_slice(&[0xff, 0xfe, 0x00, 0x02]);
+        jpeg.extend_from_slice(&[0xff, 0xda, 0x00, 0x02, 0x12, 0x34, 0xff, 0xd9]);
+
+        let (stripped, removed) = strip_metadata(Mime::JPEG, &jpeg).unwrap();
+        assert_eq!(
+            stripped,
+            vec![
+                0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x4a, 0x46, 0xff, 0xda, 0x00, 0x02, 0x12, 0x34,
+                0xff, 0xd9
+            ]
+        );
+        assert_eq!(
+            removed,
+            vec![
+                MetadataKind::Exif,
+                MetadataKind::Other,
+                MetadataKind::Comment
+            ]
+        );
+        assert!(strip_metadata(Mime::JPEG, &jpeg[..10]).is_err());
+    }
+
+    #[test]
+    fn strip_png() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        png.extend(png_chunk(b"iCCP", b"profile"));
+        png.extend(png_chunk(b"tEXt", b"Author\0somebody"));
+        png.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        png.extend(png_chunk(b"IEND", &[]));
+
+        let mut expected = b"\x89PNG\r\n\x1a\n".to_vec();
+        expected.extend(png_chunk(b"IHDR", &[0; 13]));
+        expected.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        expected.extend(png_chunk(b"IEND", &[]));
+
+        let (stripped, removed) = strip_metadata(Mime::PNG, &png).unwrap();
+        assert_eq!(stripped, expected);
+        assert_eq!(removed, vec![MetadataKind::IccProfile, MetadataKind::Text]);
+    }
+
+    #[test]
+    fn strip_webp() {
+        let original = webp(&[
+            webp_chunk(b"VP8X", &[0x2c, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            webp_chunk(b"ICCP", b"profile"),
+            webp_chunk(b"VP8L", &[1, 2, 3]),
+            webp_chunk(b"EXIF", b"Exif"),
+        ]);
+        let expected = webp(&[
+            webp_chunk(b"VP8X", &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            webp_chunk(b"VP8L", &[1, 2, 3]),
+        ]);
+
+        let (stripped, removed) = strip_metadata(Mime::WEBP, &original).unwrap();
+        assert_eq!(stripped, expected);
+        assert_eq!(removed, vec![MetadataKind::IccProfile, MetadataKind::Exif]);
+        assert!(strip_metadata(Mime::WEBP, &original[..original.len() - 2]).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! Converts images while importing them.
+
+extern crate image as codec;
+extern crate ravif;
+
+use self::codec::codecs::jpeg::JpegEncoder;
+use self::codec::codecs::png::PngEncoder;
+use self::codec::codecs::webp::WebPEncoder;
+use self::codec::imageops::FilterType;
+use self::codec::{DynamicImage, GenericImageView};
+use super::mango_image::perceptual_hash;
+use super::sanitize::strip_metadata;
+use super::{ImageFile, MangoImage, Mime};
+use error::{Error, ErrorKind};
+use meta::{checksum, Transform};
+use std::fs;
+
+/// Speed of the AVIF encoder from 1 (slowest, smallest) to 10 (fastest).
+const AVIF_SPEED: u8 = 6;
+
+/// Tells what should be changed while importing an image.
+///
+/// Nothing gets changed by default, the options which are set get applied in this order:
+/// downscaling, grayscale conversion, conversion into another format.
+/// The result is stored in the [Transform](../meta/struct.Transform.html) of the image metadata.
+///
+/// JPEG images keep their format, they only get re-encoded as JPEG if they are downscaled or
+/// converted to grayscale. There is no JPEG XL encoder, so nothing can be converted into it.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// The format PNG images get converted into, see [set_png_target](#method.set_png_target).
+    png_target: Option<Mime>,
+    /// Removes metadata like EXIF data and ICC profiles.
+    ///
+    /// Images which get re-encoded lose their metadata anyway.
+    pub strip_metadata: bool,
+    /// Images with a bigger width or height get scaled down to it, keeping their aspect ratio.
+    pub max_dimension: Option<u32>,
+    /// Converts the image to grayscale, for black and white manga.
+    pub grayscale: bool,
+    /// The quality of the lossy formats (JPEG and AVIF), from 1 to 100.
+    pub quality: u8,
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self {
+            png_target: None,
+            strip_metadata: false,
+            max_dimension: None,
+            grayscale: false,
+            quality: 90,
+        }
+    }
+
+    /// Sets the format PNG images get converted into: WEBP (lossless), AVIF or JPEG.
+    ///
+    /// JPEG XL fails with `UnsupportedFormat`, there is no encoder for it.
+    pub fn set_png_target(&mut self, target: Option<Mime>) -> Result<(), Error> {
+        if target == Some(Mime::JXL) {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "images can't be converted into JPEG XL",
+            ));
+        }
+        self.png_target = target;
+        Ok(())
+    }
+
+    /// The format PNG images get converted into, None keeps them as PNG.
+    pub fn png_target(&self) -> Option<Mime> {
+        self.png_target
+    }
+
+    fn target(&self, mime: Mime) -> Mime {
+        match (mime, self.png_target) {
+            (Mime::PNG, Some(target)) => target,
+            _ => mime,
+        }
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageFile {
+    /// Converts the ImageFile to a MangoImage, changing it as the options say.
+    ///
+    /// The checksum of the image is the one of the converted image,
+    /// the checksum of the file is kept in the transform of the metadata.
+    /// If the image gets converted into another format, the extension of its filename changes.
+    pub fn to_mango_image_with(&self, options: &ImportOptions) -> Result<MangoImage, Error> {
+        let source = fs::read(self.get_path())?;
+        let mut meta = self.get_meta().to_base64_metadata();
+
+        let (data, mime, transform) = transcode(&source, meta.mime, options)?;
+        if let Some(transform) = transform {
+            if mime != meta.mime {
+                meta.mime = mime;
+                let stem = match meta.filename.rfind('.') {
+                    Some(dot) => &meta.filename[..dot],
+                    None => &meta.filename,
+                };
+                meta.filename = format!("{}.{}", stem, meta.mime.extension());
+            }
+            meta.checksum = checksum(&data);
+            meta.transform = Some(transform);
+        }
+
+        meta.perceptual_hash = perceptual_hash(&data);
+        Ok(MangoImage::new(data, meta))
+    }
+}
+
+/// Applies the options to the encoded image, returns None as transform if nothing changed.
+fn transcode(
+    source: &[u8],
+    mime: Mime,
+    options: &ImportOptions,
+) -> Result<(Vec<u8>, Mime, Option<Transform>), Error> {
+    let target = options.target(mime);
+    let mut transform = Transform {
+        original_mime: mime,
+        original_checksum: checksum(source),
+        metadata_stripped: false,
+        original_size: None,
+        grayscale: false,
+    };
+
+    let mut image = None;
+    if target != mime || options.max_dimension.is_some() || options.grayscale {
+        let mut decoded = codec::load_from_memory(source)
+            .map_err(|e| Error::with_cause(ErrorKind::Decode, "couldn't decode the image", e))?;
+
+        let (width, height) = decoded.dimensions();
+        if let Some(max) = options.max_dimension {
+            if width > max || height > max {
+                decoded = decoded.resize(max, max, FilterType::Lanczos3);
+                transform.original_size = Some((width, height));
+            }
+        }
+        if options.grayscale && decoded.color().has_color() {
+            decoded = decoded.grayscale();
+            transform.grayscale = true;
+        }
+
+        if target != mime || transform.original_size.is_some() || transform.grayscale {
+            image = Some(decoded);
+        }
+    }
+
+    let (data, mime) = match image {
+        Some(image) => {
+            transform.metadata_stripped = true;
+            (encode(&image, target, options.quality)?, target)
+        }
+        None if options.strip_metadata => {
+            transform.metadata_stripped = true;
+            (strip_metadata(mime, source)?.0, mime)
+        }
+        None => return Ok((source.to_vec(), mime, None)),
+    };
+
+    Ok((data, mime, Some(transform)))
+}
+
+fn encode(image: &DynamicImage, mime: Mime, quality: u8) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    let quality = quality.clamp(1, 100);
+
+    let result = match mime {
+        Mime::PNG => image.write_with_encoder(PngEncoder::new(&mut data)),
+        Mime::WEBP => image.write_with_encoder(WebPEncoder::new_lossless(&mut data)),
+        Mime::JPEG => {
+            // JPEG has no alpha channel
+            let image = if image.color().has_color() {
+                DynamicImage::ImageRgb8(image.to_rgb8())
+            } else {
+                DynamicImage::ImageLuma8(image.to_luma8())
+            };
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))
+        }
+        Mime::AVIF => return encode_avif(image, quality),
+        // never a target, set_png_target rejects it and JPEG XL images can't be decoded
+        Mime::JXL => {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFormat,
+                "images can't be converted into JPEG XL",
+            ))
+        }
+    };
+
+    match result {
+        Ok(()) => Ok(data),
+        Err(e) => Err(Error::with_cause(
+            ErrorKind::Encode,
+            format!("couldn't encode the image as {:?}", mime),
+            e,
+        )),
+    }
+}
+
+fn encode_avif(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, Error> {
+    let rgba = image.to_rgba8();
+    let pixels: Vec<ravif::RGBA8> = rgba
+        .pixels()
+        .map(|p| ravif::RGBA8::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    let buffer = ravif::Img::new(&pixels[..], rgba.width() as usize, rgba.height() as usize);
+
+    ravif::Encoder::new()
+        .with_quality(f32::from(quality))
+        .with_speed(AVIF_SPEED)
+        .encode_rgba(buffer)
+        .map(|encoded| encoded.avif_file)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode the image as AVIF", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::codec;
+    use super::ImportOptions;
+    use error::ErrorKind;
+    use image::{ImageFile, Mime};
+    use meta::checksum;
+    use std::fs;
+    use std::path::Path;
+
+    /// Writes a small PNG version of test.jpg.
+    fn small_png(name: &str) -> ImageFile {
+        let image = codec::open("test.jpg").unwrap().thumbnail(48, 48);
+        image.save(name).unwrap();
+        ImageFile::open(Path::new(name)).unwrap()
+    }
+
+    #[test]
+    fn nothing_to_change() {
+        let file = ImageFile::open(Path::new("test.jpg")).unwrap();
+        let image = file.to_mango_image_with(&ImportOptions::new()).unwrap();
+        assert!(image.get_meta_ref().transform.is_none());
+        assert_eq!(image.get_image_data(), fs::read("test.jpg").unwrap());
+    }
+
+    #[test]
+    fn downscale_and_grayscale() {
+        let file = ImageFile::open(Path::new("test.jpg")).unwrap();
+        let mut options = ImportOptions::new();
+        options.max_dimension = Some(64);
+        options.grayscale = true;
+
+        let image = file.to_mango_image_with(&options).unwrap();
+        let meta = image.get_meta_ref();
+        let transform = meta.transform.as_ref().unwrap();
+        assert_eq!(meta.mime, Mime::JPEG);
+        assert!(transform.grayscale && transform.metadata_stripped);
+        assert!(transform.original_size.is_some());
+        assert_eq!(transform.original_checksum, file.get_meta().checksum);
+        assert_eq!(meta.checksum, checksum(image.get_image_data_ref()));
+
+        let decoded = codec::load_from_memory(image.get_image_data_ref()).unwrap();
+        assert!(decoded.width() <= 64 && decoded.height() <= 64);
+        assert!(!decoded.color().has_color());
+    }
+
+    #[test]
+    fn png_to_webp_and_avif() {
+        let file = small_png("transcode.png");
+        let mut options = ImportOptions::new();
+        options.set_png_target(Some(Mime::WEBP)).unwrap();
+
+        let image = file.to_mango_image_with(&options).unwrap();
+        assert_eq!(image.get_meta_ref().mime, Mime::WEBP);
+        assert_eq!(image.get_meta_ref().filename, "transcode.webp");
+        let webp = codec::load_from_memory(image.get_image_data_ref()).unwrap();
+        let png = codec::open("transcode.png").unwrap();
+        // lossless
+        assert_eq!(webp.to_rgba8(), png.to_rgba8());
+
+        options.set_png_target(Some(Mime::AVIF)).unwrap();
+        let image = file.to_mango_image_with(&options).unwrap();
+        assert_eq!(image.get_meta_ref().mime, Mime::AVIF);
+        assert_eq!(&image.get_image_data_ref()[4..12], b"ftypavif");
+    }
+
+    #[test]
+    fn jpeg_xl_target_is_rejected() {
+        let mut options = ImportOptions::new();
+        let error = options.set_png_target(Some(Mime::JXL)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnsupportedFormat);
+        assert_eq!(options.png_target(), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! ComicInfo.xml
+//!
+//! Lists like writers or genres are stored comma separated. The first writer becomes the author
+//! and the author gets written as first writer. Artists are written as pencillers.
+
+use super::xmltree::Element;
+use super::{
+    all_text, check_root, child_elements, format_number, push_text, split_list, text, Conversion,
+    UnmappedField,
+};
+use error::Error;
+use meta::{
+    AgeRating, Creator, CreatorRole, Identifier, Language, MangoMetadata, PublicationDate,
+    ReadingDirection,
+};
+
+/// The creator elements in the order of the schema.
+const CREATORS: &[(&str, CreatorRole)] = &[
+    ("Writer", CreatorRole::Writer),
+    ("Penciller", CreatorRole::Penciller),
+    ("Inker", CreatorRole::Inker),
+    ("Colorist", CreatorRole::Colorist),
+    ("Letterer", CreatorRole::Letterer),
+    ("CoverArtist", CreatorRole::CoverArtist),
+    ("Editor", CreatorRole::Editor),
+    ("Translator", CreatorRole::Translator),
+];
+
+pub fn import(root: &Element) -> Result<Conversion<MangoMetadata>, Error> {
+    check_root(root, "ComicInfo")?;
+
+    let mut meta = MangoMetadata::new();
+    let mut unmapped = Vec::new();
+    let mut date = (None, None, None);
+
+    for element in child_elements(root) {
+        let value = match text(element) {
+            Some(value) => value,
+            None => {
+                // elements like <Pages> only contain other elements
+                let value = all_text(element);
+                if !value.is_empty() || !element.children.is_empty() {
+                    unmapped.push(UnmappedField::new(element.name.as_str(), value));
+                }
+                continue;
+            }
+        };
+
+        let mapped = match element.name.as_str() {
+            "Title" => set(&mut meta.title, value.clone()),
+            "Series" => set(&mut meta.series, value.clone()),
+            "Number" => parse(&mut meta.chapter, &value),
+            "Volume" => parse(&mut meta.volume, &value),
+            "Summary" => set(&mut meta.summary, value.clone()),
+            "Year" => parse(&mut date.0, &value),
+            "Month" => parse(&mut date.1, &value),
+            "Day" => parse(&mut date.2, &value),
+            "Publisher" => set(&mut meta.publisher, value.clone()),
+            "Genre" => {
+                meta.genres.extend(split_list(&value));
+                true
+            }
+            "Tags" => {
+                meta.tags.extend(split_list(&value));
+                true
+            }
+            "Web" => set(&mut meta.source, value.clone()),
+            "ScanInformation" => set(&mut meta.translation, value.clone()),
+            "LanguageISO" => match Language::parse(&value) {
+                Ok(language) => set(&mut meta.language, language),
+                Err(_) => false,
+            },
+            "Manga" => match value.as_str() {
+                "YesAndRightToLeft" => {
+                    set(&mut meta.reading_direction, ReadingDirection::RightToLeft)
+                }
+                "No" => set(&mut meta.reading_direction, ReadingDirection::LeftToRight),
+                "Unknown" => true,
+                _ => false,
+            },
+            "AgeRating" => match import_age_rating(&value) {
+                Some(rating) => set(&mut meta.age_rating, rating),
+                None => value == "Unknown" || value == "Rating Pending",
+            },
+            "GTIN" => {
+                meta.identifiers.push(import_gtin(&value));
+                true
+            }
+            // the page count is given by the images of the file
+            "PageCount" => true,
+            name => match CREATORS.iter().find(|c| c.0 == name) {
+                Some(&(_, role)) => {
+                    for name in split_list(&value) {
+                        if role == CreatorRole::Writer && meta.author.is_none() {
+                            meta.author = Some(name);
+                        } else {
+                            meta.creators.push(Creator::new(name, role));
+                        }
+                    }
+                    true
+                }
+                None => false,
+            },
+        };
+
+        if !mapped {
+            unmapped.push(UnmappedField::new(element.name.as_str(), value));
+        }
+    }
+
+    if let (Some(year), month, day) = date {
+        meta.year = Some(year);
+        if month.is_some() {
+            meta.publication_date = Some(PublicationDate::new(year, month, day));
+        }
+    }
+
+    Ok(Conversion::new(meta, unmapped))
+}
+
+pub fn export(meta: &MangoMetadata) -> (Element, Vec<UnmappedField>) {
+    let mut root = Element::new("ComicInfo");
+    let mut unmapped = Vec::new();
+
+    if let Some(ref title) = meta.title {
+        push_text(&mut root, "Title", title);
+    }
+    if let Some(ref series) = meta.series {
+        push_text(&mut root, "Series", series);
+    }
+    if let Some(chapter) = meta.chapter {
+        push_text(&mut root, "Number", &format_number(chapter));
+    }
+    if let Some(volume) = meta.volume {
+        push_text(&mut root, "Volume", &volume.to_string());
+    }
+    if let Some(ref summary) = meta.summary {
+        push_text(&mut root, "Summary", summary);
+    }
+
+    match meta.publication_date {
+        Some(date) => {
+            push_text(&mut root, "Year", &date.year.to_string());
+            if let Some(month) = date.month {
+                push_text(&mut root, "Month", &month.to_string());
+            }
+            if let Some(day) = date.day {
+                push_text(&mut root, "Day", &day.to_string());
+            }
+        }
+        None => {
+            if let Some(year) = meta.year {
+                push_text(&mut root, "Year", &year.to_string());
+            }
+        }
+    }
+
+    for &(element, role) in CREATORS {
+        let mut names: Vec<&str> = Vec::new();
+        if role == CreatorRole::Writer {
+            names.extend(meta.author.as_deref());
+        }
+        if role == CreatorRole::Penciller {
+            names.extend(
+                meta.creators_by_role(CreatorRole::Artist)
+                    .iter()
+                    .map(|c| c.name.as_str()),
+            );
+        }
+        names.extend(meta.creators_by_role(role).iter().map(|c| c.name.as_str()));
+
+        if !names.is_empty() {
+            push_text(&mut root, element, &names.join(", "));
+        }
+    }
+
+    for creator in meta.creators_by_role(CreatorRole::Other) {
+        unmapped.push(UnmappedField::new(
+            "creators",
+            format!("{} (Other)", creator.name),
+        ));
+    }
+
+    if let Some(ref publisher) = meta.publisher {
+        push_text(&mut root, "Publisher", publisher);
+    }
+    if !meta.genres.is_empty() {
+        push_text(&mut root, "Genre", &meta.genres.join(", "));
+    }
+    if !meta.tags.is_empty() {
+        push_text(&mut root, "Tags", &meta.tags.join(", "));
+    }
+    if let Some(ref source) = meta.source {
+        push_text(&mut root, "Web", source);
+    }
+    if let Some(ref language) = meta.language {
+        push_text(&mut root, "LanguageISO", language.as_str());
+    }
+
+    match meta.reading_direction {
+        Some(ReadingDirection::RightToLeft) => push_text(&mut root, "Manga", "YesAndRightToLeft"),
+        Some(ReadingDirection::LeftToRight) => push_text(&mut root, "Manga", "No"),
+        Some(ReadingDirection::TopToBottom) => {
+            unmapped.push(UnmappedField::new("reading_direction", "TopToBottom"))
+        }
+        Some(ReadingDirection::Unknown) | None => (),
+    }
+
+    if let Some(ref translation) = meta.translation {
+        push_text(&mut root, "ScanInformation", translation);
+    }
+    if let Some(rating) = meta.age_rating {
+        push_text(&mut root, "AgeRating", export_age_rating(rating));
+    }
+
+    let mut gtin_written = false;
+    for identifier in &meta.identifiers {
+        if !gtin_written && (identifier.scheme == "isbn" || identifier.scheme == "gtin") {
+            push_text(&mut root, "GTIN", &identifier.value);
+            gtin_written = true;
+        } else {
+            unmapped.push(UnmappedField::new(
+                "identifiers",
+                format!("{}:{}", identifier.scheme, identifier.value),
+            ));
+        }
+    }
+
+    (root, unmapped)
+}
+
+fn set<T>(field: &mut Option<T>, value: T) -> bool {
+    *field = Some(value);
+    true
+}
+
+fn parse<T: ::std::str::FromStr>(field: &mut Option<T>, value: &str) -> bool {
+    match value.parse() {
+        Ok(value) => set(field, value),
+        Err(_) => false,
+    }
+}
+
+fn import_age_rating(value: &str) -> Option<AgeRating> {
+    match value {
+        "Early Childhood" | "Everyone" | "Everyone 10+" | "G" | "Kids to Adults" => {
+            Some(AgeRating::AllAges)
+        }
+        "Teen" | "PG" => Some(AgeRating::Teen),
+        "MA15+" => Some(AgeRating::OlderTeen),
+        "Mature 17+" | "M" => Some(AgeRating::Mature),
+        "Adults Only 18+" | "R18+" | "X18+" => Some(AgeRating::Adult),
+        _ => None,
+    }
+}
+
+fn export_age_rating(rating: AgeRating) -> &'static str {
+    match rating {
+        AgeRating::AllAges => "Everyone",
+        AgeRating::Teen => "Teen",
+        AgeRating::OlderTeen => "MA15+",
+        AgeRating::Mature => "Mature 17+",
+        AgeRating::Adult => "Adults Only 18+",
+        AgeRating::Unknown => "Unknown",
+    }
+}
+
+/// ISBNs are GTINs as well, those get stored as ISBN.
+fn import_gtin(value: &str) -> Identifier {
+    let digits: String = value.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    let is_isbn = digits.len() == 10
+        || (digits.len() == 13 && (digits.starts_with("978") || digits.starts_with("979")));
+
+    if is_isbn {
+        Identifier::isbn(&digits)
+    } else {
+        Identifier::new("gtin", value)
+    }
+}
@@ -0,0 +1,418 @@
+//! MetronInfo.xml
+//!
+//! The title is stored as the first story and the volume as the manga volume.
+//! The language is an attribute of the series, so it can only be written together with a series.
+//! Cover dates have to be complete, partial dates can't be written.
+
+use super::xmltree::Element;
+use super::{
+    all_text, check_root, child_elements, format_number, push_element, push_text, text,
+    text_element, Conversion, UnmappedField,
+};
+use error::Error;
+use meta::{AgeRating, Creator, CreatorRole, Identifier, Language, MangoMetadata, PublicationDate};
+
+/// The sources of IDs known to the schema and the identifier schemes they are stored as.
+const SOURCES: &[(&str, &str)] = &[
+    ("AniList", "anilist"),
+    ("Comic Vine", "comicvine"),
+    ("Grand Comics Database", "gcd"),
+    ("Kitsu", "kitsu"),
+    ("League of Comic Geeks", "locg"),
+    ("MangaDex", "mangadex"),
+    ("MangaUpdates", "mangaupdates"),
+    ("Metron", "metron"),
+    ("MyAnimeList", "mal"),
+];
+
+/// The roles of the schema, the first one of each creator role gets written.
+const ROLES: &[(&str, CreatorRole)] = &[
+    ("Writer", CreatorRole::Writer),
+    ("Script", CreatorRole::Writer),
+    ("Story", CreatorRole::Writer),
+    ("Artist", CreatorRole::Artist),
+    ("Illustrator", CreatorRole::Artist),
+    ("Penciller", CreatorRole::Penciller),
+    ("Inker", CreatorRole::Inker),
+    ("Colorist", CreatorRole::Colorist),
+    ("Letterer", CreatorRole::Letterer),
+    ("Cover", CreatorRole::CoverArtist),
+    ("Editor", CreatorRole::Editor),
+    ("Translator", CreatorRole::Translator),
+    ("Other", CreatorRole::Other),
+];
+
+pub fn import(root: &Element) -> Result<Conversion<MangoMetadata>, Error> {
+    check_root(root, "MetronInfo")?;
+
+    let mut meta = MangoMetadata::new();
+    let mut unmapped = Vec::new();
+
+    for element in child_elements(root) {
+        match element.name.as_str() {
+            "ID" => {
+                for id in child_elements(element) {
+                    match (id.attributes.get("source"), text(id)) {
+                        (Some(source), Some(value)) => {
+                            meta.identifiers.push(import_source(source, value))
+                        }
+                        _ => unmapped.push(UnmappedField::new("ID", all_text(id))),
+                    }
+                }
+            }
+            "Publisher" => {
+                for child in child_elements(element) {
+                    match (child.name.as_str(), text(child)) {
+                        ("Name", Some(name)) => meta.publisher = Some(name),
+                        (_, Some(value)) => unmapped.push(UnmappedField::new(
+                            format!("Publisher/{}", child.name),
+                            value,
+                        )),
+                        (_, None) => (),
+                    }
+                }
+            }
+            "Series" => {
+                if let Some(lang) = element.attributes.get("lang") {
+                    match Language::parse(lang) {
+                        Ok(language) => meta.language = Some(language),
+                        Err(_) => unmapped.push(UnmappedField::new("Series/lang", lang.as_str())),
+                    }
+                }
+                for child in child_elements(element) {
+                    match (child.name.as_str(), text(child)) {
+                        ("Name", Some(name)) => meta.series = Some(name),
+                        (_, None) if child.children.is_empty() => (),
+                        _ => unmapped.push(UnmappedField::new(
+                            format!("Series/{}", child.name),
+                            all_text(child),
+                        )),
+                    }
+                }
+            }
+            "Stories" => {
+                for story in child_elements(element).filter_map(text) {
+                    if meta.title.is_none() {
+                        meta.title = Some(story);
+                    } else {
+                        unmapped.push(UnmappedField::new("Stories/Story", story));
+                    }
+                }
+            }
+            "Genres" => meta.genres.extend(child_elements(element).filter_map(text)),
+            "Tags" => meta.tags.extend(child_elements(element).filter_map(text)),
+            "GTIN" => {
+                for child in child_elements(element) {
+                    if let Some(value) = text(child) {
+                        meta.identifiers.push(match child.name.as_str() {
+                            "ISBN" => Identifier::isbn(&value),
+                            name => Identifier::new(name, value),
+                        });
+                    }
+                }
+            }
+            "URLs" => {
+                let mut urls: Vec<&Element> = child_elements(element).collect();
+                // the primary url becomes the source
+                if let Some(i) = urls.iter().position(|u| is_primary(u)) {
+                    let primary = urls.remove(i);
+                    urls.insert(0, primary);
+                }
+                for url in urls.into_iter().filter_map(text) {
+                    if meta.source.is_none() {
+                        meta.source = Some(url);
+                    } else {
+                        unmapped.push(UnmappedField::new("URLs/URL", url));
+                    }
+                }
+            }
+            "Credits" => {
+                for credit in child_elements(element) {
+                    import_credit(credit, &mut meta, &mut unmapped);
+                }
+            }
+            // the page count is given by the images of the file
+            "PageCount" | "LastModified" => (),
+            name => {
+                let value = match text(element) {
+                    Some(value) => value,
+                    None => {
+                        let value = all_text(element);
+                        if value.is_empty() && element.children.is_empty() {
+                            continue;
+                        }
+                        value
+                    }
+                };
+
+                let mapped = match name {
+                    "MangaVolume" => parse(&mut meta.volume, &value),
+                    "Number" => parse(&mut meta.chapter, &value),
+                    "Summary" => {
+                        meta.summary = Some(value.clone());
+                        true
+                    }
+                    "CoverDate" => match super::parse_date(&value) {
+                        Some((year, month, day)) => {
+                            meta.year = Some(year);
+                            meta.publication_date = Some(PublicationDate::new(year, month, day));
+                            true
+                        }
+                        None => false,
+                    },
+                    "AgeRating" => match import_age_rating(&value) {
+                        Some(rating) => {
+                            meta.age_rating = Some(rating);
+                            true
+                        }
+                        None => false,
+                    },
+                    _ => false,
+                };
+
+                if !mapped {
+                    unmapped.push(UnmappedField::new(name, value));
+                }
+            }
+        }
+    }
+
+    Ok(Conversion::new(meta, unmapped))
+}
+
+fn import_source(source: &str, value: String) -> Identifier {
+    match SOURCES.iter().find(|s| s.0 == source) {
+        Some(&(_, scheme)) => Identifier::new(scheme, value),
+        None => Identifier::new(source, value),
+    }
+}
+
+fn is_primary(element: &Element) -> bool {
+    element.attributes.get("primary").map(String::as_str) == Some("true")
+}
+
+fn import_credit(credit: &Element, meta: &mut MangoMetadata, unmapped: &mut Vec<UnmappedField>) {
+    let name = match credit.get_child("Creator").and_then(text) {
+        Some(name) => name,
+        None => return,
+    };
+
+    let roles: Vec<String> = credit
+        .get_child("Roles")
+        .map(|roles| child_elements(roles).filter_map(text).collect())
+        .unwrap_or_default();
+
+    for role_name in roles {
+        let role = match ROLES.iter().find(|r| r.0 == role_name) {
+            Some(&(_, role)) => role,
+            None => {
+                unmapped.push(UnmappedField::new(
+                    "Credits/Credit",
+                    format!("{} ({})", name, role_name),
+                ));
+                continue;
+            }
+        };
+
+        if role == CreatorRole::Writer && meta.author.is_none() {
+            meta.author = Some(name.clone());
+        } else {
+            meta.creators.push(Creator::new(name.as_str(), role));
+        }
+    }
+}
+
+fn parse<T: ::std::str::FromStr>(field: &mut Option<T>, value: &str) -> bool {
+    match value.parse() {
+        Ok(value) => {
+            *field = Some(value);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn import_age_rating(value: &str) -> Option<AgeRating> {
+    match value {
+        "Everyone" => Some(AgeRating::AllAges),
+        "Teen" => Some(AgeRating::Teen),
+        "Teen Plus" => Some(AgeRating::OlderTeen),
+        "Mature" => Some(AgeRating::Mature),
+        "Explicit" | "Adult" => Some(AgeRating::Adult),
+        "Unknown" => Some(AgeRating::Unknown),
+        _ => None,
+    }
+}
+
+fn export_age_rating(rating: AgeRating) -> &'static str {
+    match rating {
+        AgeRating::AllAges => "Everyone",
+        AgeRating::Teen => "Teen",
+        AgeRating::OlderTeen => "Teen Plus",
+        AgeRating::Mature => "Mature",
+        AgeRating::Adult => "Adult",
+        AgeRating::Unknown => "Unknown",
+    }
+}
+
+pub fn export(meta: &MangoMetadata) -> (Element, Vec<UnmappedField>) {
+    let mut root = Element::new("MetronInfo");
+    let mut unmapped = Vec::new();
+
+    let mut ids = Element::new("ID");
+    let mut gtin = Element::new("GTIN");
+    for identifier in &meta.identifiers {
+        let source = SOURCES.iter().find(|s| s.1 == identifier.scheme);
+        match (source, identifier.scheme.as_str()) {
+            (Some(&(source, _)), _) => {
+                let name = if ids.children.is_empty() {
+                    "Primary"
+                } else {
+                    "Alternative"
+                };
+                let mut id = text_element(name, &identifier.value);
+                id.attributes
+                    .insert("source".to_string(), source.to_string());
+                push_element(&mut ids, id);
+            }
+            (None, "isbn") if gtin.get_child("ISBN").is_none() => {
+                push_text(&mut gtin, "ISBN", &identifier.value)
+            }
+            (None, "upc") if gtin.get_child("UPC").is_none() => {
+                push_text(&mut gtin, "UPC", &identifier.value)
+            }
+            _ => unmapped.push(UnmappedField::new(
+                "identifiers",
+                format!("{}:{}", identifier.scheme, identifier.value),
+            )),
+        }
+    }
+    if !ids.children.is_empty() {
+        push_element(&mut root, ids);
+    }
+
+    if let Some(ref publisher) = meta.publisher {
+        let mut element = Element::new("Publisher");
+        push_text(&mut element, "Name", publisher);
+        push_element(&mut root, element);
+    }
+
+    match meta.series {
+        Some(ref series) => {
+            let mut element = Element::new("Series");
+            if let Some(ref language) = meta.language {
+                element
+                    .attributes
+                    .insert("lang".to_string(), language.to_string());
+            }
+            push_text(&mut element, "Name", series);
+            push_element(&mut root, element);
+        }
+        None => {
+            if let Some(ref language) = meta.language {
+                unmapped.push(UnmappedField::new("language", language.as_str()));
+            }
+        }
+    }
+
+    if let Some(volume) = meta.volume {
+        push_text(&mut root, "MangaVolume", &volume.to_string());
+    }
+    if let Some(chapter) = meta.chapter {
+        push_text(&mut root, "Number", &format_number(chapter));
+    }
+    if let Some(ref title) = meta.title {
+        let mut stories = Element::new("Stories");
+        push_text(&mut stories, "Story", title);
+        push_element(&mut root, stories);
+    }
+    if let Some(ref summary) = meta.summary {
+        push_text(&mut root, "Summary", summary);
+    }
+
+    match meta.publication_date {
+        Some(PublicationDate {
+            year,
+            month: Some(month),
+            day: Some(day),
+        }) => push_text(
+            &mut root,
+            "CoverDate",
+            &format!("{:04}-{:02}-{:02}", year, month, day),
+        ),
+        Some(date) => unmapped.push(UnmappedField::new(
+            "publication_date",
+            format!("{:?}", date),
+        )),
+        None => {
+            if let Some(year) = meta.year {
+                unmapped.push(UnmappedField::new("year", year.to_string()));
+            }
+        }
+    }
+
+    if !meta.genres.is_empty() {
+        let mut genres = Element::new("Genres");
+        for genre in &meta.genres {
+            push_text(&mut genres, "Genre", genre);
+        }
+        push_element(&mut root, genres);
+    }
+    if !meta.tags.is_empty() {
+        let mut tags = Element::new("Tags");
+        for tag in &meta.tags {
+            push_text(&mut tags, "Tag", tag);
+        }
+        push_element(&mut root, tags);
+    }
+
+    if !gtin.children.is_empty() {
+        push_element(&mut root, gtin);
+    }
+    if let Some(rating) = meta.age_rating {
+        push_text(&mut root, "AgeRating", export_age_rating(rating));
+    }
+    if let Some(ref source) = meta.source {
+        let mut urls = Element::new("URLs");
+        let mut url = text_element("URL", source);
+        url.attributes
+            .insert("primary".to_string(), "true".to_string());
+        push_element(&mut urls, url);
+        push_element(&mut root, urls);
+    }
+
+    let author = meta
+        .author
+        .as_ref()
+        .map(|a| Creator::new(a.as_str(), CreatorRole::Writer));
+    let mut credits = Element::new("Credits");
+    for creator in author.iter().chain(meta.creators.iter()) {
+        // every creator role has a role in the schema
+        let role = ROLES
+            .iter()
+            .find(|r| r.1 == creator.role)
+            .map_or("Other", |r| r.0);
+
+        let mut credit = Element::new("Credit");
+        push_text(&mut credit, "Creator", &creator.name);
+        let mut roles = Element::new("Roles");
+        push_text(&mut roles, "Role", role);
+        push_element(&mut credit, roles);
+        push_element(&mut credits, credit);
+    }
+    if !credits.children.is_empty() {
+        push_element(&mut root, credits);
+    }
+
+    if let Some(ref translation) = meta.translation {
+        unmapped.push(UnmappedField::new("translation", translation.as_str()));
+    }
+    if let Some(direction) = meta.reading_direction {
+        unmapped.push(UnmappedField::new(
+            "reading_direction",
+            format!("{:?}", direction),
+        ));
+    }
+
+    (root, unmapped)
+}
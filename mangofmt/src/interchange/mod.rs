@@ -0,0 +1,415 @@
+//! Converting metadata from and to the formats of other comic tools.
+//!
+//! Supported are [ComicInfo.xml](https://anansi-project.github.io/docs/comicinfo/intro) of
+//! ComicRack, the OPF files of [Calibre](https://calibre-ebook.com) and
+//! [MetronInfo.xml](https://metron-project.github.io/docs/category/metroninfo).
+//!
+//! None of these formats can hold everything a [MangoMetadata](../meta/struct.MangoMetadata.html)
+//! can, and the other way around. Every conversion returns the fields it couldn't carry over,
+//! so nothing gets lost silently.
+//!
+//! The functions only work on the metadata, so sidecar files can be written next to a `.mango`
+//! file without ever touching its images.
+//!
+//! This module requires the *interchange* feature.
+
+extern crate xmltree;
+
+mod comicinfo;
+mod metron;
+mod opf;
+
+use self::xmltree::{Element, EmitterConfig, XMLNode};
+use error::{Error, ErrorKind};
+use meta::MangoMetadata;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The metadata formats mango can convert from and to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// ComicInfo.xml of ComicRack, the schema version 2.1 is written.
+    ComicInfo,
+    /// The OPF 2.0 metadata Calibre uses.
+    Opf,
+    /// MetronInfo.xml of the Metron project, the schema version 1.0 is written.
+    MetronInfo,
+}
+
+impl Format {
+    /// Returns the path of the sidecar file of the .mango file at `mango_path`.
+    ///
+    /// For `volume1.mango` those are `volume1.ComicInfo.xml`, `volume1.opf` and
+    /// `volume1.MetronInfo.xml`.
+    pub fn sidecar_path(self, mango_path: &Path) -> PathBuf {
+        let stem = mango_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let name = match self {
+            Format::ComicInfo => format!("{}.ComicInfo.xml", stem),
+            Format::Opf => format!("{}.opf", stem),
+            Format::MetronInfo => format!("{}.MetronInfo.xml", stem),
+        };
+
+        mango_path.with_file_name(name)
+    }
+}
+
+/// A field which couldn't be converted.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnmappedField {
+    /// The name of the field in the format it was converted from.
+    ///
+    /// That is the name of the MangoMetadata field when exporting
+    /// and the name of the element when importing.
+    pub name: String,
+    /// The value which got lost.
+    pub value: String,
+}
+
+impl UnmappedField {
+    pub fn new<S: Into<String>, V: Into<String>>(name: S, value: V) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// The outcome of a conversion.
+#[derive(Clone, Debug)]
+pub struct Conversion<T> {
+    /// The converted data.
+    pub value: T,
+    /// All fields that couldn't be carried over, in the order they were found.
+    pub unmapped: Vec<UnmappedField>,
+}
+
+impl<T> Conversion<T> {
+    fn new(value: T, unmapped: Vec<UnmappedField>) -> Self {
+        Self { value, unmapped }
+    }
+
+    /// Returns true if every field got converted.
+    pub fn is_lossless(&self) -> bool {
+        self.unmapped.is_empty()
+    }
+}
+
+/// Reads metadata from a document in the given format.
+pub fn import(format: Format, xml: &str) -> Result<Conversion<MangoMetadata>, Error> {
+    let root = Element::parse(xml.as_bytes())
+        .map_err(|e| Error::with_cause(ErrorKind::Decode, "invalid xml document", e))?;
+
+    match format {
+        Format::ComicInfo => comicinfo::import(&root),
+        Format::Opf => opf::import(&root),
+        Format::MetronInfo => metron::import(&root),
+    }
+}
+
+/// Writes metadata into a document in the given format.
+pub fn export(format: Format, meta: &MangoMetadata) -> Result<Conversion<String>, Error> {
+    let (root, unmapped) = match format {
+        Format::ComicInfo => comicinfo::export(meta),
+        Format::Opf => opf::export(meta),
+        Format::MetronInfo => metron::export(meta),
+    };
+
+    let mut xml = Vec::new();
+    let config = EmitterConfig::new().perform_indent(true);
+    root.write_with_config(&mut xml, config)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "failed to write xml", e))?;
+
+    let xml = String::from_utf8(xml)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "failed to write xml", e))?;
+    Ok(Conversion::new(xml, unmapped))
+}
+
+/// Reads the sidecar file of the .mango file at `mango_path`.
+///
+/// See [Format::sidecar_path](enum.Format.html#method.sidecar_path) for where it is expected.
+pub fn read_sidecar(format: Format, mango_path: &Path) -> Result<Conversion<MangoMetadata>, Error> {
+    let xml = fs::read_to_string(format.sidecar_path(mango_path))?;
+    import(format, &xml)
+}
+
+/// Writes `meta` to a sidecar file next to the .mango file at `mango_path`
+/// and returns the path of the written file.
+///
+/// The .mango file itself doesn't get read, it doesn't even have to exist yet.
+pub fn write_sidecar(
+    format: Format,
+    meta: &MangoMetadata,
+    mango_path: &Path,
+) -> Result<Conversion<PathBuf>, Error> {
+    let xml = export(format, meta)?;
+    let path = format.sidecar_path(mango_path);
+    fs::write(&path, xml.value)?;
+    Ok(Conversion::new(path, xml.unmapped))
+}
+
+//------------------------------------------------------------------------------
+//  Helpers shared by the formats
+//------------------------------------------------------------------------------
+
+fn check_root(root: &Element, name: &str) -> Result<(), Error> {
+    if root.name != name {
+        return Err(Error::new(
+            ErrorKind::Decode,
+            format!("expected a <{}> document, got <{}>", name, root.name),
+        ));
+    }
+
+    Ok(())
+}
+
+fn child_elements(element: &Element) -> impl Iterator<Item = &Element> {
+    element.children.iter().filter_map(XMLNode::as_element)
+}
+
+/// Returns the trimmed text of an element, None if it is empty.
+fn text(element: &Element) -> Option<String> {
+    element
+        .get_text()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Returns all text inside of an element and its children, separated by commas.
+fn all_text(element: &Element) -> String {
+    let mut parts = Vec::new();
+    collect_text(element, &mut parts);
+    parts.join(", ")
+}
+
+fn collect_text(element: &Element, parts: &mut Vec<String>) {
+    for node in &element.children {
+        match *node {
+            XMLNode::Element(ref child) => collect_text(child, parts),
+            XMLNode::Text(ref t) | XMLNode::CData(ref t) if !t.trim().is_empty() => {
+                parts.push(t.trim().to_string())
+            }
+            _ => (),
+        }
+    }
+}
+
+fn text_element(name: &str, value: &str) -> Element {
+    let mut element = Element::new(name);
+    element.children.push(XMLNode::Text(value.to_string()));
+    element
+}
+
+fn push_text(parent: &mut Element, name: &str, value: &str) {
+    parent
+        .children
+        .push(XMLNode::Element(text_element(name, value)));
+}
+
+fn push_element(parent: &mut Element, element: Element) {
+    parent.children.push(XMLNode::Element(element));
+}
+
+/// Formats a chapter number without a fraction if it is a whole number.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a date in the form YYYY, YYYY-MM or YYYY-MM-DD, anything after the day is ignored.
+fn parse_date(value: &str) -> Option<(i16, Option<u8>, Option<u8>)> {
+    let date = value.get(..10).unwrap_or(value);
+    let mut parts = date.split('-');
+
+    let year = parts.next()?.parse().ok()?;
+    let month = match parts.next() {
+        Some(month) => Some(month.parse().ok().filter(|m| (1..=12).contains(m))?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(day) => Some(day.parse().ok().filter(|d| (1..=31).contains(d))?),
+        None => None,
+    };
+
+    Some((year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, read_sidecar, write_sidecar, Format};
+    use meta::{
+        AgeRating, Creator, CreatorRole, Identifier, Language, MangoMetadata, PublicationDate,
+        ReadingDirection,
+    };
+    use std::fs;
+    use std::path::Path;
+
+    fn metadata() -> MangoMetadata {
+        let mut meta = MangoMetadata::new();
+        meta.title = Some("The <First> & Only".to_string());
+        meta.author = Some("Jane Doe".to_string());
+        meta.publisher = Some("Mango Press".to_string());
+        meta.language = Some(Language::parse("ja").unwrap());
+        meta.volume = Some(2);
+        meta.chapter = Some(10.5);
+        meta.year = Some(2018);
+        meta.series = Some("Mango".to_string());
+        meta.creators = vec![
+            Creator::new("John Doe", CreatorRole::Writer),
+            Creator::new("Max Mustermann", CreatorRole::Colorist),
+            Creator::new("Erika Mustermann", CreatorRole::Editor),
+            Creator::new("Someone", CreatorRole::Translator),
+        ];
+        meta.genres = vec!["Action".to_string()];
+        meta.tags = vec!["Pirates".to_string()];
+        meta.summary = Some("A summary.".to_string());
+        meta.identifiers = vec![Identifier::isbn("978-3-16-148410-0")];
+        meta.publication_date = Some(PublicationDate::new(2018, Some(5), Some(4)));
+        meta
+    }
+
+    fn assert_common(read: &MangoMetadata) {
+        let meta = metadata();
+        assert_eq!(read.title, meta.title);
+        assert_eq!(read.author, meta.author);
+        assert_eq!(read.publisher, meta.publisher);
+        assert_eq!(read.language, meta.language);
+        assert_eq!(read.series, meta.series);
+        assert_eq!(read.summary, meta.summary);
+        assert_eq!(read.identifier("isbn"), Some("9783161484100"));
+        assert_eq!(read.publication_date, meta.publication_date);
+        assert_eq!(read.year, Some(2018));
+    }
+
+    #[test]
+    fn comicinfo_round_trip() {
+        let mut meta = metadata();
+        meta.translation = Some("Scans".to_string());
+        meta.age_rating = Some(AgeRating::Teen);
+        meta.reading_direction = Some(ReadingDirection::RightToLeft);
+
+        let xml = export(Format::ComicInfo, &meta).unwrap();
+        assert!(xml.is_lossless());
+
+        let read = import(Format::ComicInfo, &xml.value).unwrap();
+        assert!(read.is_lossless());
+        let read = read.value;
+
+        assert_common(&read);
+        assert_eq!(read.volume, Some(2));
+        assert_eq!(read.chapter, Some(10.5));
+        assert_eq!(read.creators, meta.creators);
+        assert_eq!(read.genres, meta.genres);
+        assert_eq!(read.tags, meta.tags);
+        assert_eq!(read.translation, meta.translation);
+        assert_eq!(read.age_rating, Some(AgeRating::Teen));
+        assert_eq!(read.reading_direction, Some(ReadingDirection::RightToLeft));
+    }
+
+    #[test]
+    fn opf_round_trip() {
+        let xml = export(Format::Opf, &metadata()).unwrap();
+        let names: Vec<_> = xml.unmapped.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["chapter"]);
+
+        let read = import(Format::Opf, &xml.value).unwrap();
+        assert!(read.is_lossless());
+        let read = read.value;
+
+        assert_common(&read);
+        assert_eq!(read.volume, Some(2));
+        assert_eq!(read.creators, metadata().creators);
+        assert_eq!(read.tags, vec!["Action".to_string(), "Pirates".to_string()]);
+    }
+
+    #[test]
+    fn metron_round_trip() {
+        let mut meta = metadata();
+        meta.age_rating = Some(AgeRating::OlderTeen);
+
+        let xml = export(Format::MetronInfo, &meta).unwrap();
+        assert!(xml.is_lossless());
+
+        let read = import(Format::MetronInfo, &xml.value).unwrap();
+        assert!(read.is_lossless());
+        let read = read.value;
+
+        assert_common(&read);
+        assert_eq!(read.volume, Some(2));
+        assert_eq!(read.chapter, Some(10.5));
+        assert_eq!(read.creators, meta.creators);
+        assert_eq!(read.genres, meta.genres);
+        assert_eq!(read.tags, meta.tags);
+        assert_eq!(read.age_rating, Some(AgeRating::OlderTeen));
+    }
+
+    #[test]
+    fn report_unmapped_export() {
+        let mut meta = MangoMetadata::new();
+        meta.reading_direction = Some(ReadingDirection::TopToBottom);
+        meta.creators = vec![Creator::new("Someone", CreatorRole::Other)];
+
+        let xml = export(Format::ComicInfo, &meta).unwrap();
+        let names: Vec<_> = xml.unmapped.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["creators", "reading_direction"]);
+        assert_eq!(xml.unmapped[0].value, "Someone (Other)");
+    }
+
+    #[test]
+    fn report_unmapped_import() {
+        let xml = r#"<?xml version="1.0"?>
+            <ComicInfo>
+                <Title>Test</Title>
+                <StoryArc>Some Arc</StoryArc>
+                <Volume>not a number</Volume>
+                <Notes></Notes>
+            </ComicInfo>"#;
+
+        let read = import(Format::ComicInfo, xml).unwrap();
+        assert_eq!(read.value.title, Some("Test".to_string()));
+        assert_eq!(read.value.volume, None);
+
+        let fields: Vec<_> = read
+            .unmapped
+            .iter()
+            .map(|f| (f.name.as_str(), f.value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![("StoryArc", "Some Arc"), ("Volume", "not a number")]
+        );
+    }
+
+    #[test]
+    fn wrong_document() {
+        assert!(import(Format::ComicInfo, "<MetronInfo/>").is_err());
+        assert!(import(Format::Opf, "<package").is_err());
+    }
+
+    #[test]
+    fn sidecar() {
+        let mango_path = Path::new("sidecar.mango");
+        let written = write_sidecar(Format::ComicInfo, &metadata(), mango_path).unwrap();
+        assert_eq!(written.value, Path::new("sidecar.ComicInfo.xml"));
+
+        let read = read_sidecar(Format::ComicInfo, mango_path).unwrap();
+        assert_common(&read.value);
+
+        fs::remove_file(written.value).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! The OPF 2.0 metadata of Calibre.
+//!
+//! Creators are matched by their MARC relator code. Genres and tags are both written as subjects,
+//! subjects are read back as tags. The volume is stored as the index in the series.
+
+use super::xmltree::{Element, Namespace};
+use super::{
+    all_text, check_root, child_elements, push_element, text, text_element, Conversion,
+    UnmappedField,
+};
+use error::{Error, ErrorKind};
+use meta::{Creator, CreatorRole, Identifier, Language, MangoMetadata, PublicationDate};
+
+const OPF_NAMESPACE: &str = "http://www.idpf.org/2007/opf";
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+
+/// The identifier referenced by the package.
+const UNIQUE_IDENTIFIER: &str = "bookid";
+
+/// Calibre writes this date if the publication date is unknown.
+const UNDEFINED_YEAR: i16 = 101;
+
+/// MARC relator codes, roles without a code can't be written.
+const ROLES: &[(&str, CreatorRole)] = &[
+    ("aut", CreatorRole::Writer),
+    ("art", CreatorRole::Artist),
+    ("ill", CreatorRole::Artist),
+    ("clr", CreatorRole::Colorist),
+    ("cov", CreatorRole::CoverArtist),
+    ("edt", CreatorRole::Editor),
+    ("trl", CreatorRole::Translator),
+];
+
+pub fn import(root: &Element) -> Result<Conversion<MangoMetadata>, Error> {
+    check_root(root, "package")?;
+    let metadata = root
+        .get_child("metadata")
+        .ok_or_else(|| Error::new(ErrorKind::Decode, "the package has no metadata"))?;
+
+    let mut meta = MangoMetadata::new();
+    let mut unmapped = Vec::new();
+
+    for element in child_elements(metadata) {
+        if element.name == "meta" {
+            import_meta(element, &mut meta, &mut unmapped);
+            continue;
+        }
+
+        let value = match text(element) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let mapped = match element.name.as_str() {
+            "title" if meta.title.is_none() => {
+                meta.title = Some(value.clone());
+                true
+            }
+            "creator" | "contributor" => import_creator(element, &value, &mut meta),
+            "publisher" => {
+                meta.publisher = Some(value.clone());
+                true
+            }
+            "date" => import_date(&value, &mut meta),
+            "language" if meta.language.is_none() => match Language::parse(&value) {
+                Ok(language) => {
+                    meta.language = Some(language);
+                    true
+                }
+                Err(_) => false,
+            },
+            "description" => {
+                meta.summary = Some(value.clone());
+                true
+            }
+            "subject" => {
+                meta.tags.push(value.clone());
+                true
+            }
+            "identifier" => match import_identifier(element, &value) {
+                Some(identifier) => {
+                    meta.identifiers.push(identifier);
+                    true
+                }
+                None => false,
+            },
+            "source" => {
+                meta.source = Some(value.clone());
+                true
+            }
+            _ => false,
+        };
+
+        if !mapped {
+            unmapped.push(UnmappedField::new(element.name.as_str(), value));
+        }
+    }
+
+    Ok(Conversion::new(meta, unmapped))
+}
+
+fn import_meta(element: &Element, meta: &mut MangoMetadata, unmapped: &mut Vec<UnmappedField>) {
+    let (name, value) = match element.attributes.get("name") {
+        Some(name) => (
+            name.clone(),
+            element
+                .attributes
+                .get("content")
+                .cloned()
+                .unwrap_or_default(),
+        ),
+        // OPF 3 uses properties instead of names
+        None => match element.attributes.get("property") {
+            Some(property) => (property.clone(), all_text(element)),
+            None => return,
+        },
+    };
+
+    let mapped = match name.as_str() {
+        "calibre:series" => {
+            meta.series = Some(value.clone());
+            true
+        }
+        "calibre:series_index" => match value.parse::<f64>() {
+            Ok(index) if index.fract() == 0.0 && index.abs() <= f64::from(i16::MAX) => {
+                meta.volume = Some(index as i16);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+
+    if !mapped {
+        unmapped.push(UnmappedField::new(name, value));
+    }
+}
+
+fn import_creator(element: &Element, name: &str, meta: &mut MangoMetadata) -> bool {
+    // creators without a role are authors for calibre
+    let role = match element.attributes.get("role") {
+        Some(code) => match ROLES.iter().find(|r| r.0 == code.as_str()) {
+            Some(&(_, role)) => role,
+            None => return false,
+        },
+        None if element.name == "creator" => CreatorRole::Writer,
+        None => return false,
+    };
+
+    if role == CreatorRole::Writer && meta.author.is_none() {
+        meta.author = Some(name.to_string());
+    } else {
+        meta.creators.push(Creator::new(name, role));
+    }
+
+    true
+}
+
+fn import_date(value: &str, meta: &mut MangoMetadata) -> bool {
+    match super::parse_date(value) {
+        Some((UNDEFINED_YEAR, _, _)) => true,
+        Some((year, month, day)) => {
+            meta.year = Some(year);
+            if month.is_some() {
+                meta.publication_date = Some(PublicationDate::new(year, month, day));
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+fn import_identifier(element: &Element, value: &str) -> Option<Identifier> {
+    if let Some(scheme) = element.attributes.get("scheme") {
+        return Some(match scheme.to_lowercase().as_str() {
+            "isbn" => Identifier::isbn(value),
+            scheme => Identifier::new(scheme, value),
+        });
+    }
+
+    // identifiers without a scheme are usually URNs like urn:isbn:...
+    let mut parts = value.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("urn"), Some("isbn"), Some(isbn)) => Some(Identifier::isbn(isbn)),
+        (Some("urn"), Some(scheme), Some(value)) => Some(Identifier::new(scheme, value)),
+        _ => None,
+    }
+}
+
+pub fn export(meta: &MangoMetadata) -> (Element, Vec<UnmappedField>) {
+    let mut root = Element::new("package");
+    root.namespace = Some(OPF_NAMESPACE.to_string());
+    root.namespaces = Some(namespace(&[("", OPF_NAMESPACE)]));
+    root.attributes
+        .insert("version".to_string(), "2.0".to_string());
+
+    let mut metadata = Element::new("metadata");
+    metadata.namespaces = Some(namespace(&[("dc", DC_NAMESPACE), ("opf", OPF_NAMESPACE)]));
+    let mut unmapped = Vec::new();
+
+    if let Some(ref title) = meta.title {
+        push_element(&mut metadata, dc("title", title));
+    }
+    if let Some(ref author) = meta.author {
+        push_element(&mut metadata, creator("creator", author, "aut"));
+    }
+
+    for creator in &meta.creators {
+        let code = ROLES.iter().find(|r| r.1 == creator.role).map(|r| r.0);
+        let name = match creator.role {
+            CreatorRole::Editor | CreatorRole::Translator => "contributor",
+            _ => "creator",
+        };
+
+        match code {
+            Some(code) => push_element(&mut metadata, self::creator(name, &creator.name, code)),
+            None => unmapped.push(UnmappedField::new(
+                "creators",
+                format!("{} ({:?})", creator.name, creator.role),
+            )),
+        }
+    }
+
+    if let Some(ref publisher) = meta.publisher {
+        push_element(&mut metadata, dc("publisher", publisher));
+    }
+
+    let date = match meta.publication_date {
+        Some(date) => Some(format_date(date)),
+        None => meta.year.map(|year| format!("{:04}", year)),
+    };
+    if let Some(date) = date {
+        push_element(&mut metadata, dc("date", &date));
+    }
+
+    if let Some(ref language) = meta.language {
+        push_element(&mut metadata, dc("language", language.as_str()));
+    }
+    if let Some(ref summary) = meta.summary {
+        push_element(&mut metadata, dc("description", summary));
+    }
+    for subject in meta.genres.iter().chain(meta.tags.iter()) {
+        push_element(&mut metadata, dc("subject", subject));
+    }
+
+    for (i, identifier) in meta.identifiers.iter().enumerate() {
+        let mut element = dc("identifier", &identifier.value);
+        element
+            .attributes
+            .insert("opf:scheme".to_string(), identifier.scheme.to_uppercase());
+        if i == 0 {
+            element
+                .attributes
+                .insert("id".to_string(), UNIQUE_IDENTIFIER.to_string());
+            root.attributes.insert(
+                "unique-identifier".to_string(),
+                UNIQUE_IDENTIFIER.to_string(),
+            );
+        }
+        push_element(&mut metadata, element);
+    }
+
+    if let Some(ref source) = meta.source {
+        push_element(&mut metadata, dc("source", source));
+    }
+    if let Some(ref series) = meta.series {
+        push_element(&mut metadata, calibre_meta("calibre:series", series));
+    }
+    if let Some(volume) = meta.volume {
+        push_element(
+            &mut metadata,
+            calibre_meta("calibre:series_index", &volume.to_string()),
+        );
+    }
+
+    if let Some(ref translation) = meta.translation {
+        unmapped.push(UnmappedField::new("translation", translation.as_str()));
+    }
+    if let Some(chapter) = meta.chapter {
+        unmapped.push(UnmappedField::new("chapter", super::format_number(chapter)));
+    }
+    if let Some(rating) = meta.age_rating {
+        unmapped.push(UnmappedField::new("age_rating", format!("{:?}", rating)));
+    }
+    if let Some(direction) = meta.reading_direction {
+        unmapped.push(UnmappedField::new(
+            "reading_direction",
+            format!("{:?}", direction),
+        ));
+    }
+
+    push_element(&mut root, metadata);
+    (root, unmapped)
+}
+
+fn namespace(mappings: &[(&str, &str)]) -> Namespace {
+    let mut namespace = Namespace::empty();
+    for &(prefix, uri) in mappings {
+        namespace.put(prefix, uri);
+    }
+    namespace
+}
+
+fn dc(name: &str, value: &str) -> Element {
+    let mut element = text_element(name, value);
+    element.prefix = Some("dc".to_string());
+    element.namespace = Some(DC_NAMESPACE.to_string());
+    element
+}
+
+fn creator(name: &str, value: &str, role: &str) -> Element {
+    let mut element = dc(name, value);
+    element
+        .attributes
+        .insert("opf:role".to_string(), role.to_string());
+    element
+}
+
+fn calibre_meta(name: &str, content: &str) -> Element {
+    let mut element = Element::new("meta");
+    element
+        .attributes
+        .insert("name".to_string(), name.to_string());
+    element
+        .attributes
+        .insert("content".to_string(), content.to_string());
+    element
+}
+
+fn format_date(date: PublicationDate) -> String {
+    match (date.month, date.day) {
+        (Some(month), Some(day)) => format!("{:04}-{:02}-{:02}", date.year, month, day),
+        (Some(month), None) => format!("{:04}-{:02}", date.year, month),
+        _ => format!("{:04}", date.year),
+    }
+}
@@ -1,48 +1,50 @@
 use super::{CompressionType, EncryptionType};
-use file::{ErrorKind, MangoFile, MangoFileError};
+use error::{Error, ErrorKind};
+use file::{open_error, MangoFile};
 use image::{MangoImage, Mime};
 use meta::MangoImageMetadata;
 use meta::MangoMetadata;
+use meta::{is_false, PageKind, PerceptualHash, Transform};
 use serde_json;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::BufReader;
 use std::path::Path;
+use toc::TableOfContents;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct JsonMangoFile {
     pub meta: MangoMetadata,
+    #[serde(default, skip_serializing_if = "TableOfContents::is_empty")]
+    toc: TableOfContents,
     images: Vec<Base64Image>,
 }
 
 impl JsonMangoFile {
-    fn new(meta: MangoMetadata, images: Vec<Base64Image>) -> Self {
-        Self { meta, images }
+    fn new(meta: MangoMetadata, toc: TableOfContents, images: Vec<Base64Image>) -> Self {
+        Self { meta, toc, images }
     }
 
     fn get_images(&self) -> Vec<Base64Image> {
         self.images.clone()
     }
 
-    pub fn open(p: &Path) -> Result<MangoFile, MangoFileError> {
-        let file = File::open(p);
-        if file.is_err() {
-            return Err(MangoFileError::convert_io_open(file.err().unwrap()));
-        }
+    pub fn open(p: &Path) -> Result<MangoFile, Error> {
+        let file = File::open(p).map_err(open_error)?;
+        Self::into_mango(serde_json::from_reader(BufReader::new(file)))
+    }
 
-        let json_result = serde_json::from_reader(file.unwrap());
-        if json_result.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::DecodeError,
-                "couldn't decode JSON to MangoFile",
-                json_result.err().unwrap(),
-            ));
-        }
+    pub fn decode(bytes: &[u8]) -> Result<MangoFile, Error> {
+        Self::into_mango(serde_json::from_slice(bytes))
+    }
+
+    fn into_mango(json: serde_json::Result<JsonMangoFile>) -> Result<MangoFile, Error> {
+        let json_file = json.map_err(|e| {
+            Error::with_cause(ErrorKind::Decode, "couldn't decode JSON to MangoFile", e)
+        })?;
 
         // convert JsonMangoFile to MangoFile
         let mut mango_imgs = Vec::new();
 
-        let json_file: JsonMangoFile = json_result.unwrap();
-
         for image in json_file.get_images() {
             mango_imgs.push(Base64Image::to_mango(&image));
         }
@@ -50,40 +52,44 @@ impl JsonMangoFile {
         let mut mango_file = MangoFile::new();
         mango_file.set_images(mango_imgs);
         mango_file.set_meta(json_file.meta);
+        mango_file.set_toc(json_file.toc);
 
         Ok(mango_file)
     }
 
-    pub fn save(p: &Path, file: &MangoFile) -> Result<(), MangoFileError> {
-        let mut base64_imgs = Vec::new();
-
-        for image in file.get_images() {
-            base64_imgs.push(Base64Image::from_mango(&image));
+    /// Reads only the metadata, the images get skipped without decoding them.
+    pub fn read_metadata(p: &Path) -> Result<MangoMetadata, Error> {
+        #[derive(Deserialize)]
+        struct MetadataOnly {
+            meta: MangoMetadata,
         }
 
-        let json_string =
-            serde_json::to_string_pretty(&JsonMangoFile::new(file.get_meta(), base64_imgs));
+        let file = File::open(p).map_err(open_error)?;
+        let json: Result<MetadataOnly, _> = serde_json::from_reader(BufReader::new(file));
 
-        if json_string.is_err() {
-            return Err(MangoFileError::with_cause(
-                ErrorKind::EncodeError,
-                "couldn't encode JSON to MangoFile",
-                json_string.err().unwrap(),
-            ));
+        match json {
+            Ok(json) => Ok(json.meta),
+            Err(e) => Err(Error::with_cause(
+                ErrorKind::Decode,
+                "couldn't decode JSON to MangoFile",
+                e,
+            )),
         }
+    }
 
-        let f = File::create(p);
-
-        if f.is_err() {
-            return Err(MangoFileError::convert_io_save(f.err().unwrap()));
-        }
+    pub fn encode(file: &MangoFile) -> Result<Vec<u8>, Error> {
+        let mut base64_imgs = Vec::new();
 
-        let write = f.unwrap().write_all(json_string.unwrap().as_bytes());
-        if write.is_err() {
-            return Err(MangoFileError::convert_io_save(write.err().unwrap()));
+        for image in file.images() {
+            base64_imgs.push(Base64Image::from_mango(image));
         }
 
-        Ok(())
+        serde_json::to_vec_pretty(&JsonMangoFile::new(
+            file.get_meta(),
+            file.get_toc().clone(),
+            base64_imgs,
+        ))
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode JSON to MangoFile", e))
     }
 }
 
@@ -96,6 +102,18 @@ struct Base64ImageMetadata {
     pub filename: String,
     pub checksum: String,
     pub mime: Mime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_kind: Option<PageKind>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub double_page: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<PerceptualHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
 }
 
 impl Base64ImageMetadata {
@@ -107,6 +125,12 @@ impl Base64ImageMetadata {
             filename: meta.filename.clone(),
             checksum: meta.checksum.clone(),
             mime: meta.mime,
+            page_kind: meta.page_kind,
+            double_page: meta.double_page,
+            label: meta.label.clone(),
+            caption: meta.caption.clone(),
+            perceptual_hash: meta.perceptual_hash,
+            transform: meta.transform.clone(),
         }
     }
 
@@ -118,6 +142,12 @@ impl Base64ImageMetadata {
             filename: self.filename.clone(),
             checksum: self.checksum.clone(),
             mime: self.mime,
+            page_kind: self.page_kind,
+            double_page: self.double_page,
+            label: self.label.clone(),
+            caption: self.caption.clone(),
+            perceptual_hash: self.perceptual_hash,
+            transform: self.transform.clone(),
         }
     }
 }
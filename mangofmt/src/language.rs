@@ -0,0 +1,508 @@
+//! BCP 47 language tags.
+
+use error::{Error, ErrorKind};
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Languages with a 2 letter ISO 639-1 code: (ISO 639-1, ISO 639-2/T, ISO 639-2/B, english name).
+const LANGUAGES: &[(&str, &str, &str, &str)] = &[
+    ("ar", "ara", "ara", "Arabic"),
+    ("bg", "bul", "bul", "Bulgarian"),
+    ("bn", "ben", "ben", "Bengali"),
+    ("ca", "cat", "cat", "Catalan"),
+    ("cs", "ces", "cze", "Czech"),
+    ("da", "dan", "dan", "Danish"),
+    ("de", "deu", "ger", "German"),
+    ("el", "ell", "gre", "Greek"),
+    ("en", "eng", "eng", "English"),
+    ("eo", "epo", "epo", "Esperanto"),
+    ("es", "spa", "spa", "Spanish"),
+    ("et", "est", "est", "Estonian"),
+    ("eu", "eus", "baq", "Basque"),
+    ("fa", "fas", "per", "Persian"),
+    ("fi", "fin", "fin", "Finnish"),
+    ("fr", "fra", "fre", "French"),
+    ("he", "heb", "heb", "Hebrew"),
+    ("hi", "hin", "hin", "Hindi"),
+    ("hr", "hrv", "hrv", "Croatian"),
+    ("hu", "hun", "hun", "Hungarian"),
+    ("id", "ind", "ind", "Indonesian"),
+    ("it", "ita", "ita", "Italian"),
+    ("ja", "jpn", "jpn", "Japanese"),
+    ("ko", "kor", "kor", "Korean"),
+    ("lt", "lit", "lit", "Lithuanian"),
+    ("lv", "lav", "lav", "Latvian"),
+    ("mn", "mon", "mon", "Mongolian"),
+    ("ms", "msa", "may", "Malay"),
+    ("my", "mya", "bur", "Burmese"),
+    ("nb", "nob", "nob", "Norwegian Bokmål"),
+    ("nl", "nld", "dut", "Dutch"),
+    ("no", "nor", "nor", "Norwegian"),
+    ("pl", "pol", "pol", "Polish"),
+    ("pt", "por", "por", "Portuguese"),
+    ("ro", "ron", "rum", "Romanian"),
+    ("ru", "rus", "rus", "Russian"),
+    ("sk", "slk", "slo", "Slovak"),
+    ("sl", "slv", "slv", "Slovenian"),
+    ("sr", "srp", "srp", "Serbian"),
+    ("sv", "swe", "swe", "Swedish"),
+    ("ta", "tam", "tam", "Tamil"),
+    ("th", "tha", "tha", "Thai"),
+    ("tl", "tgl", "tgl", "Tagalog"),
+    ("tr", "tur", "tur", "Turkish"),
+    ("uk", "ukr", "ukr", "Ukrainian"),
+    ("vi", "vie", "vie", "Vietnamese"),
+    ("zh", "zho", "chi", "Chinese"),
+];
+
+/// Languages without a 2 letter code.
+const OTHER_LANGUAGES: &[(&str, &str)] = &[
+    ("fil", "Filipino"),
+    ("yue", "Cantonese"),
+    ("cmn", "Mandarin Chinese"),
+];
+
+/// Deprecated language subtags and their replacements.
+const DEPRECATED_LANGUAGES: &[(&str, &str)] = &[("in", "id"), ("iw", "he"), ("ji", "yi")];
+
+const SCRIPTS: &[(&str, &str)] = &[
+    ("Arab", "Arabic"),
+    ("Cyrl", "Cyrillic"),
+    ("Hang", "Hangul"),
+    ("Hans", "Simplified"),
+    ("Hant", "Traditional"),
+    ("Hira", "Hiragana"),
+    ("Jpan", "Japanese"),
+    ("Kana", "Katakana"),
+    ("Kore", "Korean"),
+    ("Latn", "Latin"),
+];
+
+const REGIONS: &[(&str, &str)] = &[
+    ("419", "Latin America"),
+    ("AR", "Argentina"),
+    ("AT", "Austria"),
+    ("AU", "Australia"),
+    ("BR", "Brazil"),
+    ("CA", "Canada"),
+    ("CH", "Switzerland"),
+    ("CN", "China"),
+    ("DE", "Germany"),
+    ("ES", "Spain"),
+    ("FR", "France"),
+    ("GB", "United Kingdom"),
+    ("HK", "Hong Kong"),
+    ("ID", "Indonesia"),
+    ("IN", "India"),
+    ("IT", "Italy"),
+    ("JP", "Japan"),
+    ("KR", "South Korea"),
+    ("MX", "Mexico"),
+    ("MY", "Malaysia"),
+    ("PH", "Philippines"),
+    ("PT", "Portugal"),
+    ("RU", "Russia"),
+    ("SG", "Singapore"),
+    ("TH", "Thailand"),
+    ("TW", "Taiwan"),
+    ("US", "United States"),
+    ("VN", "Vietnam"),
+];
+
+/// The values of the Language enum used by older versions of mangofmt.
+const LEGACY_LANGUAGES: &[(&str, &str)] = &[
+    ("EN", "en"),
+    ("JP", "ja"),
+    ("DE", "de"),
+    ("FR", "fr"),
+    ("IT", "it"),
+    ("CN", "zh"),
+    ("ES", "es"),
+];
+
+fn lookup<'a>(table: &[(&str, &'a str)], key: &str) -> Option<&'a str> {
+    table
+        .iter()
+        .find(|entry| entry.0 == key)
+        .map(|entry| entry.1)
+}
+
+fn is_alpha(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_digit(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_alphanum(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn title_case(s: &str) -> String {
+    let lower = s.to_ascii_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn invalid(tag: &str, reason: &str) -> Error {
+    Error::new(
+        ErrorKind::Decode,
+        format!("invalid language tag \"{}\": {}", tag, reason),
+    )
+}
+
+/// A validated and normalized BCP 47 language tag, like "en", "pt-BR" or "zh-Hant".
+///
+/// Tags get normalized while parsing:
+/// - the subtags get their canonical case ("ZH-hant-tw" becomes "zh-Hant-TW")
+/// - "_" is accepted as separator ("pt_BR" becomes "pt-BR")
+/// - 3 letter ISO 639-2 codes get replaced by their 2 letter counterpart ("jpn" becomes "ja")
+/// - deprecated codes get replaced ("iw" becomes "he")
+///
+/// Files written by older versions of mangofmt, which only knew a few hardcoded languages,
+/// get migrated while reading them ("JP" becomes "ja", "CN" becomes "zh").
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Language {
+    tag: String,
+}
+
+impl Language {
+    /// Parses and normalizes a language tag.
+    pub fn parse(tag: &str) -> Result<Language, Error> {
+        if tag.is_empty() {
+            return Err(invalid(tag, "the tag is empty"));
+        }
+
+        let subtags: Vec<&str> = tag.split(&['-', '_'][..]).collect();
+        let mut normalized: Vec<String> = Vec::with_capacity(subtags.len());
+        let mut iter = subtags.iter().peekable();
+
+        // a private use only tag
+        if subtags[0].eq_ignore_ascii_case("x") {
+            return Self::parse_private_use(tag, iter).map(|private| Language { tag: private });
+        }
+
+        // primary language
+        let language = iter.next().unwrap().to_ascii_lowercase();
+        if !is_alpha(&language) || language.len() < 2 || language.len() > 8 || language.len() == 4 {
+            return Err(invalid(tag, "the primary language subtag is malformed"));
+        }
+        normalized.push(Self::canonical_language(&language));
+
+        // extended language subtags (up to 3)
+        let mut extlangs = 0;
+        while let Some(subtag) = iter.peek() {
+            if language.len() <= 3 && subtag.len() == 3 && is_alpha(subtag) && extlangs < 3 {
+                normalized.push(subtag.to_ascii_lowercase());
+                extlangs += 1;
+                iter.next();
+            } else {
+                break;
+            }
+        }
+
+        // script
+        if let Some(subtag) = iter.peek() {
+            if subtag.len() == 4 && is_alpha(subtag) {
+                normalized.push(title_case(subtag));
+                iter.next();
+            }
+        }
+
+        // region
+        if let Some(subtag) = iter.peek() {
+            if (subtag.len() == 2 && is_alpha(subtag)) || (subtag.len() == 3 && is_digit(subtag)) {
+                normalized.push(subtag.to_ascii_uppercase());
+                iter.next();
+            }
+        }
+
+        // variants
+        while let Some(subtag) = iter.peek() {
+            let is_variant = is_alphanum(subtag)
+                && ((subtag.len() >= 5 && subtag.len() <= 8)
+                    || (subtag.len() == 4 && subtag.as_bytes()[0].is_ascii_digit()));
+            if !is_variant {
+                break;
+            }
+
+            let variant = subtag.to_ascii_lowercase();
+            if normalized.contains(&variant) {
+                return Err(invalid(tag, "a variant is repeated"));
+            }
+            normalized.push(variant);
+            iter.next();
+        }
+
+        // extensions
+        let mut singletons = Vec::new();
+        while let Some(subtag) = iter.peek() {
+            if subtag.len() != 1 || subtag.eq_ignore_ascii_case("x") {
+                break;
+            }
+            if !is_alphanum(subtag) {
+                return Err(invalid(tag, "an extension singleton is malformed"));
+            }
+
+            let singleton = subtag.to_ascii_lowercase();
+            if singletons.contains(&singleton) {
+                return Err(invalid(tag, "an extension is repeated"));
+            }
+            iter.next();
+
+            let mut extension = vec![singleton.clone()];
+            while let Some(subtag) = iter.peek() {
+                if subtag.len() < 2 || subtag.len() > 8 || !is_alphanum(subtag) {
+                    break;
+                }
+                extension.push(subtag.to_ascii_lowercase());
+                iter.next();
+            }
+
+            if extension.len() == 1 {
+                return Err(invalid(tag, "an extension is empty"));
+            }
+            singletons.push(singleton);
+            normalized.push(extension.join("-"));
+        }
+
+        // private use
+        if iter.peek().is_some() {
+            if !iter.peek().unwrap().eq_ignore_ascii_case("x") {
+                return Err(invalid(tag, "unexpected subtag"));
+            }
+            normalized.push(Self::parse_private_use(tag, iter)?);
+        }
+
+        Ok(Language {
+            tag: normalized.join("-"),
+        })
+    }
+
+    fn parse_private_use<'a, I>(tag: &str, mut iter: I) -> Result<String, Error>
+    where
+        I: Iterator<Item = &'a &'a str>,
+    {
+        // skip the "x"
+        iter.next();
+
+        let mut private = vec![String::from("x")];
+        for subtag in iter {
+            if subtag.is_empty() || subtag.len() > 8 || !is_alphanum(subtag) {
+                return Err(invalid(tag, "a private use subtag is malformed"));
+            }
+            private.push(subtag.to_ascii_lowercase());
+        }
+
+        if private.len() == 1 {
+            return Err(invalid(tag, "the private use part is empty"));
+        }
+
+        Ok(private.join("-"))
+    }
+
+    fn canonical_language(language: &str) -> String {
+        if let Some(replacement) = lookup(DEPRECATED_LANGUAGES, language) {
+            return replacement.to_string();
+        }
+
+        if language.len() == 3 {
+            if let Some(entry) = LANGUAGES
+                .iter()
+                .find(|entry| entry.1 == language || entry.2 == language)
+            {
+                return entry.0.to_string();
+            }
+        }
+
+        language.to_string()
+    }
+
+    /// Converts a value of the Language enum used by older versions of mangofmt, like "JP".
+    ///
+    /// Returns None if the value is not one of them.
+    pub fn from_legacy(value: &str) -> Option<Language> {
+        lookup(LEGACY_LANGUAGES, value).map(|tag| Language {
+            tag: tag.to_string(),
+        })
+    }
+
+    /// Returns the whole tag.
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the primary language subtag, e.g. "zh" for "zh-Hant-TW".
+    pub fn language(&self) -> &str {
+        self.tag.split('-').next().unwrap_or("")
+    }
+
+    /// Returns the script subtag, e.g. "Hant" for "zh-Hant-TW".
+    pub fn script(&self) -> Option<&str> {
+        self.subtags()
+            .skip(1)
+            .take_while(|s| s.len() != 1)
+            .find(|s| s.len() == 4 && is_alpha(s))
+    }
+
+    /// Returns the region subtag, e.g. "TW" for "zh-Hant-TW".
+    pub fn region(&self) -> Option<&str> {
+        self.subtags()
+            .skip(1)
+            .take_while(|s| s.len() != 1)
+            .find(|s| (s.len() == 2 && is_alpha(s)) || (s.len() == 3 && is_digit(s)))
+    }
+
+    fn subtags(&self) -> impl Iterator<Item = &str> {
+        // a private use only tag has no language, script or region
+        let skip_all = self.tag.starts_with("x-");
+        self.tag.split('-').filter(move |_| !skip_all)
+    }
+
+    /// Returns an english name for the tag, e.g. "Chinese (Traditional, Taiwan)".
+    ///
+    /// Unknown subtags are shown as they are.
+    pub fn display_name(&self) -> String {
+        if self.tag.starts_with("x-") {
+            return self.tag.clone();
+        }
+
+        let language = self.language();
+        let name = LANGUAGES
+            .iter()
+            .find(|entry| entry.0 == language)
+            .map(|entry| entry.3)
+            .or_else(|| lookup(OTHER_LANGUAGES, language))
+            .unwrap_or(language);
+
+        let mut details = Vec::new();
+        if let Some(script) = self.script() {
+            details.push(lookup(SCRIPTS, script).unwrap_or(script));
+        }
+        if let Some(region) = self.region() {
+            details.push(lookup(REGIONS, region).unwrap_or(region));
+        }
+
+        if details.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} ({})", name, details.join(", "))
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Language::parse(s)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tag)
+    }
+}
+
+impl Serialize for Language {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.tag)
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        match Language::from_legacy(&value) {
+            Some(language) => Ok(language),
+            None => Language::parse(&value).map_err(de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Language;
+    use serde_json;
+
+    fn tag(value: &str) -> String {
+        Language::parse(value).unwrap().to_string()
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(tag("EN"), "en");
+        assert_eq!(tag("pt_br"), "pt-BR");
+        assert_eq!(tag("ZH-hant-tw"), "zh-Hant-TW");
+        assert_eq!(tag("jpn"), "ja");
+        assert_eq!(tag("ger"), "de");
+        assert_eq!(tag("iw"), "he");
+        assert_eq!(tag("es-419"), "es-419");
+        assert_eq!(tag("sl-ROZAJ-biske"), "sl-rozaj-biske");
+        assert_eq!(tag("en-US-u-CA-gregory-X-Foo"), "en-US-u-ca-gregory-x-foo");
+        assert_eq!(tag("x-whatever"), "x-whatever");
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(Language::parse("").is_err());
+        assert!(Language::parse("e").is_err());
+        assert!(Language::parse("english!").is_err());
+        assert!(Language::parse("en--US").is_err());
+        assert!(Language::parse("en-US-").is_err());
+        assert!(Language::parse("de-DE-1901-1901").is_err());
+        assert!(Language::parse("en-a").is_err());
+        assert!(Language::parse("x").is_err());
+    }
+
+    #[test]
+    fn subtags() {
+        let lang = Language::parse("zh-Hans-CN").unwrap();
+        assert_eq!(lang.language(), "zh");
+        assert_eq!(lang.script(), Some("Hans"));
+        assert_eq!(lang.region(), Some("CN"));
+
+        let lang = Language::parse("ko").unwrap();
+        assert_eq!(lang.script(), None);
+        assert_eq!(lang.region(), None);
+    }
+
+    #[test]
+    fn display_names() {
+        assert_eq!(Language::parse("ko").unwrap().display_name(), "Korean");
+        assert_eq!(
+            Language::parse("pt-BR").unwrap().display_name(),
+            "Portuguese (Brazil)"
+        );
+        assert_eq!(
+            Language::parse("zh-Hant").unwrap().display_name(),
+            "Chinese (Traditional)"
+        );
+    }
+
+    #[test]
+    fn migrate_legacy_values() {
+        let lang: Language = serde_json::from_str("\"JP\"").unwrap();
+        assert_eq!(lang.as_str(), "ja");
+
+        let lang: Language = serde_json::from_str("\"CN\"").unwrap();
+        assert_eq!(lang.as_str(), "zh");
+
+        let lang: Language = serde_json::from_str("\"pt-BR\"").unwrap();
+        assert_eq!(serde_json::to_string(&lang).unwrap(), "\"pt-BR\"");
+    }
+}
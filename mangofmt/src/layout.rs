@@ -0,0 +1,987 @@
+//! Finds and replaces the metadata inside of a saved MangoFile without decoding the images,
+//! and reads single images without decoding the other ones.
+//!
+//! A MangoFile is a map (CBOR) or document (BSON) which contains the metadata under the key
+//! "meta" and the images under the key "images". Everything which isn't needed is skipped by
+//! seeking over it, so only the metadata, the requested image or the headers needed to count the
+//! images get read.
+//!
+//! Every length read from the file is checked against the length of the file before anything
+//! gets allocated for it, so corrupted files fail with a decode error.
+
+use bson;
+use error::{Error, ErrorKind};
+use image::MangoImage;
+use meta::MangoMetadata;
+use serde_cbor;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use storage::StoredImage;
+
+/// The key under which the metadata is stored.
+const META_KEY: &str = "meta";
+
+/// The key under which the images are stored.
+const IMAGES_KEY: &str = "images";
+
+/// How deeply CBOR items may be nested, the same limit serde_cbor uses.
+const MAX_CBOR_DEPTH: u32 = 128;
+
+/// The key of the filler which keeps the size of rewritten metadata the same.
+/// Readers ignore unknown keys, so it doesn't show up anywhere.
+const PADDING_KEY: &str = "_padding";
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Encoding {
+    Cbor,
+    Bson,
+}
+
+/// The position of the encoded metadata inside of a file.
+#[derive(Debug, Copy, Clone)]
+pub struct MetaRegion {
+    pub encoding: Encoding,
+    /// The offset of the first byte of the metadata.
+    pub start: u64,
+    pub len: u64,
+}
+
+impl MetaRegion {
+    /// Finds the metadata, the reader has to be at the start of the file.
+    ///
+    /// Returns None if the file neither starts like a CBOR map nor like a BSON document,
+    /// e.g. because it is a JSON file.
+    pub fn find<R: Read + Seek>(reader: &mut R) -> Result<Option<MetaRegion>, Error> {
+        Self::find_region(reader).map_err(truncated)
+    }
+
+    fn find_region<R: Read + Seek>(reader: &mut R) -> Result<Option<MetaRegion>, Error> {
+        let file_len = stream_len(reader)?;
+        let mut header = Vec::with_capacity(5);
+        reader.by_ref().take(5).read_to_end(&mut header)?;
+
+        let cbor = header.first().is_some_and(|first| first >> 5 == CBOR_MAP);
+        // the size of the document and the type of its first element
+        let bson = header.len() == 5
+            && read_u32(&mut &header[..4])? >= 5
+            && BSON_TYPES.contains(&header[4]);
+
+        if cbor {
+            reader.seek(SeekFrom::Start(0))?;
+            match find_cbor(reader, file_len) {
+                Ok(region) => return Ok(Some(region)),
+                Err(e) if !bson => return Err(e),
+                Err(_) => {}
+            }
+        }
+        if bson {
+            reader.seek(SeekFrom::Start(0))?;
+            return find_bson(reader, file_len).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// Reads and decodes the metadata.
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> Result<MangoMetadata, Error> {
+        let bytes = read_region(reader, self.start, self.len)?;
+        decode(self.encoding, &bytes)
+    }
+
+    /// Encodes the metadata so it fits into this region.
+    ///
+    /// Returns None if the metadata is too big.
+    pub fn encode_fitting(&self, meta: &MangoMetadata) -> Result<Option<Vec<u8>>, Error> {
+        let bytes = encode(self.encoding, meta)?;
+        let len = bytes.len() as u64;
+
+        if len == self.len {
+            return Ok(Some(bytes));
+        }
+        if len > self.len {
+            return Ok(None);
+        }
+
+        let padded = match self.encoding {
+            Encoding::Cbor => pad_cbor(&bytes, self.len - len)?,
+            Encoding::Bson => pad_bson(meta, self.len - len)?,
+        };
+
+        Ok(padded.filter(|p| p.len() as u64 == self.len))
+    }
+
+    /// Returns the bytes in front of the metadata, with all lengths adjusted to a metadata of
+    /// `new_len` bytes.
+    pub fn header<R: Read + Seek>(&self, reader: &mut R, new_len: u64) -> Result<Vec<u8>, Error> {
+        let mut header = read_region(reader, 0, self.start)?;
+
+        if self.encoding == Encoding::Bson {
+            // the document starts with its total size
+            let total = u64::from(read_u32(&mut &header[..4])?);
+            let total = total + new_len - self.len;
+            if total > u64::from(i32::MAX as u32) {
+                return Err(Error::new(
+                    ErrorKind::Encode,
+                    "the BSON document got too big",
+                ));
+            }
+            header[..4].copy_from_slice(&(total as u32).to_le_bytes());
+        }
+
+        Ok(header)
+    }
+}
+
+/// The position of a single encoded image inside of a file.
+#[derive(Debug, Copy, Clone)]
+pub struct PageRegion {
+    pub encoding: Encoding,
+    /// The offset of the first byte of the image.
+    pub start: u64,
+    pub len: u64,
+}
+
+impl PageRegion {
+    /// Finds the image with the given index, the reader has to be at the start of the file.
+    pub fn find<R: Read + Seek>(
+        reader: &mut R,
+        encoding: Encoding,
+        index: usize,
+    ) -> Result<PageRegion, Error> {
+        PageCursor::find(reader, encoding)?.seek_page(reader, index)
+    }
+
+    /// Decodes the image from its encoded bytes, which start at `start`.
+    pub fn decode(&self, bytes: &[u8]) -> Result<StoredImage, Error> {
+        match self.encoding {
+            Encoding::Cbor => serde_cbor::from_slice(bytes)
+                .map_err(|e| Error::with_cause(ErrorKind::Decode, "couldn't decode CBOR", e)),
+            Encoding::Bson => {
+                let document = bson::decode_document(&mut &bytes[..]).map_err(|e| {
+                    Error::with_cause(ErrorKind::Decode, "couldn't decode BSON Document", e)
+                })?;
+                bson::from_bson(bson::Bson::Document(document)).map_err(|e| {
+                    Error::with_cause(ErrorKind::Decode, "couldn't decode BSON Document", e)
+                })
+            }
+        }
+    }
+
+    /// Reads and decodes the image.
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> Result<StoredImage, Error> {
+        let bytes = read_region(reader, self.start, self.len)?;
+        self.decode(&bytes)
+    }
+}
+
+/// A position in the array of images, the search for an image can continue from it instead of
+/// starting over at the start of the file.
+#[derive(Debug, Copy, Clone)]
+pub struct PageCursor {
+    encoding: Encoding,
+    /// The number of images (CBOR) or the offset of the null byte which ends the array (BSON).
+    end: u64,
+    /// The index of the image the cursor points to.
+    index: usize,
+    /// The offset of that image, of its element for BSON.
+    position: u64,
+    /// The offset of the first image.
+    first: u64,
+    file_len: u64,
+}
+
+impl PageCursor {
+    /// Points to the first image, the reader has to be at the start of the file.
+    pub fn find<R: Read + Seek>(reader: &mut R, encoding: Encoding) -> Result<PageCursor, Error> {
+        Self::find_first(reader, encoding).map_err(truncated)
+    }
+
+    fn find_first<R: Read + Seek>(reader: &mut R, encoding: Encoding) -> Result<PageCursor, Error> {
+        let file_len = stream_len(reader)?;
+        let (end, position) = match encoding {
+            Encoding::Cbor => {
+                let count = find_cbor_images(reader)?;
+                (count, reader.stream_position()?)
+            }
+            Encoding::Bson => {
+                let start = find_bson_images(reader)?;
+                reader.seek(SeekFrom::Start(start))?;
+                let total = u64::from(read_u32(reader)?);
+                if total < 5 {
+                    return Err(invalid("the BSON array is too short"));
+                }
+                check_region(start, total, file_len)?;
+                (start + total - 1, start + 4)
+            }
+        };
+
+        Ok(PageCursor {
+            encoding,
+            end,
+            index: 0,
+            position,
+            first: position,
+            file_len,
+        })
+    }
+
+    /// Moves the cursor to the image with the given index and returns where that image is.
+    ///
+    /// If the search fails, e.g. because the reader failed, the cursor stays at the last image it
+    /// got to, so the search can be continued. Images before the cursor are searched from the
+    /// first image again.
+    pub fn seek_page<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        index: usize,
+    ) -> Result<PageRegion, Error> {
+        if index < self.index {
+            self.index = 0;
+            self.position = self.first;
+        }
+
+        match self.encoding {
+            Encoding::Cbor => self.seek_cbor_page(reader, index),
+            Encoding::Bson => self.seek_bson_page(reader, index),
+        }
+        .map_err(truncated)
+    }
+
+    fn seek_cbor_page<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        index: usize,
+    ) -> Result<PageRegion, Error> {
+        if index as u64 >= self.end {
+            return Err(out_of_range(index));
+        }
+
+        loop {
+            reader.seek(SeekFrom::Start(self.position))?;
+            skip_cbor(reader)?;
+            let end = reader.stream_position()?;
+            check_region(self.position, end - self.position, self.file_len)?;
+
+            if self.index == index {
+                return Ok(PageRegion {
+                    encoding: Encoding::Cbor,
+                    start: self.position,
+                    len: end - self.position,
+                });
+            }
+            self.index += 1;
+            self.position = end;
+        }
+    }
+
+    fn seek_bson_page<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        index: usize,
+    ) -> Result<PageRegion, Error> {
+        // the elements of an array are stored in order under their index
+        while self.position < self.end {
+            reader.seek(SeekFrom::Start(self.position))?;
+            let element_type = read_u8(reader)?;
+            read_cstring(reader)?;
+            let start = reader.stream_position()?;
+            let len = read_bson_len(reader, element_type)?;
+            if start + len > self.end {
+                return Err(invalid("a BSON element is longer than its document"));
+            }
+
+            if self.index == index {
+                if element_type != BSON_DOCUMENT {
+                    return Err(invalid("an image is not a BSON document"));
+                }
+                check_region(start, len, self.file_len)?;
+                return Ok(PageRegion {
+                    encoding: Encoding::Bson,
+                    start,
+                    len,
+                });
+            }
+            self.index += 1;
+            self.position = start + len;
+        }
+
+        Err(out_of_range(index))
+    }
+}
+
+/// Reads the image with the given index, the data of images it shares is read as well.
+pub fn read_image<R: Read + Seek>(
+    reader: &mut R,
+    encoding: Encoding,
+    index: usize,
+) -> Result<MangoImage, Error> {
+    reader.seek(SeekFrom::Start(0))?;
+    let stored = PageRegion::find(reader, encoding, index)?.read(reader)?;
+
+    let shared = match stored.data_ref() {
+        // the data always belongs to an image which comes before
+        Some(shared) if shared < index => {
+            reader.seek(SeekFrom::Start(0))?;
+            let stored = PageRegion::find(reader, encoding, shared)?.read(reader)?;
+            Some(stored.into_image(None)?)
+        }
+        _ => None,
+    };
+
+    stored.into_image(shared.as_ref())
+}
+
+/// Counts the images without reading them.
+pub fn page_count<R: Read + Seek>(reader: &mut R, encoding: Encoding) -> Result<usize, Error> {
+    reader.seek(SeekFrom::Start(0))?;
+    match encoding {
+        Encoding::Cbor => find_cbor_images(reader).map(|count| count as usize),
+        Encoding::Bson => {
+            find_bson_images(reader).and_then(|start| count_bson_elements(reader, start))
+        }
+    }
+    .map_err(truncated)
+}
+
+pub fn encode(encoding: Encoding, meta: &MangoMetadata) -> Result<Vec<u8>, Error> {
+    match encoding {
+        Encoding::Cbor => serde_cbor::to_vec(meta)
+            .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode to CBOR", e)),
+        Encoding::Bson => encode_bson(bson_document(meta)?),
+    }
+}
+
+fn decode(encoding: Encoding, bytes: &[u8]) -> Result<MangoMetadata, Error> {
+    match encoding {
+        Encoding::Cbor => serde_cbor::from_slice(bytes)
+            .map_err(|e| Error::with_cause(ErrorKind::Decode, "couldn't decode CBOR", e)),
+        Encoding::Bson => {
+            let document = bson::decode_document(&mut &bytes[..]).map_err(|e| {
+                Error::with_cause(ErrorKind::Decode, "couldn't decode BSON Document", e)
+            })?;
+            bson::from_bson(bson::Bson::Document(document)).map_err(|e| {
+                Error::with_cause(ErrorKind::Decode, "couldn't decode BSON Document", e)
+            })
+        }
+    }
+}
+
+fn invalid(msg: &'static str) -> Error {
+    Error::new(ErrorKind::Decode, msg)
+}
+
+/// Checks that `len` bytes starting at `start` are inside of a file of `file_len` bytes.
+fn check_region(start: u64, len: u64, file_len: u64) -> Result<(), Error> {
+    match start.checked_add(len) {
+        Some(end) if end <= file_len => Ok(()),
+        _ => Err(invalid("a length points past the end of the file")),
+    }
+}
+
+/// Reads `len` bytes starting at `start`, after checking that the file is long enough.
+fn read_region<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> Result<Vec<u8>, Error> {
+    check_region(start, len, stream_len(reader)?)?;
+    reader.seek(SeekFrom::Start(start))?;
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Turns running into the end of the file while following the structure into a decode error.
+fn truncated(error: Error) -> Error {
+    if error.io_kind() == Some(io::ErrorKind::UnexpectedEof) {
+        Error::with_cause(ErrorKind::Decode, "the file ends too early", error)
+    } else {
+        error
+    }
+}
+
+fn out_of_range(index: usize) -> Error {
+    Error::new(
+        ErrorKind::OutOfRange,
+        format!("page {} is out of range", index),
+    )
+}
+
+//------------------------------------------------------------------------------
+//  CBOR
+//------------------------------------------------------------------------------
+
+const CBOR_TEXT: u8 = 3;
+const CBOR_ARRAY: u8 = 4;
+const CBOR_MAP: u8 = 5;
+/// The size of a byte string header with a 4 byte length.
+const CBOR_LONG_HEADER: u64 = 5;
+
+fn find_cbor<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<MetaRegion, Error> {
+    if !seek_cbor_key(reader, META_KEY)? {
+        return Err(invalid("the CBOR map contains no metadata"));
+    }
+
+    let start = reader.stream_position()?;
+    skip_cbor(reader)?;
+    let end = reader.stream_position()?;
+    check_region(start, end - start, file_len)?;
+
+    Ok(MetaRegion {
+        encoding: Encoding::Cbor,
+        start,
+        len: end - start,
+    })
+}
+
+/// Moves the reader to the first image and returns how many images there are.
+fn find_cbor_images<R: Read + Seek>(reader: &mut R) -> Result<u64, Error> {
+    if !seek_cbor_key(reader, IMAGES_KEY)? {
+        return Err(invalid("the CBOR map contains no images"));
+    }
+
+    match read_cbor_header(reader)? {
+        (CBOR_ARRAY, Some(count)) => Ok(count),
+        _ => Err(invalid("the images are not a CBOR array")),
+    }
+}
+
+/// Moves the reader to the value stored under `key` in the map at the start of the file.
+///
+/// Returns false if the map doesn't contain the key.
+fn seek_cbor_key<R: Read + Seek>(reader: &mut R, key: &str) -> Result<bool, Error> {
+    let (major, entries) = read_cbor_header(reader)?;
+    if major != CBOR_MAP {
+        return Err(invalid("the file is not a CBOR map"));
+    }
+
+    let mut remaining = entries;
+    while remaining != Some(0) {
+        let (major, len) = read_cbor_header(reader)?;
+        if major == 7 && len.is_none() {
+            // the end of a map with an unknown size
+            break;
+        }
+
+        // only keys as long as the wanted one get read, the others are skipped
+        if major == CBOR_TEXT && len == Some(key.len() as u64) {
+            let mut found = vec![0; key.len()];
+            reader.read_exact(&mut found)?;
+            if found == key.as_bytes() {
+                return Ok(true);
+            }
+        } else {
+            skip_cbor_body(reader, major, len, 1)?;
+        }
+
+        skip_cbor(reader)?;
+        remaining = remaining.map(|r| r - 1);
+    }
+
+    Ok(false)
+}
+
+/// Reads the major type and the argument of a data item, the argument is None for items with an
+/// unknown length.
+fn read_cbor_header<R: Read>(reader: &mut R) -> Result<(u8, Option<u64>), Error> {
+    let first = read_u8(reader)?;
+    let major = first >> 5;
+    let info = first & 0x1f;
+
+    let argument = match info {
+        0..=23 => Some(u64::from(info)),
+        24 => Some(u64::from(read_u8(reader)?)),
+        25 => Some(read_be(reader, 2)?),
+        26 => Some(read_be(reader, 4)?),
+        27 => Some(read_be(reader, 8)?),
+        31 => None,
+        _ => return Err(invalid("invalid CBOR")),
+    };
+
+    Ok((major, argument))
+}
+
+/// Skips over a complete data item.
+fn skip_cbor<R: Read + Seek>(reader: &mut R) -> Result<(), Error> {
+    skip_cbor_item(reader, 0)
+}
+
+/// `depth` is how many arrays, maps and tags the item is nested in.
+fn skip_cbor_item<R: Read + Seek>(reader: &mut R, depth: u32) -> Result<(), Error> {
+    let (major, argument) = read_cbor_header(reader)?;
+    skip_cbor_body(reader, major, argument, depth)
+}
+
+fn skip_cbor_body<R: Read + Seek>(
+    reader: &mut R,
+    major: u8,
+    argument: Option<u64>,
+    depth: u32,
+) -> Result<(), Error> {
+    if depth > MAX_CBOR_DEPTH {
+        return Err(invalid("the CBOR items are nested too deeply"));
+    }
+
+    match (major, argument) {
+        // integers and simple values only consist of their header
+        (0, Some(_)) | (1, Some(_)) | (7, Some(_)) => Ok(()),
+        // byte and text strings
+        (2, Some(len)) | (3, Some(len)) => {
+            if len > i64::MAX as u64 {
+                return Err(invalid("a CBOR string is too long"));
+            }
+            reader.seek(SeekFrom::Current(len as i64))?;
+            Ok(())
+        }
+        // arrays, maps and tags
+        (4, Some(len)) => skip_cbor_items(reader, len, depth + 1),
+        (5, Some(len)) => skip_cbor_items(reader, len.saturating_mul(2), depth + 1),
+        (6, Some(_)) => skip_cbor_item(reader, depth + 1),
+        // items with an unknown length end with a break
+        (2, None) | (3, None) | (4, None) | (5, None) => loop {
+            let (major, argument) = read_cbor_header(reader)?;
+            if major == 7 && argument.is_none() {
+                return Ok(());
+            }
+            skip_cbor_body(reader, major, argument, depth + 1)?;
+        },
+        _ => Err(invalid("invalid CBOR")),
+    }
+}
+
+fn skip_cbor_items<R: Read + Seek>(reader: &mut R, count: u64, depth: u32) -> Result<(), Error> {
+    for _ in 0..count {
+        skip_cbor_item(reader, depth)?;
+    }
+    Ok(())
+}
+
+fn write_cbor_header(buf: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    if argument < 24 {
+        buf.push(major | argument as u8);
+    } else if argument <= u64::from(u8::MAX) {
+        buf.push(major | 24);
+        buf.push(argument as u8);
+    } else if argument <= u64::from(u16::MAX) {
+        buf.push(major | 25);
+        buf.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u64::from(u32::MAX) {
+        buf.push(major | 26);
+        buf.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+/// Adds a padding entry to an encoded map, so it grows by `extra` bytes.
+fn pad_cbor(map: &[u8], extra: u64) -> Result<Option<Vec<u8>>, Error> {
+    let mut rest = map;
+    let (major, entries) = read_cbor_header(&mut rest)?;
+    let entries = match (major, entries) {
+        (CBOR_MAP, Some(entries)) => entries,
+        _ => return Err(Error::new(ErrorKind::Encode, "metadata is not a CBOR map")),
+    };
+
+    // the filler is a byte string with a 4 byte length
+    if extra > u64::from(u32::MAX) {
+        return Ok(None);
+    }
+
+    let mut padded = Vec::with_capacity(map.len() + extra as usize);
+    write_cbor_header(&mut padded, CBOR_MAP, entries + 1);
+    padded.extend_from_slice(rest);
+    write_cbor_header(&mut padded, CBOR_TEXT, PADDING_KEY.len() as u64);
+    padded.extend_from_slice(PADDING_KEY.as_bytes());
+
+    // the padding always uses a 4 byte length, so its header has a fixed size
+    let target = map.len() as u64 + extra;
+    let used = padded.len() as u64 + CBOR_LONG_HEADER;
+    if used > target || target - used > u64::from(u32::MAX) {
+        return Ok(None);
+    }
+
+    let filler = target - used;
+    padded.push(2 << 5 | 26);
+    padded.extend_from_slice(&(filler as u32).to_be_bytes());
+    padded.resize(target as usize, 0);
+
+    Ok(Some(padded))
+}
+
+//------------------------------------------------------------------------------
+//  BSON
+//------------------------------------------------------------------------------
+
+/// The element types of the BSON specification, besides the deprecated ones and min/max key.
+const BSON_TYPES: &[u8] = &[
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0D, 0x10, 0x11, 0x12, 0x13,
+];
+const BSON_DOCUMENT: u8 = 0x03;
+const BSON_ARRAY: u8 = 0x04;
+/// The size of a binary element without its key and data.
+const BSON_BINARY_OVERHEAD: u64 = 1 + 1 + 4 + 1;
+
+fn find_bson<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<MetaRegion, Error> {
+    let (element_type, start, len) = find_bson_element(reader, META_KEY.as_bytes(), None)?
+        .ok_or_else(|| invalid("the BSON document contains no metadata"))?;
+    if element_type != BSON_DOCUMENT {
+        return Err(invalid("the metadata is not a BSON document"));
+    }
+    check_region(start, len, file_len)?;
+
+    Ok(MetaRegion {
+        encoding: Encoding::Bson,
+        start,
+        len,
+    })
+}
+
+/// Returns where the array with the images starts.
+fn find_bson_images<R: Read + Seek>(reader: &mut R) -> Result<u64, Error> {
+    let (element_type, start, _) = find_bson_element(reader, IMAGES_KEY.as_bytes(), None)?
+        .ok_or_else(|| invalid("the BSON document contains no images"))?;
+    if element_type != BSON_ARRAY {
+        return Err(invalid("the images are not a BSON array"));
+    }
+    Ok(start)
+}
+
+/// Finds the element stored under `key` in the document which starts at the position of the
+/// reader, `offset` is that position or None at the start of the file.
+///
+/// Returns the type of the element, where its value starts and how long it is.
+fn find_bson_element<R: Read + Seek>(
+    reader: &mut R,
+    key: &[u8],
+    offset: Option<u64>,
+) -> Result<Option<(u8, u64, u64)>, Error> {
+    let offset = offset.unwrap_or(0);
+    let total = u64::from(read_u32(reader)?);
+    check_region(offset, total, stream_len(reader)?)?;
+    let mut position = 4;
+
+    // the document ends with a null byte
+    while position + 1 < total {
+        let element_type = read_u8(reader)?;
+        let found = read_cstring(reader)?;
+        let start = reader.stream_position()?;
+        let len = read_bson_len(reader, element_type)?;
+
+        if found == key {
+            return Ok(Some((element_type, start, len)));
+        }
+
+        let end = reader.seek(SeekFrom::Start(start + len))?;
+        position = end - offset;
+        if position >= total {
+            return Err(invalid("a BSON element is longer than its document"));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Counts the elements of the document which starts at `offset`.
+fn count_bson_elements<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<usize, Error> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let total = u64::from(read_u32(reader)?);
+    check_region(offset, total, stream_len(reader)?)?;
+    let mut position = 4;
+    let mut count = 0;
+
+    while position + 1 < total {
+        let element_type = read_u8(reader)?;
+        read_cstring(reader)?;
+        let start = reader.stream_position()?;
+        let len = read_bson_len(reader, element_type)?;
+
+        let end = reader.seek(SeekFrom::Start(start + len))?;
+        position = end - offset;
+        if position >= total {
+            return Err(invalid("a BSON element is longer than its document"));
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Returns how long the value of an element is, the reader has to be at its start.
+fn read_bson_len<R: Read>(reader: &mut R, element_type: u8) -> Result<u64, Error> {
+    Ok(match element_type {
+        // double, datetime, timestamp and int64
+        0x01 | 0x09 | 0x11 | 0x12 => 8,
+        // string
+        0x02 => 4 + u64::from(read_u32(reader)?),
+        // document and array, which include the size of their length
+        0x03 | 0x04 => u64::from(read_u32(reader)?),
+        // binary
+        0x05 => 4 + 1 + u64::from(read_u32(reader)?),
+        // bool
+        0x08 => 1,
+        // null
+        0x0A => 0,
+        // int32
+        0x10 => 4,
+        _ => return Err(invalid("unsupported BSON element")),
+    })
+}
+
+fn bson_document(meta: &MangoMetadata) -> Result<bson::Document, Error> {
+    match bson::to_bson(meta) {
+        Ok(bson::Bson::Document(document)) => Ok(document),
+        Ok(_) => Err(Error::new(
+            ErrorKind::Encode,
+            "metadata is not a BSON document",
+        )),
+        Err(e) => Err(Error::with_cause(
+            ErrorKind::Encode,
+            "couldn't encode to BSON",
+            e,
+        )),
+    }
+}
+
+fn encode_bson(document: bson::Document) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    bson::encode_document(&mut buf, &document)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode to BSON", e))?;
+    Ok(buf)
+}
+
+/// Adds a padding element to the encoded metadata, so it grows by `extra` bytes.
+fn pad_bson(meta: &MangoMetadata, extra: u64) -> Result<Option<Vec<u8>>, Error> {
+    let overhead = BSON_BINARY_OVERHEAD + PADDING_KEY.len() as u64;
+    // BSON documents are at most 2 GB
+    if extra < overhead || extra > i32::MAX as u64 {
+        return Ok(None);
+    }
+
+    let mut document = bson_document(meta)?;
+    document.insert(
+        PADDING_KEY,
+        bson::Bson::Binary(
+            bson::spec::BinarySubtype::Generic,
+            vec![0; (extra - overhead) as usize],
+        ),
+    );
+    encode_bson(document).map(Some)
+}
+
+//------------------------------------------------------------------------------
+//  Reading
+//------------------------------------------------------------------------------
+
+/// Returns the length of the stream, the position stays the same.
+fn stream_len<R: Seek>(reader: &mut R) -> io::Result<u64> {
+    let position = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(len)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_be<R: Read>(reader: &mut R, size: usize) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes[8 - size..])?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_cstring<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut string = Vec::new();
+    loop {
+        match read_u8(reader)? {
+            0 => return Ok(string),
+            byte => string.push(byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, write_cbor_header, Encoding, MetaRegion, PageCursor, PageRegion};
+    use error::ErrorKind;
+    use file::{MangoFile, Serialization};
+    use meta::MangoMetadata;
+    use std::io::{self, Cursor, Read, Seek, SeekFrom};
+    use std::path::Path;
+
+    /// A CBOR map with the metadata `meta` and no images.
+    fn cbor_file(meta: &[u8]) -> Cursor<Vec<u8>> {
+        let mut file = Vec::new();
+        write_cbor_header(&mut file, 5, 2);
+        write_cbor_header(&mut file, 3, 4);
+        file.extend_from_slice(b"meta");
+        file.extend_from_slice(meta);
+        write_cbor_header(&mut file, 3, 6);
+        file.extend_from_slice(b"images");
+        write_cbor_header(&mut file, 4, 0);
+        Cursor::new(file)
+    }
+
+    fn metadata(title: &str) -> MangoMetadata {
+        let mut meta = MangoMetadata::new();
+        meta.title = Some(title.to_string());
+        meta
+    }
+
+    #[test]
+    fn pad_to_same_size() {
+        for encoding in &[Encoding::Cbor, Encoding::Bson] {
+            let old = encode(*encoding, &metadata(&"a".repeat(300))).unwrap();
+            let region = MetaRegion {
+                encoding: *encoding,
+                start: 0,
+                len: old.len() as u64,
+            };
+
+            let new = region.encode_fitting(&metadata("b")).unwrap().unwrap();
+            assert_eq!(new.len(), old.len());
+
+            let read = region.read(&mut Cursor::new(new)).unwrap();
+            assert_eq!(read.title, Some("b".to_string()));
+        }
+    }
+
+    #[test]
+    fn too_big() {
+        let old = encode(Encoding::Cbor, &metadata("a")).unwrap();
+        let region = MetaRegion {
+            encoding: Encoding::Cbor,
+            start: 0,
+            len: old.len() as u64,
+        };
+
+        assert!(region.encode_fitting(&metadata("bb")).unwrap().is_none());
+    }
+
+    #[test]
+    fn lengths_past_the_end() {
+        // a byte string which claims to be a terabyte long
+        let mut meta = Vec::new();
+        write_cbor_header(&mut meta, 2, 1 << 40);
+        let error = MetaRegion::find(&mut cbor_file(&meta)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+
+        // a BSON document with metadata which claims to be 4 GB long
+        let mut bson = vec![0, 0, 0, 0, 0x03];
+        bson.extend_from_slice(b"meta\0");
+        bson.extend_from_slice(&0xffff_fff0_u32.to_le_bytes());
+        bson.extend_from_slice(&[0; 8]);
+        let len = bson.len() as u32;
+        bson[..4].copy_from_slice(&len.to_le_bytes());
+        let error = MetaRegion::find(&mut Cursor::new(bson)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+
+        // regions which don't come from find are checked as well
+        let region = MetaRegion {
+            encoding: Encoding::Cbor,
+            start: 4,
+            len: u64::MAX - 2,
+        };
+        let error = region.read(&mut Cursor::new(vec![0; 16])).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+        let page = PageRegion {
+            encoding: Encoding::Bson,
+            start: 0,
+            len: 1 << 40,
+        };
+        let error = page.read(&mut Cursor::new(vec![0; 16])).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let meta = vec![0x81; 100_000];
+        let error = MetaRegion::find(&mut cbor_file(&meta)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+
+        // indefinite arrays as well
+        let meta = vec![0x9f; 100_000];
+        let error = MetaRegion::find(&mut cbor_file(&meta)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Decode);
+    }
+
+    #[test]
+    fn neither_cbor_nor_bson() {
+        let json = Cursor::new(b"{\"meta\": {}, \"images\": []}".to_vec());
+        assert!(MetaRegion::find(&mut json.clone()).unwrap().is_none());
+        assert!(MetaRegion::find(&mut Cursor::new(Vec::new()))
+            .unwrap()
+            .is_none());
+
+        let meta = encode(Encoding::Cbor, &metadata("found")).unwrap();
+        let region = MetaRegion::find(&mut cbor_file(&meta)).unwrap().unwrap();
+        assert_eq!(region.encoding, Encoding::Cbor);
+        assert_eq!(region.len, meta.len() as u64);
+    }
+
+    /// A reader which fails to read past `limit`.
+    struct Limited {
+        inner: Cursor<Vec<u8>>,
+        limit: u64,
+    }
+
+    impl Read for Limited {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let left = self.limit.saturating_sub(self.inner.position());
+            if left == 0 {
+                return Err(io::Error::other("not there yet"));
+            }
+            let len = buf.len().min(left as usize);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    impl Seek for Limited {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn cursor_continues_after_a_failed_read() {
+        let mut file = MangoFile::new();
+        for i in 0..6 {
+            let name = if i % 2 == 0 { "test.jpg" } else { "test2.jpg" };
+            file.add_image_by_path(Path::new(name)).unwrap();
+        }
+
+        for &(format, encoding) in &[
+            (Serialization::Cbor, Encoding::Cbor),
+            (Serialization::Bson, Encoding::Bson),
+        ] {
+            let bytes = file.to_bytes(format).unwrap();
+            let third = PageRegion::find(&mut Cursor::new(&bytes), encoding, 3).unwrap();
+            let last = PageRegion::find(&mut Cursor::new(&bytes), encoding, 5).unwrap();
+
+            let mut reader = Limited {
+                inner: Cursor::new(bytes.clone()),
+                limit: third.start,
+            };
+            let mut cursor = PageCursor::find(&mut reader, encoding).unwrap();
+            assert!(cursor.seek_page(&mut reader, 5).is_err());
+            assert_eq!(cursor.index, 3);
+
+            reader.limit = bytes.len() as u64;
+            let region = cursor.seek_page(&mut reader, 5).unwrap();
+            assert_eq!((region.start, region.len), (last.start, last.len));
+
+            // images before the cursor are found as well
+            let region = cursor.seek_page(&mut reader, 3).unwrap();
+            assert_eq!((region.start, region.len), (third.start, third.len));
+            let error = cursor.seek_page(&mut reader, 6).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::OutOfRange);
+        }
+    }
+}
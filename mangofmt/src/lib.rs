@@ -17,6 +17,10 @@
 //! Both structs contain their own Metadata, and expose a bunch of methods which allow creation,
 //! modification, compression, encryption and much more.
 //!
+//! Errors from all parts of the crate can be converted into a single [Error](struct.Error.html),
+//! which tells you what [kind](enum.ErrorKind.html) of problem occurred and keeps the original
+//! error as its source.
+//!
 //! There are 2 important modules that are hidden from the outside: **compression** & **encryption**.
 //! This is done on purpose. They contain the implementation of optional compression & encryption logic,
 //! which can get accessed via the [MangoImage api](struct.MangoImage.html).
@@ -51,22 +55,51 @@ extern crate serde_bytes;
 extern crate serde_cbor;
 extern crate serde_json;
 extern crate sha2;
+#[cfg(feature = "async")]
+extern crate tokio;
 
+#[macro_use]
+extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+#[cfg(feature = "catalog")]
+pub mod catalog;
 mod compression;
 mod encryption;
+pub mod error;
 pub mod file;
 pub mod image;
+#[cfg(feature = "interchange")]
+pub mod interchange;
 mod json;
+mod language;
+mod layout;
+#[cfg(any(feature = "catalog", feature = "opds", feature = "server"))]
+mod library;
 pub mod meta;
+#[cfg(feature = "opds")]
+pub mod opds;
+pub mod progress;
+#[cfg(feature = "server")]
+pub mod server;
+mod storage;
+pub mod toc;
 
 #[doc(inline)]
 pub use compression::{CompressionError, CompressionType};
 #[doc(inline)]
 pub use encryption::{EncryptionError, EncryptionType};
 #[doc(inline)]
-pub use file::MangoFile;
+pub use error::{Error, ErrorKind};
+#[doc(inline)]
+pub use file::{MangoFile, Serialization};
+#[cfg(feature = "transcode")]
+#[doc(inline)]
+pub use image::ImportOptions;
 #[doc(inline)]
 pub use image::{ImageFile, MangoImage, Mime};
+#[doc(inline)]
+pub use progress::{Control, Progress, ProgressHandler};
@@ -0,0 +1,66 @@
+//! Helpers for a directory of .mango files, shared by the server, the OPDS feeds and the catalog.
+//!
+//! A volume is identified by its path relative to the directory, with `/` between the
+//! components. The urls of a volume are the routes of the server.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The media type of a .mango file.
+#[cfg(any(feature = "opds", feature = "server"))]
+pub const MANGO_MEDIA_TYPE: &str = "application/x-mango";
+
+/// Returns the ids of all .mango files below `root`, sorted by their path.
+///
+/// Hidden files and directories are skipped.
+pub fn find_volumes(root: &Path) -> io::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    collect_volumes(root, "", &mut ids)?;
+    Ok(ids)
+}
+
+/// `prefix` is the id of `dir` itself.
+fn collect_volumes(dir: &Path, prefix: &str, ids: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = match entry.file_name().into_string() {
+            Ok(ref name) if name.starts_with('.') => continue,
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let id = format!("{}{}", prefix, name);
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_volumes(&entry.path(), &format!("{}/", id), ids)?;
+        } else if file_type.is_file() && name.ends_with(".mango") {
+            ids.push(id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the url of a volume, the urls of its pages and its file are below it.
+#[cfg(any(feature = "opds", feature = "server"))]
+pub fn volume_url(id: &str) -> String {
+    format!("/volumes/{}", percent_encode(id))
+}
+
+/// Escapes everything besides unreserved characters, so the result is a single path segment.
+#[cfg(any(feature = "opds", feature = "server"))]
+pub fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &byte in s.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -2,27 +2,34 @@
 
 use compression::CompressionType;
 use encryption::EncryptionType;
+use error::Error;
 use hex::ToHex;
 use image::Mime;
 use json::base64option;
+#[doc(inline)]
+pub use language::Language;
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::{Digest, Sha256};
 use std::clone::Clone;
 use std::default::Default;
+use std::fmt;
 use std::fs::File;
+use std::io;
 use std::io::Read;
 use std::path::Path;
 
-fn get_checksum(file: &mut File) -> Option<String> {
+fn get_checksum(file: &mut File) -> io::Result<String> {
     let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(checksum(&data))
+}
 
-    if file.read_to_end(&mut data).is_err() {
-        return None;
-    }
-
+/// Returns the SHA-256 checksum of the data as hex string.
+pub(crate) fn checksum(data: &[u8]) -> String {
     let mut hasher = Sha256::default();
-    hasher.input(&data);
-    let checksum = hasher.result();
-    Some(checksum.to_hex())
+    hasher.input(data);
+    hasher.result().to_hex()
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -33,29 +40,15 @@ pub struct ImageFileMetadata {
 }
 
 impl ImageFileMetadata {
-    pub fn new(path: &Path) -> Option<ImageFileMetadata> {
-        match File::open(&path) {
-            Ok(mut file) => {
-                let checksum_opt = get_checksum(&mut file);
-                if checksum_opt.is_some() {
-                    let checksum = checksum_opt.unwrap();
-                    match Mime::get_from_path(path) {
-                        Ok(mime) => {
-                            let path = path.to_str().unwrap().to_string();
-                            Some(ImageFileMetadata {
-                                path,
-                                checksum,
-                                mime,
-                            })
-                        }
-                        Err(_e) => None,
-                    }
-                } else {
-                    None
-                }
-            }
-            Err(_e) => None,
-        }
+    pub fn new(path: &Path) -> Result<ImageFileMetadata, Error> {
+        let mime = Mime::get_from_path(path)?;
+        let checksum = get_checksum(&mut File::open(path)?)?;
+
+        Ok(ImageFileMetadata {
+            path: path.to_string_lossy().into_owned(),
+            checksum,
+            mime,
+        })
     }
 
     pub fn to_base64_metadata(&self) -> MangoImageMetadata {
@@ -63,6 +56,78 @@ impl ImageFileMetadata {
     }
 }
 
+/// What kind of page an image is.
+///
+/// Kinds this version doesn't know about are read as `Other`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PageKind {
+    FrontCover,
+    Story,
+    Credits,
+    Advertisement,
+    BackCover,
+    #[serde(other)]
+    Other,
+}
+
+/// A perceptual hash (dHash) of an image.
+///
+/// It is computed from a tiny grayscale version of the image, so it barely changes when the image
+/// gets re-encoded or resized. The more bits two hashes have in common, the more similar the
+/// images look.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PerceptualHash(pub u64);
+
+impl PerceptualHash {
+    /// Returns the number of bits which are different, 0 means the images look the same.
+    pub fn distance(self, other: PerceptualHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl fmt::Display for PerceptualHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+// stored as hex string, BSON can't store every u64
+impl Serialize for PerceptualHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PerceptualHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u64::from_str_radix(&hex, 16)
+            .map(PerceptualHash)
+            .map_err(|_| D::Error::custom(format!("invalid perceptual hash: {}", hex)))
+    }
+}
+
+/// What got changed compared to the source file of an image,
+/// by the `transcode` feature or by sanitizing it.
+///
+/// An image was re-encoded if its format differs from the original one, or if it was downscaled
+/// or converted to grayscale.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Transform {
+    /// The format of the source file.
+    pub original_mime: Mime,
+    /// The checksum of the source file.
+    pub original_checksum: String,
+    /// Metadata like EXIF data or ICC profiles got removed.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub metadata_stripped: bool,
+    /// The width and height of the source image, if it got downscaled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<(u32, u32)>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub grayscale: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MangoImageMetadata {
     pub compression: Option<CompressionType>,
@@ -72,6 +137,28 @@ pub struct MangoImageMetadata {
     pub filename: String,
     pub checksum: String,
     pub mime: Mime,
+    /// What kind of page this is, None if it is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_kind: Option<PageKind>,
+    /// The image is a double-page spread and should be shown on its own.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub double_page: bool,
+    /// A bookmark, e.g. the title of a chapter starting on this page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// An alternative text describing the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Gets computed when an image gets imported with the `phash` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<PerceptualHash>,
+    /// What got changed, None if the image is the source file as it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+}
+
+pub(crate) fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 impl MangoImageMetadata {
@@ -91,32 +178,239 @@ impl MangoImageMetadata {
             filename,
             checksum: data.checksum,
             mime: data.mime,
+            page_kind: None,
+            double_page: false,
+            label: None,
+            caption: None,
+            perceptual_hash: None,
+            transform: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub enum Language {
-    EN,
-    JP,
-    DE,
-    FR,
-    IT,
-    CN,
-    ES,
+/// The part somebody played in creating a comic.
+///
+/// Roles this version doesn't know about are read as `Other`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CreatorRole {
+    Writer,
+    Artist,
+    Penciller,
+    Inker,
+    Colorist,
+    Letterer,
+    CoverArtist,
+    Editor,
+    Translator,
+    #[serde(other)]
+    Other,
+}
+
+/// A person or group who worked on a comic.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Creator {
+    pub name: String,
+    pub role: CreatorRole,
 }
 
+impl Creator {
+    pub fn new<S: Into<String>>(name: S, role: CreatorRole) -> Self {
+        Self {
+            name: name.into(),
+            role,
+        }
+    }
+}
+
+/// The audience a comic is suitable for.
+///
+/// Ratings this version doesn't know about are read as `Unknown`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AgeRating {
+    AllAges,
+    Teen,
+    OlderTeen,
+    Mature,
+    Adult,
+    #[serde(other)]
+    Unknown,
+}
+
+/// The direction in which the pages should be read.
+///
+/// Directions this version doesn't know about are read as `Unknown`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReadingDirection {
+    /// Western comics.
+    LeftToRight,
+    /// Most manga.
+    RightToLeft,
+    /// Webtoons and other long strip formats.
+    TopToBottom,
+    #[serde(other)]
+    Unknown,
+}
+
+/// An identifier of the comic in some catalogue, like an ISBN.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Identifier {
+    /// The catalogue the value belongs to, in lower case, e.g. "isbn", "issn" or "asin".
+    pub scheme: String,
+    pub value: String,
+}
+
+impl Identifier {
+    pub fn new<S: Into<String>, V: Into<String>>(scheme: S, value: V) -> Self {
+        Self {
+            scheme: scheme.into().to_lowercase(),
+            value: value.into(),
+        }
+    }
+
+    /// Creates an ISBN identifier, dashes and spaces get removed.
+    pub fn isbn(value: &str) -> Self {
+        let value: String = value
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .collect();
+        Self::new("isbn", value)
+    }
+}
+
+/// The date a comic got published, as precise as it is known.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PublicationDate {
+    pub year: i16,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl PublicationDate {
+    pub fn new(year: i16, month: Option<u8>, day: Option<u8>) -> Self {
+        Self { year, month, day }
+    }
+}
+
+/// The metadata of a MangoFile.
+///
+/// All fields besides the original ones (title up to year) are left out when they are empty,
+/// and fields unknown to this version get ignored while reading,
+/// so files stay readable for older and newer versions of mangofmt.
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "StoredMetadata", into = "StoredMetadata")]
 pub struct MangoMetadata {
     pub title: Option<String>,
+    /// The main author, see `creators` for everyone else who worked on it.
     pub author: Option<String>,
     pub publisher: Option<String>,
     pub source: Option<String>,
     pub translation: Option<String>,
     pub language: Option<Language>,
     pub volume: Option<i16>,
-    pub chapter: Option<i16>,
+    /// The chapter number, can be fractional like 10.5 for extra chapters.
+    pub chapter: Option<f64>,
     pub year: Option<i16>,
+    pub series: Option<String>,
+    pub creators: Vec<Creator>,
+    pub genres: Vec<String>,
+    pub tags: Vec<String>,
+    pub summary: Option<String>,
+    pub age_rating: Option<AgeRating>,
+    pub reading_direction: Option<ReadingDirection>,
+    pub identifiers: Vec<Identifier>,
+    pub publication_date: Option<PublicationDate>,
+}
+
+/// How [MangoMetadata](struct.MangoMetadata.html) gets stored.
+///
+/// Older versions read `chapter` as i16 and fail on anything else, so it only holds whole
+/// chapter numbers. Fractional ones are truncated there and stored exactly in `chapter_number`,
+/// which older versions ignore.
+#[derive(Serialize, Deserialize)]
+struct StoredMetadata {
+    title: Option<String>,
+    author: Option<String>,
+    publisher: Option<String>,
+    source: Option<String>,
+    translation: Option<String>,
+    language: Option<Language>,
+    volume: Option<i16>,
+    #[serde(default, with = "legacy_chapter")]
+    chapter: Option<f64>,
+    year: Option<i16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chapter_number: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    creators: Vec<Creator>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    genres: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    age_rating: Option<AgeRating>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reading_direction: Option<ReadingDirection>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    identifiers: Vec<Identifier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publication_date: Option<PublicationDate>,
+}
+
+impl From<StoredMetadata> for MangoMetadata {
+    fn from(stored: StoredMetadata) -> Self {
+        Self {
+            title: stored.title,
+            author: stored.author,
+            publisher: stored.publisher,
+            source: stored.source,
+            translation: stored.translation,
+            language: stored.language,
+            volume: stored.volume,
+            chapter: stored.chapter_number.or(stored.chapter),
+            year: stored.year,
+            series: stored.series,
+            creators: stored.creators,
+            genres: stored.genres,
+            tags: stored.tags,
+            summary: stored.summary,
+            age_rating: stored.age_rating,
+            reading_direction: stored.reading_direction,
+            identifiers: stored.identifiers,
+            publication_date: stored.publication_date,
+        }
+    }
+}
+
+impl From<MangoMetadata> for StoredMetadata {
+    fn from(meta: MangoMetadata) -> Self {
+        Self {
+            title: meta.title,
+            author: meta.author,
+            publisher: meta.publisher,
+            source: meta.source,
+            translation: meta.translation,
+            language: meta.language,
+            volume: meta.volume,
+            chapter: meta.chapter,
+            year: meta.year,
+            chapter_number: meta
+                .chapter
+                .filter(|&c| legacy_chapter::truncate(c) != Some(c)),
+            series: meta.series,
+            creators: meta.creators,
+            genres: meta.genres,
+            tags: meta.tags,
+            summary: meta.summary,
+            age_rating: meta.age_rating,
+            reading_direction: meta.reading_direction,
+            identifiers: meta.identifiers,
+            publication_date: meta.publication_date,
+        }
+    }
 }
 
 impl MangoMetadata {
@@ -131,8 +425,30 @@ impl MangoMetadata {
             volume: None,
             chapter: None,
             year: None,
+            series: None,
+            creators: Vec::new(),
+            genres: Vec::new(),
+            tags: Vec::new(),
+            summary: None,
+            age_rating: None,
+            reading_direction: None,
+            identifiers: Vec::new(),
+            publication_date: None,
         }
     }
+
+    /// Returns all creators with the given role.
+    pub fn creators_by_role(&self, role: CreatorRole) -> Vec<&Creator> {
+        self.creators.iter().filter(|c| c.role == role).collect()
+    }
+
+    /// Returns the value of the first identifier with the given scheme.
+    pub fn identifier(&self, scheme: &str) -> Option<&str> {
+        self.identifiers
+            .iter()
+            .find(|i| i.scheme.eq_ignore_ascii_case(scheme))
+            .map(|i| i.value.as_str())
+    }
 }
 
 impl Default for MangoMetadata {
@@ -140,3 +456,184 @@ impl Default for MangoMetadata {
         Self::new()
     }
 }
+
+/// The chapter in the format older versions read, a whole number which fits into an i16.
+mod legacy_chapter {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Returns the chapter number truncated to a whole number, None if it doesn't fit.
+    pub fn truncate(chapter: f64) -> Option<f64> {
+        let whole = chapter.trunc();
+        if whole >= f64::from(i16::MIN) && whole <= f64::from(i16::MAX) {
+            Some(whole)
+        } else {
+            None
+        }
+    }
+
+    pub fn serialize<S>(chapter: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match chapter.and_then(truncate) {
+            Some(whole) => serializer.serialize_some(&(whole as i16)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Files of earlier 0.5 versions may contain fractional numbers here as well.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <Option<f64>>::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadingDirection;
+    use super::{Creator, CreatorRole, MangoImageMetadata, MangoMetadata, PageKind};
+    use bson;
+    use serde_cbor;
+    use serde_json;
+
+    #[test]
+    fn fractional_chapter() {
+        let mut meta = MangoMetadata::new();
+        meta.chapter = Some(10.5);
+
+        let bytes = serde_cbor::to_vec(&meta).unwrap();
+        let read: MangoMetadata = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(read.chapter, Some(10.5));
+    }
+
+    #[test]
+    fn whole_chapter_stays_integer() {
+        let mut meta = MangoMetadata::new();
+        meta.chapter = Some(10.0);
+
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json["chapter"], serde_json::json!(10));
+        assert!(json.get("chapter_number").is_none());
+    }
+
+    #[test]
+    fn old_readers_get_an_integer_chapter() {
+        /// The metadata as mangofmt 0.4 reads it.
+        #[derive(Deserialize)]
+        struct OldMetadata {
+            title: Option<String>,
+            #[allow(dead_code)]
+            author: Option<String>,
+            #[allow(dead_code)]
+            publisher: Option<String>,
+            #[allow(dead_code)]
+            source: Option<String>,
+            #[allow(dead_code)]
+            translation: Option<String>,
+            #[allow(dead_code)]
+            language: Option<String>,
+            volume: Option<i16>,
+            chapter: Option<i16>,
+            #[allow(dead_code)]
+            year: Option<i16>,
+        }
+
+        let mut meta = MangoMetadata::new();
+        meta.title = Some("extra".to_string());
+        meta.volume = Some(2);
+        meta.chapter = Some(10.5);
+
+        let cbor = serde_cbor::to_vec(&meta).unwrap();
+        let json = serde_json::to_vec(&meta).unwrap();
+        let bson = bson::to_bson(&meta).unwrap();
+
+        let old: Vec<OldMetadata> = vec![
+            serde_cbor::from_slice(&cbor).unwrap(),
+            serde_json::from_slice(&json).unwrap(),
+            bson::from_bson(bson.clone()).unwrap(),
+        ];
+        for old in old {
+            assert_eq!(old.title.as_deref(), Some("extra"));
+            assert_eq!(old.volume, Some(2));
+            assert_eq!(old.chapter, Some(10));
+        }
+
+        let new: MangoMetadata = serde_cbor::from_slice(&cbor).unwrap();
+        assert_eq!(new.chapter, Some(10.5));
+        let new: MangoMetadata = bson::from_bson(bson).unwrap();
+        assert_eq!(new.chapter, Some(10.5));
+
+        // too large for the old format, they see no chapter at all
+        meta.chapter = Some(40000.0);
+        let old: OldMetadata = serde_json::from_slice(&serde_json::to_vec(&meta).unwrap()).unwrap();
+        assert_eq!(old.chapter, None);
+        let new: MangoMetadata =
+            serde_json::from_slice(&serde_json::to_vec(&meta).unwrap()).unwrap();
+        assert_eq!(new.chapter, Some(40000.0));
+    }
+
+    #[test]
+    fn read_fractional_chapter_of_earlier_versions() {
+        let json = r#"{"title":null,"author":null,"publisher":null,"source":null,
+            "translation":null,"language":null,"volume":null,"chapter":7.5,"year":null}"#;
+        let meta: MangoMetadata = serde_json::from_str(json).unwrap();
+        assert_eq!(meta.chapter, Some(7.5));
+    }
+
+    #[test]
+    fn read_old_metadata() {
+        let json = r#"{"title":"test","author":null,"publisher":null,"source":null,
+            "translation":null,"language":null,"volume":1,"chapter":3,"year":2018}"#;
+        let meta: MangoMetadata = serde_json::from_str(json).unwrap();
+
+        assert_eq!(meta.chapter, Some(3.0));
+        assert!(meta.creators.is_empty());
+        assert!(meta.series.is_none());
+    }
+
+    #[test]
+    fn ignore_unknown_values() {
+        let json = r#"{"title":null,"author":null,"publisher":null,"source":null,
+            "translation":null,"language":null,"volume":null,"chapter":null,"year":null,
+            "from_the_future":true,"reading_direction":"Spiral",
+            "creators":[{"name":"someone","role":"Cook"}]}"#;
+        let meta: MangoMetadata = serde_json::from_str(json).unwrap();
+
+        assert_eq!(meta.reading_direction, Some(ReadingDirection::Unknown));
+        assert_eq!(
+            meta.creators,
+            vec![Creator::new("someone", CreatorRole::Other)]
+        );
+    }
+
+    #[test]
+    fn read_old_image_metadata() {
+        let json = r#"{"compression":null,"encryption":null,"iv":null,
+            "filename":"test.jpg","checksum":"abc","mime":"JPEG"}"#;
+        let meta: MangoImageMetadata = serde_json::from_str(json).unwrap();
+
+        assert!(meta.page_kind.is_none());
+        assert!(!meta.double_page);
+        assert!(meta.label.is_none());
+        assert!(meta.caption.is_none());
+    }
+
+    #[test]
+    fn page_fields() {
+        let json = r#"{"compression":null,"encryption":null,"iv":null,
+            "filename":"test.jpg","checksum":"abc","mime":"JPEG",
+            "page_kind":"FrontCover","double_page":true,"label":"Chapter 1"}"#;
+        let meta: MangoImageMetadata = serde_json::from_str(json).unwrap();
+
+        assert_eq!(meta.page_kind, Some(PageKind::FrontCover));
+        assert!(meta.double_page);
+        assert_eq!(meta.label, Some(String::from("Chapter 1")));
+
+        let bytes = serde_cbor::to_vec(&meta).unwrap();
+        let read: MangoImageMetadata = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(read.page_kind, Some(PageKind::FrontCover));
+        assert!(read.double_page);
+    }
+}
@@ -0,0 +1,293 @@
+//! Writes feeds as OPDS 1.2 catalogs, which are Atom documents.
+
+extern crate xmltree;
+
+use self::xmltree::{Element, EmitterConfig, Namespace, XMLNode};
+use super::{feed_url, timestamp, Feed, FeedKind, NavigationEntry, Volume};
+use error::{Error, ErrorKind};
+use library::{percent_encode, volume_url, MANGO_MEDIA_TYPE};
+
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+const OPDS_NAMESPACE: &str = "http://opds-spec.org/2010/catalog";
+const PSE_NAMESPACE: &str = "http://vaemendis.net/opds-pse/ns";
+const DC_NAMESPACE: &str = "http://purl.org/dc/terms/";
+
+const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+pub fn write(feed: &Feed, prefix: &str) -> Result<String, Error> {
+    let mut root = Element::new("feed");
+    root.namespace = Some(ATOM_NAMESPACE.to_string());
+    let mut namespace = Namespace::empty();
+    namespace.put("", ATOM_NAMESPACE);
+    namespace.put("opds", OPDS_NAMESPACE);
+    namespace.put("pse", PSE_NAMESPACE);
+    namespace.put("dc", DC_NAMESPACE);
+    root.namespaces = Some(namespace);
+
+    push_text(&mut root, "id", &format!("urn:mango:feed:{}", feed.path));
+    push_text(&mut root, "title", &feed.title);
+    push_text(&mut root, "updated", &timestamp(feed.updated));
+    let mut author = Element::new("author");
+    push_text(&mut author, "name", "mango");
+    push_element(&mut root, author);
+
+    let own_type = feed_type(feed.kind);
+    push_element(
+        &mut root,
+        link("self", &feed_url(prefix, &feed.path), own_type),
+    );
+    push_element(
+        &mut root,
+        link("start", &feed_url(prefix, ""), NAVIGATION_TYPE),
+    );
+
+    for entry in &feed.navigation {
+        push_element(&mut root, navigation_entry(entry, prefix));
+    }
+    for volume in &feed.publications {
+        push_element(&mut root, volume_entry(volume));
+    }
+
+    let mut xml = Vec::new();
+    let config = EmitterConfig::new().perform_indent(true);
+    root.write_with_config(&mut xml, config)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "failed to write xml", e))?;
+
+    String::from_utf8(xml)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "failed to write xml", e))
+}
+
+fn navigation_entry(entry: &NavigationEntry, prefix: &str) -> Element {
+    let mut element = Element::new("entry");
+    push_text(&mut element, "title", &entry.title);
+    push_text(
+        &mut element,
+        "id",
+        &format!("urn:mango:feed:{}", entry.path),
+    );
+    push_text(&mut element, "updated", &timestamp(entry.updated));
+
+    let what = match entry.kind {
+        FeedKind::Navigation => "entries",
+        FeedKind::Acquisition => "volumes",
+    };
+    let mut content = text_element("content", &format!("{} {}", entry.count, what));
+    content
+        .attributes
+        .insert("type".to_string(), "text".to_string());
+    push_element(&mut element, content);
+
+    let url = feed_url(prefix, &entry.path);
+    push_element(
+        &mut element,
+        link("subsection", &url, feed_type(entry.kind)),
+    );
+    element
+}
+
+fn volume_entry(volume: &Volume) -> Element {
+    let meta = &volume.meta;
+    let mut element = Element::new("entry");
+    push_text(&mut element, "title", &volume.title());
+    push_text(
+        &mut element,
+        "id",
+        &format!("urn:mango:volume:{}", percent_encode(&volume.id)),
+    );
+    push_text(&mut element, "updated", &timestamp(volume.modified));
+
+    for name in volume.authors() {
+        let mut author = Element::new("author");
+        push_text(&mut author, "name", name);
+        push_element(&mut element, author);
+    }
+    if let Some(ref language) = meta.language {
+        push_element(&mut element, dc("language", language.as_str()));
+    }
+    if let Some(ref publisher) = meta.publisher {
+        push_element(&mut element, dc("publisher", publisher));
+    }
+    if let Some(year) = meta.year {
+        push_element(&mut element, dc("issued", &format!("{:04}", year)));
+    }
+    if let Some(ref summary) = meta.summary {
+        let mut summary = text_element("summary", summary);
+        summary
+            .attributes
+            .insert("type".to_string(), "text".to_string());
+        push_element(&mut element, summary);
+    }
+    for genre in meta.genres.iter().chain(&meta.tags) {
+        let mut category = Element::new("category");
+        category
+            .attributes
+            .insert("term".to_string(), genre.to_string());
+        category
+            .attributes
+            .insert("label".to_string(), genre.to_string());
+        push_element(&mut element, category);
+    }
+
+    let url = volume_url(&volume.id);
+    if let Some(mime) = volume.mime {
+        let cover = format!("{}/pages/0", url);
+        let thumbnail = format!("{}/thumbnail", cover);
+        push_element(
+            &mut element,
+            link("http://opds-spec.org/image", &cover, mime.content_type()),
+        );
+        push_element(
+            &mut element,
+            link(
+                "http://opds-spec.org/image/thumbnail",
+                &thumbnail,
+                "image/jpeg",
+            ),
+        );
+
+        let pages = format!("{}/pages/{{pageNumber}}", url);
+        let mut stream = link(
+            "http://vaemendis.net/opds-pse/stream",
+            &pages,
+            mime.content_type(),
+        );
+        stream
+            .attributes
+            .insert("pse:count".to_string(), volume.pages.to_string());
+        push_element(&mut element, stream);
+    }
+    push_element(
+        &mut element,
+        link(
+            "http://opds-spec.org/acquisition",
+            &format!("{}/file", url),
+            MANGO_MEDIA_TYPE,
+        ),
+    );
+
+    element
+}
+
+fn feed_type(kind: FeedKind) -> &'static str {
+    match kind {
+        FeedKind::Navigation => NAVIGATION_TYPE,
+        FeedKind::Acquisition => ACQUISITION_TYPE,
+    }
+}
+
+fn link(rel: &str, href: &str, media_type: &str) -> Element {
+    let mut element = Element::new("link");
+    for &(name, value) in &[("rel", rel), ("href", href), ("type", media_type)] {
+        element
+            .attributes
+            .insert(name.to_string(), value.to_string());
+    }
+    element
+}
+
+fn dc(name: &str, value: &str) -> Element {
+    let mut element = text_element(name, value);
+    element.prefix = Some("dc".to_string());
+    element.namespace = Some(DC_NAMESPACE.to_string());
+    element
+}
+
+fn text_element(name: &str, value: &str) -> Element {
+    let mut element = Element::new(name);
+    element.children.push(XMLNode::Text(value.to_string()));
+    element
+}
+
+fn push_text(parent: &mut Element, name: &str, value: &str) {
+    push_element(parent, text_element(name, value));
+}
+
+fn push_element(parent: &mut Element, element: Element) {
+    parent.children.push(XMLNode::Element(element));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::catalog;
+    use super::xmltree::Element;
+
+    fn links<'a>(entry: &'a Element, rel: &str) -> Vec<&'a Element> {
+        entry
+            .children
+            .iter()
+            .filter_map(|n| n.as_element())
+            .filter(|e| {
+                e.name == "link" && e.attributes.get("rel").map(String::as_str) == Some(rel)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn acquisition_feed() {
+        let catalog = catalog();
+        let xml = catalog
+            .feed(&["series", "One Piece"])
+            .unwrap()
+            .to_atom("/opds")
+            .unwrap();
+        let root = Element::parse(xml.as_bytes()).unwrap();
+
+        assert_eq!(root.name, "feed");
+        assert_eq!(root.namespace.as_deref(), Some(super::ATOM_NAMESPACE));
+        assert_eq!(
+            root.get_child("updated").unwrap().get_text().unwrap(),
+            "2023-11-14T22:13:22Z"
+        );
+        let own = links(&root, "self")[0];
+        assert_eq!(own.attributes["href"], "/opds/series/One%20Piece");
+        assert_eq!(own.attributes["type"], super::ACQUISITION_TYPE);
+
+        let entries: Vec<_> = root
+            .children
+            .iter()
+            .filter_map(|n| n.as_element())
+            .filter(|e| e.name == "entry")
+            .collect();
+        assert_eq!(entries.len(), 2);
+        let entry = entries[0];
+        assert_eq!(
+            entry.get_child("title").unwrap().get_text().unwrap(),
+            "One Piece 1"
+        );
+
+        let stream = links(entry, "http://vaemendis.net/opds-pse/stream")[0];
+        assert_eq!(
+            stream.attributes["href"],
+            "/volumes/a%2Fone.mango/pages/{pageNumber}"
+        );
+        assert_eq!(stream.attributes["type"], "image/png");
+        assert!(xml.contains("pse:count=\"3\""));
+        let acquisition = links(entry, "http://opds-spec.org/acquisition")[0];
+        assert_eq!(
+            acquisition.attributes["href"],
+            "/volumes/a%2Fone.mango/file"
+        );
+        assert!(xml.contains("<dc:language>ja</dc:language>"));
+    }
+
+    #[test]
+    fn navigation_feed() {
+        let catalog = catalog();
+        let xml = catalog.feed(&[]).unwrap().to_atom("/opds").unwrap();
+        let root = Element::parse(xml.as_bytes()).unwrap();
+
+        let entries: Vec<_> = root
+            .children
+            .iter()
+            .filter_map(|n| n.as_element())
+            .filter(|e| e.name == "entry")
+            .collect();
+        assert_eq!(entries.len(), 4);
+        let all = links(entries[0], "subsection")[0];
+        assert_eq!(all.attributes["href"], "/opds/all");
+        assert_eq!(all.attributes["type"], super::ACQUISITION_TYPE);
+        let series = links(entries[1], "subsection")[0];
+        assert_eq!(series.attributes["type"], super::NAVIGATION_TYPE);
+    }
+}
@@ -0,0 +1,259 @@
+//! Writes feeds as OPDS 2.0 catalogs, which are JSON documents.
+
+use super::{feed_url, timestamp, Feed, FeedKind, Volume};
+use error::{Error, ErrorKind};
+use library::{percent_encode, volume_url, MANGO_MEDIA_TYPE};
+use serde_json;
+
+const FEED_TYPE: &str = "application/opds+json";
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    metadata: FeedMetadata,
+    links: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    navigation: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    publications: Vec<Publication<'a>>,
+}
+
+#[derive(Serialize)]
+struct FeedMetadata {
+    title: String,
+    modified: String,
+    #[serde(rename = "numberOfItems")]
+    number_of_items: usize,
+}
+
+#[derive(Serialize)]
+struct Link {
+    href: String,
+    #[serde(rename = "type")]
+    media_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rel: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    templated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<LinkProperties>,
+}
+
+#[derive(Serialize)]
+struct LinkProperties {
+    #[serde(rename = "numberOfItems")]
+    number_of_items: usize,
+}
+
+#[derive(Serialize)]
+struct Publication<'a> {
+    metadata: PublicationMetadata<'a>,
+    links: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<Link>,
+}
+
+#[derive(Serialize)]
+struct PublicationMetadata<'a> {
+    #[serde(rename = "@type")]
+    schema_type: &'static str,
+    identifier: String,
+    title: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    author: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    publisher: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    modified: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    subject: Vec<&'a str>,
+    #[serde(rename = "belongsTo", skip_serializing_if = "Option::is_none")]
+    belongs_to: Option<BelongsTo<'a>>,
+    #[serde(rename = "numberOfPages")]
+    number_of_pages: usize,
+}
+
+#[derive(Serialize)]
+struct BelongsTo<'a> {
+    series: Vec<Series<'a>>,
+}
+
+#[derive(Serialize)]
+struct Series<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<i16>,
+}
+
+impl Link {
+    fn new(href: String, media_type: &'static str, rel: Option<&'static str>) -> Self {
+        Link {
+            href,
+            media_type,
+            rel,
+            title: None,
+            templated: None,
+            properties: None,
+        }
+    }
+}
+
+pub fn write(feed: &Feed, prefix: &str) -> Result<String, Error> {
+    let navigation = feed
+        .navigation
+        .iter()
+        .map(|entry| {
+            let mut link = Link::new(feed_url(prefix, &entry.path), FEED_TYPE, None);
+            link.title = Some(entry.title.clone());
+            link.properties = Some(LinkProperties {
+                number_of_items: entry.count,
+            });
+            link
+        })
+        .collect();
+
+    let json = JsonFeed {
+        metadata: FeedMetadata {
+            title: feed.title.clone(),
+            modified: timestamp(feed.updated),
+            number_of_items: match feed.kind {
+                FeedKind::Navigation => feed.navigation.len(),
+                FeedKind::Acquisition => feed.publications.len(),
+            },
+        },
+        links: vec![
+            Link::new(feed_url(prefix, &feed.path), FEED_TYPE, Some("self")),
+            Link::new(feed_url(prefix, ""), FEED_TYPE, Some("start")),
+        ],
+        navigation,
+        publications: feed.publications.iter().map(|v| publication(v)).collect(),
+    };
+
+    serde_json::to_string(&json)
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode to JSON", e))
+}
+
+fn publication(volume: &Volume) -> Publication<'_> {
+    let meta = &volume.meta;
+    let url = volume_url(&volume.id);
+
+    let mut links = vec![Link::new(
+        format!("{}/file", url),
+        MANGO_MEDIA_TYPE,
+        Some("http://opds-spec.org/acquisition"),
+    )];
+    let mut images = Vec::new();
+
+    if let Some(mime) = volume.mime {
+        let mut stream = Link::new(
+            format!("{}/pages/{{pageNumber}}", url),
+            mime.content_type(),
+            Some("http://vaemendis.net/opds-pse/stream"),
+        );
+        stream.templated = Some(true);
+        stream.properties = Some(LinkProperties {
+            number_of_items: volume.pages,
+        });
+        links.push(stream);
+
+        images.push(Link::new(
+            format!("{}/pages/0", url),
+            mime.content_type(),
+            None,
+        ));
+        images.push(Link::new(
+            format!("{}/pages/0/thumbnail", url),
+            "image/jpeg",
+            None,
+        ));
+    }
+
+    let identifier = match meta.identifier("isbn") {
+        Some(isbn) => format!("urn:isbn:{}", isbn),
+        None => format!("urn:mango:volume:{}", percent_encode(&volume.id)),
+    };
+
+    Publication {
+        metadata: PublicationMetadata {
+            schema_type: "http://schema.org/Book",
+            identifier,
+            title: volume.title(),
+            author: volume.authors(),
+            language: meta.language.as_ref().map(|l| l.as_str()),
+            publisher: meta.publisher.as_deref(),
+            published: meta.year.map(|year| format!("{:04}", year)),
+            modified: timestamp(volume.modified),
+            description: meta.summary.as_deref(),
+            subject: meta
+                .genres
+                .iter()
+                .chain(&meta.tags)
+                .map(String::as_str)
+                .collect(),
+            belongs_to: meta.series.as_ref().map(|name| BelongsTo {
+                series: vec![Series {
+                    name,
+                    position: meta.volume,
+                }],
+            }),
+            number_of_pages: volume.pages,
+        },
+        links,
+        images,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::catalog;
+    use serde_json::{self, Value};
+
+    #[test]
+    fn feeds() {
+        let catalog = catalog();
+
+        let json = catalog
+            .feed(&["series"])
+            .unwrap()
+            .to_json("/opds2")
+            .unwrap();
+        let feed: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(feed["metadata"]["numberOfItems"], 1);
+        assert_eq!(feed["links"][0]["href"], "/opds2/series");
+        assert_eq!(feed["navigation"][0]["href"], "/opds2/series/One%20Piece");
+        assert_eq!(feed["navigation"][0]["properties"]["numberOfItems"], 2);
+        assert!(feed.get("publications").is_none());
+
+        let json = catalog.feed(&["all"]).unwrap().to_json("/opds2").unwrap();
+        let feed: Value = serde_json::from_str(&json).unwrap();
+        let publication = &feed["publications"][0];
+        assert_eq!(publication["metadata"]["title"], "One Piece 1");
+        assert_eq!(publication["metadata"]["author"][1], "Someone");
+        assert_eq!(
+            publication["metadata"]["belongsTo"]["series"][0]["position"],
+            1
+        );
+        assert_eq!(publication["metadata"]["numberOfPages"], 3);
+        assert_eq!(
+            publication["links"][0]["href"],
+            "/volumes/a%2Fone.mango/file"
+        );
+        assert_eq!(
+            publication["links"][0]["rel"],
+            "http://opds-spec.org/acquisition"
+        );
+        assert_eq!(
+            publication["links"][1]["href"],
+            "/volumes/a%2Fone.mango/pages/{pageNumber}"
+        );
+        assert_eq!(publication["links"][1]["templated"], true);
+        assert_eq!(publication["images"][1]["type"], "image/jpeg");
+        assert!(feed.get("navigation").is_none());
+    }
+}
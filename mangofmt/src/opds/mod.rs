@@ -0,0 +1,571 @@
+//! Generates [OPDS](https://opds.io) catalogs of a directory of .mango files, so e-reader apps
+//! like KOReader, Panels and Chunky can browse it.
+//!
+//! **Feature:** opds
+//!
+//! A [Catalog](struct.Catalog.html) reads the metadata of every volume once, without reading
+//! the images besides the first one. Its feeds can be written as OPDS 1.2 (Atom) or as
+//! OPDS 2.0 (JSON). They are found under these paths:
+//!
+//! * the empty path is the start feed, it links to the other ones
+//! * `all` contains all volumes
+//! * `series`, `authors` and `languages` list the series, authors and languages
+//! * `series/{name}`, `authors/{name}` and `languages/{tag}` contain their volumes
+//!
+//! Every volume has an acquisition link to its file, links to its cover and an
+//! [OPDS-PSE](https://github.com/anansi-project/opds-pse) stream link, so readers can show its
+//! pages one by one without knowing the mango format. These links point to the routes of the
+//! [server](../server/index.html), the feeds themselves are linked below the prefix which is
+//! passed when they are written. The server answers them under `/opds` and `/opds2`.
+
+mod atom;
+mod json;
+
+use error::Error;
+use file::MangoFile;
+use image::Mime;
+use library::{find_volumes, percent_encode};
+use meta::{CreatorRole, MangoMetadata};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A .mango file of a catalog.
+#[derive(Clone)]
+pub struct Volume {
+    /// The path relative to the directory of the catalog, with `/` between the components.
+    pub id: String,
+    pub meta: MangoMetadata,
+    pub pages: usize,
+    /// The format of the first page, the pages of a volume usually share it.
+    pub mime: Option<Mime>,
+    /// When the file was modified the last time.
+    pub modified: SystemTime,
+}
+
+impl Volume {
+    /// Reads the volume with the given id below `root`.
+    pub fn read(root: &Path, id: &str) -> Result<Volume, Error> {
+        let path = root.join(id);
+        let meta = MangoFile::read_metadata(&path)?;
+        let pages = MangoFile::read_page_count(&path)?;
+        let mime = if pages > 0 {
+            Some(MangoFile::read_image(&path, 0)?.get_meta_ref().mime)
+        } else {
+            None
+        };
+
+        Ok(Volume {
+            id: id.to_string(),
+            meta,
+            pages,
+            mime,
+            modified: fs::metadata(&path)?.modified()?,
+        })
+    }
+
+    /// Returns the title, or the file name if the volume has none.
+    pub fn title(&self) -> String {
+        match self.meta.title {
+            Some(ref title) => title.clone(),
+            None => {
+                let name = self.id.rsplit('/').next().unwrap_or(&self.id);
+                name.trim_end_matches(".mango").to_string()
+            }
+        }
+    }
+
+    /// Returns the main author followed by the writers and artists.
+    pub fn authors(&self) -> Vec<&str> {
+        let mut authors: Vec<&str> = self.meta.author.iter().map(String::as_str).collect();
+        for creator in &self.meta.creators {
+            let name = creator.name.as_str();
+            if (creator.role == CreatorRole::Writer || creator.role == CreatorRole::Artist)
+                && !authors.contains(&name)
+            {
+                authors.push(name);
+            }
+        }
+        authors
+    }
+
+    /// Orders volumes by series or title, then by volume and chapter number.
+    fn order(&self, other: &Volume) -> Ordering {
+        let name = |v: &Volume| {
+            v.meta
+                .series
+                .clone()
+                .unwrap_or_else(|| v.title())
+                .to_lowercase()
+        };
+
+        name(self)
+            .cmp(&name(other))
+            .then(self.meta.volume.cmp(&other.meta.volume))
+            .then(
+                self.meta
+                    .chapter
+                    .partial_cmp(&other.meta.chapter)
+                    .unwrap_or(Ordering::Equal),
+            )
+            .then_with(|| self.title().cmp(&other.title()))
+    }
+}
+
+/// The volumes of a directory.
+#[derive(Clone)]
+pub struct Catalog {
+    volumes: Vec<Volume>,
+}
+
+/// What a feed contains.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FeedKind {
+    /// Links to other feeds.
+    Navigation,
+    /// Volumes.
+    Acquisition,
+}
+
+/// A link from a navigation feed to another feed.
+#[derive(Clone, Debug)]
+pub struct NavigationEntry {
+    pub title: String,
+    /// The path of the linked feed, with percent encoded segments.
+    pub path: String,
+    /// What the linked feed contains.
+    pub kind: FeedKind,
+    /// How many entries the linked feed has.
+    pub count: usize,
+    pub updated: SystemTime,
+}
+
+/// A feed of a catalog, see the [module documentation](index.html) for which feeds there are.
+#[derive(Clone)]
+pub struct Feed<'a> {
+    pub kind: FeedKind,
+    /// The path of the feed, with percent encoded segments.
+    pub path: String,
+    pub title: String,
+    pub updated: SystemTime,
+    /// The entries of a navigation feed.
+    pub navigation: Vec<NavigationEntry>,
+    /// The volumes of an acquisition feed.
+    pub publications: Vec<&'a Volume>,
+}
+
+impl<'a> Feed<'a> {
+    /// Writes the feed as OPDS 1.2 Atom document, the feeds are linked below `prefix`.
+    pub fn to_atom(&self, prefix: &str) -> Result<String, Error> {
+        atom::write(self, prefix)
+    }
+
+    /// Writes the feed as OPDS 2.0 JSON document, the feeds are linked below `prefix`.
+    pub fn to_json(&self, prefix: &str) -> Result<String, Error> {
+        json::write(self, prefix)
+    }
+}
+
+/// The volumes of a navigation entry and how they are shown.
+struct Group<'a> {
+    key: String,
+    title: String,
+    volumes: Vec<&'a Volume>,
+}
+
+impl Catalog {
+    pub fn new(mut volumes: Vec<Volume>) -> Catalog {
+        volumes.sort_by(Volume::order);
+        Catalog { volumes }
+    }
+
+    /// Reads all .mango files below `root`, files which can't be read are skipped.
+    pub fn scan(root: &Path) -> Result<Catalog, Error> {
+        Catalog::new(Vec::new()).refresh(root)
+    }
+
+    /// Scans `root` again, only the files which are new or have been modified since are read.
+    ///
+    /// The other volumes are taken from this catalog, files which can't be read are skipped.
+    pub fn refresh(&self, root: &Path) -> Result<Catalog, Error> {
+        let known: HashMap<&str, &Volume> = self
+            .volumes
+            .iter()
+            .map(|volume| (volume.id.as_str(), volume))
+            .collect();
+
+        let volumes = find_volumes(root)?
+            .iter()
+            .filter_map(|id| {
+                let modified = fs::metadata(root.join(id)).and_then(|m| m.modified());
+                match (known.get(id.as_str()), modified) {
+                    (Some(volume), Ok(modified)) if volume.modified == modified => {
+                        return Some((*volume).clone());
+                    }
+                    _ => {}
+                }
+                match Volume::read(root, id) {
+                    Ok(volume) => Some(volume),
+                    Err(e) => {
+                        warn!("skipped {}: {}", id, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Catalog::new(volumes))
+    }
+
+    /// Returns the volumes, ordered by series or title and their number.
+    pub fn volumes(&self) -> &[Volume] {
+        &self.volumes
+    }
+
+    /// Returns the feed with the given path, e.g. `["series", "One Piece"]`.
+    ///
+    /// The segments are not percent encoded, None is returned if there is no such feed.
+    pub fn feed(&self, path: &[&str]) -> Option<Feed<'_>> {
+        match *path {
+            [] => Some(self.start()),
+            ["all"] => Some(self.acquisition(
+                "all".to_string(),
+                "All volumes".to_string(),
+                self.volumes.iter().collect(),
+            )),
+            [group] => {
+                let (title, groups) = self.groups(group)?;
+                Some(self.navigation(group, title, groups))
+            }
+            [group, key] => {
+                let (_, groups) = self.groups(group)?;
+                // the keys of the groups are merged regardless of their case
+                let key = key.to_lowercase();
+                let found = groups.into_iter().find(|g| g.key.to_lowercase() == key)?;
+                Some(self.acquisition(
+                    format!("{}/{}", group, percent_encode(&found.key)),
+                    found.title,
+                    found.volumes,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&self) -> Feed<'_> {
+        let mut navigation = vec![NavigationEntry {
+            title: "All volumes".to_string(),
+            path: "all".to_string(),
+            kind: FeedKind::Acquisition,
+            count: self.volumes.len(),
+            updated: self.updated(self.volumes.iter()),
+        }];
+
+        for group in &["series", "authors", "languages"] {
+            let (title, groups) = self.groups(group).unwrap_or_default();
+            navigation.push(NavigationEntry {
+                title: title.to_string(),
+                path: group.to_string(),
+                kind: FeedKind::Navigation,
+                count: groups.len(),
+                updated: self.updated(groups.iter().flat_map(|g| g.volumes.iter().cloned())),
+            });
+        }
+
+        Feed {
+            kind: FeedKind::Navigation,
+            path: String::new(),
+            title: "Mango library".to_string(),
+            updated: self.updated(self.volumes.iter()),
+            navigation,
+            publications: Vec::new(),
+        }
+    }
+
+    fn navigation(&self, group: &str, title: &str, groups: Vec<Group>) -> Feed<'_> {
+        let navigation = groups
+            .iter()
+            .map(|g| NavigationEntry {
+                title: g.title.clone(),
+                path: format!("{}/{}", group, percent_encode(&g.key)),
+                kind: FeedKind::Acquisition,
+                count: g.volumes.len(),
+                updated: self.updated(g.volumes.iter().cloned()),
+            })
+            .collect();
+
+        Feed {
+            kind: FeedKind::Navigation,
+            path: group.to_string(),
+            title: title.to_string(),
+            updated: self.updated(groups.iter().flat_map(|g| g.volumes.iter().cloned())),
+            navigation,
+            publications: Vec::new(),
+        }
+    }
+
+    fn acquisition<'a>(
+        &'a self,
+        path: String,
+        title: String,
+        volumes: Vec<&'a Volume>,
+    ) -> Feed<'a> {
+        Feed {
+            kind: FeedKind::Acquisition,
+            path,
+            title,
+            updated: self.updated(volumes.iter().cloned()),
+            navigation: Vec::new(),
+            publications: volumes,
+        }
+    }
+
+    /// Groups the volumes, returns None if there is no such grouping.
+    fn groups(&self, group: &str) -> Option<(&'static str, Vec<Group<'_>>)> {
+        let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+        let title = match group {
+            "series" => "Series",
+            "authors" => "Authors",
+            "languages" => "Languages",
+            _ => return None,
+        };
+
+        for volume in &self.volumes {
+            let keys: Vec<(String, String)> = match group {
+                "series" => volume
+                    .meta
+                    .series
+                    .iter()
+                    .map(|s| (s.clone(), s.clone()))
+                    .collect(),
+                "authors" => volume
+                    .authors()
+                    .iter()
+                    .map(|a| (a.to_string(), a.to_string()))
+                    .collect(),
+                _ => volume
+                    .meta
+                    .language
+                    .iter()
+                    .map(|l| (l.as_str().to_string(), l.display_name()))
+                    .collect(),
+            };
+
+            for (key, title) in keys {
+                groups
+                    .entry(key.to_lowercase())
+                    .or_insert_with(|| Group {
+                        key,
+                        title,
+                        volumes: Vec::new(),
+                    })
+                    .volumes
+                    .push(volume);
+            }
+        }
+
+        Some((title, groups.into_values().collect()))
+    }
+
+    /// Returns when the newest of the volumes was modified.
+    fn updated<'a, I: Iterator<Item = &'a Volume>>(&self, volumes: I) -> SystemTime {
+        volumes.map(|v| v.modified).max().unwrap_or(UNIX_EPOCH)
+    }
+}
+
+/// Returns the url of the feed with the given path.
+fn feed_url(prefix: &str, path: &str) -> String {
+    if path.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{}/{}", prefix, path)
+    }
+}
+
+/// Formats a time as RFC 3339 timestamp in UTC, e.g. `2023-11-14T22:13:20Z`.
+fn timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // converts the days since 1970 into a date of the proleptic gregorian calendar
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{timestamp, Catalog, FeedKind, Volume};
+    use file::MangoFile;
+    use image::{MangoImage, Mime};
+    use language::Language;
+    use meta::{Creator, CreatorRole, MangoMetadata};
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    pub fn volume(id: &str, series: Option<&str>, number: i16, language: &str) -> Volume {
+        let mut meta = MangoMetadata::new();
+        meta.title = Some(format!("{} {}", series.unwrap_or("Single"), number));
+        meta.series = series.map(str::to_string);
+        meta.volume = Some(number);
+        meta.author = Some("Oda".to_string());
+        meta.creators.push(Creator::new("Oda", CreatorRole::Artist));
+        meta.creators
+            .push(Creator::new("Someone", CreatorRole::Writer));
+        meta.language = Some(Language::parse(language).unwrap());
+
+        Volume {
+            id: id.to_string(),
+            meta,
+            pages: 3,
+            mime: Some(Mime::PNG),
+            modified: UNIX_EPOCH + Duration::from_secs(1_700_000_000 + number as u64),
+        }
+    }
+
+    pub fn catalog() -> Catalog {
+        Catalog::new(vec![
+            volume("b/two.mango", Some("One Piece"), 2, "ja"),
+            volume("a/one.mango", Some("One Piece"), 1, "ja"),
+            volume("single.mango", None, 1, "de"),
+        ])
+    }
+
+    #[test]
+    fn feeds() {
+        let catalog = catalog();
+
+        let start = catalog.feed(&[]).unwrap();
+        assert_eq!(start.kind, FeedKind::Navigation);
+        let counts: Vec<_> = start
+            .navigation
+            .iter()
+            .map(|n| (n.path.as_str(), n.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("all", 3), ("series", 1), ("authors", 2), ("languages", 2)]
+        );
+
+        let series = catalog.feed(&["series"]).unwrap();
+        assert_eq!(series.navigation[0].path, "series/One%20Piece");
+        assert_eq!(series.navigation[0].title, "One Piece");
+
+        let volumes = catalog.feed(&["series", "One Piece"]).unwrap();
+        assert_eq!(volumes.kind, FeedKind::Acquisition);
+        let ids: Vec<_> = volumes.publications.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(ids, vec!["a/one.mango", "b/two.mango"]);
+        assert_eq!(
+            volumes.updated,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_002)
+        );
+
+        let languages = catalog.feed(&["languages"]).unwrap();
+        let titles: Vec<_> = languages
+            .navigation
+            .iter()
+            .map(|n| n.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["German", "Japanese"]);
+        assert_eq!(
+            catalog
+                .feed(&["languages", "de"])
+                .unwrap()
+                .publications
+                .len(),
+            1
+        );
+        assert_eq!(
+            catalog
+                .feed(&["authors", "Someone"])
+                .unwrap()
+                .publications
+                .len(),
+            3
+        );
+
+        // keys are found regardless of their case
+        let volumes = catalog.feed(&["series", "one piece"]).unwrap();
+        assert_eq!(volumes.publications.len(), 2);
+        assert_eq!(volumes.path, "series/One%20Piece");
+
+        assert!(catalog.feed(&["series", "Naruto"]).is_none());
+        assert!(catalog.feed(&["genres"]).is_none());
+        assert!(catalog.feed(&["all", "x", "y"]).is_none());
+    }
+
+    #[test]
+    fn refresh() {
+        let root = Path::new("opds_refresh");
+        fs::create_dir_all(root).unwrap();
+        let mut file = MangoFile::new();
+        let image = MangoImage::from_bytes(fs::read("test.jpg").unwrap(), "test.jpg").unwrap();
+        file.add_image(image);
+        file.get_meta_mut().title = Some("first".to_string());
+        file.save(&root.join("one.mango")).unwrap();
+
+        let catalog = Catalog::scan(root).unwrap();
+        assert_eq!(catalog.volumes().len(), 1);
+        assert_eq!(catalog.volumes()[0].meta.title, Some("first".to_string()));
+
+        // unchanged files are taken from the catalog, removed ones are dropped
+        let mut cached = volume("one.mango", None, 1, "de");
+        cached.modified = catalog.volumes()[0].modified;
+        let catalog = Catalog::new(vec![cached, volume("gone.mango", None, 2, "de")]);
+        file.get_meta_mut().title = Some("second".to_string());
+        file.save(&root.join("two.mango")).unwrap();
+
+        let catalog = catalog.refresh(root).unwrap();
+        let mut titles: Vec<_> = catalog
+            .volumes()
+            .iter()
+            .map(|v| (v.id.as_str(), v.meta.title.clone().unwrap()))
+            .collect();
+        titles.sort();
+        assert_eq!(
+            titles,
+            vec![
+                ("one.mango", "Single 1".to_string()),
+                ("two.mango", "second".to_string())
+            ]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn format_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661);
+        assert_eq!(timestamp(leap_day), "2000-02-29T01:01:01Z");
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(timestamp(time), "2023-11-14T22:13:20Z");
+    }
+}
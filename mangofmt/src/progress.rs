@@ -0,0 +1,83 @@
+//! Progress reports and cancellation of long running operations.
+//!
+//! The `_with_progress` methods of [MangoFile](../struct.MangoFile.html) call a
+//! [ProgressHandler](trait.ProgressHandler.html) after every chunk of bytes or every page,
+//! any closure taking a `&Progress` and returning a [Control](enum.Control.html) is one.
+//! Opening and saving report once more before the file gets decoded or encoded, which can take
+//! a while for large files as well.
+//!
+//! Returning `Control::Cancel` stops the operation with an error of the kind
+//! [Cancelled](../enum.ErrorKind.html#variant.Cancelled).
+//! Nothing gets changed in that case, the file or the images stay as they were before.
+
+use error::{Error, ErrorKind};
+
+/// What a long running operation is busy with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    /// Reading a .mango file.
+    Read,
+    /// Writing a .mango file.
+    Write,
+    /// Decoding a .mango file which has been read.
+    Decode,
+    /// Encoding a .mango file before it gets written.
+    Encode,
+    /// Compressing the images of a file.
+    Compress,
+    /// Encrypting the images of a file.
+    Encrypt,
+    /// Reading image files and adding them to a file.
+    Import,
+}
+
+/// How far an operation got.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress {
+    pub stage: Stage,
+    /// Index of the page which was just processed,
+    /// `None` while a file gets read, decoded, encoded or written.
+    pub page: Option<usize>,
+    /// Number of pages the operation works on, 0 while they aren't known yet.
+    pub page_count: usize,
+    /// Bytes processed so far.
+    pub bytes: u64,
+    /// Bytes which get processed in total, if that is known up front.
+    pub total_bytes: Option<u64>,
+}
+
+/// Tells an operation whether to go on after it reported its progress.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Control {
+    Continue,
+    Cancel,
+}
+
+/// Receives the progress of an operation and decides whether it goes on.
+pub trait ProgressHandler {
+    fn report(&mut self, progress: &Progress) -> Control;
+}
+
+impl<F> ProgressHandler for F
+where
+    F: FnMut(&Progress) -> Control,
+{
+    fn report(&mut self, progress: &Progress) -> Control {
+        self(progress)
+    }
+}
+
+/// Reports the progress and turns a cancellation into an error.
+pub(crate) fn report(handler: &mut dyn ProgressHandler, progress: Progress) -> Result<(), Error> {
+    match handler.report(&progress) {
+        Control::Continue => Ok(()),
+        Control::Cancel => Err(cancelled(progress.stage)),
+    }
+}
+
+pub(crate) fn cancelled(stage: Stage) -> Error {
+    Error::new(
+        ErrorKind::Cancelled,
+        format!("{:?} was cancelled", stage).to_lowercase(),
+    )
+}
@@ -0,0 +1,460 @@
+//! Just enough HTTP/1.1 for serving pages: reading the head of a request and writing responses.
+//!
+//! Request bodies are never read, every route only answers GET and HEAD.
+
+use std::fs::File;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+/// The longest request line or header line which gets accepted.
+const MAX_LINE: u64 = 8 * 1024;
+
+/// The most headers a request may have.
+const MAX_HEADERS: usize = 100;
+
+/// The head of an HTTP request.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    /// The path of the target, still percent encoded.
+    pub path: String,
+    /// The query of the target without the `?`, still percent encoded.
+    pub query: Option<String>,
+    /// E.g. `HTTP/1.1`.
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Creates an HTTP/1.1 request without headers, `target` is the path with an optional query.
+    pub fn new(method: &str, target: &str) -> Self {
+        let (path, query) = match target.find('?') {
+            Some(i) => (&target[..i], Some(target[i + 1..].to_string())),
+            None => (target, None),
+        };
+
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns the value of the first header with this name, the case of the name doesn't matter.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the decoded value of the first query parameter with this name.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let query = self.query.as_ref()?;
+
+        query.split('&').find_map(|pair| {
+            let (key, value) = match pair.find('=') {
+                Some(i) => (&pair[..i], &pair[i + 1..]),
+                None => (pair, ""),
+            };
+            if percent_decode(&key.replace('+', " "))? == name {
+                percent_decode(&value.replace('+', " "))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Tells if the connection may be used for another request.
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.header("Connection").unwrap_or("");
+        let has_body = self.header("Transfer-Encoding").is_some()
+            || self
+                .header("Content-Length")
+                .is_some_and(|len| len.trim() != "0");
+
+        // the body isn't read, so it would be taken for the next request
+        if has_body {
+            return false;
+        }
+
+        if self.version == "HTTP/1.1" {
+            !connection.eq_ignore_ascii_case("close")
+        } else {
+            connection.eq_ignore_ascii_case("keep-alive")
+        }
+    }
+
+    /// Reads the head of the next request, returns None if the connection got closed before it.
+    ///
+    /// Malformed requests are reported as `InvalidData`.
+    pub(crate) fn read<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>> {
+        // empty lines in front of a request are allowed
+        let line = loop {
+            match read_line(reader)? {
+                None => return Ok(None),
+                Some(ref line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+
+        let mut parts = line.split(' ');
+        let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version))
+                if parts.next().is_none() && version.starts_with("HTTP/1.") =>
+            {
+                (method, target, version)
+            }
+            _ => return Err(invalid("malformed request line")),
+        };
+
+        let mut request = Request::new(method, target);
+        request.version = version.to_string();
+
+        loop {
+            let line = read_line(reader)?.ok_or_else(|| invalid("the request ended early"))?;
+            if line.is_empty() {
+                return Ok(Some(request));
+            }
+            if request.headers.len() == MAX_HEADERS {
+                return Err(invalid("too many headers"));
+            }
+
+            match line.find(':') {
+                Some(i) => request.headers.push((
+                    line[..i].trim().to_string(),
+                    line[i + 1..].trim().to_string(),
+                )),
+                None => return Err(invalid("malformed header")),
+            }
+        }
+    }
+}
+
+/// Reads a line without its line break.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    reader.take(MAX_LINE).read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(invalid("the line is too long or incomplete"));
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| invalid("the line is not UTF-8"))
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// An HTTP response.
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    /// The headers besides `Content-Length` and `Connection`, which get added when it is written.
+    pub headers: Vec<(String, String)>,
+    /// The body, unless it gets streamed from a file, see [with_file](#method.with_file).
+    pub body: Vec<u8>,
+    file: Option<FileBody>,
+}
+
+/// A part of a file which gets copied when the response is written.
+#[derive(Clone, Debug)]
+struct FileBody {
+    file: Arc<File>,
+    offset: u64,
+    len: u64,
+}
+
+impl Response {
+    /// Creates a response without headers and body.
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            file: None,
+        }
+    }
+
+    /// Creates a plain text response, e.g. for errors.
+    pub fn text(status: u16, text: &str) -> Self {
+        Response::new(status).with_body("text/plain; charset=utf-8", text.as_bytes().to_vec())
+    }
+
+    /// Adds a header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the body and its `Content-Type`.
+    pub fn with_body(self, content_type: &str, body: Vec<u8>) -> Self {
+        let mut response = self.with_header("Content-Type", content_type);
+        response.body = body;
+        response.file = None;
+        response
+    }
+
+    /// Sets the body to `len` bytes of a file starting at `offset`, and its `Content-Type`.
+    ///
+    /// The file isn't read until the response is written, so large files never end up in memory.
+    pub fn with_file(self, content_type: &str, file: File, offset: u64, len: u64) -> Self {
+        let mut response = self.with_header("Content-Type", content_type);
+        response.body = Vec::new();
+        response.file = Some(FileBody {
+            file: Arc::new(file),
+            offset,
+            len,
+        });
+        response
+    }
+
+    /// Returns the length of the body, whether it's in memory or in a file.
+    pub fn content_length(&self) -> u64 {
+        match self.file {
+            Some(ref body) => body.len,
+            None => self.body.len() as u64,
+        }
+    }
+
+    /// Writes the body, reading it from the file if it has been set with
+    /// [with_file](#method.with_file).
+    pub fn write_body<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let body = match self.file {
+            Some(ref body) => body,
+            None => return writer.write_all(&self.body),
+        };
+
+        let mut file = &*body.file;
+        file.seek(SeekFrom::Start(body.offset))?;
+        let copied = io::copy(&mut file.take(body.len), writer)?;
+        if copied < body.len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the file got shorter while it was sent",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the first header with this name, the case of the name doesn't matter.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Writes the response, the body is left out for HEAD requests.
+    pub(crate) fn write<W: Write>(
+        &self,
+        writer: &mut W,
+        head_only: bool,
+        keep_alive: bool,
+    ) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        // 304 responses describe the body they left out
+        if self.status != 304 {
+            head.push_str(&format!("Content-Length: {}\r\n", self.content_length()));
+        }
+        if !keep_alive {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str("\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        if !head_only {
+            self.write_body(writer)?;
+        }
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        _ => "",
+    }
+}
+
+/// The part of a body a `Range` header asks for.
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum ByteRange {
+    /// The whole body, also used for ranges which can't be parsed or aren't supported.
+    Full,
+    /// The first and the last byte.
+    Partial(u64, u64),
+    /// The range lies behind the end of the body.
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Parses the value of a `Range` header for a body of `len` bytes.
+    ///
+    /// Only single ranges are supported, multiple ranges get the whole body.
+    pub(crate) fn parse(header: &str, len: u64) -> ByteRange {
+        let spec = match header.trim().strip_prefix("bytes=") {
+            Some(spec) if !spec.contains(',') => spec.trim(),
+            _ => return ByteRange::Full,
+        };
+        let dash = match spec.find('-') {
+            Some(dash) => dash,
+            None => return ByteRange::Full,
+        };
+        let (first, last) = (&spec[..dash], &spec[dash + 1..]);
+
+        if first.is_empty() {
+            // the last n bytes
+            return match last.parse::<u64>() {
+                Ok(0) => ByteRange::Unsatisfiable,
+                Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+                Ok(n) => ByteRange::Partial(len.saturating_sub(n), len - 1),
+                Err(_) => ByteRange::Full,
+            };
+        }
+
+        let first = match first.parse::<u64>() {
+            Ok(first) => first,
+            Err(_) => return ByteRange::Full,
+        };
+        let last = if last.is_empty() {
+            None
+        } else {
+            match last.parse::<u64>() {
+                Ok(last) if last >= first => Some(last),
+                _ => return ByteRange::Full,
+            }
+        };
+
+        if first >= len {
+            return ByteRange::Unsatisfiable;
+        }
+        let last = last.map_or(len - 1, |last| last.min(len - 1));
+        ByteRange::Partial(first, last)
+    }
+}
+
+/// Decodes `%XX` escapes, returns None if they are malformed or the result is not UTF-8.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, ByteRange, Request};
+    use library::percent_encode;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_request() {
+        let mut bytes = Cursor::new(
+            &b"\r\nGET /volumes/a%20b?key=x%2By&a HTTP/1.1\r\nHost: x\r\nrange:  bytes=0-1 \r\n\r\n"[..],
+        );
+        let request = Request::read(&mut bytes).unwrap().unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/volumes/a%20b");
+        assert_eq!(request.query_param("key"), Some("x+y".to_string()));
+        assert_eq!(request.query_param("a"), Some(String::new()));
+        assert_eq!(request.query_param("b"), None);
+        assert_eq!(request.header("Range"), Some("bytes=0-1"));
+        assert!(request.keep_alive());
+        assert!(Request::read(&mut bytes).unwrap().is_none());
+
+        let mut bytes = Cursor::new(&b"GET / HTTP/1.0\r\n\r\n"[..]);
+        assert!(!Request::read(&mut bytes).unwrap().unwrap().keep_alive());
+
+        for bad in &[
+            &b"GET /\r\n\r\n"[..],
+            b"GET / HTTP/1.1\r\nHost\r\n\r\n",
+            b"GET / HTTP/1.1\r\n",
+        ] {
+            assert!(Request::read(&mut Cursor::new(*bad)).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_range() {
+        assert_eq!(ByteRange::parse("bytes=0-9", 100), ByteRange::Partial(0, 9));
+        assert_eq!(
+            ByteRange::parse("bytes=90-", 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=90-200", 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=-10", 100),
+            ByteRange::Partial(90, 99)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=-200", 100),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(
+            ByteRange::parse("bytes=100-", 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(ByteRange::parse("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6", 100), ByteRange::Full);
+        assert_eq!(ByteRange::parse("bytes=5-1", 100), ByteRange::Full);
+        assert_eq!(ByteRange::parse("items=0-1", 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn percent() {
+        let id = "shelf/vol 1+ü.mango";
+        assert_eq!(percent_encode(id), "shelf%2Fvol%201%2B%C3%BC.mango");
+        assert_eq!(percent_decode(&percent_encode(id)), Some(id.to_string()));
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%+1"), None);
+    }
+}
@@ -0,0 +1,796 @@
+//! Serves a directory of .mango files over HTTP, for web and mobile readers.
+//!
+//! **Feature:** server
+//!
+//! All routes answer GET and HEAD requests:
+//!
+//! * `/volumes` lists all .mango files below the directory, with their metadata and page count.
+//! * `/volumes/{id}` returns the metadata of a volume and the metadata of all of its pages.
+//! * `/volumes/{id}/pages/{index}` returns a page with the `Content-Type` of its format.
+//! * `/volumes/{id}/pages/{index}/thumbnail` returns a JPEG thumbnail of a page.
+//! * `/volumes/{id}/file` returns the .mango file itself.
+//! * `/opds/...` and `/opds2/...` return the OPDS 1.2 and 2.0 feeds of the
+//!   [opds module](../opds/index.html), if the *opds* feature is enabled as well.
+//!
+//! The id of a volume is its path relative to the directory, percent encoded as a single path
+//! segment, e.g. `shelf%2Fvolume1.mango`. The listings contain the urls, so clients don't have
+//! to build them.
+//!
+//! Pages and thumbnails have an `ETag` made from the checksum of the page, files one made from
+//! their size and modification time. All of them support `If-None-Match` as well as single byte
+//! ranges with `Range` and `If-Range`.
+//! Only the requested page is read from CBOR and BSON files.
+//!
+//! Encrypted pages need their key, either in an `X-Mango-Key` header or in a `key` query
+//! parameter. The key is only used for that request and never stored. Compressed pages get
+//! decompressed before they are sent.
+//!
+//! [Server::serve](struct.Server.html#method.serve) handles every connection in its own thread.
+//! [Server::handle](struct.Server.html#method.handle) can be used to put the routes behind
+//! another HTTP server instead.
+
+mod http;
+mod thumbnail;
+
+pub use self::http::{Request, Response};
+
+use self::http::{percent_decode, ByteRange};
+use error::{Error, ErrorKind};
+use file::MangoFile;
+use image::MangoImage;
+use library::{find_volumes, volume_url, MANGO_MEDIA_TYPE};
+use meta::{MangoImageMetadata, MangoMetadata};
+#[cfg(feature = "opds")]
+use opds::{Catalog, FeedKind};
+use serde_json;
+#[cfg(feature = "opds")]
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "opds")]
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The default biggest width or height of a thumbnail.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Connections which don't send a request for this long get closed.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Serves the .mango files of a directory and its subdirectories.
+#[derive(Clone, Debug)]
+pub struct Server {
+    root: PathBuf,
+    thumbnail_size: u32,
+    #[cfg(feature = "opds")]
+    catalog: CachedCatalog,
+}
+
+/// The catalog of the last OPDS request, shared by all connections.
+#[cfg(feature = "opds")]
+#[derive(Clone, Default)]
+struct CachedCatalog(Arc<Mutex<Option<Arc<Catalog>>>>);
+
+#[cfg(feature = "opds")]
+impl fmt::Debug for CachedCatalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CachedCatalog")
+    }
+}
+
+#[derive(Serialize)]
+struct VolumeList {
+    volumes: Vec<VolumeEntry>,
+}
+
+#[derive(Serialize)]
+struct VolumeEntry {
+    id: String,
+    url: String,
+    pages: usize,
+    meta: MangoMetadata,
+}
+
+#[derive(Serialize)]
+struct VolumeDetails {
+    id: String,
+    url: String,
+    meta: MangoMetadata,
+    pages: Vec<PageEntry>,
+}
+
+#[derive(Serialize)]
+struct PageEntry {
+    index: usize,
+    url: String,
+    thumbnail: String,
+    meta: MangoImageMetadata,
+}
+
+impl Server {
+    /// Creates a server for the .mango files below `root`.
+    pub fn new(root: &Path) -> Server {
+        Server {
+            root: root.to_path_buf(),
+            thumbnail_size: THUMBNAIL_SIZE,
+            #[cfg(feature = "opds")]
+            catalog: CachedCatalog::default(),
+        }
+    }
+
+    /// Sets the biggest width or height of the thumbnails, 256 pixels by default.
+    pub fn set_thumbnail_size(&mut self, size: u32) {
+        self.thumbnail_size = size;
+    }
+
+    /// Accepts connections until the process ends, each one gets handled in its own thread.
+    pub fn serve(&self, listener: TcpListener) {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let server = self.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = server.connection(stream) {
+                            debug!("closed a connection: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("couldn't accept a connection: {}", e),
+            }
+        }
+    }
+
+    /// Answers a single request.
+    pub fn handle(&self, request: &Request) -> Response {
+        if request.method != "GET" && request.method != "HEAD" {
+            return Response::text(405, "only GET and HEAD are supported")
+                .with_header("Allow", "GET, HEAD");
+        }
+
+        let segments: Option<Vec<String>> = match request.path.strip_prefix('/') {
+            Some(path) => path.split('/').map(percent_decode).collect(),
+            None => None,
+        };
+        let segments = match segments {
+            Some(segments) => segments,
+            None => return Response::text(400, "malformed path"),
+        };
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        match segments.as_slice() {
+            ["volumes"] | ["volumes", ""] => self.list(),
+            ["volumes", id] => match self.volume_path(id) {
+                Some(path) => self.volume(id, &path),
+                None => not_found(),
+            },
+            ["volumes", id, "file"] => match self.volume_path(id) {
+                Some(path) => download(request, &path),
+                None => not_found(),
+            },
+            ["volumes", id, "pages", index] => self.page(request, id, index, false),
+            ["volumes", id, "pages", index, "thumbnail"] => self.page(request, id, index, true),
+            #[cfg(feature = "opds")]
+            ["opds", path @ ..] => self.opds(path, false),
+            #[cfg(feature = "opds")]
+            ["opds2", path @ ..] => self.opds(path, true),
+            _ => not_found(),
+        }
+    }
+
+    /// Answers the requests of a connection until it gets closed.
+    fn connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+
+        loop {
+            let request = match Request::read(&mut reader) {
+                Ok(Some(request)) => request,
+                Ok(None) => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::InvalidData => {
+                    return Response::text(400, &e.to_string()).write(&mut writer, false, false);
+                }
+                Err(e) => return Err(e),
+            };
+
+            let keep_alive = request.keep_alive();
+            self.handle(&request)
+                .write(&mut writer, request.method == "HEAD", keep_alive)?;
+            if !keep_alive {
+                return Ok(());
+            }
+        }
+    }
+
+    fn list(&self) -> Response {
+        let ids = match find_volumes(&self.root) {
+            Ok(ids) => ids,
+            Err(e) => return error_response(&e.into()),
+        };
+
+        let volumes = ids
+            .into_iter()
+            .filter_map(|id| {
+                let path = self.root.join(&id);
+                let read = MangoFile::read_metadata(&path)
+                    .and_then(|meta| Ok((meta, MangoFile::read_page_count(&path)?)));
+
+                match read {
+                    Ok((meta, pages)) => Some(VolumeEntry {
+                        url: volume_url(&id),
+                        id,
+                        pages,
+                        meta,
+                    }),
+                    Err(e) => {
+                        warn!("skipped {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        json(&VolumeList { volumes })
+    }
+
+    fn volume(&self, id: &str, path: &Path) -> Response {
+        let file = match MangoFile::open(path) {
+            Ok(file) => file,
+            Err(e) => return error_response(&e),
+        };
+
+        let url = volume_url(id);
+        let pages = file
+            .images()
+            .enumerate()
+            .map(|(index, image)| PageEntry {
+                index,
+                url: format!("{}/pages/{}", url, index),
+                thumbnail: format!("{}/pages/{}/thumbnail", url, index),
+                meta: image.get_meta(),
+            })
+            .collect();
+
+        json(&VolumeDetails {
+            id: id.to_string(),
+            url,
+            meta: file.get_meta(),
+            pages,
+        })
+    }
+
+    fn page(&self, request: &Request, id: &str, index: &str, thumbnail: bool) -> Response {
+        let (path, index) = match (self.volume_path(id), index.parse::<usize>()) {
+            (Some(path), Ok(index)) => (path, index),
+            _ => return not_found(),
+        };
+
+        let image = match MangoFile::read_image(&path, index) {
+            Ok(image) => image,
+            Err(e) => return error_response(&e),
+        };
+        let encrypted = image.get_meta_ref().encryption.is_some();
+        let checksum = image.get_meta_ref().checksum.clone();
+
+        let key = request
+            .header("X-Mango-Key")
+            .map(str::to_string)
+            .or_else(|| request.query_param("key"));
+        let image = match plain(image, key) {
+            Ok(image) => image,
+            Err(response) => return response,
+        };
+
+        let response = if thumbnail {
+            match thumbnail::thumbnail(image.get_image_data_ref(), self.thumbnail_size) {
+                Ok(data) => {
+                    let etag = format!("\"{}-t{}\"", checksum, self.thumbnail_size);
+                    send(request, &etag, "image/jpeg", data)
+                }
+                Err(e) => return error_response(&e),
+            }
+        } else {
+            let etag = format!("\"{}\"", checksum);
+            let content_type = image.get_meta_ref().mime.content_type();
+            send(request, &etag, content_type, image.get_image_data())
+        };
+
+        // shared caches must not keep decrypted pages
+        if encrypted {
+            response.with_header("Cache-Control", "private")
+        } else {
+            response
+        }
+    }
+
+    /// Answers with an OPDS 1.2 feed or, if `json` is set, an OPDS 2.0 feed.
+    #[cfg(feature = "opds")]
+    fn opds(&self, path: &[&str], json: bool) -> Response {
+        let catalog = match self.catalog() {
+            Ok(catalog) => catalog,
+            Err(e) => return error_response(&e),
+        };
+        let path: Vec<&str> = path.iter().cloned().filter(|s| !s.is_empty()).collect();
+        let feed = match catalog.feed(&path) {
+            Some(feed) => feed,
+            None => return not_found(),
+        };
+
+        let (rendered, content_type) = if json {
+            (feed.to_json("/opds2"), "application/opds+json")
+        } else {
+            let content_type = match feed.kind {
+                FeedKind::Navigation => "application/atom+xml;profile=opds-catalog;kind=navigation",
+                FeedKind::Acquisition => {
+                    "application/atom+xml;profile=opds-catalog;kind=acquisition"
+                }
+            };
+            (feed.to_atom("/opds"), content_type)
+        };
+
+        match rendered {
+            Ok(body) => Response::new(200).with_body(content_type, body.into_bytes()),
+            Err(e) => error_response(&e),
+        }
+    }
+
+    /// Returns the catalog of the directory, only the files which are new or have been modified
+    /// since the last call get read.
+    #[cfg(feature = "opds")]
+    fn catalog(&self) -> Result<Arc<Catalog>, Error> {
+        let mut cached = self
+            .catalog
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let catalog = match *cached {
+            Some(ref catalog) => catalog.refresh(&self.root)?,
+            None => Catalog::scan(&self.root)?,
+        };
+        let catalog = Arc::new(catalog);
+        *cached = Some(catalog.clone());
+        Ok(catalog)
+    }
+
+    /// Returns the path of the volume, None if the id isn't a .mango file below the root.
+    fn volume_path(&self, id: &str) -> Option<PathBuf> {
+        if !id.ends_with(".mango") {
+            return None;
+        }
+
+        let mut path = self.root.clone();
+        for component in id.split('/') {
+            if component.is_empty()
+                || component == "."
+                || component == ".."
+                || component.contains('\\')
+                || component.contains(':')
+            {
+                return None;
+            }
+            path.push(component);
+        }
+
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decrypts and decompresses a page.
+fn plain(mut image: MangoImage, key: Option<String>) -> Result<MangoImage, Response> {
+    let encryption = image.get_meta_ref().encryption.clone();
+
+    if let Some(ref encryption) = encryption {
+        if !encryption.is_supported() {
+            return Err(Response::text(
+                501,
+                &format!("{} isn't supported by this server", encryption),
+            ));
+        }
+
+        let key =
+            key.ok_or_else(|| Response::text(403, "the page is encrypted, a key is needed"))?;
+        image = image.decrypt(key).map_err(|_| wrong_key())?;
+    }
+
+    if image.get_meta_ref().compression.is_some() {
+        image = image.uncompress().map_err(|e| match encryption {
+            Some(_) => wrong_key(),
+            None => error_response(&e.into()),
+        })?;
+    }
+
+    // a wrong key doesn't always make decrypting fail, but the data is garbage then
+    if encryption.is_some() {
+        image.verify().map_err(|_| wrong_key())?;
+    }
+
+    Ok(image)
+}
+
+/// Sends a whole .mango file, its `ETag` is made from its size and modification time.
+///
+/// The file gets streamed, only the requested range of it is read.
+fn download(request: &Request, path: &Path) -> Response {
+    let open = File::open(path).and_then(|file| {
+        let metadata = file.metadata()?;
+        Ok((file, metadata.len(), metadata.modified()?))
+    });
+    let (file, len, modified) = match open {
+        Ok(open) => open,
+        Err(e) => return error_response(&e.into()),
+    };
+
+    let seconds = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let etag = format!("\"{:x}-{:x}\"", len, seconds);
+    respond(request, &etag, len, |response, range| {
+        response.with_file(MANGO_MEDIA_TYPE, file, range.start, range.end - range.start)
+    })
+}
+
+/// Builds the response of a page or thumbnail, taking care of conditional and range requests.
+fn send(request: &Request, etag: &str, content_type: &str, body: Vec<u8>) -> Response {
+    let len = body.len() as u64;
+    respond(request, etag, len, |response, range| {
+        if range.start == 0 && range.end == len {
+            response.with_body(content_type, body)
+        } else {
+            let part = body[range.start as usize..range.end as usize].to_vec();
+            response.with_body(content_type, part)
+        }
+    })
+}
+
+/// Takes care of conditional and range requests for a body of `len` bytes, `with_body` adds the
+/// requested part of the body to the response.
+fn respond<F>(request: &Request, etag: &str, len: u64, with_body: F) -> Response
+where
+    F: FnOnce(Response, Range<u64>) -> Response,
+{
+    if let Some(tags) = request.header("If-None-Match") {
+        if tags
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        {
+            return Response::new(304).with_header("ETag", etag);
+        }
+    }
+
+    let range = match request.header("Range") {
+        // ranges only apply to the version the client has parts of
+        Some(range) if request.header("If-Range").is_none_or(|tag| tag == etag) => {
+            ByteRange::parse(range, len)
+        }
+        _ => ByteRange::Full,
+    };
+
+    let response = Response::new(200)
+        .with_header("ETag", etag)
+        .with_header("Accept-Ranges", "bytes");
+    match range {
+        ByteRange::Full => with_body(response, 0..len),
+        ByteRange::Partial(first, last) => {
+            let response = response.with_header(
+                "Content-Range",
+                &format!("bytes {}-{}/{}", first, last, len),
+            );
+            let mut response = with_body(response, first..last + 1);
+            response.status = 206;
+            response
+        }
+        ByteRange::Unsatisfiable => Response::text(416, "the range is not satisfiable")
+            .with_header("Content-Range", &format!("bytes */{}", len)),
+    }
+}
+
+fn json<T: ::serde::Serialize>(value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::new(200).with_body("application/json", body),
+        Err(e) => error_response(&Error::with_cause(
+            ErrorKind::Encode,
+            "couldn't encode to JSON",
+            e,
+        )),
+    }
+}
+
+fn not_found() -> Response {
+    Response::text(404, "not found")
+}
+
+fn wrong_key() -> Response {
+    Response::text(403, "the key is wrong")
+}
+
+fn error_response(error: &Error) -> Response {
+    let status = match error.kind() {
+        ErrorKind::OutOfRange => 404,
+        ErrorKind::UnsupportedFormat => 501,
+        _ => {
+            warn!("couldn't answer a request: {}", error);
+            500
+        }
+    };
+    Response::text(status, &error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Request, Server};
+    use file::MangoFile;
+    use image::Mime;
+    use serde_json::{self, Value};
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::{Path, PathBuf};
+    use std::thread;
+
+    /// Creates a directory with `vol 1.mango` (CBOR) and `shelf/vol2.mango` (BSON).
+    fn library(name: &str) -> PathBuf {
+        let root = PathBuf::from(name);
+        fs::create_dir_all(root.join("shelf")).unwrap();
+
+        let mut file = MangoFile::new();
+        file.get_meta_mut().title = Some("first".to_string());
+        file.add_image_by_path(Path::new("test.jpg")).unwrap();
+        file.add_image_by_path(Path::new("test2.jpg")).unwrap();
+        file.save(&root.join("vol 1.mango")).unwrap();
+
+        file.get_meta_mut().title = Some("second".to_string());
+        file.save_bson(&root.join("shelf/vol2.mango")).unwrap();
+
+        root
+    }
+
+    fn get(server: &Server, target: &str) -> super::Response {
+        server.handle(&Request::new("GET", target))
+    }
+
+    #[test]
+    fn volumes() {
+        let root = library("server_volumes");
+        let server = Server::new(&root);
+
+        let response = get(&server, "/volumes");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        let list: Value = serde_json::from_slice(&response.body).unwrap();
+        let volumes = list["volumes"].as_array().unwrap();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0]["id"], "shelf/vol2.mango");
+        assert_eq!(volumes[0]["meta"]["title"], "second");
+        assert_eq!(volumes[1]["id"], "vol 1.mango");
+        assert_eq!(volumes[1]["url"], "/volumes/vol%201.mango");
+        assert_eq!(volumes[1]["pages"], 2);
+
+        let response = get(&server, "/volumes/shelf%2Fvol2.mango");
+        assert_eq!(response.status, 200);
+        let volume: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(volume["meta"]["title"], "second");
+        assert_eq!(volume["pages"][1]["meta"]["filename"], "test2.jpg");
+        assert_eq!(
+            volume["pages"][1]["thumbnail"],
+            "/volumes/shelf%2Fvol2.mango/pages/1/thumbnail"
+        );
+
+        for target in &[
+            "/volumes/missing.mango",
+            "/volumes/..%2Fserver_volumes%2Fvol%201.mango",
+            "/volumes/shelf",
+            "/volumes/vol%201.mango/pages/2",
+            "/volumes/vol%201.mango/pages/x",
+            "/other",
+        ] {
+            assert_eq!(get(&server, target).status, 404, "{}", target);
+        }
+        assert_eq!(get(&server, "/volumes/%zz").status, 400);
+        let response = server.handle(&Request::new("POST", "/volumes"));
+        assert_eq!(response.status, 405);
+        assert_eq!(response.header("Allow"), Some("GET, HEAD"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn pages() {
+        let root = library("server_pages");
+        let mut server = Server::new(&root);
+        server.set_thumbnail_size(32);
+        let original = fs::read("test2.jpg").unwrap();
+
+        let target = "/volumes/shelf%2Fvol2.mango/pages/1";
+        let response = get(&server, target);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some("image/jpeg"));
+        assert_eq!(response.body, original);
+        let etag = response.header("ETag").unwrap().to_string();
+
+        let response =
+            server.handle(&Request::new("GET", target).with_header("If-None-Match", &etag));
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+
+        let response =
+            server.handle(&Request::new("GET", target).with_header("Range", "bytes=10-19"));
+        assert_eq!(response.status, 206);
+        assert_eq!(response.body, &original[10..20]);
+        let range = format!("bytes 10-19/{}", original.len());
+        assert_eq!(response.header("Content-Range"), Some(range.as_str()));
+
+        let response = server.handle(
+            &Request::new("GET", target)
+                .with_header("Range", "bytes=10-19")
+                .with_header("If-Range", "\"old\""),
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, original);
+
+        let response =
+            server.handle(&Request::new("GET", target).with_header("Range", "bytes=999999-"));
+        assert_eq!(response.status, 416);
+
+        let response = get(&server, &format!("{}/thumbnail", target));
+        assert_eq!(response.status, 200);
+        assert_eq!(Mime::sniff(&response.body), Some(Mime::JPEG));
+        assert!(response.body.len() < original.len());
+        assert_ne!(response.header("ETag"), Some(etag.as_str()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "aes")]
+    fn encrypted_pages() {
+        use encryption::EncryptionType;
+        use image::MangoImage;
+
+        let root = PathBuf::from("server_encrypted");
+        fs::create_dir_all(&root).unwrap();
+        let original = fs::read("test.jpg").unwrap();
+        let image = MangoImage::from_bytes(original.clone(), "test.jpg").unwrap();
+        let mut file = MangoFile::new();
+        file.add_image(
+            image
+                .encrypt(EncryptionType::AES128, "se cret".to_string())
+                .unwrap(),
+        );
+        file.save(&root.join("locked.mango")).unwrap();
+
+        let server = Server::new(&root);
+        let target = "/volumes/locked.mango/pages/0";
+        assert_eq!(get(&server, target).status, 403);
+        let wrong = Request::new("GET", target).with_header("X-Mango-Key", "wrong");
+        assert_eq!(server.handle(&wrong).status, 403);
+
+        let response = get(&server, &format!("{}?key=se+cret", target));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, original);
+        assert_eq!(response.header("Cache-Control"), Some("private"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file() {
+        let root = library("server_file");
+        let server = Server::new(&root);
+
+        let target = "/volumes/vol%201.mango/file";
+        let response = get(&server, target);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some("application/x-mango"));
+        let original = fs::read(root.join("vol 1.mango")).unwrap();
+        assert!(response.body.is_empty());
+        assert_eq!(response.content_length(), original.len() as u64);
+        let mut body = Vec::new();
+        response.write_body(&mut body).unwrap();
+        assert_eq!(body, original);
+
+        let etag = response.header("ETag").unwrap();
+        let request = Request::new("GET", target).with_header("If-None-Match", etag);
+        assert_eq!(server.handle(&request).status, 304);
+
+        let response =
+            server.handle(&Request::new("GET", target).with_header("Range", "bytes=10-19"));
+        assert_eq!(response.status, 206);
+        let mut body = Vec::new();
+        response.write_body(&mut body).unwrap();
+        assert_eq!(body, &original[10..20]);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "opds")]
+    fn opds() {
+        let root = library("server_opds");
+        let server = Server::new(&root);
+
+        for target in &["/opds", "/opds/"] {
+            let response = get(&server, target);
+            assert_eq!(response.status, 200);
+            assert_eq!(
+                response.header("Content-Type"),
+                Some("application/atom+xml;profile=opds-catalog;kind=navigation")
+            );
+        }
+
+        let response = get(&server, "/opds2/all");
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.header("Content-Type"),
+            Some("application/opds+json")
+        );
+        let feed: Value = serde_json::from_slice(&response.body).unwrap();
+        let publications = feed["publications"].as_array().unwrap();
+        assert_eq!(publications.len(), 2);
+        let stream = &publications[0]["links"][1];
+        assert_eq!(stream["href"], "/volumes/vol%201.mango/pages/{pageNumber}");
+        assert_eq!(stream["type"], "image/jpeg");
+        assert_eq!(stream["properties"]["numberOfItems"], 2);
+
+        assert_eq!(get(&server, "/opds/genres").status, 404);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn serve() {
+        let root = library("server_serve");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = Server::new(&root);
+        thread::spawn(move || server.serve(listener));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(
+                b"GET /volumes/vol%201.mango/pages/0 HTTP/1.1\r\nHost: x\r\n\r\n\
+                  HEAD /volumes HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+
+        let original = fs::read("test.jpg").unwrap();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nETag: \"{}\"\r\n",
+            ::meta::checksum(&original)
+        );
+        assert!(received.starts_with(head.as_bytes()));
+        let length = format!("Content-Length: {}\r\n\r\n", original.len());
+        let body_start = received
+            .windows(length.len())
+            .position(|w| w == length.as_bytes())
+            .unwrap()
+            + length.len();
+        assert_eq!(
+            &received[body_start..body_start + original.len()],
+            &original[..]
+        );
+
+        let rest = String::from_utf8_lossy(&received[body_start + original.len()..]).into_owned();
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(rest.ends_with("Connection: close\r\n\r\n"));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"nonsense\r\n\r\n").unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert!(received.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
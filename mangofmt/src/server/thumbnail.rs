@@ -0,0 +1,39 @@
+//! Creates the thumbnails of pages.
+
+extern crate image as codec;
+
+use self::codec::codecs::jpeg::JpegEncoder;
+use self::codec::{DynamicImage, GenericImageView};
+use error::{Error, ErrorKind};
+
+/// The quality of the JPEG encoded thumbnails.
+const QUALITY: u8 = 80;
+
+/// Scales an encoded image down so it fits into `size` x `size` pixels and encodes it as JPEG.
+///
+/// Smaller images keep their size. JPEG XL and AVIF pages can't be decoded.
+pub fn thumbnail(data: &[u8], size: u32) -> Result<Vec<u8>, Error> {
+    let image = codec::load_from_memory(data).map_err(|e| {
+        Error::with_cause(ErrorKind::UnsupportedFormat, "couldn't decode the image", e)
+    })?;
+
+    let (width, height) = image.dimensions();
+    let image = if width > size || height > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+
+    // JPEG has no alpha channel
+    let image = if image.color().has_color() {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    } else {
+        DynamicImage::ImageLuma8(image.to_luma8())
+    };
+
+    let mut data = Vec::new();
+    image
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut data, QUALITY))
+        .map_err(|e| Error::with_cause(ErrorKind::Encode, "couldn't encode the thumbnail", e))?;
+    Ok(data)
+}
@@ -0,0 +1,161 @@
+//! Content addressed storage of the images of a MangoFile.
+//!
+//! If [deduplication](../struct.MangoFile.html#method.set_deduplicate) is turned on, images with
+//! the same bytes, like credit pages repeated in every chapter, are stored once.
+//! Every other image with those bytes only stores the index of the image which contains them
+//! under the key "data_ref" and no "data". Older readers expect "data" in every image and
+//! can't open such files, so it is off by default and files look exactly like they always did.
+//! Files with "data_ref" are read regardless of the setting.
+
+use error::{Error as MangoError, ErrorKind};
+use image::MangoImage;
+use meta::{MangoImageMetadata, MangoMetadata};
+use serde::de::Error;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_bytes::{ByteBuf, Bytes};
+use std::collections::HashMap;
+use toc::TableOfContents;
+
+/// A MangoFile as it gets serialized.
+#[derive(Serialize)]
+pub struct StoredFile<'a> {
+    pub meta: &'a MangoMetadata,
+    #[serde(skip_serializing_if = "is_empty")]
+    pub toc: &'a TableOfContents,
+    pub images: StoredImages<'a>,
+}
+
+fn is_empty(toc: &&TableOfContents) -> bool {
+    toc.is_empty()
+}
+
+/// The images of a MangoFile, duplicates are only stored once if `deduplicate` is set.
+pub struct StoredImages<'a> {
+    pub images: &'a [MangoImage],
+    pub deduplicate: bool,
+}
+
+impl<'a> Serialize for StoredImages<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.images, self.deduplicate, serializer)
+    }
+}
+
+#[derive(Serialize)]
+struct StoredImageRef<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Bytes<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_ref: Option<usize>,
+    meta: &'a MangoImageMetadata,
+}
+
+/// A single image as it is stored, its data might belong to another image.
+#[derive(Deserialize)]
+pub struct StoredImage {
+    #[serde(default)]
+    data: Option<ByteBuf>,
+    #[serde(default)]
+    data_ref: Option<usize>,
+    meta: MangoImageMetadata,
+}
+
+impl StoredImage {
+    /// The index of the image whose data this one shares, None if it stores its own data.
+    pub fn data_ref(&self) -> Option<usize> {
+        match self.data {
+            Some(_) => None,
+            None => self.data_ref,
+        }
+    }
+
+    /// Turns the stored image into a MangoImage, `shared` is the image [data_ref] points to.
+    pub fn into_image(self, shared: Option<&MangoImage>) -> Result<MangoImage, MangoError> {
+        match (self.data, self.data_ref) {
+            (Some(data), _) => Ok(MangoImage::new(data.into(), self.meta)),
+            (None, Some(index)) => match shared {
+                Some(other) => Ok(MangoImage::sharing_data(other, self.meta)),
+                None => Err(MangoError::new(
+                    ErrorKind::Decode,
+                    format!(
+                        "the image refers to the data of image {}, which is missing",
+                        index
+                    ),
+                )),
+            },
+            (None, None) => Err(MangoError::new(ErrorKind::Decode, "the image has no data")),
+        }
+    }
+}
+
+fn serialize<S: Serializer>(
+    images: &[MangoImage],
+    deduplicate: bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    // the checksum is the same for identical images, the bytes decide if they can be shared
+    let mut stored: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut seq = serializer.serialize_seq(Some(images.len()))?;
+
+    for (index, image) in images.iter().enumerate() {
+        let meta = image.get_meta_ref();
+        if !deduplicate {
+            seq.serialize_element(&StoredImageRef {
+                data: Some(Bytes::new(image.get_image_data_ref())),
+                data_ref: None,
+                meta,
+            })?;
+            continue;
+        }
+
+        let candidates = stored.entry(meta.checksum.as_str()).or_default();
+        let data_ref = candidates
+            .iter()
+            .find(|&&i| images[i].same_data(image))
+            .cloned();
+
+        if data_ref.is_none() {
+            candidates.push(index);
+        }
+
+        seq.serialize_element(&StoredImageRef {
+            data: match data_ref {
+                Some(_) => None,
+                None => Some(Bytes::new(image.get_image_data_ref())),
+            },
+            data_ref,
+            meta,
+        })?;
+    }
+
+    seq.end()
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<MangoImage>, D::Error> {
+    let stored: Vec<StoredImage> = Vec::deserialize(deserializer)?;
+    let mut images: Vec<MangoImage> = Vec::with_capacity(stored.len());
+
+    for image in stored {
+        let shared = match image.data_ref() {
+            Some(index) => match images.get(index) {
+                Some(other) => Some(other),
+                None => {
+                    return Err(D::Error::custom(format!(
+                        "image {} refers to the data of image {}, which doesn't come before it",
+                        images.len(),
+                        index
+                    )))
+                }
+            },
+            None => None,
+        };
+
+        let image = image.into_image(shared).map_err(D::Error::custom)?;
+        images.push(image);
+    }
+
+    Ok(images)
+}
@@ -0,0 +1,447 @@
+//! Table of contents of a MangoFile.
+
+use std::ops::Range;
+
+/// A chapter or any other section inside of a MangoFile.
+///
+/// Entries can be nested, e.g. a chapter can contain entries for its parts.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TocEntry {
+    pub title: Option<String>,
+    /// The chapter number, can be fractional like 10.5 for extra chapters.
+    pub chapter: Option<f64>,
+    /// The index of the first page of the entry.
+    pub start: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    pub fn new(title: Option<String>, chapter: Option<f64>, start: usize) -> Self {
+        Self {
+            title,
+            chapter,
+            start,
+            children: Vec::new(),
+        }
+    }
+
+    /// Adds a nested entry, entries are kept sorted by their first page.
+    pub fn add_child(&mut self, entry: TocEntry) {
+        insert_sorted(&mut self.children, entry);
+    }
+}
+
+/// The table of contents of a MangoFile.
+///
+/// Every entry covers the pages from its start up to the start of the next entry on the same
+/// level, the last one covers everything up to the end of the file (or the end of its parent).
+/// Pages before the first entry don't belong to any entry.
+///
+/// The page indices are kept valid by the page related methods of
+/// [MangoFile](../file/struct.MangoFile.html).
+/// If you change the pages of a file yourself, call the `page_*` methods of this struct.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(transparent)]
+pub struct TableOfContents {
+    entries: Vec<TocEntry>,
+}
+
+impl TableOfContents {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the top level entries.
+    pub fn entries(&self) -> &[TocEntry] {
+        &self.entries
+    }
+
+    /// Returns the top level entries in a mutable form.
+    ///
+    /// Call [sort](#method.sort) after changing the start of an entry.
+    pub fn entries_mut(&mut self) -> &mut Vec<TocEntry> {
+        &mut self.entries
+    }
+
+    /// Adds a top level entry, entries are kept sorted by their first page.
+    pub fn add(&mut self, entry: TocEntry) {
+        insert_sorted(&mut self.entries, entry);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Sorts all entries by their first page.
+    pub fn sort(&mut self) {
+        sort_entries(&mut self.entries);
+    }
+
+    /// Returns the innermost entry the page belongs to.
+    pub fn find(&self, page: usize) -> Option<&TocEntry> {
+        self.path(page).pop()
+    }
+
+    /// Returns all entries the page belongs to, from the top level one to the innermost one.
+    pub fn path(&self, page: usize) -> Vec<&TocEntry> {
+        let mut path = Vec::new();
+        let mut level = &self.entries;
+
+        while let Some(entry) = level.iter().rev().find(|e| e.start <= page) {
+            path.push(entry);
+            level = &entry.children;
+        }
+
+        path
+    }
+
+    /// Returns the pages covered by each top level entry of a file with `page_count` pages.
+    pub fn ranges(&self, page_count: usize) -> Vec<(&TocEntry, Range<usize>)> {
+        let mut ranges = Vec::with_capacity(self.entries.len());
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let end = match self.entries.get(i + 1) {
+                Some(next) => next.start,
+                None => page_count,
+            };
+            let start = entry.start.min(page_count);
+            ranges.push((entry, start..end.max(start)));
+        }
+
+        ranges
+    }
+
+    /// Updates the entries after a page got inserted at `index`.
+    ///
+    /// A page inserted at the first page of an entry becomes the new first page of it.
+    pub fn page_inserted(&mut self, index: usize) {
+        shift_inserted(&mut self.entries, index);
+    }
+
+    /// Updates the entries after the page at `index` got removed from a file,
+    /// which now has `page_count` pages.
+    ///
+    /// Entries which don't contain any page anymore get removed.
+    pub fn page_removed(&mut self, index: usize, page_count: usize) {
+        shift_removed(&mut self.entries, index);
+        self.truncate(page_count);
+    }
+
+    /// Updates the entries after several pages got removed at once, `removed` tells for every
+    /// page whether it got removed. `page_count` pages are left.
+    ///
+    /// Entries which don't contain any page anymore get removed.
+    pub fn pages_removed(&mut self, removed: &[bool], page_count: usize) {
+        // the number of pages which are left in front of each page
+        let mut kept_before = Vec::with_capacity(removed.len() + 1);
+        kept_before.push(0);
+        for &gone in removed {
+            let kept = kept_before[kept_before.len() - 1];
+            kept_before.push(if gone { kept } else { kept + 1 });
+        }
+
+        let shift = |start: usize| kept_before[start.min(removed.len())];
+        for entry in self.entries.iter_mut() {
+            shift_entry(entry, &shift);
+        }
+        merge_empty(&mut self.entries);
+        self.truncate(page_count);
+    }
+
+    /// Updates the entries after a page got moved from `from` to `to`.
+    ///
+    /// The page becomes part of the entry it got moved into.
+    pub fn page_moved(&mut self, from: usize, to: usize, page_count: usize) {
+        // moving a page is the same as removing it and inserting it again
+        shift_removed(&mut self.entries, from);
+        shift_inserted(&mut self.entries, to);
+        self.truncate(page_count);
+    }
+
+    /// Updates the entries after the order of all `page_count` pages got reversed.
+    ///
+    /// The entries keep their pages, so their order gets reversed as well.
+    /// Pages in front of the first entry become part of the last one.
+    pub fn pages_reversed(&mut self, page_count: usize) {
+        reverse_entries(&mut self.entries, page_count, page_count);
+    }
+
+    /// Removes all entries which start at or after `page_count`.
+    pub fn truncate(&mut self, page_count: usize) {
+        truncate_entries(&mut self.entries, page_count);
+    }
+
+    /// Adds the entries of another table of contents, whose pages got added after the first
+    /// `offset` pages.
+    pub fn append(&mut self, other: TableOfContents, offset: usize) {
+        for mut entry in other.entries {
+            shift_entry(&mut entry, &|start| start + offset);
+            self.add(entry);
+        }
+    }
+
+    /// Splits the table of contents at the page `index`.
+    ///
+    /// Returns the entries of the pages starting at `index`, with their pages counted from the
+    /// split. An entry which contains pages of both sides gets copied into both.
+    pub fn split_off(&mut self, index: usize) -> TableOfContents {
+        TableOfContents {
+            entries: split_entries(&mut self.entries, index),
+        }
+    }
+}
+
+fn insert_sorted(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    let index = entries
+        .iter()
+        .position(|e| e.start > entry.start)
+        .unwrap_or(entries.len());
+    entries.insert(index, entry);
+}
+
+fn sort_entries(entries: &mut [TocEntry]) {
+    entries.sort_by_key(|e| e.start);
+    for entry in entries.iter_mut() {
+        sort_entries(&mut entry.children);
+    }
+}
+
+fn shift_inserted(entries: &mut [TocEntry], index: usize) {
+    for entry in entries.iter_mut() {
+        if entry.start > index {
+            entry.start += 1;
+        }
+        shift_inserted(&mut entry.children, index);
+    }
+}
+
+fn shift_removed(entries: &mut Vec<TocEntry>, index: usize) {
+    for entry in entries.iter_mut() {
+        if entry.start > index {
+            entry.start -= 1;
+        }
+        shift_removed(&mut entry.children, index);
+    }
+    remove_empty(entries);
+}
+
+/// Merges the entries of all levels which don't contain any page anymore into the next ones.
+fn merge_empty(entries: &mut Vec<TocEntry>) {
+    for entry in entries.iter_mut() {
+        merge_empty(&mut entry.children);
+    }
+    remove_empty(entries);
+}
+
+fn remove_empty(entries: &mut Vec<TocEntry>) {
+    // an entry starting at the same page as the next one doesn't contain any page anymore
+    let mut i = 0;
+    while i + 1 < entries.len() {
+        if entries[i].start == entries[i + 1].start {
+            let removed = entries.remove(i);
+            for child in removed.children {
+                if child.start >= entries[i].start {
+                    entries[i].add_child(child);
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn reverse_entries(entries: &mut [TocEntry], end: usize, page_count: usize) {
+    let mut next_start = end;
+    for entry in entries.iter_mut().rev() {
+        let entry_end = next_start;
+        next_start = entry.start;
+
+        reverse_entries(&mut entry.children, entry_end, page_count);
+        entry.start = page_count - entry_end.min(page_count);
+    }
+    entries.reverse();
+}
+
+fn shift_entry<F: Fn(usize) -> usize>(entry: &mut TocEntry, shift: &F) {
+    entry.start = shift(entry.start);
+    for child in entry.children.iter_mut() {
+        shift_entry(child, shift);
+    }
+}
+
+fn split_entries(entries: &mut Vec<TocEntry>, index: usize) -> Vec<TocEntry> {
+    let position = entries
+        .iter()
+        .position(|e| e.start >= index)
+        .unwrap_or(entries.len());
+    let mut tail = entries.split_off(position);
+    for entry in tail.iter_mut() {
+        shift_entry(entry, &|start| start - index);
+    }
+
+    // the entry in front of the split continues on the other side
+    let starts_at_split = tail.first().is_some_and(|e| e.start == 0);
+    if let Some(entry) = entries.last_mut() {
+        if !starts_at_split {
+            let mut continued = TocEntry::new(entry.title.clone(), entry.chapter, 0);
+            continued.children = split_entries(&mut entry.children, index);
+            tail.insert(0, continued);
+        }
+    }
+
+    tail
+}
+
+fn truncate_entries(entries: &mut Vec<TocEntry>, page_count: usize) {
+    entries.retain(|e| e.start < page_count);
+    for entry in entries.iter_mut() {
+        truncate_entries(&mut entry.children, page_count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TableOfContents, TocEntry};
+
+    fn entry(title: &str, start: usize) -> TocEntry {
+        TocEntry::new(Some(title.to_string()), None, start)
+    }
+
+    fn toc() -> TableOfContents {
+        let mut toc = TableOfContents::new();
+        let mut second = entry("2", 5);
+        second.add_child(entry("2.2", 7));
+        second.add_child(entry("2.1", 5));
+        toc.add(second);
+        toc.add(entry("1", 0));
+        toc.add(entry("3", 10));
+        toc
+    }
+
+    fn starts(toc: &TableOfContents) -> Vec<usize> {
+        toc.entries().iter().map(|e| e.start).collect()
+    }
+
+    #[test]
+    fn find() {
+        let toc = toc();
+        assert_eq!(toc.find(3).unwrap().title, Some("1".to_string()));
+        assert_eq!(toc.find(6).unwrap().title, Some("2.1".to_string()));
+        assert_eq!(toc.find(9).unwrap().title, Some("2.2".to_string()));
+        assert_eq!(toc.find(12).unwrap().title, Some("3".to_string()));
+        assert_eq!(toc.path(8).len(), 2);
+    }
+
+    #[test]
+    fn ranges() {
+        let toc = toc();
+        let ranges: Vec<_> = toc.ranges(12).into_iter().map(|r| r.1).collect();
+        assert_eq!(ranges, vec![0..5, 5..10, 10..12]);
+    }
+
+    #[test]
+    fn insert_page() {
+        let mut toc = toc();
+        toc.page_inserted(5);
+        assert_eq!(starts(&toc), vec![0, 5, 11]);
+        assert_eq!(toc.entries()[1].children[1].start, 8);
+
+        toc.page_inserted(0);
+        assert_eq!(starts(&toc), vec![0, 6, 12]);
+    }
+
+    #[test]
+    fn remove_page() {
+        let mut toc = toc();
+        toc.page_removed(2, 11);
+        assert_eq!(starts(&toc), vec![0, 4, 9]);
+
+        // remove both pages of the last chapter
+        toc.page_removed(10, 10);
+        assert_eq!(starts(&toc), vec![0, 4, 9]);
+        toc.page_removed(9, 9);
+        assert_eq!(starts(&toc), vec![0, 4]);
+    }
+
+    #[test]
+    fn remove_pages() {
+        let removed: Vec<bool> = (0..12).map(|i| [0, 5, 6, 9].contains(&i)).collect();
+        // the same as removing them one after another
+        let mut expected = toc();
+        for (count, &index) in [9, 6, 5, 0].iter().enumerate() {
+            expected.page_removed(index, 11 - count);
+        }
+
+        let mut toc = toc();
+        toc.pages_removed(&removed, 8);
+        assert_eq!(toc, expected);
+        assert_eq!(starts(&toc), vec![0, 4, 6]);
+        // 2.1 lost both of its pages
+        let children = &toc.entries()[1].children;
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].title, Some("2.2".to_string()));
+    }
+
+    #[test]
+    fn remove_empty_entry() {
+        let mut toc = TableOfContents::new();
+        toc.add(entry("1", 0));
+        toc.add(entry("2", 1));
+        toc.add(entry("3", 2));
+
+        toc.page_removed(1, 2);
+        assert_eq!(starts(&toc), vec![0, 1]);
+        assert_eq!(toc.entries()[1].title, Some("3".to_string()));
+    }
+
+    #[test]
+    fn reverse_pages() {
+        let mut toc = toc();
+        toc.pages_reversed(12);
+        assert_eq!(starts(&toc), vec![0, 2, 7]);
+        assert_eq!(toc.entries()[1].title, Some("2".to_string()));
+
+        let children: Vec<_> = toc.entries()[1].children.iter().map(|e| e.start).collect();
+        assert_eq!(children, vec![2, 5]);
+        assert_eq!(toc.find(3).unwrap().title, Some("2.2".to_string()));
+    }
+
+    #[test]
+    fn split_and_append() {
+        let mut toc = toc();
+        let tail = toc.split_off(6);
+        assert_eq!(starts(&toc), vec![0, 5]);
+        assert_eq!(toc.entries()[1].children.len(), 1);
+
+        // the second chapter and its first part continue after the split
+        assert_eq!(starts(&tail), vec![0, 4]);
+        assert_eq!(tail.entries()[0].title, Some("2".to_string()));
+        let children: Vec<_> = tail.entries()[0].children.iter().map(|e| e.start).collect();
+        assert_eq!(children, vec![0, 1]);
+
+        let mut whole = TableOfContents::new();
+        whole.add(entry("1", 0));
+        whole.append(tail, 6);
+        assert_eq!(starts(&whole), vec![0, 6, 10]);
+        assert_eq!(whole.find(7).unwrap().title, Some("2.2".to_string()));
+    }
+
+    #[test]
+    fn move_page() {
+        let mut toc = toc();
+        // move the first page to the start of the third chapter
+        toc.page_moved(0, 9, 12);
+        assert_eq!(starts(&toc), vec![0, 4, 9]);
+
+        // and back again
+        toc.page_moved(9, 0, 12);
+        assert_eq!(starts(&toc), vec![0, 5, 10]);
+    }
+}
@@ -1,3 +1,4 @@
-from .mango import MangoFile, MangoImage
-from .enums import EncryptionType, CompressionType, Language
+from .mango import MangoFile, MangoImage, MangoMetaData, Progress
+from .enums import EncryptionType, CompressionType, Language, PageKind
+from .enums import ProgressStage
 
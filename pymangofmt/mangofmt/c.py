@@ -3,7 +3,7 @@ import platform
 import ctypes
 from ctypes import Structure, POINTER
 from ctypes import c_int, c_void_p, c_ubyte, c_size_t, c_bool, c_char_p
-from ctypes import c_uint
+from ctypes import c_double, c_short, c_uint32, c_uint64, CFUNCTYPE
 
 library_path = "libmango.so"
 
@@ -22,6 +22,32 @@ elif platform.system() == "Windows":
 
 libmango = ctypes.cdll.LoadLibrary(library_path)
 
+# MANGO_ABI_VERSION from libmango.h the bindings below were written for
+ABI_VERSION = 1
+
+
+def _check_abi_version():
+    try:
+        libmango.mango_abi_version.argtypes = ()
+        libmango.mango_abi_version.restype = c_uint32
+        libmango.mango_version.argtypes = ()
+        libmango.mango_version.restype = c_char_p
+    except AttributeError:
+        raise ImportError("{} is too old for mangofmt, it needs ABI version {}"
+                          .format(library_path, ABI_VERSION))
+
+    found = libmango.mango_abi_version()
+    if found != ABI_VERSION:
+        raise ImportError("mangofmt needs libmango ABI version {}, but {} "
+                          "({}) implements version {}".format(
+                              ABI_VERSION,
+                              library_path,
+                              libmango.mango_version().decode("utf-8"),
+                              found))
+
+
+_check_abi_version()
+
 
 class RustMangoFile(Structure):
     pass
@@ -52,6 +78,32 @@ class IntOption(Structure):
                 ("present", c_int)]
 
 
+class FloatOption(Structure):
+    _fields_ = [("value", c_double),
+                ("present", c_int)]
+
+
+class MangoProgress(Structure):
+    _fields_ = [("stage", c_int),
+                ("has_page", c_bool),
+                ("page", c_size_t),
+                ("page_count", c_size_t),
+                ("bytes", c_uint64),
+                ("total_bytes", c_uint64)]
+
+
+ProgressCallback = CFUNCTYPE(c_bool, POINTER(MangoProgress), c_void_p)
+
+
+libmango.mango_last_error_message.argtypes = ()
+libmango.mango_last_error_message.restype = c_char_p
+
+libmango.mango_string_free.argtypes = (c_void_p,)
+libmango.mango_string_free.restype = None
+
+libmango.mango_imagedata_free.argtypes = (ImageData,)
+libmango.mango_imagedata_free.restype = None
+
 libmango.mango_encryption_is_supported.argtypes = (c_char_p,)
 libmango.mango_encryption_is_supported.restype = c_bool
 
@@ -61,9 +113,11 @@ libmango.mango_compression_is_supported.restype = c_bool
 # -----------------------------------------------------------------------------
 # Mango File
 # -----------------------------------------------------------------------------
+libmango.mangofile_new.argtypes = ()
 libmango.mangofile_new.restype = POINTER(RustMangoFile)
 
 libmango.mangofile_free.argtypes = (POINTER(RustMangoFile),)
+libmango.mangofile_free.restype = None
 
 # Image Manipulation
 libmango.mangofile_add_image.argtypes = (
@@ -78,26 +132,40 @@ libmango.mangofile_add_image_by_path.argtypes = (
 )
 libmango.mangofile_add_image_by_path.restype = c_int
 
-libmango.mangofile_remove_image.argtypes = (
+libmango.mangofile_remove_image.argtypes = (POINTER(RustMangoFile), c_size_t)
+libmango.mangofile_remove_image.restype = c_int
+
+libmango.mangofile_get_image.argtypes = (
     POINTER(RustMangoFile),
-    c_int,
+    c_size_t,
+    POINTER(POINTER(RustMangoImage))
 )
-
-libmango.mangofile_get_image.argtypes = (POINTER(RustMangoFile), c_uint)
-libmango.mangofile_get_image.restype = POINTER(RustMangoImage)
+libmango.mangofile_get_image.restype = c_int
 
 libmango.mangofile_set_image.argtypes = (
     POINTER(RustMangoFile),
     POINTER(RustMangoImage),
-    c_int
+    c_size_t
 )
 libmango.mangofile_set_image.restype = c_int
 
-libmango.mangofile_get_image_count.argtypes = (POINTER(RustMangoFile),)
+libmango.mangofile_get_image_count.argtypes = (
+    POINTER(RustMangoFile),
+    POINTER(c_size_t)
+)
 libmango.mangofile_get_image_count.restype = c_int
 
-libmango.mangofile_get_meta.argtypes = (POINTER(RustMangoFile),)
-libmango.mangofile_get_meta.restype = POINTER(RustMangoMetadata)
+libmango.mangofile_get_meta.argtypes = (
+    POINTER(RustMangoFile),
+    POINTER(POINTER(RustMangoMetadata))
+)
+libmango.mangofile_get_meta.restype = c_int
+
+libmango.mangofile_set_meta.argtypes = (
+    POINTER(RustMangoFile),
+    POINTER(RustMangoMetadata)
+)
+libmango.mangofile_set_meta.restype = c_int
 
 # Save
 libmango.mangofile_save.argtypes = (POINTER(RustMangoFile), c_char_p)
@@ -113,117 +181,168 @@ libmango.mangofile_save_json.argtypes = (POINTER(RustMangoFile), c_char_p)
 libmango.mangofile_save_json.restype = c_int
 
 # Open
-libmango.mangofile_open.argtypes = (c_char_p, POINTER(c_int))
-libmango.mangofile_open.restype = POINTER(RustMangoFile)
+libmango.mangofile_open.argtypes = (c_char_p, POINTER(POINTER(RustMangoFile)))
+libmango.mangofile_open.restype = c_int
 
-# -----------------------------------------------------------------------------
-# Mango Metadata
-# -----------------------------------------------------------------------------
-# title
-libmango.mangometa_get_title.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_title.restype = c_void_p
+# Buffers
+libmango.mangofile_open_buffer.argtypes = (
+    POINTER(c_ubyte),
+    c_size_t,
+    c_char_p,
+    POINTER(POINTER(RustMangoFile))
+)
+libmango.mangofile_open_buffer.restype = c_int
 
-libmango.mangometa_set_title.argtypes = (POINTER(RustMangoMetadata), c_char_p)
-libmango.mangometa_set_title.restype = None
+libmango.mangofile_save_to_buffer.argtypes = (
+    POINTER(RustMangoFile),
+    c_char_p,
+    POINTER(ImageData)
+)
+libmango.mangofile_save_to_buffer.restype = c_int
 
-# author
-libmango.mangometa_get_author.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_author.restype = c_void_p
+# Progress
+libmango.mangofile_open_with_progress.argtypes = (
+    c_char_p,
+    ProgressCallback,
+    c_void_p,
+    POINTER(POINTER(RustMangoFile))
+)
+libmango.mangofile_open_with_progress.restype = c_int
 
-libmango.mangometa_set_author.argtypes = (POINTER(RustMangoMetadata), c_char_p)
-libmango.mangometa_set_author.restype = None
+libmango.mangofile_save_with_progress.argtypes = (
+    POINTER(RustMangoFile),
+    c_char_p,
+    c_char_p,
+    ProgressCallback,
+    c_void_p
+)
+libmango.mangofile_save_with_progress.restype = c_int
 
-# publisher
-libmango.mangometa_get_publisher.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_publisher.restype = c_void_p
+libmango.mangofile_import_images.argtypes = (
+    POINTER(RustMangoFile),
+    POINTER(c_char_p),
+    c_size_t,
+    ProgressCallback,
+    c_void_p
+)
+libmango.mangofile_import_images.restype = c_int
 
-libmango.mangometa_set_publisher.argtypes = (
-    POINTER(RustMangoMetadata),
-    c_char_p
+libmango.mangofile_compress_images.argtypes = (
+    POINTER(RustMangoFile),
+    c_char_p,
+    ProgressCallback,
+    c_void_p
 )
-libmango.mangometa_set_publisher.restype = None
+libmango.mangofile_compress_images.restype = c_int
 
-# source
-libmango.mangometa_get_source.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_source.restype = c_void_p
+libmango.mangofile_encrypt_images.argtypes = (
+    POINTER(RustMangoFile),
+    c_char_p,
+    c_char_p,
+    ProgressCallback,
+    c_void_p
+)
+libmango.mangofile_encrypt_images.restype = c_int
 
-libmango.mangometa_set_source.argtypes = (POINTER(RustMangoMetadata), c_char_p)
-libmango.mangometa_set_source.restype = None
+# -----------------------------------------------------------------------------
+# Mango Metadata
+# -----------------------------------------------------------------------------
+libmango.mangometa_free.argtypes = (POINTER(RustMangoMetadata),)
+libmango.mangometa_free.restype = None
 
-# translation
-libmango.mangometa_get_translation.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_translation.restype = c_void_p
+libmango.mangometa_new.argtypes = ()
+libmango.mangometa_new.restype = POINTER(RustMangoMetadata)
 
-libmango.mangometa_set_translation.argtypes = (
+libmango.mangometa_copy.argtypes = (
     POINTER(RustMangoMetadata),
-    c_char_p
+    POINTER(POINTER(RustMangoMetadata))
 )
-libmango.mangometa_set_translation.restype = None
+libmango.mangometa_copy.restype = c_int
 
-# volume
-libmango.mangometa_get_volume.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_volume.restype = IntOption
+for field in ("title", "author", "publisher", "source", "translation",
+              "language"):
+    getter = getattr(libmango, "mangometa_get_" + field)
+    getter.argtypes = (POINTER(RustMangoMetadata), POINTER(c_void_p))
+    getter.restype = c_int
 
-libmango.mangometa_set_volume.argtypes = (
-    POINTER(RustMangoMetadata),
-    POINTER(c_int)
-)
-libmango.mangometa_set_volume.restype = None
+    setter = getattr(libmango, "mangometa_set_" + field)
+    setter.argtypes = (POINTER(RustMangoMetadata), c_char_p)
+    setter.restype = c_int
+
+for field in ("volume", "chapter", "year"):
+    getter = getattr(libmango, "mangometa_get_" + field)
+    getter.argtypes = (POINTER(RustMangoMetadata), POINTER(IntOption))
+    getter.restype = c_int
 
-# chapter
-libmango.mangometa_get_chapter.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_chapter.restype = IntOption
+    setter = getattr(libmango, "mangometa_set_" + field)
+    setter.argtypes = (POINTER(RustMangoMetadata), POINTER(c_short))
+    setter.restype = c_int
 
-libmango.mangometa_set_chapter.argtypes = (
+libmango.mangometa_get_chapter_number.argtypes = (
     POINTER(RustMangoMetadata),
-    POINTER(c_int)
+    POINTER(FloatOption)
 )
-libmango.mangometa_set_chapter.restype = None
+libmango.mangometa_get_chapter_number.restype = c_int
 
-# chapter
-libmango.mangometa_get_year.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_year.restype = IntOption
-
-libmango.mangometa_set_year.argtypes = (
+libmango.mangometa_set_chapter_number.argtypes = (
     POINTER(RustMangoMetadata),
-    POINTER(c_int)
+    POINTER(c_double)
 )
-libmango.mangometa_set_year.restype = None
-
-# language
-libmango.mangometa_get_language.argtypes = (POINTER(RustMangoMetadata),)
-libmango.mangometa_get_language.restype = c_void_p
+libmango.mangometa_set_chapter_number.restype = c_int
 
 # -----------------------------------------------------------------------------
 # Mango Image
 # -----------------------------------------------------------------------------
-libmango.mangoimg_get_meta.argtypes = (POINTER(RustMangoImage),)
-libmango.mangoimg_get_meta.restype = POINTER(RustMangoImageMetadata)
+libmango.mangoimg_free.argtypes = (POINTER(RustMangoImage),)
+libmango.mangoimg_free.restype = None
 
-libmango.mangoimg_get_image_data.argtypes = (POINTER(RustMangoImage),)
-libmango.mangoimg_get_image_data.restype = ImageData
+libmango.mangoimg_get_meta.argtypes = (
+    POINTER(RustMangoImage),
+    POINTER(POINTER(RustMangoImageMetadata))
+)
+libmango.mangoimg_get_meta.restype = c_int
+
+libmango.mangoimg_get_image_data.argtypes = (
+    POINTER(RustMangoImage),
+    POINTER(ImageData)
+)
+libmango.mangoimg_get_image_data.restype = c_int
 
-libmango.mangoimg_get_base64_image_data.argtypes = (POINTER(RustMangoImage),)
-libmango.mangoimg_get_base64_image_data.restype = c_void_p
+libmango.mangoimg_get_base64_image_data.argtypes = (
+    POINTER(RustMangoImage),
+    POINTER(c_void_p)
+)
+libmango.mangoimg_get_base64_image_data.restype = c_int
 
 libmango.mangoimg_compress.argtypes = (POINTER(RustMangoImage), c_char_p)
-libmango.mangoimg_compress.restype = c_bool
+libmango.mangoimg_compress.restype = c_int
 
 libmango.mangoimg_uncompress.argtypes = (POINTER(RustMangoImage),)
-libmango.mangoimg_uncompress.restype = c_bool
+libmango.mangoimg_uncompress.restype = c_int
 
 libmango.mangoimg_encrypt.argtypes = (
     POINTER(RustMangoImage),
     c_char_p,
     c_char_p
 )
-libmango.mangoimg_encrypt.restype = c_bool
+libmango.mangoimg_encrypt.restype = c_int
 
 libmango.mangoimg_decrypt.argtypes = (POINTER(RustMangoImage), c_char_p)
-libmango.mangoimg_decrypt.restype = c_bool
+libmango.mangoimg_decrypt.restype = c_int
 
-libmango.mangoimg_from_path.argtypes = (c_char_p, POINTER(c_int))
-libmango.mangoimg_from_path.restype = POINTER(RustMangoImage)
+libmango.mangoimg_from_path.argtypes = (
+    c_char_p,
+    POINTER(POINTER(RustMangoImage))
+)
+libmango.mangoimg_from_path.restype = c_int
+
+libmango.mangoimg_from_buffer.argtypes = (
+    POINTER(c_ubyte),
+    c_size_t,
+    c_char_p,
+    POINTER(POINTER(RustMangoImage))
+)
+libmango.mangoimg_from_buffer.restype = c_int
 
 libmango.mangoimg_save.argtypes = (POINTER(RustMangoImage), c_char_p)
 libmango.mangoimg_save.restype = c_int
@@ -231,24 +350,35 @@ libmango.mangoimg_save.restype = c_int
 # -----------------------------------------------------------------------------
 # Mango Image Metadata
 # -----------------------------------------------------------------------------
-libmango.mangoimgmeta_compression.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_compression.restype = c_char_p
-
-libmango.mangoimgmeta_encryption.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_encryption.restype = c_char_p
-
-libmango.mangoimgmeta_filename.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_filename.restype = c_char_p
-
-libmango.mangoimgmeta_mime.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_mime.restype = c_char_p
-
-# char * mangoimgmeta_checksum(MangoImageMeta);
-libmango.mangoimgmeta_checksum.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_checksum.restype = c_char_p
+libmango.mangoimgmeta_free.argtypes = (POINTER(RustMangoImageMetadata),)
+libmango.mangoimgmeta_free.restype = None
+
+for field in ("compression", "encryption", "checksum", "mime", "filename",
+              "page_kind", "label", "caption", "perceptual_hash",
+              "original_checksum", "original_mime"):
+    getter = getattr(libmango, "mangoimgmeta_" + field)
+    getter.argtypes = (POINTER(RustMangoImageMetadata), POINTER(c_void_p))
+    getter.restype = c_int
+
+for field in ("filename", "page_kind", "label", "caption"):
+    setter = getattr(libmango, "mangoimgmeta_set_" + field)
+    setter.argtypes = (POINTER(RustMangoImageMetadata), c_char_p)
+    setter.restype = c_int
+
+libmango.mangoimgmeta_double_page.argtypes = (
+    POINTER(RustMangoImageMetadata),
+    POINTER(c_bool)
+)
+libmango.mangoimgmeta_double_page.restype = c_int
 
-libmango.mangoimgmeta_iv.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_iv.restype = POINTER(c_int)
+libmango.mangoimgmeta_set_double_page.argtypes = (
+    POINTER(RustMangoImageMetadata),
+    c_bool
+)
+libmango.mangoimgmeta_set_double_page.restype = c_int
 
-libmango.mangoimgmeta_iv_size.argtypes = (POINTER(RustMangoImageMetadata),)
-libmango.mangoimgmeta_iv_size.restype = c_int
+libmango.mangoimgmeta_iv.argtypes = (
+    POINTER(RustMangoImageMetadata),
+    POINTER(ImageData)
+)
+libmango.mangoimgmeta_iv.restype = c_int
@@ -17,12 +17,37 @@ class EncryptionType(Enum):
         return libmango.mango_encryption_is_supported(self.value.encode("utf-8"))
 
 
-class Language(Enum):
-    EN = "EN"
-    JP = "JP"
-    DE = "DE"
-    FR = "FR"
-    IT = "IT"
-    CN = "CN"
-    ES = "ES"
+class PageKind(Enum):
+    FRONT_COVER = "FRONT_COVER"
+    STORY = "STORY"
+    CREDITS = "CREDITS"
+    ADVERTISEMENT = "ADVERTISEMENT"
+    BACK_COVER = "BACK_COVER"
+    OTHER = "OTHER"
+
+
+class ProgressStage(Enum):
+    """What a long running operation is busy with, see :obj:`Progress`."""
+    READ = 0
+    WRITE = 1
+    COMPRESS = 2
+    ENCRYPT = 3
+    IMPORT = 4
+    DECODE = 5
+    ENCODE = 6
+
+
+class Language(str, Enum):
+    """Some common languages as BCP 47 language tags.
+
+    Any other valid tag like "pt-BR" can be used as a plain string as well,
+    members of this enum compare equal to their tag.
+    """
+    EN = "en"
+    JP = "ja"
+    DE = "de"
+    FR = "fr"
+    IT = "it"
+    CN = "zh"
+    ES = "es"
 
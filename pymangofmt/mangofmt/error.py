@@ -1,3 +1,6 @@
+from mangofmt.c import libmango
+
+
 class MangoFileError(Exception):
     pass
 
@@ -16,3 +19,59 @@ class ReadError(MangoFileError):
 
 class WriteError(MangoFileError):
     pass
+
+
+class CryptoError(MangoFileError):
+    pass
+
+
+class CompressionError(MangoFileError):
+    pass
+
+
+class IntegrityError(MangoFileError):
+    pass
+
+
+class UnsupportedError(MangoFileError):
+    pass
+
+
+class CancelledError(MangoFileError):
+    pass
+
+
+# mango_status from libmango.h
+_EXCEPTIONS = {
+    1: ValueError,  # null pointer
+    2: ValueError,  # invalid argument
+    3: IndexError,
+    4: FileNotFoundError,
+    5: PermissionError,
+    7: DecodeError,
+    8: EncodeError,
+    9: CryptoError,
+    10: CompressionError,
+    11: IntegrityError,
+    12: UnsupportedError,
+    13: RuntimeError,  # panic
+    14: CancelledError,
+    15: WriteError,
+}
+
+
+def check(status, io_error=ReadError):
+    """Raises the exception matching a status returned by libmango.
+
+    Args:
+        status (int): the returned mango_status, 0 means everything went ok.
+        io_error: what gets raised for other io errors.
+    """
+    if status == 0:
+        return
+
+    message = libmango.mango_last_error_message()
+    if message is not None:
+        message = message.decode("utf-8")
+
+    raise _EXCEPTIONS.get(status, io_error)(message)
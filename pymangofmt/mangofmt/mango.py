@@ -1,9 +1,70 @@
-import base64
 import ctypes
-from mangofmt.c import libmango
+from collections import namedtuple
+from mangofmt.c import libmango, ImageData, IntOption, FloatOption
+from mangofmt.c import ProgressCallback
+from mangofmt.c import RustMangoFile, RustMangoImage
+from mangofmt.c import RustMangoMetadata, RustMangoImageMetadata
 from mangofmt.enums import CompressionType, EncryptionType, Language
-from mangofmt.error import DecodeError, ReadError
-from mangofmt.error import EncodeError, WriteError
+from mangofmt.enums import PageKind, ProgressStage
+from mangofmt.error import check, WriteError
+
+
+def _take_string(ptr):
+    """Decodes a string returned by libmango and frees it."""
+    if not ptr:
+        return None
+
+    try:
+        return ctypes.cast(ptr, ctypes.c_char_p).value.decode("utf-8")
+    finally:
+        libmango.mango_string_free(ptr)
+
+
+def _encode(value):
+    return None if value is None else value.encode("utf-8")
+
+
+def _type_value(value, enum_type):
+    """Accepts a member of `enum_type` or its name as a string."""
+    if isinstance(value, enum_type):
+        return value.value
+    elif isinstance(value, str):
+        return value
+    else:
+        raise TypeError
+
+
+Progress = namedtuple("Progress",
+                      ["stage", "page", "page_count", "bytes", "total_bytes"])
+Progress.__doc__ = """How far a long running operation got.
+
+The callbacks passed to the methods of :obj:`MangoFile` which take a
+`progress` argument get called with it. Returning False from the callback
+cancels the operation, it raises :obj:`CancelledError` then and leaves
+everything as it was before.
+
+`page` is None while a file gets read, decoded, encoded or written,
+`page_count` is 0 while the pages aren't known yet and `total_bytes` is None
+if it isn't known up front.
+"""
+
+
+def _progress_callback(progress):
+    """Wraps a Python callable so libmango can call it, None stays NULL."""
+    if progress is None:
+        return ProgressCallback()
+
+    def callback(pointer, _):
+        report = pointer.contents
+        return progress(Progress(
+            ProgressStage(report.stage),
+            report.page if report.has_page else None,
+            report.page_count,
+            report.bytes,
+            report.total_bytes or None,
+        )) is not False
+
+    return ProgressCallback(callback)
 
 
 class MangoFile(object):
@@ -29,18 +90,28 @@ class MangoFile(object):
             self._pointer = pointer
 
     def __del__(self):
-        libmango.mangofile_free(self._pointer)
+        if getattr(self, "_pointer", None) is not None:
+            libmango.mangofile_free(self._pointer)
 
     @property
     def meta_data(self):
         """:obj:`MangoMetaData` Metadata of the file.
 
-        This property is read only.
+        Changes to it are changes to the file.
 
-        Note:
-            libmango currently has no way to create a metadata struct.
+        Assigning a :obj:`MangoMetaData` replaces all of it with a copy,
+        later changes to the assigned object don't affect the file.
         """
-        return MangoMetaData(libmango.mangofile_get_meta(self._pointer), self)
+        pointer = ctypes.POINTER(RustMangoMetadata)()
+        check(libmango.mangofile_get_meta(self._pointer, ctypes.byref(pointer)))
+        return MangoMetaData(pointer, self)
+
+    @meta_data.setter
+    def meta_data(self, meta):
+        if not isinstance(meta, MangoMetaData):
+            raise TypeError
+
+        check(libmango.mangofile_set_meta(self._pointer, meta._pointer))
 
     @property
     def image_count(self):
@@ -48,7 +119,12 @@ class MangoFile(object):
 
         This property is read only.
         """
-        return libmango.mangofile_get_image_count(self._pointer)
+        count = ctypes.c_size_t()
+        check(libmango.mangofile_get_image_count(
+            self._pointer,
+            ctypes.byref(count)
+        ))
+        return count.value
 
     @property
     def images(self):
@@ -60,35 +136,57 @@ class MangoFile(object):
             yield self.get_image(index)
 
     @staticmethod
-    def open(path):
+    def open(path, progress=None):
         """Opens an existing MangoFile.
 
         Args:
             path (str): path of the file to open.
+            progress (callable, optional): gets called with a :obj:`Progress`
+                after every chunk which was read.
 
         Raises:
             DecodeError: could not decode MangoFile
             ReadError: could not read MangoFile
             FileNotFoundError: could not find file, `path` doesn't exist
+            PermissionError: not allowed to read the file
+            CancelledError: `progress` cancelled opening the file
 
         Returns:
             A instance of :obj:`MangoFile`.
         """
-        error = ctypes.c_int(-10)
-        pointer = libmango.mangofile_open(
+        pointer = ctypes.POINTER(RustMangoFile)()
+        check(libmango.mangofile_open_with_progress(
             path.encode("utf-8"),
-            ctypes.byref(error)
-        )
+            _progress_callback(progress),
+            None,
+            ctypes.byref(pointer)
+        ))
+
+        return MangoFile(pointer)
 
-        if error.value != 0:
-            if error.value == 1:
-                raise DecodeError
-            elif error.value == 2:
-                raise ReadError
-            elif error.value == 3:
-                raise FileNotFoundError
-            else:
-                raise Exception("Unknown Error")
+    @staticmethod
+    def from_bytes(data, fmt=None):
+        """Opens a MangoFile which is already in memory.
+
+        Args:
+            data (bytes): the encoded file.
+            fmt (str): "CBOR", "BSON" or "JSON", None detects the format.
+
+        Raises:
+            DecodeError: could not decode MangoFile
+            ValueError: `fmt` is unknown
+
+        Returns:
+            A instance of :obj:`MangoFile`.
+        """
+        buffer = (ctypes.c_ubyte * len(data)).from_buffer_copy(data)
+        pointer = ctypes.POINTER(RustMangoFile)()
+        check(libmango.mangofile_open_buffer(
+            buffer,
+            len(data),
+            _encode(fmt),
+            ctypes.byref(pointer)
+        ))
 
         return MangoFile(pointer)
 
@@ -102,14 +200,20 @@ class MangoFile(object):
             IndexError: there is no image with that `index`.
 
         Returns:
-            reference of a :obj:`MangoImage` from the file.
+            a copy of a :obj:`MangoImage` from the file,
+            use :meth:`set_image` to store changes to it in the file.
         """
-        if self.image_count > index >= 0:
-            pointer = libmango.mangofile_get_image(self._pointer, index)
-            return MangoImage(pointer, self)
-        else:
+        if index < 0:
             raise IndexError
 
+        pointer = ctypes.POINTER(RustMangoImage)()
+        check(libmango.mangofile_get_image(
+            self._pointer,
+            index,
+            ctypes.byref(pointer)
+        ))
+        return MangoImage(pointer)
+
     def set_image(self, img, index):
         """Sets an image in the file.
 
@@ -122,45 +226,28 @@ class MangoFile(object):
 
         Raises:
             IndexError: the index specified does not exist yet
-            ValueError: an argument passed to the function contains
-            an invalid pointer
 
         Returns:
-            True if it set the image, false if it couldn't set it.
+            True, the file stores a copy of the image.
         """
-        if self.image_count > index >= 0:
-            success = libmango.mangofile_set_image(
-                self._pointer,
-                img._pointer,
-                index
-            )
-
-            if success == 1:
-                # TODO maybe set our self as parent of img?
-                # memeory management
-                return True
-            elif success == 0:
-                return False
-            else:
-                raise ValueError("MangoFile.set_image, " +
-                                 "object contains an invalid pointer")
-        else:
+        if index < 0:
             raise IndexError
 
+        check(libmango.mangofile_set_image(self._pointer, img._pointer, index))
+        return True
+
     def add_image(self, img):
         """Adds an image to the file.
 
         Args:
             img (:obj:`MangoImage`): image to add.
 
+        The file stores a copy of the image.
+
         Raises:
             ValueError: MangoFile or MangoImage is null/invalid
         """
-        error_code = libmango.mangofile_add_image(self._pointer, img._pointer)
-
-        if error_code != 0:
-            if error_code == -42:
-                raise ValueError("MangoFile or MangoImage is null/invalid")
+        check(libmango.mangofile_add_image(self._pointer, img._pointer))
 
     def add_image_by_path(self, path):
         """Adds an image to the file by path.
@@ -169,20 +256,14 @@ class MangoFile(object):
             path (str): path to the image file
 
         Raises:
-            ValueError: MangoFile instance's pointer is null/invalid
+            FileNotFoundError: `path` doesn't exist
+            PermissionError: not allowed to read the image
+            UnsupportedError: the file isn't a supported image
         """
-        error_code = libmango.mangofile_add_image_by_path(
+        check(libmango.mangofile_add_image_by_path(
             self._pointer,
             path.encode("utf-8")
-        )
-
-        if error_code != 0:
-            if error_code == 1:
-                raise PermissionError
-            elif error_code == -1:
-                raise Exception("mangofmt: something went horribly wrong")
-            elif error_code == -42:
-                raise ValueError("MangoFile pointer is null/invalid")
+        ))
 
     def remove_image(self, index):
         """Removes an image from the file.
@@ -193,21 +274,104 @@ class MangoFile(object):
         Raises:
             IndexError: when the index argument is out of bounds
         """
-        status = libmango.mangofile_remove_image(self._pointer, index)
-
-        if status == 0:
+        if index < 0:
             raise IndexError
 
+        check(libmango.mangofile_remove_image(self._pointer, index))
+
+    def import_images(self, paths, progress=None):
+        """Adds the image files at `paths` to the file, in that order.
+
+        Nothing gets added if one of them can't be read.
+
+        Args:
+            paths (list): paths of the image files.
+            progress (callable, optional): gets called with a :obj:`Progress`
+                after every image.
+
+        Raises:
+            FileNotFoundError: one of the `paths` doesn't exist
+            UnsupportedError: one of the files isn't a supported image
+            CancelledError: `progress` cancelled the import
+        """
+        encoded = [path.encode("utf-8") for path in paths]
+        array = (ctypes.c_char_p * len(encoded))(*encoded)
+        check(libmango.mangofile_import_images(
+            self._pointer,
+            array,
+            len(encoded),
+            _progress_callback(progress),
+            None
+        ))
+
+    def compress_images(self, ctype, progress=None):
+        """Compresses every image which is neither compressed nor encrypted.
+
+        The images stay as they were if one of them can't be compressed.
+
+        Args:
+            ctype (:obj:`CompressionType`): the compression to use.
+            progress (callable, optional): gets called with a :obj:`Progress`
+                after every image.
+
+        Raises:
+            UnsupportedError: `ctype` wasn't compiled in
+            CancelledError: `progress` cancelled the compression
+        """
+        check(libmango.mangofile_compress_images(
+            self._pointer,
+            _type_value(ctype, CompressionType).encode("utf-8"),
+            _progress_callback(progress),
+            None
+        ))
+
+    def encrypt_images(self, etype, password, progress=None):
+        """Encrypts every image which isn't encrypted with the same password.
+
+        The images stay as they were if one of them can't be encrypted.
+
+        Args:
+            etype (:obj:`EncryptionType`): the encryption to use.
+            password (str): the password for every image.
+            progress (callable, optional): gets called with a :obj:`Progress`
+                after every image.
+
+        Raises:
+            UnsupportedError: `etype` wasn't compiled in
+            CryptoError: the encryption failed
+            CancelledError: `progress` cancelled the encryption
+        """
+        check(libmango.mangofile_encrypt_images(
+            self._pointer,
+            _type_value(etype, EncryptionType).encode("utf-8"),
+            password.encode("utf-8"),
+            _progress_callback(progress),
+            None
+        ))
+
     def _save_error_handling(self, code):
-        if code == 1:
-            raise EncodeError
-        elif code == 2:
-            raise WriteError
-        elif code == 3:
-            raise PermissionError
+        check(code, io_error=WriteError)
 
-    def save(self, path):
-        error = libmango.mangofile_save(self._pointer, path.encode("utf-8"))
+    def save(self, path, progress=None, fmt=None):
+        """Saves the file.
+
+        Args:
+            path (str): where the file gets saved to.
+            progress (callable, optional): gets called with a :obj:`Progress`
+                after every chunk which was written.
+            fmt (str): "CBOR", "BSON" or "JSON", None uses the default "CBOR".
+
+        Raises:
+            CancelledError: `progress` cancelled saving, the previously
+                saved file stays untouched
+        """
+        error = libmango.mangofile_save_with_progress(
+            self._pointer,
+            path.encode("utf-8"),
+            _encode(fmt),
+            _progress_callback(progress),
+            None
+        )
         self._save_error_handling(error)
 
     def save_cbor(self, path):
@@ -231,146 +395,151 @@ class MangoFile(object):
         )
         self._save_error_handling(error)
 
+    def to_bytes(self, fmt=None):
+        """Encodes the file in memory instead of saving it.
+
+        Args:
+            fmt (str): "CBOR", "BSON" or "JSON", None uses the default "CBOR".
+
+        Raises:
+            EncodeError: could not encode MangoFile
+            ValueError: `fmt` is unknown
+
+        Returns:
+            bytes: the same data the save functions would write.
+        """
+        data = ImageData()
+        check(libmango.mangofile_save_to_buffer(
+            self._pointer,
+            _encode(fmt),
+            ctypes.byref(data)
+        ))
+
+        try:
+            return ctypes.string_at(data.pointer, data.length)
+        finally:
+            libmango.mango_imagedata_free(data)
+
 
 class MangoMetaData(object):
-    def __init__(self, pointer, parent):
+    def __init__(self, pointer=None, parent=None):
+        """Constructor:
+
+        Creates empty metadata which doesn't belong to any file,
+        assign it to :attr:`MangoFile.meta_data` to use it.
+        You should not pass anything to the parameters yourself!
+        """
+        if pointer is None:
+            pointer = libmango.mangometa_new()
+
         self._pointer = pointer
         self._parent = parent
 
+    def __del__(self):
+        if getattr(self, "_pointer", None) is not None:
+            libmango.mangometa_free(self._pointer)
+
+    def copy(self):
+        """Returns a :obj:`MangoMetaData` that doesn't belong to any file."""
+        pointer = ctypes.POINTER(RustMangoMetadata)()
+        check(libmango.mangometa_copy(self._pointer, ctypes.byref(pointer)))
+        return MangoMetaData(pointer)
+
+    def _get_string(self, getter):
+        ptr = ctypes.c_void_p()
+        check(getter(self._pointer, ctypes.byref(ptr)))
+        return _take_string(ptr.value)
+
+    def _get_int(self, getter):
+        option = IntOption()
+        check(getter(self._pointer, ctypes.byref(option)))
+        if option.present == 1:
+            return option.value
+        else:
+            return None
+
+    def _set_short(self, setter, value):
+        if value is None:
+            check(setter(self._pointer, None))
+        else:
+            check(setter(self._pointer, ctypes.byref(ctypes.c_short(value))))
+
     @property
     def title(self):
-        ptr = libmango.mangometa_get_title(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangometa_get_title)
 
     @title.setter
     def title(self, value):
-        libmango.mangometa_set_title(self._pointer, value.encode("utf-8"))
+        check(libmango.mangometa_set_title(self._pointer, _encode(value)))
 
     @property
     def author(self):
-        ptr = libmango.mangometa_get_author(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangometa_get_author)
 
     @author.setter
     def author(self, value):
-        libmango.mangometa_set_author(self._pointer, value.encode("utf-8"))
+        check(libmango.mangometa_set_author(self._pointer, _encode(value)))
 
     @property
     def publisher(self):
-        ptr = libmango.mangometa_get_publisher(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangometa_get_publisher)
 
     @publisher.setter
     def publisher(self, value):
-        libmango.mangometa_set_publisher(self._pointer, value.encode("utf-8"))
+        check(libmango.mangometa_set_publisher(self._pointer, _encode(value)))
 
     @property
     def source(self):
-        ptr = libmango.mangometa_get_source(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangometa_get_source)
 
     @source.setter
     def source(self, value):
-        libmango.mangometa_set_source(self._pointer, value.encode("utf-8"))
+        check(libmango.mangometa_set_source(self._pointer, _encode(value)))
 
     @property
     def translation(self):
-        ptr = libmango.mangometa_get_translation(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangometa_get_translation)
 
     @translation.setter
     def translation(self, value):
-        libmango.mangometa_set_translation(
+        check(libmango.mangometa_set_translation(
             self._pointer,
-            value.encode("utf-8")
-        )
+            _encode(value)
+        ))
 
     @property
     def language(self):
-        ptr = libmango.mangometa_get_language(self._pointer)
+        """:str: BCP 47 language tag, e.g. "en" or "pt-BR".
 
-        try:
-            lang = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            lang = None
-        finally:
-            # TODO free pointer
-            pass
-
-        if lang is not None:
-            return Language(lang)
-        else:
-            return None
+        A :obj:`Language` can be assigned as well.
+        """
+        return self._get_string(libmango.mangometa_get_language)
 
     @language.setter
-    def language(self, lang_enum):
-        lang = None
-
-        if isinstance(lang_enum, Language):
-            lang = lang_enum.value
-        else:
+    def language(self, lang):
+        """Raises ValueError if `lang` isn't a valid tag."""
+        if isinstance(lang, Language):
+            lang = lang.value
+        elif lang is not None and not isinstance(lang, str):
             raise TypeError
 
-        libmango.mangometa_set_language(self._pointer, lang.encode("utf-8"))
+        check(libmango.mangometa_set_language(self._pointer, _encode(lang)))
 
     @property
     def volume(self):
-        option = libmango.mangometa_get_volume(self._pointer)
-        if option.present == 1:
-            return option.value
-        else:
-            return None
+        return self._get_int(libmango.mangometa_get_volume)
 
     @volume.setter
     def volume(self, value):
-        libmango.mangometa_set_volume(
-            self._pointer,
-            ctypes.byref(ctypes.c_int(value))
-        )
+        self._set_short(libmango.mangometa_set_volume, value)
 
     @property
     def chapter(self):
-        option = libmango.mangometa_get_chapter(self._pointer)
+        option = FloatOption()
+        check(libmango.mangometa_get_chapter_number(
+            self._pointer,
+            ctypes.byref(option)
+        ))
         if option.present == 1:
             return option.value
         else:
@@ -378,111 +547,96 @@ class MangoMetaData(object):
 
     @chapter.setter
     def chapter(self, value):
-        libmango.mangometa_set_chapter(
-            self._pointer,
-            ctypes.byref(ctypes.c_int(value))
-        )
+        if value is None:
+            check(libmango.mangometa_set_chapter_number(self._pointer, None))
+        else:
+            check(libmango.mangometa_set_chapter_number(
+                self._pointer,
+                ctypes.byref(ctypes.c_double(value))
+            ))
 
     @property
     def year(self):
-        option = libmango.mangometa_get_year(self._pointer)
-        if option.present == 1:
-            return option.value
-        else:
-            return None
+        return self._get_int(libmango.mangometa_get_year)
 
     @year.setter
     def year(self, value):
-        libmango.mangometa_set_year(
-            self._pointer,
-            ctypes.byref(ctypes.c_int(value))
-        )
+        self._set_short(libmango.mangometa_set_year, value)
 
 
 class MangoImage(object):
-    def __init__(self, pointer, parent=None):
+    def __init__(self, pointer):
         self._pointer = pointer
-        self._parent = parent
 
     def __del__(self):
-        if self._parent is None:
+        if getattr(self, "_pointer", None) is not None:
             libmango.mangoimg_free(self._pointer)
 
     @staticmethod
     def from_path(path):
-        error = ctypes.c_int(-10)
-        pointer = libmango.mangoimg_from_path(
-            path.encode("utf-8"),
-            ctypes.byref(error)
-        )
+        """Reads an image file.
 
-        if error.value != 0:
-            print(error)
-            if error.value == 1:
-                raise FileNotFoundError
-            else:
-                raise Exception("Unknown Error")
+        Raises:
+            FileNotFoundError: `path` doesn't exist
+            PermissionError: not allowed to read the image
+            UnsupportedError: the file isn't a supported image
+        """
+        pointer = ctypes.POINTER(RustMangoImage)()
+        check(libmango.mangoimg_from_path(
+            path.encode("utf-8"),
+            ctypes.byref(pointer)
+        ))
 
         return MangoImage(pointer)
 
     @property
     def meta_data(self):
-        pointer = libmango.mangoimg_get_meta(self._pointer)
+        pointer = ctypes.POINTER(RustMangoImageMetadata)()
+        check(libmango.mangoimg_get_meta(self._pointer, ctypes.byref(pointer)))
         return MangoImageMetadata(pointer, self)
 
     @property
     def image_data(self):
-        ptr = libmango.mangoimg_get_base64_image_data(self._pointer)
-        try:
-            value = ctypes.cast(ptr, ctypes.c_char_p).value.decode('utf-8')
-        except Exception:
-            value = None
-
-        if value is not None:
-            value = base64.b64decode(value)
+        data = ImageData()
+        check(libmango.mangoimg_get_image_data(
+            self._pointer,
+            ctypes.byref(data)
+        ))
 
-        return value
+        try:
+            return ctypes.string_at(data.pointer, data.length)
+        finally:
+            libmango.mango_imagedata_free(data)
 
     def save(self, path):
-        code = libmango.mangoimg_save(self._pointer, path.encode("utf8"))
-
-        if code == 1:
-            raise PermissionError
+        check(libmango.mangoimg_save(self._pointer, path.encode("utf8")),
+              io_error=WriteError)
 
     def compress(self, ctype):
-        comp_type = None
-
-        if isinstance(ctype, CompressionType):
-            comp_type = ctype.value
-        elif isinstance(ctype, str):
-            comp_type = ctype
-        else:
-            raise TypeError
-
-        libmango.mangoimg_compress(self._pointer, comp_type.encode("utf-8"))
+        check(libmango.mangoimg_compress(
+            self._pointer,
+            _type_value(ctype, CompressionType).encode("utf-8")
+        ))
 
     def uncompress(self):
-        libmango.mangoimg_uncompress(self._pointer)
+        check(libmango.mangoimg_uncompress(self._pointer))
 
     def encrypt(self, etype, password):
-        enc_type = None
-
-        if isinstance(etype, EncryptionType):
-            enc_type = etype.value
-        elif isinstance(etype, str):
-            enc_type = etype
-        else:
-            raise TypeError
-        print(enc_type)
-        return libmango.mangoimg_encrypt(self._pointer,
-                                         enc_type.encode("utf-8"),
-                                         password.encode("utf-8"))
+        """Raises CryptoError or UnsupportedError if it can't encrypt."""
+        check(libmango.mangoimg_encrypt(
+            self._pointer,
+            _type_value(etype, EncryptionType).encode("utf-8"),
+            password.encode("utf-8")
+        ))
+        return True
 
     def decrypt(self, password):
-        return libmango.mangoimg_decrypt(
+        """Raises CryptoError or UnsupportedError if it can't decrypt."""
+        check(libmango.mangoimg_decrypt(
             self._pointer,
             password.encode("utf-8")
-        )
+        ))
+        return True
 
 
 class MangoImageMetadata(object):
@@ -490,17 +644,25 @@ class MangoImageMetadata(object):
         self._pointer = pointer
         self._parent = parent
 
+    def __del__(self):
+        libmango.mangoimgmeta_free(self._pointer)
+
+    def _get_string(self, getter):
+        ptr = ctypes.c_void_p()
+        check(getter(self._pointer, ctypes.byref(ptr)))
+        return _take_string(ptr.value)
+
     @property
     def compression(self):
-        comp_type = libmango.mangoimgmeta_compression(self._pointer)
+        comp_type = self._get_string(libmango.mangoimgmeta_compression)
         if comp_type is not None:
-            return CompressionType(comp_type.decode("utf-8"))
+            return CompressionType(comp_type)
         else:
             return None
 
     @property
     def encryption(self):
-        enc_type = libmango.mangoimgmeta_encryption(self._pointer)
+        enc_type = self._get_string(libmango.mangoimgmeta_encryption)
         if enc_type is not None:
             return EncryptionType(enc_type)
         else:
@@ -508,41 +670,104 @@ class MangoImageMetadata(object):
 
     @property
     def checksum(self):
-        ptr = libmango.mangoimgmeta_checksum(self._pointer)
-        try:
-            value = ptr.decode('utf-8')
-        except Exception:
-            value = None
-        finally:
-            # TODO free pointer here desu
-            pass
-
-        return value
+        return self._get_string(libmango.mangoimgmeta_checksum)
 
     @property
     def filename(self):
-        ptr = libmango.mangoimgmeta_filename(self._pointer)
-        if ptr is not None:
-            return ptr.decode("utf-8")
-        else:
-            return None
+        return self._get_string(libmango.mangoimgmeta_filename)
+
+    @filename.setter
+    def filename(self, value):
+        if value is None:
+            raise TypeError
+
+        check(libmango.mangoimgmeta_set_filename(self._pointer, _encode(value)))
 
     @property
     def mime(self):
-        ptr = libmango.mangoimgmeta_mime(self._pointer)
-        if ptr is not None:
-            return ptr.decode("utf-8")
+        return self._get_string(libmango.mangoimgmeta_mime)
+
+    @property
+    def page_kind(self):
+        """:obj:`PageKind` What the page shows, None if unknown."""
+        kind = self._get_string(libmango.mangoimgmeta_page_kind)
+        if kind is not None:
+            return PageKind(kind)
         else:
             return None
 
+    @page_kind.setter
+    def page_kind(self, kind):
+        """Raises ValueError if `kind` isn't a known page kind."""
+        if isinstance(kind, PageKind):
+            kind = kind.value
+        elif kind is not None and not isinstance(kind, str):
+            raise TypeError
+
+        check(libmango.mangoimgmeta_set_page_kind(self._pointer, _encode(kind)))
+
     @property
-    def iv(self):
-        ptr = libmango.mangoimgmeta_iv(self._pointer)
-        size = libmango.mangoimgmeta_iv_size(self._pointer)
+    def double_page(self):
+        """:bool: The image is a spread over two pages."""
+        value = ctypes.c_bool()
+        check(libmango.mangoimgmeta_double_page(
+            self._pointer,
+            ctypes.byref(value)
+        ))
+        return value.value
+
+    @double_page.setter
+    def double_page(self, value):
+        check(libmango.mangoimgmeta_set_double_page(self._pointer, bool(value)))
+
+    @property
+    def label(self):
+        """:str: Label shown for the page, like a chapter title."""
+        return self._get_string(libmango.mangoimgmeta_label)
+
+    @label.setter
+    def label(self, value):
+        check(libmango.mangoimgmeta_set_label(self._pointer, _encode(value)))
+
+    @property
+    def caption(self):
+        """:str: Text describing the page."""
+        return self._get_string(libmango.mangoimgmeta_caption)
+
+    @caption.setter
+    def caption(self, value):
+        check(libmango.mangoimgmeta_set_caption(self._pointer, _encode(value)))
 
-        iv = []
+    @property
+    def perceptual_hash(self):
+        """:str: Perceptual hash as hex, None if it wasn't computed.
 
-        for i in range(0, size):
-            iv.append(ptr[i])
+        This property is read only.
+        """
+        return self._get_string(libmango.mangoimgmeta_perceptual_hash)
+
+    @property
+    def original_checksum(self):
+        """:str: Checksum of the image before it was transformed.
 
-        return iv
+        This property is read only.
+        """
+        return self._get_string(libmango.mangoimgmeta_original_checksum)
+
+    @property
+    def original_mime(self):
+        """:str: Mime type of the image before it was transformed.
+
+        This property is read only.
+        """
+        return self._get_string(libmango.mangoimgmeta_original_mime)
+
+    @property
+    def iv(self):
+        data = ImageData()
+        check(libmango.mangoimgmeta_iv(self._pointer, ctypes.byref(data)))
+
+        try:
+            return list(data.pointer[:data.length]) if data.pointer else []
+        finally:
+            libmango.mango_imagedata_free(data)
@@ -1,6 +1,8 @@
 import os
 import pytest
 from mangofmt import MangoImage, EncryptionType, CompressionType, MangoFile
+from mangofmt import ProgressStage
+from mangofmt.error import CancelledError
 
 
 def test_meta_is_none():
@@ -137,3 +139,102 @@ def test_remove_image_index_error():
 
     with pytest.raises(IndexError):
         file.remove_image(1)
+
+
+def test_open_not_found():
+    with pytest.raises(FileNotFoundError):
+        MangoFile.open("does_not_exist.mango")
+
+
+def test_invalid_language():
+    file = MangoFile()
+    file.meta_data.language = "en"
+
+    with pytest.raises(ValueError):
+        file.meta_data.language = "not a language"
+
+    assert file.meta_data.language == "en"
+
+
+def test_image_outlives_file():
+    file = MangoFile()
+    file.add_image_by_path("test.jpg")
+    img = file.get_image(0)
+    file.remove_image(0)
+    del file
+
+    assert img.meta_data.filename == "test.jpg"
+
+
+def test_bytes():
+    file = MangoFile()
+    file.meta_data.title = "in memory"
+    file.add_image_by_path("test.jpg")
+
+    for fmt in [None, "CBOR", "BSON", "JSON"]:
+        opened = MangoFile.from_bytes(file.to_bytes(fmt), fmt)
+        assert opened.meta_data.title == "in memory"
+        assert opened.get_image(0).image_data == file.get_image(0).image_data
+
+
+def test_bytes_unknown_format():
+    with pytest.raises(ValueError):
+        MangoFile().to_bytes("XML")
+
+
+def test_progress():
+    mango_file = MangoFile()
+    reports = []
+    mango_file.import_images(["test.jpg", "test2.jpg"], reports.append)
+    assert [r.page for r in reports] == [0, 1]
+    assert reports[-1].stage == ProgressStage.IMPORT
+    assert reports[-1].page_count == 2
+
+    reports = []
+    mango_file.save("progress.mango", reports.append)
+    assert reports[-1].stage == ProgressStage.WRITE
+    assert reports[-1].page is None
+    assert reports[-1].bytes == reports[-1].total_bytes
+
+    reports = []
+    opened = MangoFile.open("progress.mango", reports.append)
+    assert opened.image_count == 2
+    assert reports[-2].stage == ProgressStage.READ
+    assert reports[-1].stage == ProgressStage.DECODE
+
+    with pytest.raises(CancelledError):
+        MangoFile.open("progress.mango", lambda progress: False)
+
+    os.remove("progress.mango")
+
+
+def test_cancel_import():
+    mango_file = MangoFile()
+
+    with pytest.raises(CancelledError):
+        mango_file.import_images(["test.jpg", "test2.jpg"],
+                                 lambda progress: progress.page == 0)
+    assert mango_file.image_count == 0
+
+
+@pytest.mark.skipif(not CompressionType.GZIP.is_supported(), reason="no GZIP support")
+def test_compress_images():
+    mango_file = MangoFile()
+    mango_file.import_images(["test.jpg", "test2.jpg"])
+
+    stages = []
+    mango_file.compress_images(CompressionType.GZIP,
+                               lambda progress: stages.append(progress.stage))
+    assert stages == [ProgressStage.COMPRESS, ProgressStage.COMPRESS]
+    for image in mango_file.images:
+        assert image.meta_data.compression == CompressionType.GZIP
+
+
+@pytest.mark.skipif(not EncryptionType.AES128.is_supported(), reason="no AES128 support")
+def test_encrypt_images():
+    mango_file = MangoFile()
+    mango_file.import_images(["test.jpg", "test2.jpg"])
+    mango_file.encrypt_images(EncryptionType.AES128, "1234567812345678")
+
+    for image in mango_file.images:
+        assert image.meta_data.encryption == EncryptionType.AES128
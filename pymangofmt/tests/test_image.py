@@ -3,7 +3,7 @@ import shutil
 import platform
 import pytest
 import subprocess
-from mangofmt import MangoImage, EncryptionType, CompressionType
+from mangofmt import MangoImage, EncryptionType, CompressionType, PageKind
 
 def is_root():
     # there is no os.geteuid on windows
@@ -124,3 +124,32 @@ def test_iv():
     assert isinstance(iv, list)
     assert len(iv) > 0
     assert isinstance(iv[0], int)
+
+
+def test_set_meta():
+    img = MangoImage.from_path("test.jpg")
+    meta = img.meta_data
+
+    meta.filename = "001.jpg"
+    assert meta.filename == "001.jpg"
+
+    assert meta.page_kind == None
+    meta.page_kind = PageKind.FRONT_COVER
+    assert img.meta_data.page_kind == PageKind.FRONT_COVER
+    meta.page_kind = "STORY"
+    assert meta.page_kind == PageKind.STORY
+    with pytest.raises(ValueError):
+        meta.page_kind = "POSTER"
+
+    assert meta.double_page == False
+    meta.double_page = True
+    assert meta.double_page == True
+
+    meta.label = "Chapter 1"
+    assert meta.label == "Chapter 1"
+    meta.caption = "a cat"
+    meta.caption = None
+    assert meta.caption == None
+
+    assert meta.original_checksum == None
+    assert meta.original_mime == None
@@ -1,4 +1,4 @@
-from mangofmt import MangoFile, EncryptionType, CompressionType, Language
+from mangofmt import MangoFile, MangoMetaData, EncryptionType, CompressionType, Language
 
 
 def test_title():
@@ -57,6 +57,13 @@ def test_chapter():
     assert meta.chapter == 2
 
 
+def test_fractional_chapter():
+    mango = MangoFile()
+    meta = mango.meta_data
+    meta.chapter = 10.5
+    assert meta.chapter == 10.5
+
+
 def test_year():
     mango = MangoFile()
     meta = mango.meta_data
@@ -71,3 +78,34 @@ def test_language():
     assert meta.language == None
     meta.language = Language.EN
     assert meta.language == Language.EN
+
+
+def test_language_tag():
+    mango = MangoFile()
+    meta = mango.meta_data
+    meta.language = "pt_br"
+    assert meta.language == "pt-BR"
+
+
+def test_standalone():
+    meta = MangoMetaData()
+    meta.title = "standalone"
+    meta.volume = 3
+
+    mango = MangoFile()
+    mango.meta_data = meta
+    meta.title = "changed later"
+
+    assert mango.meta_data.title == "standalone"
+    assert mango.meta_data.volume == 3
+
+
+def test_copy():
+    mango = MangoFile()
+    mango.meta_data.title = "test"
+
+    copy = mango.meta_data.copy()
+    copy.title = "copy"
+
+    assert mango.meta_data.title == "test"
+    assert copy.title == "copy"